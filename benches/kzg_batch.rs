@@ -0,0 +1,94 @@
+//! Batched against one-by-one verification of KZG openings and unified
+//! routing proofs. Run with `cargo bench --bench kzg_batch`.
+
+use ark_bn254::{Fr, G1Projective};
+use ark_ec::Group;
+use ark_poly::univariate::DensePolynomial;
+use ark_std::UniformRand;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use decentralized_network::zhtp::zk_proofs::{
+    verify_unified_proof, verify_unified_proofs, KzgOpening, KzgTrustedSetup, RoutingProof, UnifiedCircuit,
+    UnifiedProofClaim,
+};
+use std::collections::HashMap;
+
+fn openings(setup: &KzgTrustedSetup, count: usize) -> Vec<KzgOpening> {
+    let mut rng = rand::thread_rng();
+    (0..count)
+        .map(|_| {
+            let poly = DensePolynomial { coeffs: (0..32).map(|_| Fr::rand(&mut rng)).collect() };
+            let point = Fr::rand(&mut rng);
+            let (evaluation, proof) = setup.open(&poly, &point).expect("opening");
+            let commitment = setup.commit_polynomial(&poly).expect("commitment");
+            KzgOpening { commitment, point, evaluation, proof }
+        })
+        .collect()
+}
+
+fn bench_openings(c: &mut Criterion) {
+    let setup = KzgTrustedSetup::from_tau(Fr::rand(&mut rand::thread_rng()), 64);
+    let mut group = c.benchmark_group("kzg_openings");
+    for count in [16, 64, 256] {
+        let openings = openings(&setup, count);
+        group.bench_with_input(BenchmarkId::new("individual", count), &openings, |b, openings| {
+            b.iter(|| {
+                openings
+                    .iter()
+                    .all(|o| setup.verify_opening(&o.commitment, &o.point, &o.evaluation, &o.proof))
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batched", count), &openings, |b, openings| {
+            b.iter(|| setup.batch_verify_openings(openings))
+        });
+    }
+    group.finish();
+}
+
+fn routing_proof(source: &[u8], destination: &[u8]) -> RoutingProof {
+    let mut routing_table = HashMap::new();
+    routing_table.insert(source.to_vec(), vec![destination.to_vec()]);
+    UnifiedCircuit::new(
+        source.to_vec(),
+        destination.to_vec(),
+        vec![source.to_vec(), destination.to_vec()],
+        routing_table,
+        [0u8; 32],
+        vec![],
+        G1Projective::generator(),
+        0,
+        vec![],
+        vec![],
+    )
+    .generate_proof()
+    .expect("routing proof")
+}
+
+fn bench_unified_proofs(c: &mut Criterion) {
+    // Proofs commit against the global setup, here the development one
+    KzgTrustedSetup::allow_insecure_development_setup();
+    let mut group = c.benchmark_group("unified_proofs");
+    for count in [8u8, 32] {
+        let routes: Vec<(Vec<u8>, Vec<u8>)> = (0..count).map(|i| (vec![i], vec![i, i])).collect();
+        let proofs: Vec<RoutingProof> = routes.iter().map(|(source, destination)| routing_proof(source, destination)).collect();
+        let claims: Vec<UnifiedProofClaim> = proofs
+            .iter()
+            .zip(&routes)
+            .map(|(proof, (source, destination))| UnifiedProofClaim { proof, source, destination, stored_data_root: [0u8; 32] })
+            .collect();
+
+        group.bench_with_input(BenchmarkId::new("individual", count), &claims, |b, claims| {
+            b.iter(|| {
+                claims
+                    .iter()
+                    .all(|claim| verify_unified_proof(claim.proof, claim.source, claim.destination, claim.stored_data_root))
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("batched", count), &claims, |b, claims| {
+            b.iter(|| verify_unified_proofs(claims))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_openings, bench_unified_proofs);
+criterion_main!(benches);
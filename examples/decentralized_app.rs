@@ -12,12 +12,15 @@ use decentralized_network::{
         ZhtpNode, DAppLaunchpad, ZhtpDNS,
         dapp_launchpad::*,
         crypto::Keypair,
-        zk_proofs::ByteRoutingProof,
+        proof_envelope::ProofEnvelope,
     },
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Demo nodes run on the publicly known development trusted setup
+    decentralized_network::zhtp::zk_proofs::KzgTrustedSetup::allow_insecure_development_setup();
+
     println!("🔗 ZHTP Decentralized App Demo");
     println!("==============================");
     
@@ -53,11 +56,7 @@ async fn main() -> Result<()> {
         developer: DeveloperInfo {
             developer_id: rand::random::<[u8; 32]>(),
             display_name: Some("Demo Developer".to_string()),
-            identity_proof: ByteRoutingProof {
-                commitments: vec![],
-                elements: vec![],
-                inputs: vec![],
-            },
+            identity_proof: ProofEnvelope::none(),
             reputation: 5.0,
             contact_info: vec!["demo@zhtp.dev".to_string()],
         },
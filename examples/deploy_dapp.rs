@@ -10,12 +10,15 @@ use decentralized_network::{
         ZhtpNode, DAppLaunchpad, ZhtpDNS, ZhtpEconomics,
         dapp_launchpad::*,
         crypto::Keypair,
-        zk_proofs::ByteRoutingProof,
+        proof_envelope::ProofEnvelope,
     },
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Demo nodes run on the publicly known development trusted setup
+    decentralized_network::zhtp::zk_proofs::KzgTrustedSetup::allow_insecure_development_setup();
+
     println!("🚀 ZHTP DApp Deployment Tool");
     println!("============================");
     
@@ -143,12 +146,8 @@ fn create_sample_dapp(network: &str) -> DeployedDApp {
         .unwrap()
         .as_secs();
     
-    // Create demo ZK proof for developer identity
-    let demo_proof = ByteRoutingProof {
-        commitments: vec![vec![1u8; 32]],
-        elements: vec![vec![2u8; 32]], 
-        inputs: vec![vec![3u8; 32]],
-    };
+    // Demo developer without an identity proof
+    let demo_proof = ProofEnvelope::none();
       DeployedDApp {
         id: format!("storage-dapp-{}", timestamp),
         name: "ZHTP Storage Hub".to_string(),
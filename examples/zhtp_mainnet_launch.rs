@@ -1,6 +1,8 @@
 use anyhow::Result;
 use decentralized_network::{
     zhtp::{
+        amount::Amount,
+        crypto::Keypair,
         p2p_network::ZhtpP2PNetwork,
         zk_transactions::ZkTransaction,
     }
@@ -17,6 +19,9 @@ use log::{info, warn};
 /// zero-knowledge consensus without relying on TCP or libp2p.
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Demo nodes run on the publicly known development trusted setup
+    decentralized_network::zhtp::zk_proofs::KzgTrustedSetup::allow_insecure_development_setup();
+
     // Initialize logging
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     
@@ -180,11 +185,15 @@ async fn test_transaction_broadcast(node: &Arc<ZhtpP2PNetwork>) -> Result<()> {
     info!("Creating test transaction for network broadcast");
     
     // Create a test zero-knowledge transaction with correct parameters
+    let sender_keys = Keypair::generate();
+    let receiver_keys = Keypair::generate();
     let test_tx = ZkTransaction::new(
+        &sender_keys,
         "test_sender",    // Sender address
         "test_receiver",  // Receiver address  
-        100.0,           // Amount
-        1000.0,          // Sender balance
+        &receiver_keys.kyber_public_key(),
+        Amount::from_zhtp(100),  // Amount
+        Amount::from_zhtp(1000), // Sender balance
         1,               // Nonce
     )?;
     
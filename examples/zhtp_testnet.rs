@@ -1,6 +1,7 @@
 use anyhow::Result;
 use decentralized_network::{
     zhtp::{
+        amount::Amount,
         consensus_engine::ZhtpConsensusEngine,
         economics::ZhtpEconomics,
         crypto::Keypair,
@@ -144,7 +145,7 @@ impl ZhtpTestnet {
             
             self.consensus.register_validator(
                 validator_id.clone(),
-                stake as f64,
+                Amount::from_zhtp(stake),
             ).await?;
             
             self.validators.push(node);
@@ -274,7 +275,7 @@ impl ZhtpTestnet {
             
             // Simulate transaction fees and network usage
             let total_fees = rand::random::<u64>() % 10000 + 1000;
-            self.consensus.process_transaction_fees(total_fees as f64).await?;
+            self.consensus.process_transaction_fees(Amount::from_base_units(total_fees)).await?;
             
             // Distribute consensus rewards
             self.consensus.distribute_consensus_rewards(round).await?;
@@ -387,6 +388,9 @@ impl Default for ZhtpTestnetConfig {
 /// Main testnet launcher
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Demo nodes run on the publicly known development trusted setup
+    decentralized_network::zhtp::zk_proofs::KzgTrustedSetup::allow_insecure_development_setup();
+
     env_logger::init();
     
     println!("🌐 ZHTP Testnet - Complete Decentralized Internet Replacement");
@@ -1,16 +1,27 @@
 use crate::zhtp::{
+    amount::Amount,
     consensus_engine::ZkNetworkMetrics,
+    contracts::{self, WasmRuntime, DEFAULT_CONTRACT_GAS_LIMIT},
+    crypto::{address_from_public_key, Keypair},
+    merkle::{self, MerkleProof, NotePath, SparseMerkleProof, SparseMerkleTree},
+    shielded::{Note, ShieldedBundle, ShieldedCheckpoint, ShieldedPool, SpendingKey},
+    viewing_keys::{ReportEntry, ViewingKey, ViewingReport},
     zk_transactions::{ZkTransaction, ZkBalance, ZkTransactionPool},
-    zk_proofs::ByteRoutingProof,
+    proof_envelope::ProofEnvelope,
 };
+use crate::genesis::GenesisSpec;
+use crate::mempool::{Mempool, MempoolConfig};
+use crate::snapshot::{ChainSnapshot, SnapshotAccount, SNAPSHOT_FORMAT_VERSION};
+use crate::storage::chain_store::{AccountStateIndex, ChainStore};
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, Mutex, OwnedMutexGuard, RwLock};
 use base64::Engine;
-use rand;
 
 /// Statistics about zero-knowledge transactions in the blockchain
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,7 +36,7 @@ pub struct ZkBlockchainStats {
 pub struct Transaction {
     pub from: String,
     pub to: String,
-    pub amount: f64,
+    pub amount: Amount,
     pub timestamp: i64,
     pub signature: String,
     pub nonce: u64,
@@ -35,11 +46,91 @@ pub struct Transaction {
     // If true, transaction details are encrypted in zk_transaction
     pub is_private: bool,
     // ZK proof that this transaction is valid (when private)
-    pub validity_proof: Option<ByteRoutingProof>,
+    pub validity_proof: Option<ProofEnvelope>,
+    /// Dilithium5 public key of the sender; `from` must be the address
+    /// derived from it. Omitted from the encoding when absent so blocks
+    /// written before keys were carried keep their hashes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<Vec<u8>>,
+    /// Fee paid by the sender to the producer of the including block, on
+    /// top of `amount`. Omitted from the encoding when zero.
+    #[serde(default, skip_serializing_if = "is_zero_amount")]
+    pub fee: Amount,
+    /// Signatures authorizing a spend from a multisig account, used instead
+    /// of `signature` and `public_key`. Omitted from the encoding when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub multisig: Option<MultisigAuthorization>,
+    /// Condition keeping the transferred amount from being spent by the
+    /// recipient. Omitted from the encoding when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<TransferLock>,
+}
+
+fn is_zero_amount(amount: &Amount) -> bool {
+    amount.is_zero()
+}
+
+/// Domain tag of the transaction signing payload
+const TRANSACTION_SIGNING_DOMAIN: &str = "zhtp-transaction-v1";
+
+/// Everything a transaction signature covers, in a canonical encoding:
+/// bincode length-prefixes strings and byte vectors, so no field can bleed
+/// into the next. The sender's public key and the signatures themselves
+/// are left out; `from` already commits to the keys that may sign.
+#[derive(Serialize)]
+struct SigningPayload<'a> {
+    domain: &'static str,
+    from: &'a str,
+    to: &'a str,
+    amount: u64,
+    timestamp: i64,
+    nonce: u64,
+    fee: u64,
+    data: &'a [u8],
+    is_private: bool,
+    /// Digests of the payloads whose own encodings omit absent fields
+    zk_transaction: Option<[u8; 32]>,
+    validity_proof: Option<[u8; 32]>,
+    multisig_account: Option<&'a MultisigAccount>,
+    lock: Option<&'a TransferLock>,
+}
+
+/// Verify a base64 Dilithium5 detached signature over `message`
+fn verify_detached(signature: &str, message: &[u8], public_key: &[u8]) -> bool {
+    use pqcrypto_dilithium::dilithium5;
+    use pqcrypto_traits::sign::{DetachedSignature, PublicKey};
+    
+    if signature.is_empty() {
+        return false;
+    }
+    
+    // Decode signature from base64
+    let signature_bytes = match base64::prelude::BASE64_STANDARD.decode(signature) {
+        Ok(bytes) => bytes,
+        Err(_) => return false,
+    };
+    
+    // Convert public key bytes to Dilithium5 public key
+    let public_key = match dilithium5::PublicKey::from_bytes(public_key) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+    
+    let signature = match dilithium5::DetachedSignature::from_bytes(&signature_bytes) {
+        Ok(signature) => signature,
+        Err(_) => return false,
+    };
+    
+    dilithium5::verify_detached_signature(&signature, message, &public_key).is_ok()
+}
+
+/// SHA-256 of the bincode encoding of `value`
+fn digest_of<T: Serialize>(value: &T) -> [u8; 32] {
+    Sha256::digest(bincode::serialize(value).expect("transaction fields encode with bincode")).into()
 }
 
 impl Transaction {
-    pub fn new(from: String, to: String, amount: f64) -> Self {
+    pub fn new(from: String, to: String, amount: Amount) -> Self {
         Transaction {
             from,
             to,
@@ -51,10 +142,14 @@ impl Transaction {
             zk_transaction: None,
             is_private: false,
             validity_proof: None,
+            public_key: None,
+            fee: Amount::ZERO,
+            multisig: None,
+            lock: None,
         }
     }
 
-    pub fn with_data(from: String, to: String, amount: f64, data: Vec<u8>) -> Self {
+    pub fn with_data(from: String, to: String, amount: Amount, data: Vec<u8>) -> Self {
         Transaction {
             from,
             to,
@@ -66,6 +161,10 @@ impl Transaction {
             zk_transaction: None,
             is_private: false,
             validity_proof: None,
+            public_key: None,
+            fee: Amount::ZERO,
+            multisig: None,
+            lock: None,
         }
     }
 
@@ -84,23 +183,134 @@ impl Transaction {
             zk_transaction: Some(zk_transaction.clone()),
             is_private: true,
             validity_proof: Some(zk_transaction.validity_proof.clone()),
+            public_key: None,
+            fee: Amount::ZERO,
+            multisig: None,
+            lock: None,
         })
     }
 
+    /// Move `bundle.value_in` from the transparent account `from` into new
+    /// shielded notes. Must be signed by `from` like any transfer.
+    pub fn shield(from: String, bundle: ShieldedBundle) -> Result<Self, anyhow::Error> {
+        if bundle.value_in.is_zero() {
+            return Err(anyhow::anyhow!("Shielding bundle moves no transparent value in"));
+        }
+        let mut transaction = Transaction::new(from, SHIELDED_POOL_ADDRESS.to_string(), bundle.value_in);
+        transaction.zk_transaction = Some(ZkTransaction::shielded(bundle)?);
+        Ok(transaction)
+    }
+
+    /// Consume shielded notes, paying part of their value out to the
+    /// transparent address named in the bundle. The spend proofs authorize
+    /// it, so it carries no transparent sender.
+    pub fn unshield(bundle: ShieldedBundle) -> Result<Self, anyhow::Error> {
+        if bundle.unshield.is_none() || !bundle.value_in.is_zero() {
+            return Err(anyhow::anyhow!("Unshielding bundle must pay out and take no transparent value in"));
+        }
+        Self::new_private(ZkTransaction::shielded(bundle)?)
+    }
+
+    /// Coinbase minting `reward` to the producer of the block at `height`.
+    ///
+    /// Coinbases carry no signature: a block may open with one, and it is
+    /// valid only if it pays exactly the reward the block earns (see
+    /// [`Block::verify_coinbase`]). The height as nonce gives every coinbase
+    /// its own hash.
+    pub fn coinbase(validator: String, reward: Amount, height: u64) -> Self {
+        let mut transaction = Transaction::new("network".to_string(), validator, reward);
+        transaction.nonce = height;
+        transaction
+    }
+
+    /// Whether the transaction mints new tokens instead of spending a balance
+    pub fn is_coinbase(&self) -> bool {
+        self.from == "network" && !self.is_private
+    }
+
+    /// Upgrade of the protocol parameters sent by the chain's governance
+    /// address `from`. Must be signed by it.
+    pub fn params_upgrade(from: String, upgrade: &ParamsUpgrade) -> Self {
+        let data = serde_json::to_vec(upgrade).expect("parameter upgrade encodes as JSON");
+        Self::with_data(from, PROTOCOL_PARAMS_ADDRESS.to_string(), Amount::ZERO, data)
+    }
+
+    /// Attach a fee for the block producer. Must be set before signing.
+    pub fn with_fee(mut self, fee: Amount) -> Self {
+        self.fee = fee;
+        self
+    }
+
+    /// Lock the transferred amount in the recipient's account. Must be set
+    /// before signing.
+    pub fn with_lock(mut self, lock: TransferLock) -> Self {
+        self.lock = Some(lock);
+        self
+    }
+
+    /// Size of the transaction as stored in a block
+    pub fn encoded_size(&self) -> usize {
+        serde_json::to_vec(self).map(|bytes| bytes.len()).unwrap_or(0)
+    }
+
+    /// Gas charged for carrying the transaction, before any execution
+    pub fn intrinsic_gas(&self) -> u64 {
+        TRANSACTION_BASE_GAS.saturating_add(TRANSACTION_DATA_GAS_PER_BYTE.saturating_mul(self.data.len() as u64))
+    }
+
+    /// Most gas the transaction can use: its intrinsic gas plus, when its
+    /// data is contract code, the gas limit the contract runs with
+    pub fn gas_limit(&self) -> u64 {
+        let execution = if contracts::is_contract_code(&self.data) { DEFAULT_CONTRACT_GAS_LIMIT } else { 0 };
+        self.intrinsic_gas().saturating_add(execution)
+    }
+
     pub fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        let data = format!(
-            "{}{}{}{}{}",
-            self.from, self.to, self.amount, self.timestamp, self.nonce
-        );
-        hasher.update(data.as_bytes());
-        hex::encode(hasher.finalize())
+        hex::encode(self.hash_bytes())
+    }
+
+    /// Raw transaction hash, the leaf committed to by the block's transactions
+    /// root and the message signed by the sender
+    pub fn hash_bytes(&self) -> [u8; 32] {
+        let payload = SigningPayload {
+            domain: TRANSACTION_SIGNING_DOMAIN,
+            from: &self.from,
+            to: &self.to,
+            amount: self.amount.base_units(),
+            timestamp: self.timestamp,
+            nonce: self.nonce,
+            fee: self.fee.base_units(),
+            data: &self.data,
+            is_private: self.is_private,
+            zk_transaction: self.zk_transaction.as_ref().map(digest_of),
+            validity_proof: self.validity_proof.as_ref().map(digest_of),
+            multisig_account: self.multisig.as_ref().map(|multisig| &multisig.account),
+            lock: self.lock.as_ref(),
+        };
+        digest_of(&payload)
+    }
+
+    /// Shielded bundle carried by the transaction, if any
+    pub fn shielded_bundle(&self) -> Option<&ShieldedBundle> {
+        self.zk_transaction.as_ref().and_then(|zk_tx| zk_tx.shielded.as_ref())
+    }
+
+    /// How the transaction moves value across the shielded pool boundary
+    pub fn shielded_kind(&self) -> Option<ShieldedKind> {
+        let bundle = self.shielded_bundle()?;
+        Some(if !self.is_private {
+            ShieldedKind::Shield
+        } else if bundle.unshield.is_some() {
+            ShieldedKind::Unshield
+        } else {
+            ShieldedKind::Transfer
+        })
     }
 
     /// Sign transaction using post-quantum Dilithium5 signatures
     pub fn sign(&mut self, private_key: &[u8]) -> Result<(), anyhow::Error> {
         use pqcrypto_dilithium::dilithium5;
-        use pqcrypto_traits::sign::{SignedMessage, SecretKey};
+        use pqcrypto_traits::sign::{DetachedSignature, SecretKey};
         
         // Convert private key bytes to Dilithium5 secret key
         let secret_key = dilithium5::SecretKey::from_bytes(private_key)
@@ -111,49 +321,246 @@ impl Transaction {
         let message = hash.as_bytes();
         
         // Generate post-quantum signature
-        let signed_message = dilithium5::sign(message, &secret_key);
-        let signature_bytes = signed_message.as_bytes();
+        let signature = dilithium5::detached_sign(message, &secret_key);
         
         // Store as base64 for serialization  
-        self.signature = base64::prelude::BASE64_STANDARD.encode(signature_bytes);
+        self.signature = base64::prelude::BASE64_STANDARD.encode(signature.as_bytes());
+        Ok(())
+    }
+
+    /// Sign as the owner of `keypair`, attaching its public key.
+    ///
+    /// Fails unless `from` is the keypair's address.
+    pub fn sign_with_keypair(&mut self, keypair: &Keypair) -> Result<(), anyhow::Error> {
+        if self.from != keypair.address() {
+            return Err(anyhow::anyhow!("Keypair does not own sender address {}", self.from));
+        }
+        let hash = self.calculate_hash();
+        let signature = keypair.sign(hash.as_bytes())?;
+        self.signature = base64::prelude::BASE64_STANDARD.encode(signature.as_bytes());
+        self.public_key = Some(keypair.public_key());
         Ok(())
     }
 
     /// Verify transaction signature using post-quantum Dilithium5 verification
     pub fn verify_signature(&self, public_key: &[u8]) -> bool {
-        use pqcrypto_dilithium::dilithium5;
-        use pqcrypto_traits::sign::{PublicKey, SignedMessage};
-        
-        if self.signature.is_empty() {
-            return false;
+        self.verify_detached_signature(&self.signature, public_key)
+    }
+
+    /// Verify a base64 Dilithium5 signature over the transaction hash
+    fn verify_detached_signature(&self, signature: &str, public_key: &[u8]) -> bool {
+        verify_detached(signature, self.calculate_hash().as_bytes(), public_key)
+    }
+
+    /// Check that the attached public key owns the `from` address and signed
+    /// this transaction, or for a multisig sender that enough of its keys did
+    pub fn verify_sender(&self) -> bool {
+        if let Some(multisig) = &self.multisig {
+            return multisig.account.address() == self.from
+                && self.valid_multisig_signers(multisig) >= multisig.account.threshold;
         }
-        
-        // Decode signature from base64
-        let signature_bytes = match base64::prelude::BASE64_STANDARD.decode(&self.signature) {
-            Ok(bytes) => bytes,
-            Err(_) => return false,
-        };
-        
-        // Convert public key bytes to Dilithium5 public key
-        let public_key = match dilithium5::PublicKey::from_bytes(public_key) {
-            Ok(key) => key,
-            Err(_) => return false,
+        match &self.public_key {
+            Some(public_key) => {
+                address_from_public_key(public_key) == self.from && self.verify_signature(public_key)
+            }
+            None => false,
+        }
+    }
+
+    /// Prepare a spend from the multisig `account` for signing; `from` must
+    /// be the account's address. The transaction is final once
+    /// [`MultisigAccount::threshold`] partial signatures have been added.
+    pub fn for_multisig(mut self, account: MultisigAccount) -> Result<Self, anyhow::Error> {
+        if self.from != account.address() {
+            return Err(anyhow::anyhow!("Sender {} is not the multisig account {}", self.from, account.address()));
+        }
+        self.multisig = Some(MultisigAuthorization { account, signatures: Vec::new() });
+        Ok(self)
+    }
+
+    /// Partial signature by one of the multisig account's keys. Signers can
+    /// produce these offline from a copy of the prepared transaction.
+    pub fn partial_sign(&self, keypair: &Keypair) -> Result<MultisigSignature, anyhow::Error> {
+        let multisig = self
+            .multisig
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Transaction is not a multisig spend"))?;
+        let public_key = keypair.public_key();
+        let key_index = multisig
+            .account
+            .public_keys
+            .iter()
+            .position(|key| *key == public_key)
+            .ok_or_else(|| anyhow::anyhow!("Keypair is not a signer of {}", self.from))?;
+        let signature = keypair.sign(self.calculate_hash().as_bytes())?;
+        Ok(MultisigSignature {
+            key_index,
+            signature: base64::prelude::BASE64_STANDARD.encode(signature.as_bytes()),
+        })
+    }
+
+    /// Attach a partial signature collected from a signer. Invalid
+    /// signatures are rejected; a second signature by the same key replaces
+    /// the first.
+    pub fn add_multisig_signature(&mut self, partial: MultisigSignature) -> Result<(), anyhow::Error> {
+        let multisig = self
+            .multisig
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Transaction is not a multisig spend"))?;
+        let public_key = multisig
+            .account
+            .public_keys
+            .get(partial.key_index)
+            .ok_or_else(|| anyhow::anyhow!("Multisig {} has no key {}", self.from, partial.key_index))?;
+        if !self.verify_detached_signature(&partial.signature, public_key) {
+            return Err(anyhow::anyhow!("Invalid signature by multisig key {}", partial.key_index));
+        }
+
+        let signatures = &mut self.multisig.as_mut().expect("checked above").signatures;
+        signatures.retain(|existing| existing.key_index != partial.key_index);
+        signatures.push(partial);
+        signatures.sort_by_key(|signature| signature.key_index);
+        Ok(())
+    }
+
+    /// Number of distinct account keys with a valid signature
+    fn valid_multisig_signers(&self, multisig: &MultisigAuthorization) -> usize {
+        let mut signers = HashSet::new();
+        for partial in &multisig.signatures {
+            if let Some(public_key) = multisig.account.public_keys.get(partial.key_index) {
+                if !signers.contains(&partial.key_index) && self.verify_detached_signature(&partial.signature, public_key) {
+                    signers.insert(partial.key_index);
+                }
+            }
+        }
+        signers.len()
+    }
+}
+
+/// Maximum number of keys in a multisig account
+pub const MAX_MULTISIG_KEYS: usize = 16;
+
+/// Prefix of multisig account addresses
+pub const MULTISIG_ADDRESS_PREFIX: &str = "zhtp_multisig_";
+
+/// Account controlled jointly by a set of Dilithium keys, any `threshold` of
+/// which can authorize a spend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigAccount {
+    pub threshold: usize,
+    /// Dilithium5 public keys, sorted so that the address does not depend on
+    /// the order they were listed in
+    pub public_keys: Vec<Vec<u8>>,
+}
+
+impl MultisigAccount {
+    /// M-of-N account over `public_keys`
+    pub fn new(threshold: usize, mut public_keys: Vec<Vec<u8>>) -> Result<Self, anyhow::Error> {
+        if public_keys.is_empty() || public_keys.len() > MAX_MULTISIG_KEYS {
+            return Err(anyhow::anyhow!("A multisig account needs 1 to {} keys", MAX_MULTISIG_KEYS));
+        }
+        if threshold == 0 || threshold > public_keys.len() {
+            return Err(anyhow::anyhow!("Threshold {} is not between 1 and {}", threshold, public_keys.len()));
+        }
+        public_keys.sort();
+        if public_keys.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(anyhow::anyhow!("Multisig keys must be distinct"));
+        }
+        Ok(Self { threshold, public_keys })
+    }
+
+    /// Address of the account: the prefix followed by the hex SHA-256 of the
+    /// threshold and the sorted keys
+    pub fn address(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update((self.threshold as u64).to_le_bytes());
+        for key in &self.public_keys {
+            hasher.update((key.len() as u64).to_le_bytes());
+            hasher.update(key);
+        }
+        format!("{}{}", MULTISIG_ADDRESS_PREFIX, hex::encode(hasher.finalize()))
+    }
+}
+
+/// Signature by one key of a multisig account over a transaction hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigSignature {
+    /// Position of the signing key in [`MultisigAccount::public_keys`]
+    pub key_index: usize,
+    /// Base64 Dilithium5 detached signature
+    pub signature: String,
+}
+
+/// The account a multisig spend comes from and the signatures collected so far
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultisigAuthorization {
+    pub account: MultisigAccount,
+    pub signatures: Vec<MultisigSignature>,
+}
+
+/// When the amount of a transfer becomes spendable by its recipient.
+/// Relative times count from the timestamp of the including block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransferLock {
+    /// Locked until the given Unix time
+    Until(i64),
+    /// Locked for the given number of seconds
+    For(u64),
+    /// Released linearly over `duration` seconds from `start` (the including
+    /// block if unset), with nothing released during the first `cliff`
+    /// seconds
+    Vesting { start: Option<i64>, cliff: u64, duration: u64 },
+}
+
+impl TransferLock {
+    /// Lock on `amount` received in a block with timestamp `block_time`
+    pub(crate) fn schedule(&self, amount: Amount, block_time: i64) -> Result<BalanceLock, TransactionError> {
+        let after = |start: i64, seconds: u64| {
+            i64::try_from(seconds).ok().and_then(|seconds| start.checked_add(seconds)).ok_or(TransactionError::InvalidLock)
         };
-        
-        // Create signed message from signature bytes
-        let signed_message = match dilithium5::SignedMessage::from_bytes(&signature_bytes) {
-            Ok(msg) => msg,
-            Err(_) => return false,
+        let (start, cliff, end) = match *self {
+            TransferLock::Until(time) => (time, time, time),
+            TransferLock::For(seconds) => {
+                let end = after(block_time, seconds)?;
+                (end, end, end)
+            }
+            TransferLock::Vesting { start, cliff, duration } => {
+                if duration == 0 || cliff > duration {
+                    return Err(TransactionError::InvalidLock);
+                }
+                let start = start.unwrap_or(block_time);
+                (start, after(start, cliff)?, after(start, duration)?)
+            }
         };
-        
-        // Verify signature against transaction hash
-        let hash = self.calculate_hash();
-        let message = hash.as_bytes();
-        
-        match dilithium5::open(&signed_message, &public_key) {
-            Ok(verified_message) => verified_message == message,
-            Err(_) => false,
+        Ok(BalanceLock { amount, start, cliff, end })
+    }
+}
+
+/// Part of an account's balance that is not yet fully spendable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceLock {
+    pub amount: Amount,
+    /// Unix time the linear release is measured from
+    pub start: i64,
+    /// Unix time before which nothing is released
+    pub cliff: i64,
+    /// Unix time from which everything is released
+    pub end: i64,
+}
+
+impl BalanceLock {
+    /// Amount still locked at Unix time `now`
+    pub fn locked_at(&self, now: i64) -> Amount {
+        if now >= self.end {
+            return Amount::ZERO;
+        }
+        if now < self.cliff {
+            return self.amount;
         }
+        let elapsed = now.saturating_sub(self.start).max(0) as u64;
+        let duration = self.end.saturating_sub(self.start) as u64;
+        let released = self.amount.mul_div(elapsed, duration).unwrap_or(self.amount);
+        self.amount.saturating_sub(released)
     }
 }
 
@@ -170,393 +577,4108 @@ pub struct Block {
     // Zero-knowledge block features
     pub zk_transaction_count: u64,
     pub private_transaction_root: Option<[u8; 32]>,
-    pub block_validity_proof: Option<ByteRoutingProof>,
+    pub block_validity_proof: Option<ProofEnvelope>,
     pub has_private_transactions: bool,
+    /// Merkle root over transaction hashes (absent in blocks created before header roots)
+    #[serde(default)]
+    pub transactions_root: Option<[u8; 32]>,
+    /// Sparse Merkle root of account balances and nonces after this block
+    #[serde(default)]
+    pub state_root: Option<[u8; 32]>,
+    /// Merkle root over the receipts of `transactions`
+    #[serde(default)]
+    pub receipts_root: Option<[u8; 32]>,
+    /// Receipts of `transactions`, in block order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub receipts: Vec<TransactionReceipt>,
+    /// Dilithium5 public key of the producer; `validator` must be the
+    /// address derived from it. Not part of the block hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub producer_key: Option<Vec<u8>>,
+    /// Base64 Dilithium5 signature of the producer over `hash`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub producer_signature: Option<String>,
 }
 
-impl Block {
-    pub fn new(
-        index: u64,
-        transactions: Vec<Transaction>,
-        previous_hash: String,
-        validator: String,
-        validator_score: f64,
-        network_metrics: Option<ZkNetworkMetrics>,
-    ) -> Self {
-        // Count ZK transactions and calculate private transaction root
-        let zk_transaction_count = transactions.iter().filter(|tx| tx.is_private).count() as u64;
-        let has_private_transactions = zk_transaction_count > 0;
-        
-        let private_transaction_root = if has_private_transactions {
-            Some(Self::calculate_private_transaction_root(&transactions))
-        } else {
-            None
-        };
-        
-        let mut block = Block {
-            index,
-            timestamp: Utc::now().timestamp(),
-            transactions,
-            previous_hash,
-            hash: String::new(),
-            validator,
-            validator_score,
-            network_metrics,
-            zk_transaction_count,
-            private_transaction_root,
-            block_validity_proof: None, // Generated after block creation
-            has_private_transactions,
-        };
-        block.hash = block.calculate_hash();
-        block
+/// Proof that a transaction is included in a block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionProof {
+    pub transaction: Transaction,
+    pub block_index: u64,
+    pub block_hash: String,
+    pub proof: MerkleProof,
+}
+
+impl TransactionProof {
+    /// Check the proof against the transactions root of a trusted block header
+    pub fn verify(&self, transactions_root: &[u8; 32]) -> bool {
+        self.proof.verify(transactions_root, &self.transaction.hash_bytes())
     }
+}
 
-    /// Calculate Merkle root of private transaction commitments
-    fn calculate_private_transaction_root(transactions: &[Transaction]) -> [u8; 32] {
-        let private_hashes: Vec<[u8; 32]> = transactions
-            .iter()
-            .filter(|tx| tx.is_private)
-            .map(|tx| {
-                if let Some(zk_tx) = &tx.zk_transaction {
-                    zk_tx.commitment
-                } else {
-                    [0u8; 32] // Fallback for invalid private transactions
-                }
-            })
-            .collect();
-        
-        if private_hashes.is_empty() {
-            return [0u8; 32];
-        }
-        
-        // Simple Merkle root calculation (in production, use proper Merkle tree)
-        let mut hasher = Sha256::new();
-        for hash in &private_hashes {
-            hasher.update(hash);
-        }
-        let result = hasher.finalize();
-        let mut root = [0u8; 32];
-        root.copy_from_slice(&result[..32]);
-        root
+/// Proof of an account's balance and nonce against a block's state root.
+/// Accounts with zero balance and nonce are absent from the state tree, so
+/// for them this is a non-membership proof.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountProof {
+    pub address: String,
+    pub balance: Amount,
+    pub nonce: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locks: Vec<BalanceLock>,
+    pub block_index: u64,
+    pub block_hash: String,
+    pub proof: SparseMerkleProof,
+}
+
+impl AccountProof {
+    /// Check the proof against the state root of a trusted block header
+    pub fn verify(&self, state_root: &[u8; 32]) -> bool {
+        self.proof.verify(
+            state_root,
+            &account_key(&self.address),
+            account_leaf(self.balance, self.nonce, &self.locks).as_ref(),
+        )
     }
+}
 
-    pub fn calculate_hash(&self) -> String {
-        let mut hasher = Sha256::new();
-        let data = format!(
-            "{}{}{}{}{}{}",
-            self.index,
-            self.timestamp,
-            serde_json::to_string(&self.transactions).unwrap(),
-            self.previous_hash,
-            self.validator,
-            self.validator_score
-        );
-        hasher.update(data.as_bytes());
-        hex::encode(hasher.finalize())
+/// Account holding the transparent value backing every shielded note.
+/// Shielding transfers to it; unshielding and shielded fees draw from it.
+pub const SHIELDED_POOL_ADDRESS: &str = "shielded_pool";
+
+/// Kind of a transaction carrying a [`ShieldedBundle`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShieldedKind {
+    /// Transparent balance moved into new notes
+    Shield,
+    /// Notes spent into new notes only
+    Transfer,
+    /// Notes spent, part of their value paid to a transparent address
+    Unshield,
+}
+
+/// State tree key for an account
+fn account_key(address: &str) -> [u8; 32] {
+    Sha256::digest(address.as_bytes()).into()
+}
+
+/// State tree value for an account, `None` for an empty account
+fn account_leaf(balance: Amount, nonce: u64, locks: &[BalanceLock]) -> Option<[u8; 32]> {
+    if balance.is_zero() && nonce == 0 && locks.is_empty() {
+        return None;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(balance.base_units().to_le_bytes());
+    hasher.update(nonce.to_le_bytes());
+    // Accounts without locks keep the leaf they had before locks existed
+    for lock in locks {
+        hasher.update(lock.amount.base_units().to_le_bytes());
+        hasher.update(lock.start.to_le_bytes());
+        hasher.update(lock.cliff.to_le_bytes());
+        hasher.update(lock.end.to_le_bytes());
     }
+    Some(hasher.finalize().into())
 }
 
-#[derive(Debug, Clone)]
-struct ChainState {
-    chain: Vec<Block>,
-    pending_transactions: Vec<Transaction>,
-    balances: HashMap<String, f64>,
-    transaction_nonces: HashMap<String, u64>,
-    // Zero-knowledge transaction management
-    zk_transaction_pool: ZkTransactionPool,
-    private_balances: HashMap<String, ZkBalance>,
+/// Balance of an account split by what its locks allow it to spend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountBalance {
+    pub address: String,
+    pub balance: Amount,
+    pub locked: Amount,
+    pub spendable: Amount,
+    /// Unix time the split was computed for
+    pub as_of: i64,
+    pub locks: Vec<BalanceLock>,
 }
 
-impl ChainState {
-    fn new() -> Self {
-        let mut chain = Vec::new();
-        chain.push(Block::new(
-            0,
-            Vec::new(),
-            String::from("0"),
-            String::from("genesis"),
-            0.0,
-            None,
-        ));
+/// Reason a transaction was rejected by the state transition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TransactionError {
+    /// The sender's balance does not cover the transfer
+    InsufficientFunds { balance: Amount, required: Amount },
+    /// The nonce is not the sender's next expected nonce
+    BadNonce { expected: u64, found: u64 },
+    /// Crediting the recipient would overflow its balance
+    BalanceOverflow,
+    /// The balance covers the transfer, but part of it is still locked
+    LockedFunds { spendable: Amount, required: Amount },
+    /// The transfer lock is malformed or its times overflow
+    InvalidLock,
+    /// The shielded bundle is malformed, fails verification or is carried
+    /// by the wrong kind of transaction
+    InvalidShieldedBundle,
+    /// A shielded spend proves membership against an unknown note tree root
+    UnknownAnchor,
+    /// A shielded spend reveals a nullifier that was already spent
+    NullifierSpent,
+    /// The sender's key did not sign the transaction or does not own its
+    /// address
+    InvalidSignature,
+    /// The contract code carried by the transaction trapped, ran out of gas
+    /// or is not a valid module
+    ContractFailed(String),
+    /// The protocol parameter upgrade is malformed, not sent by the
+    /// governance address or activates too early
+    InvalidParamsUpgrade,
+}
 
-        Self {
-            chain,
-            pending_transactions: Vec::new(),
-            balances: HashMap::new(),
-            transaction_nonces: HashMap::new(),
-            zk_transaction_pool: ZkTransactionPool::new(),
-            private_balances: HashMap::new(),
+impl fmt::Display for TransactionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactionError::InsufficientFunds { balance, required } => {
+                write!(f, "insufficient funds: balance {} ZHTP, required {} ZHTP", balance, required)
+            }
+            TransactionError::BadNonce { expected, found } => {
+                write!(f, "bad nonce: expected {}, found {}", expected, found)
+            }
+            TransactionError::BalanceOverflow => write!(f, "recipient balance overflow"),
+            TransactionError::LockedFunds { spendable, required } => {
+                write!(f, "funds locked: spendable {} ZHTP, required {} ZHTP", spendable, required)
+            }
+            TransactionError::InvalidLock => write!(f, "invalid transfer lock"),
+            TransactionError::InvalidShieldedBundle => write!(f, "invalid shielded bundle"),
+            TransactionError::UnknownAnchor => write!(f, "unknown shielded anchor"),
+            TransactionError::NullifierSpent => write!(f, "note already spent"),
+            TransactionError::InvalidSignature => write!(f, "missing or invalid sender signature"),
+            TransactionError::ContractFailed(reason) => write!(f, "contract failed: {}", reason),
+            TransactionError::InvalidParamsUpgrade => write!(f, "invalid protocol parameter upgrade"),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Blockchain {
-    state: Arc<RwLock<ChainState>>,
-    pub base_reward: f64,
+impl std::error::Error for TransactionError {}
+
+/// Result of applying one transaction to the account state
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionOutcome {
+    pub tx_hash: String,
+    pub result: Result<(), TransactionError>,
+    /// What the transaction did, as committed to by the block's receipts
+    /// when it applied
+    pub receipt: TransactionReceipt,
+}
+
+impl TransactionOutcome {
+    pub fn is_applied(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// Gas used by every transaction
+pub const TRANSACTION_BASE_GAS: u64 = 21_000;
+
+/// Gas per byte of transaction payload data
+pub const TRANSACTION_DATA_GAS_PER_BYTE: u64 = 16;
+
+/// Event emitted by a contract while a transaction executed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventLog {
+    /// Contract that emitted the event
+    pub contract: String,
+    /// Event name from the contract interface
+    pub event: String,
+    /// Encoded event parameters
+    pub data: Vec<u8>,
+}
+
+/// Whether a transaction took effect
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReceiptStatus {
+    Success,
+    Failed(TransactionError),
+}
+
+/// Record of what a transaction did: committed per block for included
+/// transactions, kept locally for ones dropped during block production
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionReceipt {
+    pub tx_hash: String,
+    pub status: ReceiptStatus,
+    /// Fee actually taken from the sender
+    pub fee: Amount,
+    pub gas_used: u64,
+    /// Contract events in emission order. Native transfers emit none.
+    pub logs: Vec<EventLog>,
 }
 
-impl Blockchain {    pub fn new(base_reward: f64) -> Self {
+impl TransactionReceipt {
+    /// Receipt of a transaction that applied to the account state
+    pub fn applied(tx: &Transaction) -> Self {
+        // Protocol and private transactions have no sender balance to charge
+        let fee = if tx.from == "network" || tx.is_private { Amount::ZERO } else { tx.fee };
         Self {
-            state: Arc::new(RwLock::new(ChainState::new())),
-            base_reward,
+            tx_hash: tx.calculate_hash(),
+            status: ReceiptStatus::Success,
+            fee,
+            gas_used: tx.intrinsic_gas(),
+            logs: Vec::new(),
         }
     }
 
-    pub async fn add_transaction(&self, transaction: Transaction) -> bool {
-        if transaction.from.is_empty() || transaction.to.is_empty() {
-            return false;
+    /// Receipt of a transaction the state transition rejected
+    pub fn rejected(tx_hash: String, error: TransactionError) -> Self {
+        Self {
+            tx_hash,
+            status: ReceiptStatus::Failed(error),
+            fee: Amount::ZERO,
+            gas_used: 0,
+            logs: Vec::new(),
         }
+    }
 
-        let mut state = self.state.write().await;
-        
-        // Prevent nonce manipulation - validate provided nonce
-        let expected_nonce = {
-            let current_nonce = state.transaction_nonces
-                .entry(transaction.from.clone())
-                .or_insert(0);
-            *current_nonce
-        };
-        
-        // Always validate the provided nonce to prevent replay attacks
-        if transaction.nonce != expected_nonce {
-            // Nonce validation working correctly - only log occasionally to reduce spam
-            if rand::random::<u8>() < 10 { // Log ~4% of nonce errors
-                println!("🔒 Nonce validation active: expected {}, got {} from {}", 
-                    expected_nonce, transaction.nonce, transaction.from);
-            }
-            return false;
-        }
-        
-        // Update nonce ONLY after validation
-        if let Some(nonce_entry) = state.transaction_nonces.get_mut(&transaction.from) {
-            *nonce_entry += 1;
+    pub fn is_success(&self) -> bool {
+        self.status == ReceiptStatus::Success
+    }
+
+    /// Leaf hash of the receipt in a block's receipts root
+    pub fn hash_bytes(&self) -> [u8; 32] {
+        Sha256::digest(serde_json::to_vec(self).unwrap_or_default()).into()
+    }
+}
+
+/// Run the contract code carried by a transparent transaction with
+/// [`DEFAULT_CONTRACT_GAS_LIMIT`] gas. The receipt charges the gas burnt on
+/// top of the intrinsic gas and logs the emitted events, or records why the
+/// contract failed.
+fn execute_contract(tx: &Transaction) -> TransactionReceipt {
+    let mut runtime = WasmRuntime::new();
+    let executed = runtime.execute(&tx.data);
+    let mut receipt = TransactionReceipt::applied(tx);
+    receipt.gas_used = receipt.gas_used.saturating_add(runtime.gas_used());
+    match executed {
+        Ok(events) => {
+            let contract = contracts::contract_address(&tx.data);
+            receipt.logs = events
+                .into_iter()
+                .map(|event| EventLog { contract: contract.clone(), event: event.name, data: event.data })
+                .collect();
         }
+        Err(e) => receipt.status = ReceiptStatus::Failed(TransactionError::ContractFailed(e.to_string())),
+    }
+    receipt
+}
 
-        // Check balance
-        if transaction.from != "network" {
-            let balance = state.balances.get(&transaction.from).unwrap_or(&0.0);
-            if *balance < transaction.amount {
-                return false;
-            }
+/// A receipt and the canonical block that committed it, if any
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptLookup {
+    pub receipt: TransactionReceipt,
+    /// Block holding the transaction; `None` when the transaction was
+    /// dropped during block production instead
+    pub block_index: Option<u64>,
+    pub block_hash: Option<String>,
+}
+
+/// How far ahead of the local clock an imported block's timestamp may be, in seconds
+pub const MAX_FUTURE_BLOCK_TIME: i64 = 120;
+
+/// Most block headers returned by one range query
+pub const MAX_HEADER_RANGE: u64 = 500;
+
+/// Block header fields, without the transaction bodies
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockHeader {
+    pub index: u64,
+    pub timestamp: i64,
+    pub previous_hash: String,
+    pub hash: String,
+    pub validator: String,
+    pub validator_score: f64,
+    pub transaction_count: usize,
+    pub transactions_root: Option<[u8; 32]>,
+    pub state_root: Option<[u8; 32]>,
+    pub receipts_root: Option<[u8; 32]>,
+}
+
+impl From<&Block> for BlockHeader {
+    fn from(block: &Block) -> Self {
+        Self {
+            index: block.index,
+            timestamp: block.timestamp,
+            previous_hash: block.previous_hash.clone(),
+            hash: block.hash.clone(),
+            validator: block.validator.clone(),
+            validator_score: block.validator_score,
+            transaction_count: block.transactions.len(),
+            transactions_root: block.transactions_root,
+            state_root: block.state_root,
+            receipts_root: block.receipts_root,
         }
+    }
+}
 
-        state.pending_transactions.push(transaction);
-        true
+/// Position of a transaction on the canonical chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionLocation {
+    pub block_index: u64,
+    pub position: usize,
+}
+
+/// A canonical transaction with its position and receipt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedTransaction {
+    pub transaction: Transaction,
+    pub block_index: u64,
+    pub block_hash: String,
+    pub position: usize,
+    /// Absent for blocks created before receipts
+    pub receipt: Option<TransactionReceipt>,
+}
+
+/// One page of an address's transaction history, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressHistory {
+    pub address: String,
+    /// Number of canonical transactions sending from or to the address
+    pub total: usize,
+    pub offset: usize,
+    pub transactions: Vec<IndexedTransaction>,
+}
+
+/// Deepest reorganization accepted; older blocks are treated as final
+pub const MAX_REORG_DEPTH: u64 = 1024;
+
+/// Most side blocks held for fork choice; the lowest are dropped first
+pub const MAX_SIDE_BLOCKS: usize = 1024;
+
+/// Most transactions, the reward included, that go into one block
+pub const MAX_BLOCK_TRANSACTIONS: usize = 100;
+
+/// Smallest transaction byte and gas limits a block may be given: room for
+/// the reward and a plain transfer
+const MIN_BLOCK_BYTES: usize = 4 * 1024;
+const MIN_BLOCK_GAS: u64 = 2 * TRANSACTION_BASE_GAS;
+
+/// Consensus limits on block contents, adjustable by DAO protocol upgrades
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolParams {
+    /// Maximum total encoded size of a block's transactions
+    pub max_block_bytes: usize,
+    /// Maximum total gas of a block's transactions
+    pub max_block_gas: u64,
+}
+
+impl Default for ProtocolParams {
+    fn default() -> Self {
+        Self {
+            max_block_bytes: 1024 * 1024,
+            max_block_gas: 10_000_000,
+        }
     }
+}
 
-    /// Add a zero-knowledge transaction to the pool
-    pub async fn add_zk_transaction(&self, zk_transaction: ZkTransaction) -> Result<bool, anyhow::Error> {
-        let mut state = self.state.write().await;
-        
-        // Add to ZK transaction pool
-        let result = state.zk_transaction_pool.add_transaction(zk_transaction.clone());
-        
-        if result.is_ok() {
-            // Convert to regular transaction for blockchain inclusion
-            let blockchain_tx = Transaction::new_private(zk_transaction)?;
-            state.pending_transactions.push(blockchain_tx);
-            Ok(true)
-        } else {
-            Ok(false)
+impl ProtocolParams {
+    /// Check that blocks within these limits still fit a transfer
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if self.max_block_bytes < MIN_BLOCK_BYTES {
+            return Err(anyhow::anyhow!(
+                "Maximum block size of {} bytes is below the minimum of {}",
+                self.max_block_bytes, MIN_BLOCK_BYTES
+            ));
+        }
+        if self.max_block_gas < MIN_BLOCK_GAS {
+            return Err(anyhow::anyhow!(
+                "Maximum block gas of {} is below the minimum of {}",
+                self.max_block_gas, MIN_BLOCK_GAS
+            ));
         }
+        Ok(())
     }
+}
 
-    /// Get a copy of pending ZK transactions
-    pub async fn get_pending_zk_transactions(&self) -> Vec<ZkTransaction> {
-        let state = self.state.read().await;
-        state.zk_transaction_pool.get_pending_transactions()
-            .into_iter()
-            .cloned()
-            .collect()
+/// Reserved recipient of protocol parameter upgrades. Its account leaf
+/// commits to the chain's [`ParamsSchedule`] once it is in use.
+pub const PROTOCOL_PARAMS_ADDRESS: &str = "protocol_params";
+
+/// Fewest blocks between the block carrying a parameter upgrade and the
+/// first block it applies to
+pub const PARAMS_ACTIVATION_DELAY: u64 = 100;
+
+/// Parameters taking effect from `activation_height` on, carried as the data
+/// of a transaction to [`PROTOCOL_PARAMS_ADDRESS`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamsUpgrade {
+    pub activation_height: u64,
+    pub params: ProtocolParams,
+}
+
+/// Protocol parameters as committed by the account state: the address
+/// allowed to schedule upgrades, named by the genesis block, and the
+/// upgrades scheduled so far
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamsSchedule {
+    pub governance: Option<String>,
+    /// Parameters by the height they take effect at
+    pub upgrades: BTreeMap<u64, ProtocolParams>,
+}
+
+impl ParamsSchedule {
+    /// Parameters in force for the block at `height`
+    pub fn params_at(&self, height: u64) -> ProtocolParams {
+        self.upgrades
+            .range(..=height)
+            .next_back()
+            .map(|(_, params)| *params)
+            .unwrap_or_default()
     }
 
-    /// Process ZK transactions and update private balances
-    pub async fn process_zk_transactions(&self, block: &Block) -> Result<(), anyhow::Error> {
-        let _state = self.state.read().await;
-        
-        for transaction in &block.transactions {
-            if transaction.is_private {
-                if let Some(zk_tx) = &transaction.zk_transaction {
-                    // Update private balances based on ZK transaction commitments
-                    // In a real implementation, this would use the ZK proofs to update balances
-                    // without revealing the actual amounts
-                    
-                    // For now, we track the transaction commitment
-                    let _tx_hash = hex::encode(zk_tx.get_hash());
-                    println!("Processed private transaction with commitment: {}", 
-                             hex::encode(zk_tx.commitment));
-                }
-            }
+    pub fn is_empty(&self) -> bool {
+        self.governance.is_none() && self.upgrades.is_empty()
+    }
+}
+
+/// Number of reorg events buffered per subscriber
+const REORG_EVENT_CAPACITY: usize = 64;
+
+/// Rule for choosing between competing branches of the block tree.
+///
+/// Finalized blocks are never reverted under either rule, so only branches
+/// forking after the latest finalized block compete.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForkChoice {
+    /// Branch whose blocks were produced by the most validator stake, then
+    /// the longer branch
+    #[default]
+    HeaviestStake,
+    /// Longest branch built on the highest finalized block, then the
+    /// heavier branch
+    HighestFinalized,
+}
+
+/// Which block bodies a node keeps. Headers of every block and the account
+/// state at the tip are always kept, and bodies are only dropped once their
+/// blocks can no longer be reorganized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum PruningMode {
+    /// Keep every block
+    #[default]
+    Archive,
+    /// Keep the bodies of the `keep_blocks` most recent blocks
+    Full { keep_blocks: u64 },
+    /// Keep only the bodies of blocks that can still be reorganized
+    Pruned,
+}
+
+impl PruningMode {
+    /// Lowest height whose body must be kept with the chain at `tip`, when
+    /// nothing at or below `horizon` can be reorganized any more
+    fn retain_from(&self, tip: u64, horizon: u64) -> u64 {
+        match self {
+            PruningMode::Archive => 0,
+            PruningMode::Full { keep_blocks } => horizon.min((tip + 1).saturating_sub((*keep_blocks).max(1))),
+            PruningMode::Pruned => horizon,
         }
-        
-        Ok(())
     }
+}
 
-    /// Get private balance for an account (returns commitment)
-    pub async fn get_private_balance(&self, account: &str) -> Option<ZkBalance> {
-        let state = self.state.read().await;
-        state.private_balances.get(account).cloned()
+/// Notification that the canonical chain switched branches
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReorgEvent {
+    /// Height of the last block shared by both branches
+    pub fork_height: u64,
+    pub old_tip: String,
+    pub new_tip: String,
+    /// Hashes of the blocks removed from the canonical chain, lowest first
+    pub reverted_blocks: Vec<String>,
+    /// Hashes of the blocks added to the canonical chain, lowest first
+    pub applied_blocks: Vec<String>,
+    /// Transactions of reverted blocks that were returned to the pending pool
+    pub requeued_transactions: Vec<Transaction>,
+}
+
+/// What happened to a block passed to [`Blockchain::import_block`]
+#[derive(Debug, Clone)]
+pub enum BlockImport {
+    /// The block was already known
+    Known,
+    /// The block extended the canonical chain
+    Extended,
+    /// The block was stored on a branch that does not win fork choice
+    SideChain,
+    /// The block's branch won fork choice and became canonical
+    Reorganized(ReorgEvent),
+}
+
+/// Balance, nonce and locks of every account a block touched, and the
+/// shielded pool and parameter schedule if the block changed them, as they
+/// were before the block was applied
+#[derive(Debug, Clone, Default)]
+struct AccountUndo {
+    accounts: HashMap<String, (Amount, u64, Vec<BalanceLock>)>,
+    shielded: Option<ShieldedCheckpoint>,
+    params: Option<ParamsSchedule>,
+}
+
+impl AccountUndo {
+    fn new() -> Self {
+        Self::default()
     }
 
-    /// Set private balance for an account
-    pub async fn set_private_balance(&self, account: String, balance: ZkBalance) {
-        let mut state = self.state.write().await;
-        state.private_balances.insert(account, balance);
+    /// Fold in the journal of later changes, keeping the earliest values
+    fn merge(&mut self, later: AccountUndo) {
+        for (address, previous) in later.accounts {
+            self.accounts.entry(address).or_insert(previous);
+        }
+        if self.shielded.is_none() {
+            self.shielded = later.shielded;
+        }
+        if self.params.is_none() {
+            self.params = later.params;
+        }
     }
+}
 
-    /// Check if the blockchain supports zero-knowledge transactions
-    pub fn supports_zk_transactions(&self) -> bool {
-        true
+/// Committed account balances, next nonces and balance locks, together with
+/// the sparse Merkle tree the block state root is computed from.
+///
+/// The value backing all shielded notes is held by
+/// [`SHIELDED_POOL_ADDRESS`], whose leaf also commits to the note tree and
+/// spent nullifiers once the pool is in use. Likewise the leaf of
+/// [`PROTOCOL_PARAMS_ADDRESS`] commits to the parameter schedule.
+#[derive(Debug, Clone, Default)]
+struct AccountState {
+    balances: HashMap<String, Amount>,
+    nonces: HashMap<String, u64>,
+    locks: HashMap<String, Vec<BalanceLock>>,
+    shielded: ShieldedPool,
+    params: ParamsSchedule,
+    tree: SparseMerkleTree,
+}
+
+impl AccountState {
+    fn new(
+        balances: HashMap<String, Amount>,
+        nonces: HashMap<String, u64>,
+        locks: HashMap<String, Vec<BalanceLock>>,
+        shielded: ShieldedPool,
+        params: ParamsSchedule,
+    ) -> Self {
+        let mut state = Self {
+            balances,
+            nonces,
+            locks,
+            shielded,
+            params,
+            tree: SparseMerkleTree::new(),
+        };
+        if !state.shielded.is_empty() {
+            state.update_leaf(SHIELDED_POOL_ADDRESS);
+        }
+        if !state.params.is_empty() {
+            state.update_leaf(PROTOCOL_PARAMS_ADDRESS);
+        }
+        let addresses: Vec<String> = state
+            .balances
+            .keys()
+            .chain(state.nonces.keys())
+            .chain(state.locks.keys())
+            .cloned()
+            .collect();
+        for address in &addresses {
+            state.update_leaf(address);
+        }
+        state
     }
 
-    /// Get statistics about ZK transactions in the blockchain
-    pub async fn get_zk_statistics(&self) -> ZkBlockchainStats {
-        let state = self.state.read().await;
-        
-        let mut total_zk_transactions = 0u64;
-        let mut blocks_with_private_txs = 0u64;
-        
-        for block in &state.chain {
-            total_zk_transactions += block.zk_transaction_count;
-            if block.has_private_transactions {
-                blocks_with_private_txs += 1;
+    /// Rebuild the state by applying every block of `chain` from genesis
+    fn replay(chain: &[Block]) -> Self {
+        Self::default().replay_onto(chain)
+    }
+
+    /// Apply `blocks` on top of this state, skipping invalid transactions
+    fn replay_onto(mut self, blocks: &[Block]) -> Self {
+        let state = &mut self;
+        for block in blocks {
+            let (outcomes, undo) =
+                state.apply_transactions(&block.transactions, &block.validator, block.index, block.timestamp);
+            if let Err(e) = state.check_supply(&block.transactions, &outcomes, &undo) {
+                log::warn!("Block {} breaks the supply invariant: {}", block.index, e);
+            }
+            for outcome in outcomes {
+                if let Err(e) = outcome.result {
+                    log::warn!("Skipping transaction {} in block {}: {}", outcome.tx_hash, block.index, e);
+                }
             }
         }
-        
-        ZkBlockchainStats {
-            total_zk_transactions,
-            blocks_with_private_txs,
-            pending_zk_transactions: state.zk_transaction_pool.get_pending_transactions().len() as u64,
-            private_accounts: state.private_balances.len() as u64,
+        self
+    }
+
+    fn from_snapshot(snapshot: &ChainSnapshot) -> Self {
+        let mut balances = HashMap::new();
+        let mut nonces = HashMap::new();
+        let mut locks = HashMap::new();
+        for (address, account) in &snapshot.accounts {
+            if !account.balance.is_zero() {
+                balances.insert(address.clone(), account.balance);
+            }
+            if account.nonce > 0 {
+                nonces.insert(address.clone(), account.nonce);
+            }
+            if !account.locks.is_empty() {
+                locks.insert(address.clone(), account.locks.clone());
+            }
         }
+        Self::new(balances, nonces, locks, snapshot.shielded.clone(), snapshot.params.clone())
     }
 
-    pub fn calculate_reward(&self, validator_score: f64, network_metrics: &ZkNetworkMetrics) -> f64 {
-        let base = self.base_reward * validator_score;
-        let delivery_multiplier = network_metrics.get_delivery_success_rate();
-        let latency_multiplier = (1000.0 - network_metrics.average_latency().min(1000.0)) / 1000.0;
-        let routing_multiplier = 1.0 + (network_metrics.packets_routed as f64 / 100.0).min(0.2);
-        base * delivery_multiplier * latency_multiplier * routing_multiplier
+    /// Every non-empty account, ordered by address
+    fn snapshot_accounts(&self) -> BTreeMap<String, SnapshotAccount> {
+        self.balances
+            .keys()
+            .chain(self.nonces.keys())
+            .chain(self.locks.keys())
+            .map(|address| {
+                let account = SnapshotAccount {
+                    balance: self.balance(address),
+                    nonce: self.nonce(address),
+                    locks: self.locks(address).to_vec(),
+                };
+                (address.clone(), account)
+            })
+            .collect()
     }
 
-    pub async fn get_latest_block(&self) -> Block {
-        let state = self.state.read().await;
-        state.chain.last().unwrap().clone()
+    fn balance(&self, address: &str) -> Amount {
+        self.balances.get(address).copied().unwrap_or(Amount::ZERO)
     }
 
-    pub async fn get_balance(&self, address: &str) -> f64 {
-        let state = self.state.read().await;
-        *state.balances.get(address).unwrap_or(&0.0)
+    fn nonce(&self, address: &str) -> u64 {
+        self.nonces.get(address).copied().unwrap_or(0)
     }
 
-    pub async fn get_transactions(&self) -> Vec<Transaction> {
-        let state = self.state.read().await;
-        let mut all_transactions = Vec::new();
-        
-        // Get transactions from all blocks
-        for block in state.chain.iter() {
-            all_transactions.extend(block.transactions.clone());
-        }
-        
-        // Add pending transactions
-        all_transactions.extend(state.pending_transactions.clone());
-        
-        all_transactions
+    fn locks(&self, address: &str) -> &[BalanceLock] {
+        self.locks.get(address).map(Vec::as_slice).unwrap_or_default()
     }
 
-    pub async fn create_block(
-        &self,
-        validator_id: &str,
-        validator_score: f64,
-        network_metrics: Option<ZkNetworkMetrics>,
-    ) {
-        let mut state = self.state.write().await;
+    /// Part of an account's balance still locked at Unix time `now`
+    fn locked(&self, address: &str, now: i64) -> Amount {
+        self.locks(address)
+            .iter()
+            .fold(Amount::ZERO, |total, lock| total.saturating_add(lock.locked_at(now)))
+    }
 
-        // Calculate reward
-        let reward = if let Some(metrics) = &network_metrics {
-            self.calculate_reward(validator_score, metrics)
-        } else {
-            self.base_reward * validator_score
-        };
+    /// Part of an account's balance it may spend at Unix time `now`
+    fn spendable(&self, address: &str, now: i64) -> Amount {
+        self.balance(address).saturating_sub(self.locked(address, now))
+    }
 
-        // Create reward transaction with proper network signature
-        let mut reward_tx = Transaction::new(
-            String::from("network"),
-            validator_id.to_string(),
-            reward,
-        );
-        
-        // Generate network keypair for signing (in production, this would be a persistent network key)
-        use pqcrypto_dilithium::dilithium5;
-        use pqcrypto_traits::sign::SecretKey;
-        let (_network_pk, network_sk) = dilithium5::keypair();
-        
-        reward_tx.sign(network_sk.as_bytes()).map_err(|e| {
-            eprintln!("Failed to sign network reward transaction: {}", e);
-        }).ok();
+    fn root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
 
-        // Get all transactions
-        let mut transactions = Vec::new();
-        transactions.push(reward_tx);
-        transactions.append(&mut state.pending_transactions);
+    fn update_leaf(&mut self, address: &str) {
+        let mut leaf = account_leaf(self.balance(address), self.nonce(address), self.locks(address));
+        // The pool account keeps a plain leaf until the first note exists
+        if address == SHIELDED_POOL_ADDRESS && !self.shielded.is_empty() {
+            let mut hasher = Sha256::new();
+            hasher.update(leaf.unwrap_or_default());
+            hasher.update(self.shielded.digest());
+            leaf = Some(hasher.finalize().into());
+        }
+        // So is the parameters account until governance is set up
+        if address == PROTOCOL_PARAMS_ADDRESS && !self.params.is_empty() {
+            let mut hasher = Sha256::new();
+            hasher.update(leaf.unwrap_or_default());
+            hasher.update(digest_of(&self.params));
+            leaf = Some(hasher.finalize().into());
+        }
+        match leaf {
+            Some(leaf) => self.tree.insert(account_key(address), leaf),
+            None => {
+                self.tree.remove(&account_key(address));
+            }
+        }
+    }
 
-        // Create new block
-        let new_block = Block::new(
-            state.chain.len() as u64,
-            transactions,
-            state.chain.last().unwrap().hash.clone(),
-            validator_id.to_string(),
-            validator_score,
-            network_metrics,
-        );
+    /// Remember an account's current value in `undo` unless already recorded
+    fn record(&self, address: &str, undo: &mut AccountUndo) {
+        undo.accounts
+            .entry(address.to_string())
+            .or_insert_with(|| (self.balance(address), self.nonce(address), self.locks(address).to_vec()));
+    }
 
-        // Add block and update balances
-        state.chain.push(new_block);
+    /// Overwrite an account, remembering its previous value in `undo`
+    fn set_account(&mut self, address: &str, balance: Amount, nonce: u64, undo: &mut AccountUndo) {
+        self.record(address, undo);
+        self.balances.insert(address.to_string(), balance);
+        if nonce > 0 {
+            self.nonces.insert(address.to_string(), nonce);
+        }
+        self.update_leaf(address);
+    }
 
-        // Update balances
-        let mut new_balances = HashMap::new();
-        for block in &state.chain {
-            for tx in &block.transactions {
-                if tx.from != "network" {
-                    *new_balances.entry(tx.from.clone()).or_insert(0.0) -= tx.amount;
+    /// Add `amount` to an account's balance
+    fn credit(&mut self, address: &str, amount: Amount, undo: &mut AccountUndo) -> Result<(), TransactionError> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+        let credited = self.balance(address).checked_add(amount).ok_or(TransactionError::BalanceOverflow)?;
+        self.set_account(address, credited, self.nonce(address), undo);
+        Ok(())
+    }
+
+    /// Add `amount` to an account's balance, locked by `lock` if given
+    fn credit_locked(
+        &mut self,
+        address: &str,
+        amount: Amount,
+        lock: Option<BalanceLock>,
+        undo: &mut AccountUndo,
+    ) -> Result<(), TransactionError> {
+        self.credit(address, amount, undo)?;
+        if let Some(lock) = lock.filter(|lock| !lock.amount.is_zero()) {
+            self.record(address, undo);
+            self.locks.entry(address.to_string()).or_default().push(lock);
+            self.update_leaf(address);
+        }
+        Ok(())
+    }
+
+    /// Drop the locks of an account that have fully released by `now`
+    fn release_locks(&mut self, address: &str, now: i64, undo: &mut AccountUndo) {
+        if self.locks(address).iter().any(|lock| lock.end <= now) {
+            self.record(address, undo);
+            if let Some(locks) = self.locks.get_mut(address) {
+                locks.retain(|lock| lock.end > now);
+                if locks.is_empty() {
+                    self.locks.remove(address);
                 }
-                *new_balances.entry(tx.to.clone()).or_insert(0.0) += tx.amount;
             }
+            self.update_leaf(address);
         }
-        state.balances = new_balances;
     }
-}
 
-// ...existing code...
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Apply a single transaction included in a block with timestamp `now`,
+    /// paying its fee to `fee_recipient`. A rejected transaction leaves the
+    /// state untouched.
+    ///
+    /// Rewards from `network` mint new tokens and are not checked here; any
+    /// other transparent transaction must be signed by its sender.
+    /// Private transactions only affect the shielded pool: the value they
+    /// unshield and pay as fee leaves [`SHIELDED_POOL_ADDRESS`], while
+    /// shielding is a transparent transfer to that address carrying the
+    /// bundle that creates the notes.
+    ///
+    /// Transactions to [`PROTOCOL_PARAMS_ADDRESS`] name the governance
+    /// address in the genesis block and afterwards schedule parameter
+    /// upgrades sent by it, at least [`PARAMS_ACTIVATION_DELAY`] blocks
+    /// after `height`.
+    fn apply_transaction(
+        &mut self,
+        tx: &Transaction,
+        fee_recipient: &str,
+        height: u64,
+        now: i64,
+        undo: &mut AccountUndo,
+    ) -> Result<TransactionReceipt, TransactionError> {
+        let bundle = tx.shielded_bundle();
+        if tx.is_private {
+            if let Some(bundle) = bundle {
+                self.apply_shielded(bundle, Amount::ZERO, fee_recipient, undo)?;
+            }
+            return Ok(TransactionReceipt::applied(tx));
+        }
+        let shielding = tx.to == SHIELDED_POOL_ADDRESS && bundle.is_some() && tx.lock.is_none() && tx.from != "network";
+        if (tx.to == SHIELDED_POOL_ADDRESS || bundle.is_some()) && !shielding {
+            return Err(TransactionError::InvalidShieldedBundle);
+        }
+        let lock = tx.lock.map(|lock| lock.schedule(tx.amount, now)).transpose()?;
+        if tx.to == PROTOCOL_PARAMS_ADDRESS && tx.from == "network" {
+            let governance = String::from_utf8(tx.data.clone()).map_err(|_| TransactionError::InvalidParamsUpgrade)?;
+            if height != 0 || !tx.amount.is_zero() || self.params.governance.is_some() {
+                return Err(TransactionError::InvalidParamsUpgrade);
+            }
+            self.schedule_params(|params| params.governance = Some(governance), undo);
+            return Ok(TransactionReceipt::applied(tx));
+        }
+        if tx.from == "network" {
+            let mut changes = AccountUndo::new();
+            if let Err(e) = self.credit_locked(&tx.to, tx.amount, lock, &mut changes) {
+                self.revert(changes);
+                return Err(e);
+            }
+            undo.merge(changes);
+            return Ok(TransactionReceipt::applied(tx));
+        }
 
-    #[tokio::test]
-    async fn test_dynamic_rewards() {
-        let blockchain = Blockchain::new(100.0);
+        if !tx.verify_sender() {
+            return Err(TransactionError::InvalidSignature);
+        }
+        let expected = self.nonce(&tx.from);
+        if tx.nonce != expected {
+            return Err(TransactionError::BadNonce { expected, found: tx.nonce });
+        }
+        let balance = self.balance(&tx.from);
+        let required = tx.amount.checked_add(tx.fee).ok_or(TransactionError::BalanceOverflow)?;
+        let debited = balance
+            .checked_sub(required)
+            .ok_or(TransactionError::InsufficientFunds { balance, required })?;
+        let locked = self.locked(&tx.from, now);
+        if debited < locked {
+            return Err(TransactionError::LockedFunds { spendable: balance.saturating_sub(locked), required });
+        }
+        let upgrade = match tx.to == PROTOCOL_PARAMS_ADDRESS {
+            true => Some(self.check_params_upgrade(tx, height)?),
+            false => None,
+        };
 
-        let mut good_metrics = ZkNetworkMetrics::new(1000.0);
-        good_metrics.packets_routed = 50;
-        good_metrics.update_routing_metrics(50.0, 1024);
-        good_metrics.update_reputation(true);
-        good_metrics.update_reputation(true);
+        // Contract code runs before any value moves. A failing contract is
+        // still included: its sender pays the fee but transfers nothing.
+        let receipt = match bundle.is_none() && contracts::is_contract_code(&tx.data) {
+            true => execute_contract(tx),
+            false => TransactionReceipt::applied(tx),
+        };
+        let (debited, amount, lock) = match receipt.is_success() {
+            true => (debited, tx.amount, lock),
+            false => (balance.saturating_sub(tx.fee), Amount::ZERO, None),
+        };
+
+        let mut changes = AccountUndo::new();
+        self.set_account(&tx.from, debited, expected + 1, &mut changes);
+        self.release_locks(&tx.from, now, &mut changes);
+        let credited = self
+            .credit_locked(&tx.to, amount, lock, &mut changes)
+            .and_then(|()| self.credit(fee_recipient, tx.fee, &mut changes))
+            .and_then(|()| match bundle {
+                Some(bundle) => self.apply_shielded(bundle, tx.amount, fee_recipient, &mut changes),
+                None => Ok(()),
+            });
+        if let Err(e) = credited {
+            self.revert(changes);
+            return Err(e);
+        }
+        if let Some(upgrade) = upgrade {
+            self.schedule_params(|params| {
+                params.upgrades.insert(upgrade.activation_height, upgrade.params);
+            }, &mut changes);
+        }
+        undo.merge(changes);
+        Ok(receipt)
+    }
+
+    /// Parameter upgrade carried by `tx`, if it is well formed, sent by the
+    /// governance address and activates late enough after `height`
+    fn check_params_upgrade(&self, tx: &Transaction, height: u64) -> Result<ParamsUpgrade, TransactionError> {
+        let upgrade: ParamsUpgrade =
+            serde_json::from_slice(&tx.data).map_err(|_| TransactionError::InvalidParamsUpgrade)?;
+        let authorized = self.params.governance.as_deref() == Some(tx.from.as_str());
+        let activates = upgrade.activation_height >= height.saturating_add(PARAMS_ACTIVATION_DELAY);
+        if !authorized || !activates || !tx.amount.is_zero() || upgrade.params.validate().is_err() {
+            return Err(TransactionError::InvalidParamsUpgrade);
+        }
+        Ok(upgrade)
+    }
 
-        let mut poor_metrics = ZkNetworkMetrics::new(1000.0);
-        poor_metrics.packets_routed = 10;
-        poor_metrics.update_routing_metrics(500.0, 1024);
-        poor_metrics.update_reputation(false);
-        poor_metrics.update_reputation(false);
+    /// Change the parameter schedule, remembering its previous value in `undo`
+    fn schedule_params(&mut self, change: impl FnOnce(&mut ParamsSchedule), undo: &mut AccountUndo) {
+        if undo.params.is_none() {
+            undo.params = Some(self.params.clone());
+        }
+        change(&mut self.params);
+        self.update_leaf(PROTOCOL_PARAMS_ADDRESS);
+    }
+
+    /// Spend and create the notes of a shielded bundle. `value_in` is the
+    /// transparent value the carrying transaction already moved into the
+    /// pool account; the bundle's fee is paid from it to `fee_recipient`.
+    fn apply_shielded(
+        &mut self,
+        bundle: &ShieldedBundle,
+        value_in: Amount,
+        fee_recipient: &str,
+        undo: &mut AccountUndo,
+    ) -> Result<(), TransactionError> {
+        if bundle.value_in != value_in || bundle.verify().is_err() {
+            return Err(TransactionError::InvalidShieldedBundle);
+        }
+        if bundle.spends.iter().any(|spend| !self.shielded.is_valid_anchor(&spend.anchor)) {
+            return Err(TransactionError::UnknownAnchor);
+        }
+        if bundle.nullifiers().any(|nullifier| self.shielded.is_spent(nullifier)) {
+            return Err(TransactionError::NullifierSpent);
+        }
+        let value_out = bundle.value_out().ok_or(TransactionError::BalanceOverflow)?;
+        let pool = self.balance(SHIELDED_POOL_ADDRESS);
+        let remaining = pool
+            .checked_sub(value_out)
+            .ok_or(TransactionError::InsufficientFunds { balance: pool, required: value_out })?;
 
-        blockchain.create_block("good_node", 0.9, Some(good_metrics.clone())).await;
-        blockchain.create_block("poor_node", 0.9, Some(poor_metrics.clone())).await;
+        let mut changes = AccountUndo::new();
+        changes.shielded = Some(self.shielded.checkpoint());
+        self.set_account(SHIELDED_POOL_ADDRESS, remaining, self.nonce(SHIELDED_POOL_ADDRESS), &mut changes);
+        let applied = match &bundle.unshield {
+            Some(unshield) => self.credit(&unshield.address, unshield.amount, &mut changes),
+            None => Ok(()),
+        }
+        .and_then(|()| self.credit(fee_recipient, bundle.fee, &mut changes))
+        .and_then(|()| self.shielded.insert(bundle).map_err(|_| TransactionError::InvalidShieldedBundle));
+        if let Err(e) = applied {
+            self.revert(changes);
+            return Err(e);
+        }
+        self.update_leaf(SHIELDED_POOL_ADDRESS);
+        undo.merge(changes);
+        Ok(())
+    }
 
-        let good_balance = blockchain.get_balance("good_node").await;
-        let poor_balance = blockchain.get_balance("poor_node").await;
+    /// Apply transactions of the block at `height` with timestamp `now` in
+    /// order, reporting the outcome of each. Also returns the journal needed
+    /// to revert them with [`AccountState::revert`].
+    fn apply_transactions(
+        &mut self,
+        transactions: &[Transaction],
+        fee_recipient: &str,
+        height: u64,
+        now: i64,
+    ) -> (Vec<TransactionOutcome>, AccountUndo) {
+        let mut undo = AccountUndo::new();
+        let outcomes = transactions
+            .iter()
+            .map(|tx| {
+                let tx_hash = tx.calculate_hash();
+                match self.apply_transaction(tx, fee_recipient, height, now, &mut undo) {
+                    Ok(receipt) => TransactionOutcome { tx_hash, result: Ok(()), receipt },
+                    Err(e) => TransactionOutcome {
+                        receipt: TransactionReceipt::rejected(tx_hash.clone(), e.clone()),
+                        tx_hash,
+                        result: Err(e),
+                    },
+                }
+            })
+            .collect();
+        // Notes created by the block become spendable against its final root
+        if undo.shielded.is_some() {
+            self.shielded.end_block();
+            self.update_leaf(SHIELDED_POOL_ADDRESS);
+        }
+        (outcomes, undo)
+    }
 
-        assert!(good_balance > poor_balance);
-        assert!(good_balance > blockchain.base_reward * 0.9);
-        assert!(poor_balance < blockchain.base_reward * 0.9);
+    /// Check that applying `transactions` with the given outcomes changed
+    /// the total supply by exactly what their coinbases minted. The supply
+    /// counts every transparent balance, including the pool account backing
+    /// the shielded notes, so value moving in or out of the pool and fees
+    /// paid from either side must cancel out.
+    fn check_supply(
+        &self,
+        transactions: &[Transaction],
+        outcomes: &[TransactionOutcome],
+        undo: &AccountUndo,
+    ) -> Result<(), anyhow::Error> {
+        let minted: i128 = transactions
+            .iter()
+            .zip(outcomes)
+            .filter(|(tx, outcome)| tx.is_coinbase() && outcome.is_applied())
+            .map(|(tx, _)| i128::from(tx.amount.base_units()))
+            .sum();
+        let changed: i128 = undo
+            .accounts
+            .iter()
+            .map(|(address, (previous, _, _))| {
+                i128::from(self.balance(address).base_units()) - i128::from(previous.base_units())
+            })
+            .sum();
+        if changed != minted {
+            return Err(anyhow::anyhow!(
+                "Total supply changed by {} base units while {} were minted",
+                changed, minted
+            ));
+        }
+        Ok(())
+    }
+
+    /// State transition for a block received from another validator. The
+    /// block must stay within the parameters in force at its height, every
+    /// transaction must apply, the total supply must only grow by the
+    /// coinbase and the result must match the block's state root and
+    /// receipts; otherwise the state is left as it was.
+    fn apply_block(&mut self, block: &Block) -> Result<AccountUndo, anyhow::Error> {
+        // The gas claimed by the receipts is checked against execution below
+        block.check_limits(&self.params.params_at(block.index))?;
+        let (outcomes, undo) =
+            self.apply_transactions(&block.transactions, &block.validator, block.index, block.timestamp);
+        let error = if let Some(outcome) = outcomes.iter().find(|outcome| !outcome.is_applied()) {
+            Some(anyhow::anyhow!(
+                "Block {} contains invalid transaction {}: {}",
+                block.index,
+                outcome.tx_hash,
+                outcome.result.as_ref().unwrap_err()
+            ))
+        } else if let Err(e) = self.check_supply(&block.transactions, &outcomes, &undo) {
+            Some(anyhow::anyhow!("Block {} breaks the supply invariant: {}", block.index, e))
+        } else if block.state_root.is_some_and(|root| root != self.root()) {
+            Some(anyhow::anyhow!("Block {} does not match its state root", block.index))
+        } else if block.receipts_root.is_some()
+            && !block.receipts.iter().eq(outcomes.iter().map(|outcome| &outcome.receipt))
+        {
+            Some(anyhow::anyhow!("Block {} does not match the receipts of its execution", block.index))
+        } else {
+            None
+        };
+
+        match error {
+            Some(e) => {
+                self.revert(undo);
+                Err(e)
+            }
+            None => Ok(undo),
+        }
+    }
+
+    /// Restore the accounts recorded in an undo journal
+    fn revert(&mut self, undo: AccountUndo) {
+        if let Some(checkpoint) = &undo.shielded {
+            self.shielded.rewind(checkpoint);
+            self.update_leaf(SHIELDED_POOL_ADDRESS);
+        }
+        if let Some(params) = undo.params {
+            self.params = params;
+            self.update_leaf(PROTOCOL_PARAMS_ADDRESS);
+        }
+        for (address, (balance, nonce, locks)) in undo.accounts {
+            if balance.is_zero() {
+                self.balances.remove(&address);
+            } else {
+                self.balances.insert(address.clone(), balance);
+            }
+            if nonce == 0 {
+                self.nonces.remove(&address);
+            } else {
+                self.nonces.insert(address.clone(), nonce);
+            }
+            if locks.is_empty() {
+                self.locks.remove(&address);
+            } else {
+                self.locks.insert(address.clone(), locks);
+            }
+            self.update_leaf(&address);
+        }
+    }
+}
+
+/// Lookup tables over the canonical chain, kept in step with it
+#[derive(Debug, Clone, Default)]
+struct ChainIndex {
+    /// Height of each canonical block, by hash
+    blocks: HashMap<String, u64>,
+    /// Location of each canonical transaction, by hash
+    transactions: HashMap<String, TransactionLocation>,
+    /// Transactions sending from or to each address, in chain order
+    addresses: HashMap<String, Vec<TransactionLocation>>,
+}
+
+impl ChainIndex {
+    fn build(chain: &[Block]) -> Self {
+        let mut index = Self::default();
+        for block in chain {
+            index.push(block);
+        }
+        index
+    }
+
+    /// Index a block appended to the canonical chain
+    fn push(&mut self, block: &Block) {
+        self.blocks.insert(block.hash.clone(), block.index);
+        for (position, tx) in block.transactions.iter().enumerate() {
+            let location = TransactionLocation { block_index: block.index, position };
+            self.transactions.insert(tx.calculate_hash(), location);
+            self.addresses.entry(tx.from.clone()).or_default().push(location);
+            if tx.to != tx.from {
+                self.addresses.entry(tx.to.clone()).or_default().push(location);
+            }
+        }
+    }
+
+    /// Unindex the canonical tip block; blocks must be removed highest first
+    fn pop(&mut self, block: &Block) {
+        self.blocks.remove(&block.hash);
+        for tx in &block.transactions {
+            let hash = tx.calculate_hash();
+            if self.transactions.get(&hash).is_some_and(|location| location.block_index == block.index) {
+                self.transactions.remove(&hash);
+            }
+            for address in [&tx.from, &tx.to] {
+                if let Some(locations) = self.addresses.get_mut(address) {
+                    while locations.last().is_some_and(|location| location.block_index == block.index) {
+                        locations.pop();
+                    }
+                    if locations.is_empty() {
+                        self.addresses.remove(address);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Unindex `blocks`, the canonical blocks below `height`
+    fn prune_below(&mut self, height: u64, blocks: &[Block]) {
+        let mut touched = HashSet::new();
+        for block in blocks {
+            self.blocks.remove(&block.hash);
+            for tx in &block.transactions {
+                let hash = tx.calculate_hash();
+                if self.transactions.get(&hash).is_some_and(|location| location.block_index < height) {
+                    self.transactions.remove(&hash);
+                }
+                touched.insert(&tx.from);
+                touched.insert(&tx.to);
+            }
+        }
+        for address in touched {
+            if let Some(locations) = self.addresses.get_mut(address) {
+                locations.retain(|location| location.block_index >= height);
+                if locations.is_empty() {
+                    self.addresses.remove(address);
+                }
+            }
+        }
+    }
+}
+
+/// Reward minted by the block at `height` of a chain paying `base_reward`.
+///
+/// It follows from the chain's economics and the height alone: the score
+/// and metrics in a block header are chosen by its producer, so they cannot
+/// decide how much the producer mints. The genesis block mints its
+/// allocations instead.
+pub fn block_reward(base_reward: Amount, height: u64) -> Amount {
+    if height == 0 {
+        Amount::ZERO
+    } else {
+        base_reward
+    }
+}
+
+impl Block {
+    pub fn new(
+        index: u64,
+        transactions: Vec<Transaction>,
+        previous_hash: String,
+        validator: String,
+        validator_score: f64,
+        network_metrics: Option<ZkNetworkMetrics>,
+    ) -> Self {
+        // Count ZK transactions and calculate private transaction root
+        let zk_transaction_count = transactions.iter().filter(|tx| tx.is_private).count() as u64;
+        let has_private_transactions = zk_transaction_count > 0;
+        
+        let private_transaction_root = if has_private_transactions {
+            Some(Self::calculate_private_transaction_root(&transactions))
+        } else {
+            None
+        };
+        let transactions_root = Some(Self::calculate_transactions_root(&transactions));
+        
+        let mut block = Block {
+            index,
+            timestamp: Utc::now().timestamp(),
+            transactions,
+            previous_hash,
+            hash: String::new(),
+            validator,
+            validator_score,
+            network_metrics,
+            zk_transaction_count,
+            private_transaction_root,
+            block_validity_proof: None, // Generated after block creation
+            has_private_transactions,
+            transactions_root,
+            state_root: None, // Set once the block's state transition is applied
+            receipts_root: None,
+            receipts: Vec::new(),
+            producer_key: None,
+            producer_signature: None,
+        };
+        block.hash = block.calculate_hash();
+        block
+    }
+
+    /// Genesis block of the chain described by `spec`: the initial
+    /// allocations and governance address as network transactions, with the
+    /// specification digest in
+    /// place of a parent hash so that any difference in the specification
+    /// yields a different genesis hash
+    pub fn genesis(spec: &GenesisSpec) -> Self {
+        let allocations: Vec<Transaction> = spec
+            .allocations
+            .iter()
+            .map(|allocation| {
+                let mut tx = Transaction::new("network".to_string(), allocation.address.clone(), allocation.amount);
+                tx.timestamp = spec.timestamp;
+                tx.lock = allocation.lock;
+                tx
+            })
+            .collect();
+        let mut transactions = allocations;
+        if let Some(governance) = &spec.governance {
+            let mut tx = Transaction::with_data(
+                "network".to_string(),
+                PROTOCOL_PARAMS_ADDRESS.to_string(),
+                Amount::ZERO,
+                governance.clone().into_bytes(),
+            );
+            tx.timestamp = spec.timestamp;
+            transactions.push(tx);
+        }
+
+        let receipts = transactions.iter().map(TransactionReceipt::applied).collect();
+
+        let mut block = Block::new(
+            0,
+            transactions,
+            hex::encode(spec.spec_hash()),
+            String::from("genesis"),
+            0.0,
+            None,
+        );
+        block.timestamp = spec.timestamp;
+        let state_root = AccountState::replay(std::slice::from_ref(&block)).root();
+        block.with_state_root(state_root).with_receipts(receipts)
+    }
+
+    /// Commit the post-block account state root and rehash the block
+    pub fn with_state_root(mut self, state_root: [u8; 32]) -> Self {
+        self.state_root = Some(state_root);
+        self.hash = self.calculate_hash();
+        self
+    }
+
+    /// Commit the receipts of the block's transactions and rehash the block
+    pub fn with_receipts(mut self, receipts: Vec<TransactionReceipt>) -> Self {
+        self.receipts_root = Some(Self::calculate_receipts_root(&receipts));
+        self.receipts = receipts;
+        self.hash = self.calculate_hash();
+        self
+    }
+
+    /// Sign the final block hash as its producer. `keypair` must own the
+    /// `validator` address.
+    pub fn sign_producer(&mut self, keypair: &Keypair) -> Result<(), anyhow::Error> {
+        if keypair.address() != self.validator {
+            return Err(anyhow::anyhow!("Keypair does not own validator address {}", self.validator));
+        }
+        let signature = keypair.sign(self.hash.as_bytes())?;
+        self.producer_signature = Some(base64::prelude::BASE64_STANDARD.encode(signature.as_bytes()));
+        self.producer_key = Some(keypair.public_key());
+        Ok(())
+    }
+
+    /// Whether the block carries a valid signature by the key owning its
+    /// `validator` address. Only such blocks count towards a validator's
+    /// stake in fork choice.
+    pub fn verify_producer(&self) -> bool {
+        match (&self.producer_key, &self.producer_signature) {
+            (Some(public_key), Some(signature)) => {
+                address_from_public_key(public_key) == self.validator
+                    && verify_detached(signature, self.hash.as_bytes(), public_key)
+            }
+            _ => false,
+        }
+    }
+
+    /// Check the minting rule for a block paid at `base_reward`.
+    ///
+    /// Only the first transaction may be a coinbase, and it must pay the
+    /// block's validator exactly [`block_reward`] for the block's height.
+    /// The genesis block mints its allocations instead.
+    pub fn verify_coinbase(&self, base_reward: Amount) -> Result<(), anyhow::Error> {
+        if self.index == 0 {
+            return Ok(());
+        }
+        for (position, tx) in self.transactions.iter().enumerate().filter(|(_, tx)| tx.is_coinbase()) {
+            if position != 0 {
+                return Err(anyhow::anyhow!("Block {} mints in transaction {}, not its coinbase", self.index, position));
+            }
+            if tx.to != self.validator || tx.nonce != self.index {
+                return Err(anyhow::anyhow!("Block {} has a coinbase for another block or validator", self.index));
+            }
+            let reward = block_reward(base_reward, self.index);
+            if tx.amount != reward {
+                return Err(anyhow::anyhow!(
+                    "Block {} mints {} ZHTP, but its reward is {} ZHTP",
+                    self.index, tx.amount, reward
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that every transparent transaction spending a balance carries
+    /// a valid signature of its sender. Coinbases mint instead of spending
+    /// and private transactions are authorized by their proofs.
+    pub fn verify_senders(&self) -> Result<(), anyhow::Error> {
+        if self.index == 0 {
+            return Ok(());
+        }
+        for tx in &self.transactions {
+            if !tx.is_coinbase() && !tx.is_private && !tx.verify_sender() {
+                return Err(anyhow::anyhow!(
+                    "Block {} contains transaction {} without a valid signature from {}",
+                    self.index,
+                    tx.calculate_hash(),
+                    tx.from
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the block's own validity rules for a chain paying `base_reward`:
+    /// it must carry transactions, mint only through its coinbase and be
+    /// signed by every sender. The limits depend on the parameters in force
+    /// on its branch and are checked by [`Block::check_limits`].
+    pub fn validate(&self, base_reward: Amount) -> Result<(), anyhow::Error> {
+        if self.index > 0 && self.transactions.is_empty() {
+            return Err(anyhow::anyhow!("Block {} carries no transactions", self.index));
+        }
+        self.verify_coinbase(base_reward)?;
+        self.verify_senders()
+    }
+
+    /// Total encoded size of the block's transactions
+    pub fn transactions_size(&self) -> usize {
+        self.transactions.iter().map(Transaction::encoded_size).sum()
+    }
+
+    /// Total gas of the block's transactions: what its receipts charged,
+    /// including contract execution, or the intrinsic gas for blocks from
+    /// before receipts
+    pub fn gas_used(&self) -> u64 {
+        match self.receipts_root {
+            Some(_) => self.receipts.iter().fold(0, |gas, receipt| gas.saturating_add(receipt.gas_used)),
+            None => self.transactions.iter().fold(0, |gas, tx| gas.saturating_add(tx.intrinsic_gas())),
+        }
+    }
+
+    /// Check the block against the size and gas limits of `params`
+    pub fn check_limits(&self, params: &ProtocolParams) -> Result<(), anyhow::Error> {
+        let size = self.transactions_size();
+        if size > params.max_block_bytes {
+            return Err(anyhow::anyhow!("Block {} carries {} transaction bytes, more than {}", self.index, size, params.max_block_bytes));
+        }
+        let gas = self.gas_used();
+        if gas > params.max_block_gas {
+            return Err(anyhow::anyhow!("Block {} uses {} gas, more than {}", self.index, gas, params.max_block_gas));
+        }
+        Ok(())
+    }
+
+    /// Merkle root over the hashes of `receipts`, in block order
+    pub fn calculate_receipts_root(receipts: &[TransactionReceipt]) -> [u8; 32] {
+        let leaves: Vec<[u8; 32]> = receipts.iter().map(TransactionReceipt::hash_bytes).collect();
+        merkle::merkle_root(&leaves)
+    }
+
+    /// Merkle root over the hashes of `transactions`, in block order
+    pub fn calculate_transactions_root(transactions: &[Transaction]) -> [u8; 32] {
+        let leaves: Vec<[u8; 32]> = transactions.iter().map(Transaction::hash_bytes).collect();
+        merkle::merkle_root(&leaves)
+    }
+
+    /// Inclusion proof for the transaction at `position` in this block
+    pub fn prove_transaction(&self, position: usize) -> Option<MerkleProof> {
+        let leaves: Vec<[u8; 32]> = self.transactions.iter().map(Transaction::hash_bytes).collect();
+        MerkleProof::generate(&leaves, position)
+    }
+
+    /// Calculate Merkle root of private transaction commitments
+    fn calculate_private_transaction_root(transactions: &[Transaction]) -> [u8; 32] {
+        let private_hashes: Vec<[u8; 32]> = transactions
+            .iter()
+            .filter(|tx| tx.is_private)
+            .map(|tx| {
+                if let Some(zk_tx) = &tx.zk_transaction {
+                    zk_tx.commitment
+                } else {
+                    [0u8; 32] // Fallback for invalid private transactions
+                }
+            })
+            .collect();
+        
+        if private_hashes.is_empty() {
+            return [0u8; 32];
+        }
+        
+        // Simple Merkle root calculation (in production, use proper Merkle tree)
+        let mut hasher = Sha256::new();
+        for hash in &private_hashes {
+            hasher.update(hash);
+        }
+        let result = hasher.finalize();
+        let mut root = [0u8; 32];
+        root.copy_from_slice(&result[..32]);
+        root
+    }
+
+    pub fn calculate_hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        let data = format!(
+            "{}{}{}{}{}{}",
+            self.index,
+            self.timestamp,
+            serde_json::to_string(&self.transactions).unwrap(),
+            self.previous_hash,
+            self.validator,
+            self.validator_score
+        );
+        hasher.update(data.as_bytes());
+        // Header roots only enter the preimage when present, so blocks
+        // created before they existed keep their hashes
+        if let Some(root) = &self.transactions_root {
+            hasher.update(root);
+        }
+        if let Some(root) = &self.state_root {
+            hasher.update(root);
+        }
+        if let Some(root) = &self.receipts_root {
+            hasher.update(root);
+        }
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ChainState {
+    /// Canonical blocks from height `base` to the tip
+    chain: Vec<Block>,
+    /// Height of the first block in `chain`: zero unless the chain was
+    /// started from a snapshot or pruned
+    base: u64,
+    /// Headers of the canonical blocks below `base`. Those taken from a
+    /// snapshot are only linked by hash to its tip, not verified.
+    base_headers: Vec<BlockHeader>,
+    /// Account state after block `base` when that is not genesis, the
+    /// starting point for rebuilding the state without replaying from genesis
+    base_accounts: Option<AccountState>,
+    /// Transactions waiting to be included in a block
+    mempool: Mempool,
+    /// Account state as of the tip block
+    accounts: AccountState,
+    // Zero-knowledge transaction management
+    zk_transaction_pool: ZkTransactionPool,
+    private_balances: HashMap<String, ZkBalance>,
+    /// Known blocks that are not on the canonical chain, by hash
+    side_blocks: HashMap<String, Block>,
+    /// Undo journals of recent canonical blocks, by hash
+    undo_log: HashMap<String, AccountUndo>,
+    /// Height of the latest finalized canonical block
+    finalized_height: u64,
+    /// Validator stakes used to weigh branches
+    validator_stakes: HashMap<String, Amount>,
+    /// Receipts of transactions dropped during block production, by hash,
+    /// with the height of the block they were dropped from
+    rejected_receipts: HashMap<String, (u64, TransactionReceipt)>,
+    /// Explorer lookups over `chain`
+    index: ChainIndex,
+}
+
+impl ChainState {
+    fn from_genesis(spec: &GenesisSpec) -> Self {
+        let genesis = Block::genesis(spec);
+        let accounts = AccountState::replay(std::slice::from_ref(&genesis));
+        let mut state = Self::with_chain(vec![genesis], accounts);
+        state.validator_stakes = Self::genesis_stakes(spec);
+        state
+    }
+
+    /// State over `chain`, with `accounts` as of its last block and nothing
+    /// else known yet
+    fn with_chain(chain: Vec<Block>, accounts: AccountState) -> Self {
+        let base = chain.first().map_or(0, |block| block.index);
+        Self {
+            index: ChainIndex::build(&chain),
+            chain,
+            base,
+            base_headers: Vec::new(),
+            base_accounts: None,
+            mempool: Mempool::new(MempoolConfig::default()),
+            accounts,
+            zk_transaction_pool: ZkTransactionPool::new(),
+            private_balances: HashMap::new(),
+            side_blocks: HashMap::new(),
+            undo_log: HashMap::new(),
+            finalized_height: base,
+            validator_stakes: HashMap::new(),
+            rejected_receipts: HashMap::new(),
+        }
+    }
+
+    /// Chain state starting at the tip of a verified snapshot. The tip is
+    /// the only block held; headers below it are kept for queries and it is
+    /// final, as nothing below it could be reorganized.
+    fn from_snapshot(snapshot: &ChainSnapshot) -> Result<Self, anyhow::Error> {
+        let accounts = Self::snapshot_base(snapshot)?;
+        let mut state = Self::with_chain(vec![snapshot.tip.clone()], accounts.clone());
+        state.base_headers = snapshot.headers[..snapshot.height() as usize].to_vec();
+        state.base_accounts = Some(accounts);
+        state.private_balances = snapshot.private_balances.clone().into_iter().collect();
+        Ok(state)
+    }
+
+    /// Account state of a snapshot, checked against its tip's state root
+    fn snapshot_base(snapshot: &ChainSnapshot) -> Result<AccountState, anyhow::Error> {
+        Self::verify_block(&snapshot.tip)?;
+        let accounts = AccountState::from_snapshot(snapshot);
+        if Some(accounts.root()) != snapshot.tip.state_root {
+            return Err(anyhow::anyhow!("Snapshot accounts do not match state root of block {}", snapshot.height()));
+        }
+        Ok(accounts)
+    }
+
+    /// Snapshot of the canonical chain at `height`. Private balances are only
+    /// included for the tip, the one height they are known at.
+    fn snapshot(&self, height: u64) -> Result<ChainSnapshot, anyhow::Error> {
+        let tip = self
+            .block_at(height)
+            .ok_or_else(|| anyhow::anyhow!("Block {} is not held by this node", height))?;
+        if tip.state_root.is_none() {
+            return Err(anyhow::anyhow!("Block {} has no state root to snapshot", height));
+        }
+        let (accounts, shielded, params, private_balances) = if height == self.tip().index {
+            (
+                self.accounts.snapshot_accounts(),
+                self.accounts.shielded.clone(),
+                self.accounts.params.clone(),
+                self.private_balances.clone().into_iter().collect(),
+            )
+        } else {
+            let state = self.replay_window(self.offset(height) + 1);
+            (state.snapshot_accounts(), state.shielded, state.params, BTreeMap::new())
+        };
+        Ok(ChainSnapshot {
+            version: SNAPSHOT_FORMAT_VERSION,
+            headers: (0..=height).filter_map(|height| self.header_at(height)).collect(),
+            tip: tip.clone(),
+            accounts,
+            shielded,
+            params,
+            private_balances,
+        })
+    }
+
+    fn genesis_stakes(spec: &GenesisSpec) -> HashMap<String, Amount> {
+        spec.validators
+            .iter()
+            .map(|validator| (validator.address.clone(), validator.stake))
+            .collect()
+    }
+
+    /// Rebuild chain state from stored blocks, re-verifying every block.
+    /// Blocks start at genesis, or at the tip of `snapshot` if the chain was
+    /// started from one.
+    ///
+    /// The saved account state is only trusted if it was written for the
+    /// current tip and matches the tip's state root; otherwise it is rebuilt
+    /// by replaying the log.
+    fn from_stored(
+        chain: Vec<Block>,
+        saved: Option<AccountStateIndex>,
+        snapshot: Option<&ChainSnapshot>,
+    ) -> Result<Self, anyhow::Error> {
+        Self::verify_chain(&chain)?;
+
+        let first = chain.first().ok_or_else(|| anyhow::anyhow!("Stored chain is empty"))?;
+        let base_accounts = match snapshot {
+            Some(snapshot) if snapshot.tip.hash == first.hash => Some(Self::snapshot_base(snapshot)?),
+            Some(snapshot) => {
+                return Err(anyhow::anyhow!("Stored chain does not start at snapshot block {}", snapshot.height()));
+            }
+            None if first.index == 0 => None,
+            None => return Err(anyhow::anyhow!("Stored chain starts at block {} without a snapshot", first.index)),
+        };
+
+        let tip = chain.last().ok_or_else(|| anyhow::anyhow!("Stored chain is empty"))?;
+        let saved = saved.unwrap_or_default();
+        let matches_tip = |accounts: &AccountState| tip.state_root.is_none_or(|root| accounts.root() == root);
+
+        let saved_accounts = (saved.height == tip.index && saved.tip_hash == tip.hash)
+            .then(|| AccountState::new(saved.balances, saved.nonces, saved.locks, saved.shielded, saved.params))
+            .filter(|accounts| matches_tip(accounts));
+        let accounts = match saved_accounts {
+            Some(accounts) => accounts,
+            None => {
+                log::warn!(
+                    "Account state index at height {} does not match chain tip {}, replaying blocks",
+                    saved.height, tip.index
+                );
+                let accounts = match &base_accounts {
+                    Some(base) => base.clone().replay_onto(&chain[1..]),
+                    None => AccountState::replay(&chain),
+                };
+                if !matches_tip(&accounts) {
+                    return Err(anyhow::anyhow!("Account state does not match state root of block {}", tip.index));
+                }
+                accounts
+            }
+        };
+
+        let mut state = Self::with_chain(chain, accounts);
+        state.private_balances = saved.private_balances;
+        if let Some(snapshot) = snapshot {
+            state.base_headers = snapshot.headers[..snapshot.height() as usize].to_vec();
+            state.base_accounts = base_accounts;
+        }
+        Ok(state)
+    }
+
+    /// Check block numbering, hash linkage and block hashes
+    fn verify_chain(chain: &[Block]) -> Result<(), anyhow::Error> {
+        let base = chain.first().map_or(0, |block| block.index);
+        for (position, block) in chain.iter().enumerate() {
+            let height = base + position as u64;
+            if block.index != height {
+                return Err(anyhow::anyhow!("Stored block at height {} has index {}", height, block.index));
+            }
+            Self::verify_block(block)?;
+            if position > 0 && block.previous_hash != chain[position - 1].hash {
+                return Err(anyhow::anyhow!("Stored block {} does not link to its parent", height));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that a block's hash and roots match its contents. Receipts are
+    /// checked against execution when the block is applied.
+    fn verify_block(block: &Block) -> Result<(), anyhow::Error> {
+        if block.hash != block.calculate_hash() {
+            return Err(anyhow::anyhow!("Block {} has an invalid hash", block.index));
+        }
+        if let Some(root) = block.transactions_root {
+            if root != Block::calculate_transactions_root(&block.transactions) {
+                return Err(anyhow::anyhow!("Block {} has an invalid transactions root", block.index));
+            }
+        }
+        if let Some(root) = block.receipts_root {
+            if block.receipts.len() != block.transactions.len() || root != Block::calculate_receipts_root(&block.receipts) {
+                return Err(anyhow::anyhow!("Block {} has invalid receipts", block.index));
+            }
+        }
+        Ok(())
+    }
+
+    fn tip(&self) -> &Block {
+        self.chain.last().expect("chain always holds the genesis block")
+    }
+
+    /// Position of the block at `height` in `chain`; `height` must not be
+    /// below `base`
+    fn offset(&self, height: u64) -> usize {
+        (height - self.base) as usize
+    }
+
+    /// Canonical block at `height`, if held
+    fn block_at(&self, height: u64) -> Option<&Block> {
+        self.chain.get(height.checked_sub(self.base)? as usize)
+    }
+
+    /// Header of the canonical block at `height`, including blocks below
+    /// the snapshot the chain started from
+    fn header_at(&self, height: u64) -> Option<BlockHeader> {
+        match self.base_headers.get(height as usize) {
+            Some(header) => Some(header.clone()),
+            None => self.block_at(height).map(BlockHeader::from),
+        }
+    }
+
+    fn genesis_hash(&self) -> &str {
+        match self.base_headers.first() {
+            Some(genesis) => &genesis.hash,
+            None => &self.chain[0].hash,
+        }
+    }
+
+    /// Account state after the first `len` blocks of `chain`
+    fn replay_window(&self, len: usize) -> AccountState {
+        match &self.base_accounts {
+            Some(base) => base.clone().replay_onto(&self.chain[1..len]),
+            None => AccountState::replay(&self.chain[..len]),
+        }
+    }
+
+    /// Canonical block at `height` if it has hash `hash`
+    fn canonical_block(&self, height: u64, hash: &str) -> Option<&Block> {
+        self.block_at(height).filter(|block| block.hash == hash)
+    }
+
+    /// Side branch ending in `tip`, lowest block first, and the height of
+    /// the canonical block it forks from
+    fn side_branch(&self, tip: &Block) -> Option<(u64, Vec<Block>)> {
+        let mut branch = vec![tip.clone()];
+        loop {
+            let lowest = branch.last()?;
+            let parent_height = lowest.index.checked_sub(1)?;
+            if self.canonical_block(parent_height, &lowest.previous_hash).is_some() {
+                branch.reverse();
+                return Some((parent_height, branch));
+            }
+            let parent = self.side_blocks.get(&lowest.previous_hash)?;
+            branch.push(parent.clone());
+        }
+    }
+
+    /// Total stake of the validators that produced `blocks`. A block only
+    /// counts if its producer signature verifies, so nobody can claim
+    /// another validator's stake by naming it.
+    fn branch_weight<'a>(&self, blocks: impl Iterator<Item = &'a Block>) -> Amount {
+        blocks.filter(|block| block.verify_producer()).fold(Amount::ZERO, |weight, block| {
+            let stake = self.validator_stakes.get(&block.validator).copied().unwrap_or(Amount::ZERO);
+            weight.saturating_add(stake)
+        })
+    }
+
+    /// Whether a branch forking at `fork_height` should replace the
+    /// canonical blocks above that height
+    fn prefers_branch(&self, rule: ForkChoice, fork_height: u64, branch: &[Block]) -> bool {
+        if fork_height < self.finalized_height || self.tip().index - fork_height > MAX_REORG_DEPTH {
+            return false;
+        }
+
+        let current = &self.chain[self.offset(fork_height) + 1..];
+        let branch_score = (self.branch_weight(branch.iter()), branch.len());
+        let current_score = (self.branch_weight(current.iter()), current.len());
+        match rule {
+            ForkChoice::HeaviestStake => branch_score > current_score,
+            ForkChoice::HighestFinalized => {
+                (branch_score.1, branch_score.0) > (current_score.1, current_score.0)
+            }
+        }
+    }
+
+    /// Make `branch` canonical in place of the blocks above `fork_height`.
+    ///
+    /// Account state is rolled back with the undo journals of the reverted
+    /// blocks (or replayed from genesis or the starting snapshot when they
+    /// are missing, e.g. after a restart) and rolled forward through the
+    /// branch. On error nothing is
+    /// changed and the offending branch blocks are dropped.
+    fn reorganize(&mut self, fork_height: u64, branch: Vec<Block>) -> Result<ReorgEvent, anyhow::Error> {
+        let keep = self.offset(fork_height) + 1;
+        let mut accounts = self.accounts.clone();
+        for block in self.chain[keep..].iter().rev() {
+            match self.undo_log.get(&block.hash) {
+                Some(undo) => accounts.revert(undo.clone()),
+                None => {
+                    accounts = self.replay_window(keep);
+                    break;
+                }
+            }
+        }
+
+        let mut undos = Vec::with_capacity(branch.len());
+        for (position, block) in branch.iter().enumerate() {
+            match accounts.apply_block(block) {
+                Ok(undo) => undos.push(undo),
+                Err(e) => {
+                    for invalid in &branch[position..] {
+                        self.side_blocks.remove(&invalid.hash);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let old_tip = self.tip().hash.clone();
+        let reverted = self.chain.split_off(keep);
+        for block in reverted.iter().rev() {
+            self.undo_log.remove(&block.hash);
+            self.index.pop(block);
+        }
+        for (block, undo) in branch.iter().zip(undos) {
+            self.side_blocks.remove(&block.hash);
+            self.undo_log.insert(block.hash.clone(), undo);
+            self.index.push(block);
+        }
+        self.chain.extend(branch.iter().cloned());
+        self.accounts = accounts;
+
+        // Return user transactions of the reverted blocks to the pending pool
+        let orphaned: Vec<Transaction> = reverted
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .filter(|tx| tx.from != "network")
+            .cloned()
+            .collect();
+        let requeued = self.requeue_transactions(orphaned, &branch);
+
+        let event = ReorgEvent {
+            fork_height,
+            old_tip,
+            new_tip: self.tip().hash.clone(),
+            reverted_blocks: reverted.iter().map(|block| block.hash.clone()).collect(),
+            applied_blocks: branch.iter().map(|block| block.hash.clone()).collect(),
+            requeued_transactions: requeued,
+        };
+        for block in reverted {
+            self.side_blocks.insert(block.hash.clone(), block);
+        }
+        self.prune_fork_data();
+        Ok(event)
+    }
+
+    /// Reorganize back to the blocks `event` reverted, e.g. when the new
+    /// branch could not be persisted
+    fn undo_reorganization(&mut self, event: &ReorgEvent) -> Result<(), anyhow::Error> {
+        let blocks = event
+            .reverted_blocks
+            .iter()
+            .map(|hash| {
+                self.side_blocks
+                    .get(hash)
+                    .cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Reverted block {} is no longer held", hash))
+            })
+            .collect::<Result<Vec<Block>, anyhow::Error>>()?;
+        self.reorganize(event.fork_height, blocks)?;
+        Ok(())
+    }
+
+    /// Remove the tip block, e.g. when it could not be persisted. Its user
+    /// transactions return to the pending pool.
+    fn revert_tip(&mut self) -> Result<(), anyhow::Error> {
+        let (index, hash) = (self.tip().index, self.tip().hash.clone());
+        if index <= self.base {
+            return Err(anyhow::anyhow!("Cannot revert the base block {}", index));
+        }
+        let undo = self
+            .undo_log
+            .remove(&hash)
+            .ok_or_else(|| anyhow::anyhow!("No undo journal for block {}", index))?;
+        let block = self.chain.pop().expect("the tip is above the base");
+        self.accounts.revert(undo);
+        self.index.pop(&block);
+        let orphaned = block.transactions.into_iter().filter(|tx| tx.from != "network").collect();
+        self.requeue_transactions(orphaned, &[]);
+        Ok(())
+    }
+
+    /// Update the mempools after `blocks` became canonical: drop what they
+    /// include or what can no longer execute, then return `orphaned`
+    /// transactions to the pool. Returns the orphaned transactions that were
+    /// readmitted.
+    fn requeue_transactions(&mut self, orphaned: Vec<Transaction>, blocks: &[Block]) -> Vec<Transaction> {
+        let included: Vec<Transaction> = blocks
+            .iter()
+            .flat_map(|block| block.transactions.iter().cloned())
+            .collect();
+        let accounts = &self.accounts;
+        self.mempool.remove_committed(&included, |sender| accounts.nonce(sender));
+        self.retire_zk_transactions(&included);
+
+        let included: HashSet<String> = included.iter().map(Transaction::calculate_hash).collect();
+        let now = Utc::now().timestamp();
+        orphaned
+            .into_iter()
+            .filter(|tx| !included.contains(&tx.calculate_hash()))
+            .filter(|tx| {
+                let committed = self.accounts.nonce(&tx.from);
+                self.mempool.insert(tx.clone(), committed, now).is_ok()
+            })
+            .collect()
+    }
+
+    /// Transaction at `location` on the canonical chain
+    fn indexed_transaction(&self, location: TransactionLocation) -> Option<IndexedTransaction> {
+        let block = self.block_at(location.block_index)?;
+        Some(IndexedTransaction {
+            transaction: block.transactions.get(location.position)?.clone(),
+            block_index: block.index,
+            block_hash: block.hash.clone(),
+            position: location.position,
+            receipt: block.receipts.get(location.position).cloned(),
+        })
+    }
+
+    /// Forget side blocks and undo journals that can no longer take part in
+    /// a reorganization, and the lowest side blocks beyond
+    /// [`MAX_SIDE_BLOCKS`]
+    fn prune_fork_data(&mut self) {
+        let tip = self.tip().index;
+        let horizon = self.reorg_horizon();
+        self.side_blocks.retain(|_, block| block.index > horizon);
+        if self.side_blocks.len() > MAX_SIDE_BLOCKS {
+            let mut by_height: Vec<(u64, String)> =
+                self.side_blocks.values().map(|block| (block.index, block.hash.clone())).collect();
+            by_height.sort_unstable();
+            for (_, hash) in &by_height[..by_height.len() - MAX_SIDE_BLOCKS] {
+                self.side_blocks.remove(hash);
+            }
+        }
+        let recent: HashSet<&String> = self.chain[self.offset(horizon) + 1..].iter().map(|block| &block.hash).collect();
+        self.undo_log.retain(|hash, _| recent.contains(hash));
+        let receipt_horizon = tip.saturating_sub(MAX_REORG_DEPTH);
+        self.rejected_receipts.retain(|_, (height, _)| *height > receipt_horizon);
+    }
+
+    /// Height at and below which no canonical block can be reorganized
+    fn reorg_horizon(&self) -> u64 {
+        self.finalized_height.max(self.tip().index.saturating_sub(MAX_REORG_DEPTH))
+    }
+
+    /// Height the held chain should start at under `mode`, if that is above
+    /// the current base
+    fn prune_height(&self, mode: PruningMode) -> Option<u64> {
+        let height = mode.retain_from(self.tip().index, self.reorg_horizon());
+        (height > self.base).then_some(height)
+    }
+
+    /// Drop the canonical blocks below `height`, keeping their headers;
+    /// `accounts` is the account state after block `height`
+    fn advance_base(&mut self, height: u64, accounts: AccountState) {
+        let pruned: Vec<Block> = self.chain.drain(..self.offset(height)).collect();
+        self.index.prune_below(height, &pruned);
+        self.base_headers.extend(pruned.iter().map(BlockHeader::from));
+        self.base = height;
+        self.base_accounts = Some(accounts);
+        self.finalized_height = self.finalized_height.max(height);
+    }
+
+    /// Drop the ZK transactions included in `transactions` from the pending
+    /// ZK pool. Their value moved when the block was applied.
+    fn retire_zk_transactions(&mut self, transactions: &[Transaction]) {
+        for zk_tx in transactions.iter().filter(|tx| tx.is_private).filter_map(|tx| tx.zk_transaction.as_ref()) {
+            self.zk_transaction_pool.remove_transaction(&zk_tx.get_hash());
+        }
+    }
+
+    /// Snapshot of the account state for the persistent index
+    fn account_state_index(&self) -> AccountStateIndex {
+        let tip = self.chain.last().expect("chain always holds the genesis block");
+        AccountStateIndex {
+            height: tip.index,
+            tip_hash: tip.hash.clone(),
+            balances: self.accounts.balances.clone(),
+            nonces: self.accounts.nonces.clone(),
+            locks: self.accounts.locks.clone(),
+            shielded: self.accounts.shielded.clone(),
+            params: self.accounts.params.clone(),
+            private_balances: self.private_balances.clone(),
+        }
+    }
+}
+
+/// Chain data captured under the state lock, written to the [`ChainStore`]
+/// after the lock is released
+#[derive(Default)]
+struct PendingWrite {
+    store: Option<OwnedMutexGuard<ChainStore>>,
+    truncate: Option<u64>,
+    blocks: Vec<Block>,
+    state: Option<AccountStateIndex>,
+}
+
+impl PendingWrite {
+    /// Write to disk on a blocking thread, keeping file writes and syncs off
+    /// the async runtime. The store lock is handed back so a failed write can
+    /// be rolled back in memory before another writer runs.
+    async fn commit(self) -> (Option<OwnedMutexGuard<ChainStore>>, Result<(), anyhow::Error>) {
+        let PendingWrite { store, truncate, blocks, state } = self;
+        let Some(mut store) = store else {
+            return (None, Ok(()));
+        };
+        let written = tokio::task::spawn_blocking(move || {
+            let result = (|| {
+                if let Some(height) = truncate {
+                    store.truncate(height)?;
+                }
+                for block in &blocks {
+                    store.append_block(block)?;
+                }
+                match &state {
+                    Some(state) => store.save_state(state),
+                    None => Ok(()),
+                }
+            })();
+            (store, result)
+        })
+        .await;
+        match written {
+            Ok((store, result)) => (Some(store), result),
+            Err(e) => (None, Err(anyhow::anyhow!("Chain store write failed: {}", e))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Blockchain {
+    state: Arc<RwLock<ChainState>>,
+    pub base_reward: Amount,
+    /// Rule used by [`Blockchain::import_block`] to pick the canonical branch
+    pub fork_choice: ForkChoice,
+    /// Block bodies kept by [`Blockchain::prune`]
+    pub pruning: PruningMode,
+    /// On-disk chain storage, absent for purely in-memory chains
+    store: Option<Arc<Mutex<ChainStore>>>,
+    reorg_events: broadcast::Sender<ReorgEvent>,
+}
+
+impl Blockchain {
+    pub fn new(base_reward: Amount) -> Self {
+        Self::with_state(ChainState::from_genesis(&GenesisSpec::default()), base_reward, None)
+    }
+
+    /// In-memory chain for the network described by `spec`
+    pub fn from_genesis(spec: &GenesisSpec) -> Self {
+        Self::with_state(ChainState::from_genesis(spec), spec.economics.block_reward, None)
+    }
+
+    fn with_state(state: ChainState, base_reward: Amount, store: Option<ChainStore>) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(state)),
+            base_reward,
+            fork_choice: ForkChoice::default(),
+            pruning: PruningMode::default(),
+            store: store.map(|store| Arc::new(Mutex::new(store))),
+            reorg_events: broadcast::channel(REORG_EVENT_CAPACITY).0,
+        }
+    }
+
+    /// Open a blockchain persisted under `data_dir`, creating it from the
+    /// development genesis if needed.
+    ///
+    /// Stored blocks are re-verified on load and a torn write at the end of
+    /// the block log is discarded.
+    pub fn open<P: AsRef<Path>>(base_reward: Amount, data_dir: P) -> Result<Self, anyhow::Error> {
+        Self::open_chain(&GenesisSpec::default(), base_reward, data_dir, false)
+    }
+
+    /// Open the chain of the network described by `spec` persisted under
+    /// `data_dir`, creating it from the spec's genesis if needed. Fails if
+    /// the stored chain starts from a different genesis block.
+    pub fn open_with_genesis<P: AsRef<Path>>(spec: &GenesisSpec, data_dir: P) -> Result<Self, anyhow::Error> {
+        Self::open_chain(spec, spec.economics.block_reward, data_dir, true)
+    }
+
+    fn open_chain<P: AsRef<Path>>(
+        spec: &GenesisSpec,
+        base_reward: Amount,
+        data_dir: P,
+        require_genesis: bool,
+    ) -> Result<Self, anyhow::Error> {
+        let mut store = ChainStore::open(data_dir)?;
+        let blocks = store.load_chain()?;
+
+        let state = if blocks.is_empty() {
+            let state = ChainState::from_genesis(spec);
+            store.append_block(&state.chain[0])?;
+            store.save_state(&state.account_state_index())?;
+            state
+        } else {
+            let snapshot = store.load_snapshot()?;
+            let mut state = ChainState::from_stored(blocks, store.load_state()?, snapshot.as_ref())?;
+            let expected = Block::genesis(spec).hash;
+            if require_genesis && state.genesis_hash() != expected {
+                return Err(anyhow::anyhow!(
+                    "Stored chain starts from genesis {}, but the genesis for chain {} is {}",
+                    state.genesis_hash(), spec.chain_id, expected
+                ));
+            }
+            state.validator_stakes = ChainState::genesis_stakes(spec);
+            store.save_state(&state.account_state_index())?;
+            state
+        };
+
+        println!("📦 Loaded blockchain at height {}", state.tip().index);
+
+        Ok(Self::with_state(state, base_reward, Some(store)))
+    }
+
+    /// In-memory chain for the network described by `spec`, started from a
+    /// snapshot taken at the block with hash `trusted_hash`
+    pub fn from_snapshot(spec: &GenesisSpec, snapshot: &ChainSnapshot, trusted_hash: &str) -> Result<Self, anyhow::Error> {
+        let state = Self::snapshot_state(spec, snapshot, trusted_hash)?;
+        Ok(Self::with_state(state, spec.economics.block_reward, None))
+    }
+
+    /// Replace the chain persisted under `data_dir` with one started from a
+    /// snapshot taken at the block with hash `trusted_hash`, and open it.
+    ///
+    /// The snapshot is verified before anything on disk is touched.
+    pub fn import_snapshot<P: AsRef<Path>>(
+        spec: &GenesisSpec,
+        snapshot: &ChainSnapshot,
+        trusted_hash: &str,
+        data_dir: P,
+    ) -> Result<Self, anyhow::Error> {
+        let state = Self::snapshot_state(spec, snapshot, trusted_hash)?;
+        let mut store = ChainStore::open(data_dir)?;
+        store.reset_to_snapshot(snapshot)?;
+        store.append_block(&snapshot.tip)?;
+        store.save_state(&state.account_state_index())?;
+        println!("📸 Imported snapshot at height {}", snapshot.height());
+        Ok(Self::with_state(state, spec.economics.block_reward, Some(store)))
+    }
+
+    fn snapshot_state(spec: &GenesisSpec, snapshot: &ChainSnapshot, trusted_hash: &str) -> Result<ChainState, anyhow::Error> {
+        snapshot.verify(trusted_hash)?;
+        let expected = Block::genesis(spec).hash;
+        if snapshot.genesis_hash() != expected {
+            return Err(anyhow::anyhow!(
+                "Snapshot starts from genesis {}, but the genesis for chain {} is {}",
+                snapshot.genesis_hash(), spec.chain_id, expected
+            ));
+        }
+        let mut state = ChainState::from_snapshot(snapshot)?;
+        state.validator_stakes = ChainState::genesis_stakes(spec);
+        Ok(state)
+    }
+
+    /// Snapshot of the canonical chain at `height`, for bootstrapping other
+    /// nodes. Snapshots below the tip rebuild the account state by replaying
+    /// the blocks up to `height`.
+    pub async fn create_snapshot(&self, height: u64) -> Result<ChainSnapshot, anyhow::Error> {
+        self.state.read().await.snapshot(height)
+    }
+
+    /// Drop the block bodies [`Blockchain::pruning`] no longer keeps, in
+    /// memory and on disk. Returns the height the held chain now starts at
+    /// if anything was pruned.
+    ///
+    /// The account state at that height is rebuilt before the chain is
+    /// locked for writing. Headers, balances and explorer lookups of the
+    /// remaining blocks are unaffected.
+    pub async fn prune(&self) -> Result<Option<u64>, anyhow::Error> {
+        let snapshot = {
+            let state = self.state.read().await;
+            match state.prune_height(self.pruning) {
+                Some(height) => state.snapshot(height)?,
+                None => return Ok(None),
+            }
+        };
+        let accounts = ChainState::snapshot_base(&snapshot)?;
+
+        let store = self.lock_store().await;
+        let mut state = self.state.write().await;
+        let height = snapshot.height();
+        // Blocks may have been pruned or reorganized in the meantime
+        if height <= state.base || state.canonical_block(height, &snapshot.tip.hash).is_none() {
+            return Ok(None);
+        }
+        if let Some(mut store) = store {
+            store.prune_to(&snapshot)?;
+        }
+        state.advance_base(height, accounts);
+        Ok(Some(height))
+    }
+
+    /// Hash of the block this chain starts from
+    pub async fn genesis_hash(&self) -> String {
+        self.state.read().await.genesis_hash().to_string()
+    }
+
+    /// Block limits in force for the next block
+    pub async fn protocol_params(&self) -> ProtocolParams {
+        let state = self.state.read().await;
+        state.accounts.params.params_at(state.tip().index + 1)
+    }
+
+    /// Governance address and parameter upgrades committed by the chain
+    pub async fn params_schedule(&self) -> ParamsSchedule {
+        self.state.read().await.accounts.params.clone()
+    }
+
+    /// Submit an upgrade to new block limits, e.g. once a DAO protocol
+    /// upgrade passed. `keypair` must own the chain's governance address.
+    /// The upgrade activates [`PARAMS_ACTIVATION_DELAY`] blocks after the
+    /// latest block it can be included in, leaving that many blocks to
+    /// include it.
+    pub async fn schedule_protocol_params(
+        &self,
+        params: ProtocolParams,
+        keypair: &Keypair,
+    ) -> Result<ParamsUpgrade, anyhow::Error> {
+        params.validate()?;
+        let (upgrade, nonce) = {
+            let state = self.state.read().await;
+            let governance = state.accounts.params.governance.as_deref();
+            if governance != Some(keypair.address().as_str()) {
+                return Err(anyhow::anyhow!("Key {} is not the governance address of this chain", keypair.address()));
+            }
+            let activation_height = state.tip().index + 2 * PARAMS_ACTIVATION_DELAY;
+            let committed = state.accounts.nonce(&keypair.address());
+            (ParamsUpgrade { activation_height, params }, state.mempool.next_nonce(&keypair.address(), committed))
+        };
+        let mut tx = Transaction::params_upgrade(keypair.address(), &upgrade);
+        tx.nonce = nonce;
+        tx.sign_with_keypair(keypair)?;
+        self.submit_transaction(tx).await?;
+        Ok(upgrade)
+    }
+
+    /// Lock the chain store. Every writer takes it before the state lock, so
+    /// writes land in the order the state changed and a write that fails can
+    /// be rolled back before the next writer sees the state.
+    async fn lock_store(&self) -> Option<OwnedMutexGuard<ChainStore>> {
+        match &self.store {
+            Some(store) => Some(Arc::clone(store).lock_owned().await),
+            None => None,
+        }
+    }
+
+    /// Capture the blocks missing from the log, after dropping those at or
+    /// above `truncate` when a reorganization replaced them, and the account
+    /// state, to be committed once the state lock is released
+    fn pending_write(store: Option<OwnedMutexGuard<ChainStore>>, state: &ChainState, truncate: Option<u64>) -> PendingWrite {
+        let Some(store) = store else {
+            return PendingWrite::default();
+        };
+        let mut stored = store.len();
+        if let Some(height) = truncate {
+            stored = stored.min(height.saturating_sub(store.base()));
+        }
+        let stored = (stored as usize).min(state.chain.len());
+        PendingWrite {
+            blocks: state.chain[stored..].to_vec(),
+            state: Some(state.account_state_index()),
+            truncate,
+            store: Some(store),
+        }
+    }
+
+    /// Submit a transaction to the mempool.
+    ///
+    /// The transaction must be signed by its sender, who must be able to
+    /// cover amount and fee from its committed balance, and the nonce must
+    /// continue its committed or pooled transactions. A transaction reusing a
+    /// pooled nonce replaces the pooled one if it pays a sufficiently higher
+    /// fee rate. Private transactions, such as unshields, go through
+    /// [`Blockchain::add_zk_transaction`].
+    pub async fn submit_transaction(&self, transaction: Transaction) -> Result<(), anyhow::Error> {
+        if transaction.is_private {
+            let zk_transaction = transaction
+                .zk_transaction
+                .ok_or_else(|| anyhow::anyhow!("Private transaction carries no ZK transaction"))?;
+            if !self.add_zk_transaction(zk_transaction).await? {
+                return Err(anyhow::anyhow!("Private transaction was rejected by the ZK pool"));
+            }
+            return Ok(());
+        }
+        if transaction.from.is_empty() || transaction.to.is_empty() {
+            return Err(anyhow::anyhow!("Transaction is missing a sender or recipient"));
+        }
+        if transaction.is_coinbase() {
+            return Err(anyhow::anyhow!("Network transactions are only minted by block coinbases"));
+        }
+        if !transaction.verify_sender() {
+            return Err(match transaction.multisig {
+                Some(_) => anyhow::anyhow!("Multisig transaction lacks enough valid signatures"),
+                None => TransactionError::InvalidSignature.into(),
+            });
+        }
+        let bundle = transaction.shielded_bundle();
+        if transaction.to == SHIELDED_POOL_ADDRESS || bundle.is_some() {
+            let valid = transaction.to == SHIELDED_POOL_ADDRESS
+                && transaction.lock.is_none()
+                && bundle.is_some_and(|bundle| bundle.value_in == transaction.amount && bundle.verify().is_ok());
+            if !valid {
+                return Err(TransactionError::InvalidShieldedBundle.into());
+            }
+        }
+
+        let mut state = self.state.write().await;
+        let now = Utc::now().timestamp();
+        let balance = state.accounts.balance(&transaction.from);
+        let required = transaction.amount.saturating_add(transaction.fee);
+        if balance < required {
+            return Err(TransactionError::InsufficientFunds { balance, required }.into());
+        }
+        let spendable = state.accounts.spendable(&transaction.from, now);
+        if spendable < required {
+            return Err(TransactionError::LockedFunds { spendable, required }.into());
+        }
+
+        let committed = state.accounts.nonce(&transaction.from);
+        state.mempool.insert(transaction, committed, now)?;
+        Ok(())
+    }
+
+    pub async fn add_transaction(&self, transaction: Transaction) -> bool {
+        match self.submit_transaction(transaction).await {
+            Ok(()) => true,
+            Err(e) => {
+                log::debug!("Rejected transaction: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Pending transactions a block could include next, best fee rate first
+    pub async fn select_transactions(&self, max_transactions: usize) -> Vec<Transaction> {
+        let state = self.state.read().await;
+        let accounts = &state.accounts;
+        let params = accounts.params.params_at(state.tip().index + 1);
+        state.mempool.select(max_transactions, params.max_block_bytes, params.max_block_gas, |sender| {
+            accounts.nonce(sender)
+        })
+    }
+
+    /// Transactions waiting in the mempool
+    pub async fn get_pending_transactions(&self) -> Vec<Transaction> {
+        self.state.read().await.mempool.transactions()
+    }
+
+    /// Add a zero-knowledge transaction to the pool
+    pub async fn add_zk_transaction(&self, zk_transaction: ZkTransaction) -> Result<bool, anyhow::Error> {
+        // Account transfers must prove their validity and balance
+        if zk_transaction.shielded.is_none() && !zk_transaction.verify_proofs()? {
+            return Ok(false);
+        }
+        let mut state = self.state.write().await;
+
+        // Shielded spends must be valid against the committed pool
+        if let Some(bundle) = &zk_transaction.shielded {
+            let pool = &state.accounts.shielded;
+            if !bundle.value_in.is_zero() || !zk_transaction.verify_shielded() {
+                return Ok(false);
+            }
+            if bundle.spends.iter().any(|spend| !pool.is_valid_anchor(&spend.anchor))
+                || bundle.nullifiers().any(|nullifier| pool.is_spent(nullifier))
+            {
+                return Ok(false);
+            }
+        }
+        
+        // Add to ZK transaction pool
+        let result = state.zk_transaction_pool.add_transaction(zk_transaction.clone());
+        
+        if result.is_ok() {
+            // Convert to regular transaction for blockchain inclusion
+            let blockchain_tx = Transaction::new_private(zk_transaction)?;
+            Ok(state.mempool.insert(blockchain_tx, 0, Utc::now().timestamp()).is_ok())
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Get a copy of pending ZK transactions
+    pub async fn get_pending_zk_transactions(&self) -> Vec<ZkTransaction> {
+        let state = self.state.read().await;
+        state.zk_transaction_pool.get_pending_transactions()
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Retire the private transactions of an applied block from the pending
+    /// ZK pool. The state transition already moved their value: shielding
+    /// and unshielding change transparent balances against
+    /// [`SHIELDED_POOL_ADDRESS`], and spends and outputs change the note pool.
+    pub async fn process_zk_transactions(&self, block: &Block) -> Result<(), anyhow::Error> {
+        let mut state = self.state.write().await;
+        if !state.index.blocks.contains_key(&block.hash) {
+            return Err(anyhow::anyhow!("Block {} is not on the canonical chain", block.index));
+        }
+        state.retire_zk_transactions(&block.transactions);
+        Ok(())
+    }
+
+    /// Total supply held in transparent balances. It includes the value
+    /// backing every shielded note, held by [`SHIELDED_POOL_ADDRESS`].
+    pub async fn total_supply(&self) -> Amount {
+        let state = self.state.read().await;
+        state
+            .accounts
+            .balances
+            .values()
+            .fold(Amount::ZERO, |total, balance| total.saturating_add(*balance))
+    }
+
+    /// Get private balance for an account (returns commitment)
+    pub async fn get_private_balance(&self, account: &str) -> Option<ZkBalance> {
+        let state = self.state.read().await;
+        state.private_balances.get(account).cloned()
+    }
+
+    /// Set private balance for an account
+    pub async fn set_private_balance(&self, account: String, balance: ZkBalance) -> Result<(), anyhow::Error> {
+        let store = self.lock_store().await;
+        let mut state = self.state.write().await;
+        let previous = state.private_balances.insert(account.clone(), balance);
+        let write = Self::pending_write(store, &state, None);
+        drop(state);
+        let (_store, result) = write.commit().await;
+        if result.is_err() {
+            let mut state = self.state.write().await;
+            match previous {
+                Some(previous) => state.private_balances.insert(account, previous),
+                None => state.private_balances.remove(&account),
+            };
+        }
+        result
+    }
+
+    /// Check if the blockchain supports zero-knowledge transactions
+    pub fn supports_zk_transactions(&self) -> bool {
+        true
+    }
+
+    /// Get statistics about ZK transactions in the blockchain
+    pub async fn get_zk_statistics(&self) -> ZkBlockchainStats {
+        let state = self.state.read().await;
+        
+        let mut total_zk_transactions = 0u64;
+        let mut blocks_with_private_txs = 0u64;
+        
+        for block in &state.chain {
+            total_zk_transactions += block.zk_transaction_count;
+            if block.has_private_transactions {
+                blocks_with_private_txs += 1;
+            }
+        }
+        
+        ZkBlockchainStats {
+            total_zk_transactions,
+            blocks_with_private_txs,
+            pending_zk_transactions: state.zk_transaction_pool.get_pending_transactions().len() as u64,
+            private_accounts: state.private_balances.len() as u64,
+        }
+    }
+
+    /// Reward minted by the block at `height` of this chain
+    pub fn reward_at(&self, height: u64) -> Amount {
+        block_reward(self.base_reward, height)
+    }
+
+    /// Check a block proposed on top of the tip against [`Block::validate`]
+    /// with this chain's reward and against the protocol parameters in force
+    /// at its height, the rules every node applies on import
+    pub async fn validate_block(&self, block: &Block) -> Result<(), anyhow::Error> {
+        block.validate(self.base_reward)?;
+        block.check_limits(&self.state.read().await.accounts.params.params_at(block.index))
+    }
+
+    pub async fn get_latest_block(&self) -> Block {
+        let state = self.state.read().await;
+        state.chain.last().unwrap().clone()
+    }
+
+    pub async fn get_balance(&self, address: &str) -> Amount {
+        let state = self.state.read().await;
+        state.accounts.balance(address)
+    }
+
+    /// Committed balance of `address` split into the part still locked and
+    /// the part it can spend at Unix time `now`
+    pub async fn get_balance_at(&self, address: &str, now: i64) -> AccountBalance {
+        let state = self.state.read().await;
+        let balance = state.accounts.balance(address);
+        let locked = state.accounts.locked(address, now);
+        AccountBalance {
+            address: address.to_string(),
+            balance,
+            locked,
+            spendable: balance.saturating_sub(locked),
+            as_of: now,
+            locks: state.accounts.locks(address).to_vec(),
+        }
+    }
+
+    /// [`Blockchain::get_balance_at`] the current time
+    pub async fn get_balance_details(&self, address: &str) -> AccountBalance {
+        self.get_balance_at(address, Utc::now().timestamp()).await
+    }
+
+    /// Canonical block at `height`; a chain started from a snapshot or
+    /// pruned only holds the blocks from its base height on
+    pub async fn get_block_by_height(&self, height: u64) -> Option<Block> {
+        let state = self.state.read().await;
+        state.block_at(height).cloned()
+    }
+
+    /// Canonical block with hash `hash`
+    pub async fn get_block_by_hash(&self, hash: &str) -> Option<Block> {
+        let state = self.state.read().await;
+        let height = *state.index.blocks.get(hash)?;
+        state.block_at(height).cloned()
+    }
+
+    /// Headers of up to `count` canonical blocks starting at height `start`,
+    /// capped at [`MAX_HEADER_RANGE`]. On a chain started from a snapshot,
+    /// headers below the snapshot are as its signer claimed them.
+    pub async fn get_block_headers(&self, start: u64, count: u64) -> Vec<BlockHeader> {
+        let state = self.state.read().await;
+        let end = start.saturating_add(count.min(MAX_HEADER_RANGE));
+        (start..end).map_while(|height| state.header_at(height)).collect()
+    }
+
+    /// Canonical transaction with hash `tx_hash` and where it sits
+    pub async fn get_transaction(&self, tx_hash: &str) -> Option<IndexedTransaction> {
+        let state = self.state.read().await;
+        let location = *state.index.transactions.get(tx_hash)?;
+        state.indexed_transaction(location)
+    }
+
+    /// Canonical transactions sending from or to `address`, newest first,
+    /// skipping the `offset` newest and returning at most `limit`
+    pub async fn get_address_history(&self, address: &str, offset: usize, limit: usize) -> AddressHistory {
+        let state = self.state.read().await;
+        let locations = state.index.addresses.get(address).map(Vec::as_slice).unwrap_or_default();
+        AddressHistory {
+            address: address.to_string(),
+            total: locations.len(),
+            offset,
+            transactions: locations
+                .iter()
+                .rev()
+                .skip(offset)
+                .take(limit)
+                .filter_map(|location| state.indexed_transaction(*location))
+                .collect(),
+        }
+    }
+
+    /// Receipt of a transaction on the canonical chain, or of one dropped
+    /// during recent block production
+    pub async fn get_receipt(&self, tx_hash: &str) -> Option<ReceiptLookup> {
+        let state = self.state.read().await;
+        let committed = state
+            .index
+            .transactions
+            .get(tx_hash)
+            .and_then(|location| state.indexed_transaction(*location))
+            .and_then(|indexed| {
+                Some(ReceiptLookup {
+                    receipt: indexed.receipt?,
+                    block_index: Some(indexed.block_index),
+                    block_hash: Some(indexed.block_hash),
+                })
+            });
+        committed.or_else(|| {
+            state.rejected_receipts.get(tx_hash).map(|(_, receipt)| ReceiptLookup {
+                receipt: receipt.clone(),
+                block_index: None,
+                block_hash: None,
+            })
+        })
+    }
+
+    /// Inclusion proof for a committed transaction, looked up by hash
+    pub async fn prove_transaction(&self, tx_hash: &str) -> Option<TransactionProof> {
+        let state = self.state.read().await;
+        let location = state.index.transactions.get(tx_hash)?;
+        let block = state
+            .block_at(location.block_index)
+            .filter(|block| block.transactions_root.is_some())?;
+        Some(TransactionProof {
+            transaction: block.transactions.get(location.position)?.clone(),
+            block_index: block.index,
+            block_hash: block.hash.clone(),
+            proof: block.prove_transaction(location.position)?,
+        })
+    }
+
+    /// Proof of an account's balance and nonce against the tip block's state root
+    pub async fn prove_account(&self, address: &str) -> AccountProof {
+        let state = self.state.read().await;
+        let tip = state.chain.last().expect("chain always holds the genesis block");
+        AccountProof {
+            address: address.to_string(),
+            balance: state.accounts.balance(address),
+            nonce: state.accounts.nonce(address),
+            locks: state.accounts.locks(address).to_vec(),
+            block_index: tip.index,
+            block_hash: tip.hash.clone(),
+            proof: state.accounts.tree.prove(&account_key(address)),
+        }
+    }
+
+    /// Current root of the shielded note commitment tree, the anchor new
+    /// spends should prove membership against
+    pub async fn shielded_anchor(&self) -> [u8; 32] {
+        self.state.read().await.accounts.shielded.anchor()
+    }
+
+    /// Membership path of a note commitment in the shielded pool, together
+    /// with the anchor it leads to
+    pub async fn shielded_path(&self, commitment: &[u8; 32]) -> Option<([u8; 32], NotePath)> {
+        let state = self.state.read().await;
+        let pool = &state.accounts.shielded;
+        let path = pool.path(pool.position(commitment)?)?;
+        Some((pool.anchor(), path))
+    }
+
+    /// Unspent notes of `spending_key` created in the held canonical
+    /// blocks, decrypted with the wallet's Kyber `keypair`. Pruned block
+    /// bodies are not scanned.
+    pub async fn find_notes(&self, spending_key: &SpendingKey, keypair: &Keypair) -> Vec<Note> {
+        let state = self.state.read().await;
+        let pool = &state.accounts.shielded;
+        state
+            .chain
+            .iter()
+            .flat_map(|block| &block.transactions)
+            .filter_map(Transaction::shielded_bundle)
+            .flat_map(|bundle| &bundle.outputs)
+            .filter_map(|output| Some((pool.position(&output.note_commitment())?, output.decrypt(keypair)?)))
+            .filter(|(position, note)| spending_key.owns(note) && !pool.is_spent(&spending_key.nullifier(note, *position)))
+            .map(|(_, note)| note)
+            .collect()
+    }
+
+    /// History of the private payments a viewing key can read, scanned over
+    /// the held canonical blocks. Pruned block bodies are not scanned.
+    pub async fn scan_with_viewing_key(&self, key: &ViewingKey) -> ViewingReport {
+        let state = self.state.read().await;
+        let mut report = ViewingReport::new(key);
+        report.first_block = state.chain.first().map_or(0, |block| block.index);
+        report.last_block = state.tip().index;
+        for block in &state.chain {
+            for tx in &block.transactions {
+                let Some(zk_tx) = &tx.zk_transaction else {
+                    continue;
+                };
+                for (direction, payment) in key.view(zk_tx) {
+                    report.push(ReportEntry {
+                        block_index: block.index,
+                        block_hash: block.hash.clone(),
+                        tx_hash: tx.calculate_hash(),
+                        timestamp: block.timestamp,
+                        direction,
+                        payment,
+                    });
+                }
+            }
+        }
+        report
+    }
+
+    pub async fn get_transactions(&self) -> Vec<Transaction> {
+        let state = self.state.read().await;
+        let mut all_transactions = Vec::new();
+        
+        // Get transactions from all blocks
+        for block in state.chain.iter() {
+            all_transactions.extend(block.transactions.clone());
+        }
+        
+        // Add pending transactions
+        all_transactions.extend(state.mempool.transactions());
+        
+        all_transactions
+    }
+
+    /// Create a block from the reward and the best paying pending
+    /// transactions.
+    ///
+    /// Transactions are applied to the tip's account state one by one, with
+    /// fees going to the validator; rejected ones are dropped from the block
+    /// and the mempool. Returns the outcome of every
+    /// transaction considered, reward first.
+    ///
+    /// The block is unsigned and so carries no stake weight in fork choice;
+    /// validators holding their key use [`Self::create_signed_block`].
+    pub async fn create_block(
+        &self,
+        validator_id: &str,
+        validator_score: f64,
+        network_metrics: Option<ZkNetworkMetrics>,
+    ) -> Vec<TransactionOutcome> {
+        self.produce_block(validator_id, validator_score, network_metrics, None).await
+    }
+
+    /// Like [`Self::create_block`], for the validator owning `keypair`, with
+    /// the block signed by it as producer.
+    pub async fn create_signed_block(
+        &self,
+        keypair: &Keypair,
+        validator_score: f64,
+        network_metrics: Option<ZkNetworkMetrics>,
+    ) -> Vec<TransactionOutcome> {
+        self.produce_block(&keypair.address(), validator_score, network_metrics, Some(keypair)).await
+    }
+
+    async fn produce_block(
+        &self,
+        validator_id: &str,
+        validator_score: f64,
+        network_metrics: Option<ZkNetworkMetrics>,
+        signer: Option<&Keypair>,
+    ) -> Vec<TransactionOutcome> {
+        let store = self.lock_store().await;
+        let mut guard = self.state.write().await;
+        let state = &mut *guard;
+        let height = state.tip().index + 1;
+        let now = Utc::now().timestamp();
+
+        // Mint the reward through the block's coinbase
+        let reward = block_reward(self.base_reward, height);
+        let reward_tx = Transaction::coinbase(validator_id.to_string(), reward, height);
+
+        // Take the reward and the highest fee rate pending transactions that
+        // fit the block limits next to it
+        state.mempool.expire(now);
+        let accounts = &state.accounts;
+        let params = accounts.params.params_at(height);
+        let pending = state.mempool.select(
+            MAX_BLOCK_TRANSACTIONS - 1,
+            params.max_block_bytes.saturating_sub(reward_tx.encoded_size()),
+            params.max_block_gas.saturating_sub(reward_tx.intrinsic_gas()),
+            |sender| accounts.nonce(sender),
+        );
+        let mut transactions = Vec::with_capacity(pending.len() + 1);
+        transactions.push(reward_tx);
+        transactions.extend(pending);
+
+        // Apply them to the account state, keeping only valid ones
+        let (outcomes, undo) = state.accounts.apply_transactions(&transactions, validator_id, height, now);
+        if let Err(e) = state.accounts.check_supply(&transactions, &outcomes, &undo) {
+            log::error!("Not producing block {}: {}", height, e);
+            state.accounts.revert(undo);
+            return outcomes;
+        }
+        let mut included = Vec::with_capacity(transactions.len());
+        let mut receipts = Vec::with_capacity(transactions.len());
+        for (tx, outcome) in transactions.into_iter().zip(&outcomes) {
+            match &outcome.result {
+                Ok(()) => {
+                    included.push(tx);
+                    receipts.push(outcome.receipt.clone());
+                }
+                Err(e) => {
+                    log::warn!("Dropping transaction {} from {}: {}", outcome.tx_hash, tx.from, e);
+                    state.rejected_receipts.insert(outcome.tx_hash.clone(), (height, outcome.receipt.clone()));
+                    // Later pooled transactions from this sender can no
+                    // longer apply either
+                    state.mempool.remove_hash(&outcome.tx_hash);
+                    state.mempool.remove_from_nonce(&tx.from, tx.nonce);
+                }
+            }
+        }
+
+        // Create new block committing to the resulting state and receipts
+        let mut new_block = Block::new(
+            height,
+            included,
+            state.chain.last().unwrap().hash.clone(),
+            validator_id.to_string(),
+            validator_score,
+            network_metrics,
+        );
+        // Locks were evaluated at the time the transactions were applied
+        new_block.timestamp = now;
+        let mut new_block = new_block.with_state_root(state.accounts.root()).with_receipts(receipts);
+        if let Some(keypair) = signer {
+            if let Err(e) = new_block.sign_producer(keypair) {
+                log::error!("Failed to sign block {}: {}", height, e);
+            }
+        }
+        let accounts = &state.accounts;
+        state.mempool.remove_committed(&new_block.transactions, |sender| accounts.nonce(sender));
+        state.retire_zk_transactions(&new_block.transactions);
+        state.undo_log.insert(new_block.hash.clone(), undo);
+        state.index.push(&new_block);
+        state.chain.push(new_block);
+        state.prune_fork_data();
+
+        let write = Self::pending_write(store, state, None);
+        drop(guard);
+        let (_store, result) = write.commit().await;
+        if let Err(e) = result {
+            log::error!("Failed to persist block {}, dropping it: {}", height, e);
+            if let Err(e) = self.state.write().await.revert_tip() {
+                log::error!("Failed to drop unpersisted block {}: {}", height, e);
+            }
+        }
+
+        outcomes
+    }
+
+    /// Import a block produced elsewhere, e.g. announced by another validator.
+    ///
+    /// The block must pass [`Block::validate`], so every transfer in it is
+    /// signed by its sender, and its parent must already be known. A block
+    /// extending the tip is applied directly; any other block is kept in the block tree and
+    /// triggers a reorganization if its branch wins [`Blockchain::fork_choice`].
+    /// Subscribers of [`Blockchain::subscribe_reorgs`] are notified of every
+    /// reorganization. If the result cannot be written to disk the in-memory
+    /// chain is rolled back and the error returned.
+    pub async fn import_block(&self, block: Block) -> Result<BlockImport, anyhow::Error> {
+        ChainState::verify_block(&block)?;
+        let store = self.lock_store().await;
+        let mut state = self.state.write().await;
+        block.validate(self.base_reward)?;
+
+        if state.canonical_block(block.index, &block.hash).is_some() || state.side_blocks.contains_key(&block.hash) {
+            return Ok(BlockImport::Known);
+        }
+        let parent_height = block
+            .index
+            .checked_sub(1)
+            .ok_or_else(|| anyhow::anyhow!("Cannot import a second genesis block"))?;
+        if parent_height < state.finalized_height {
+            return Err(anyhow::anyhow!("Block {} conflicts with finalized block {}", block.index, state.finalized_height));
+        }
+        let parent = state
+            .canonical_block(parent_height, &block.previous_hash)
+            .or_else(|| state.side_blocks.get(&block.previous_hash).filter(|parent| parent.index == parent_height))
+            .ok_or_else(|| anyhow::anyhow!("Block {} has unknown parent {}", block.index, block.previous_hash))?;
+        // Locks release by block time, so it must not run backwards or ahead
+        // of the local clock
+        if block.timestamp < parent.timestamp || block.timestamp > Utc::now().timestamp() + MAX_FUTURE_BLOCK_TIME {
+            return Err(anyhow::anyhow!("Block {} has an implausible timestamp {}", block.index, block.timestamp));
+        }
+
+        // Extending the tip is the common case
+        if parent_height == state.tip().index && block.previous_hash == state.tip().hash {
+            let undo = state.accounts.apply_block(&block)?;
+            state.undo_log.insert(block.hash.clone(), undo);
+            state.index.push(&block);
+            state.chain.push(block.clone());
+            state.requeue_transactions(Vec::new(), std::slice::from_ref(&block));
+            state.prune_fork_data();
+            let write = Self::pending_write(store, &state, None);
+            drop(state);
+            let (_store, result) = write.commit().await;
+            if let Err(e) = result {
+                self.state.write().await.revert_tip()?;
+                return Err(e);
+            }
+            return Ok(BlockImport::Extended);
+        }
+
+        state.side_blocks.insert(block.hash.clone(), block.clone());
+        let (fork_height, branch) = state
+            .side_branch(&block)
+            .ok_or_else(|| anyhow::anyhow!("Block {} does not connect to the canonical chain", block.index))?;
+        if !state.prefers_branch(self.fork_choice, fork_height, &branch) {
+            state.prune_fork_data();
+            return Ok(BlockImport::SideChain);
+        }
+
+        let event = state.reorganize(fork_height, branch)?;
+        println!(
+            "🔀 Chain reorganized at height {}: {} blocks reverted, {} applied",
+            fork_height,
+            event.reverted_blocks.len(),
+            event.applied_blocks.len()
+        );
+        let write = Self::pending_write(store, &state, Some(fork_height + 1));
+        drop(state);
+        let (_store, result) = write.commit().await;
+        if let Err(e) = result {
+            self.state.write().await.undo_reorganization(&event)?;
+            return Err(e);
+        }
+        // Sending only fails when nobody is subscribed
+        let _ = self.reorg_events.send(event.clone());
+        Ok(BlockImport::Reorganized(event))
+    }
+
+    /// Mark a canonical block and its ancestors as final; they will never be
+    /// reverted by a reorganization
+    pub async fn finalize_block(&self, hash: &str) -> Result<(), anyhow::Error> {
+        let mut state = self.state.write().await;
+        let height = state
+            .chain
+            .iter()
+            .rposition(|block| block.hash == hash)
+            .ok_or_else(|| anyhow::anyhow!("Cannot finalize non-canonical block {}", hash))? as u64
+            + state.base;
+        if height > state.finalized_height {
+            state.finalized_height = height;
+            state.prune_fork_data();
+        }
+        Ok(())
+    }
+
+    /// Set the stake fork choice credits to blocks produced by `validator_id`
+    pub async fn set_validator_stake(&self, validator_id: &str, stake: Amount) {
+        let mut state = self.state.write().await;
+        state.validator_stakes.insert(validator_id.to_string(), stake);
+    }
+
+    /// Receive an event for every chain reorganization
+    pub fn subscribe_reorgs(&self) -> broadcast::Receiver<ReorgEvent> {
+        self.reorg_events.subscribe()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sign `tx` as `keypair`, the owner of its sender address
+    fn signed(mut tx: Transaction, keypair: &Keypair) -> Transaction {
+        tx.sign_with_keypair(keypair).expect("keypair owns the sender address");
+        tx
+    }
+
+    #[tokio::test]
+    async fn test_rewards_ignore_reported_scores() -> Result<(), anyhow::Error> {
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+
+        let mut good_metrics = ZkNetworkMetrics::new(1000.0);
+        good_metrics.packets_routed = 50;
+        good_metrics.update_routing_metrics(50.0, 1024);
+        good_metrics.update_reputation(true);
+
+        // Producers choose their header score and metrics, so the reward
+        // depends on the height alone
+        blockchain.create_block("good_node", 1000.0, Some(good_metrics)).await;
+        blockchain.create_block("poor_node", 0.1, None).await;
+        assert_eq!(blockchain.get_balance("good_node").await, Amount::from_zhtp(100));
+        assert_eq!(blockchain.get_balance("poor_node").await, Amount::from_zhtp(100));
+        assert_eq!(blockchain.reward_at(0), Amount::ZERO);
+
+        let mut inflated = blockchain.get_latest_block().await;
+        inflated.validator_score = f64::MAX;
+        inflated.transactions[0].amount = Amount::MAX;
+        assert!(blockchain.validate_block(&inflated).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_state_transition_rejects_invalid_transactions() {
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        let validator1 = Keypair::generate();
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+
+        // Admission only checks the committed balance, so both transfers get in
+        let transfer = |to: &str, zhtp: u64, nonce: u64| {
+            let mut tx = Transaction::new(validator1.address(), to.to_string(), Amount::from_zhtp(zhtp));
+            tx.nonce = nonce;
+            signed(tx, &validator1)
+        };
+        assert!(blockchain.add_transaction(transfer("alice", 60, 0)).await);
+        assert!(blockchain.add_transaction(transfer("bob", 60, 1)).await);
+        assert!(blockchain.add_transaction(transfer("carol", 10, 2)).await);
+
+        let outcomes = blockchain.create_block("validator2", 1.0, None).await;
+        let results: Vec<_> = outcomes.iter().map(|outcome| outcome.result.clone()).collect();
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Ok(()),
+                Err(TransactionError::InsufficientFunds {
+                    balance: Amount::from_zhtp(40),
+                    required: Amount::from_zhtp(60),
+                }),
+                Err(TransactionError::BadNonce { expected: 1, found: 2 }),
+            ]
+        );
+
+        let tip = blockchain.get_latest_block().await;
+        assert_eq!(tip.transactions.len(), 2);
+        assert_eq!(blockchain.get_balance(&validator1.address()).await, Amount::from_zhtp(40));
+        assert_eq!(blockchain.get_balance("alice").await, Amount::from_zhtp(60));
+        assert!(blockchain.get_balance("bob").await.is_zero());
+
+        // Replaying the chain reaches the same state as the incremental path
+        let chain = blockchain.state.read().await.chain.clone();
+        assert_eq!(Some(AccountState::replay(&chain).root()), tip.state_root);
+
+        // The sender continues from the committed nonce
+        assert!(blockchain.add_transaction(transfer("bob", 30, 1)).await);
+        let outcomes = blockchain.create_block("validator2", 1.0, None).await;
+        assert!(outcomes.iter().all(TransactionOutcome::is_applied));
+        assert_eq!(blockchain.get_balance("bob").await, Amount::from_zhtp(30));
+    }
+
+    #[tokio::test]
+    async fn test_fees_and_replacement() -> Result<(), anyhow::Error> {
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        let validator1 = Keypair::generate();
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+
+        // Amount plus fee must be covered by the committed balance
+        let overdrawn = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(100))
+            .with_fee(Amount::from_zhtp(1));
+        let overdrawn = signed(overdrawn, &validator1);
+        assert!(blockchain.submit_transaction(overdrawn).await.is_err());
+
+        // Only the owner of the sender address can move its funds, whether
+        // through the mempool or in a block
+        let unsigned = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(10));
+        let error = blockchain.submit_transaction(unsigned.clone()).await.unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&TransactionError::InvalidSignature));
+        let mut accounts = blockchain.state.read().await.accounts.clone();
+        let (outcomes, _) = accounts.apply_transactions(&[unsigned], "validator2", 1, Utc::now().timestamp());
+        assert_eq!(outcomes[0].result, Err(TransactionError::InvalidSignature));
+
+        let transfer = |fee: u64| {
+            let tx = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(10))
+                .with_fee(Amount::from_zhtp(fee));
+            signed(tx, &validator1)
+        };
+        blockchain.submit_transaction(transfer(1)).await?;
+        // Reusing the nonce needs a higher fee rate
+        assert!(blockchain.submit_transaction(transfer(1)).await.is_err());
+        blockchain.submit_transaction(transfer(2)).await?;
+        assert_eq!(blockchain.get_pending_transactions().await.len(), 1);
+
+        let outcomes = blockchain.create_block("validator2", 1.0, None).await;
+        assert!(outcomes.iter().all(TransactionOutcome::is_applied));
+        assert!(blockchain.get_pending_transactions().await.is_empty());
+
+        // The fee moves from the sender to the block's validator
+        assert_eq!(blockchain.get_balance(&validator1.address()).await, Amount::from_zhtp(88));
+        assert_eq!(blockchain.get_balance("validator2").await, Amount::from_zhtp(102));
+        let chain = blockchain.state.read().await.chain.clone();
+        assert_eq!(AccountState::replay(&chain).root(), blockchain.state.read().await.accounts.root());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_coinbase_minting_rule() -> Result<(), anyhow::Error> {
+        let local = Blockchain::new(Amount::from_zhtp(100));
+        let remote = Blockchain::new(Amount::from_zhtp(100));
+        remote.create_block("validator1", 0.5, None).await;
+
+        // The coinbase pays the validator the chain's reward
+        let block = remote.get_latest_block().await;
+        let coinbase = &block.transactions[0];
+        assert!(coinbase.is_coinbase() && coinbase.signature.is_empty());
+        assert_eq!((coinbase.to.as_str(), coinbase.amount, coinbase.nonce), ("validator1", Amount::from_zhtp(100), 1));
+        block.verify_coinbase(Amount::from_zhtp(100))?;
+        assert!(block.verify_coinbase(Amount::from_zhtp(10)).is_err());
+        assert!(matches!(local.import_block(block.clone()).await?, BlockImport::Extended));
+
+        // Blocks minting more than their reward, or outside the coinbase, are refused
+        let inflated = Block::new(
+            2,
+            vec![Transaction::coinbase("validator1".to_string(), Amount::from_zhtp(500), 2)],
+            block.hash.clone(),
+            "validator1".to_string(),
+            0.5,
+            None,
+        );
+        assert!(local.import_block(inflated).await.is_err());
+        let misplaced = Block::new(
+            2,
+            vec![
+                Transaction::coinbase("validator1".to_string(), Amount::from_zhtp(100), 2),
+                Transaction::coinbase("validator1".to_string(), Amount::from_zhtp(100), 2),
+            ],
+            block.hash.clone(),
+            "validator1".to_string(),
+            0.5,
+            None,
+        );
+        assert!(local.import_block(misplaced).await.is_err());
+
+        // Nobody can submit a minting transaction
+        let mint = Transaction::new("network".to_string(), "alice".to_string(), Amount::from_zhtp(1));
+        assert!(local.submit_transaction(mint).await.is_err());
+        assert_eq!(local.get_balance("validator1").await, Amount::from_zhtp(100));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_requires_sender_signatures() -> Result<(), anyhow::Error> {
+        let remote = Blockchain::new(Amount::from_zhtp(100));
+        let sender = Keypair::generate();
+        remote.create_block(&sender.address(), 1.0, None).await;
+        let local = fork_of(&remote).await;
+        let parent = remote.get_latest_block().await;
+        let block_with = |transfer: Transaction| {
+            Block::new(
+                2,
+                vec![Transaction::coinbase("validator1".to_string(), Amount::from_zhtp(100), 2), transfer],
+                parent.hash.clone(),
+                "validator1".to_string(),
+                1.0,
+                None,
+            )
+        };
+
+        // A peer cannot move funds it holds no key for
+        let unsigned = Transaction::new(sender.address(), "thief".to_string(), Amount::from_zhtp(100));
+        assert!(block_with(unsigned.clone()).verify_senders().is_err());
+        assert!(local.import_block(block_with(unsigned.clone())).await.is_err());
+        let mut forged = unsigned.clone();
+        forged.sign_with_keypair(&sender)?;
+        forged.amount = Amount::from_zhtp(50);
+        assert!(local.import_block(block_with(forged)).await.is_err());
+        assert_eq!(local.get_balance(&sender.address()).await, Amount::from_zhtp(100));
+        assert_eq!(local.get_balance("thief").await, Amount::ZERO);
+
+        let mut signed = unsigned;
+        signed.sign_with_keypair(&sender)?;
+        assert!(matches!(local.import_block(block_with(signed)).await?, BlockImport::Extended));
+        assert_eq!(local.get_balance("thief").await, Amount::from_zhtp(100));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transfer_locks() -> Result<(), anyhow::Error> {
+        use crate::genesis::GenesisAllocation;
+        let genesis_time = Utc::now().timestamp() - 500;
+        let (alice, bob) = (Keypair::generate(), Keypair::generate());
+        let vesting = TransferLock::Vesting { start: None, cliff: 100, duration: 1000 };
+        let blockchain = Blockchain::from_genesis(&GenesisSpec {
+            timestamp: genesis_time,
+            allocations: vec![GenesisAllocation {
+                address: alice.address(),
+                amount: Amount::from_zhtp(100),
+                lock: Some(vesting),
+            }],
+            ..GenesisSpec::default()
+        });
+
+        // Nothing vests before the cliff, then the allocation releases linearly
+        for (elapsed, locked) in [(50, 100), (500, 50), (1000, 0)] {
+            let balance = blockchain.get_balance_at(&alice.address(), genesis_time + elapsed).await;
+            assert_eq!(balance.locked, Amount::from_zhtp(locked));
+            assert_eq!(balance.spendable, Amount::from_zhtp(100 - locked));
+        }
+
+        let overspend = signed(Transaction::new(alice.address(), bob.address(), Amount::from_zhtp(80)), &alice);
+        let error = blockchain.submit_transaction(overspend).await.unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(TransactionError::LockedFunds { .. })));
+        let transfer = Transaction::new(alice.address(), bob.address(), Amount::from_zhtp(40));
+        blockchain.submit_transaction(signed(transfer, &alice)).await?;
+
+        // A relative timelock counts from the including block
+        let mut locked = Transaction::new(alice.address(), bob.address(), Amount::from_zhtp(10))
+            .with_lock(TransferLock::For(3600));
+        locked.nonce = 1;
+        blockchain.submit_transaction(signed(locked, &alice)).await?;
+        let mut malformed = Transaction::new(alice.address(), "carol".to_string(), Amount::from_zhtp(1))
+            .with_lock(TransferLock::Vesting { start: None, cliff: 10, duration: 5 });
+        malformed.nonce = 2;
+        blockchain.submit_transaction(signed(malformed, &alice)).await?;
+        let outcomes = blockchain.create_block("validator1", 1.0, None).await;
+        assert_eq!(outcomes.last().unwrap().result, Err(TransactionError::InvalidLock));
+
+        let block = blockchain.get_latest_block().await;
+        let bob_balance = blockchain.get_balance_at(&bob.address(), block.timestamp).await;
+        assert_eq!((bob_balance.balance, bob_balance.locked), (Amount::from_zhtp(50), Amount::from_zhtp(10)));
+        assert_eq!(bob_balance.locks[0].end, block.timestamp + 3600);
+        let overspend = signed(Transaction::new(bob.address(), "carol".to_string(), Amount::from_zhtp(45)), &bob);
+        assert!(blockchain.submit_transaction(overspend).await.is_err());
+
+        // Locks are part of the committed state
+        let chain = blockchain.state.read().await.chain.clone();
+        assert_eq!(Some(AccountState::replay(&chain).root()), block.state_root);
+        let proof = blockchain.prove_account(&bob.address()).await;
+        assert!(proof.verify(&block.state_root.unwrap()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transaction_receipts() -> Result<(), anyhow::Error> {
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        let validator1 = Keypair::generate();
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+        let peer = fork_of(&blockchain).await;
+
+        let mut paid = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(10))
+            .with_fee(Amount::from_zhtp(1));
+        paid.data = vec![0u8; 4];
+        let mut overdrawn = Transaction::new(validator1.address(), "bob".to_string(), Amount::from_zhtp(95));
+        overdrawn.nonce = 1;
+        let (paid, overdrawn) = (signed(paid, &validator1), signed(overdrawn, &validator1));
+        let (paid_hash, overdrawn_hash) = (paid.calculate_hash(), overdrawn.calculate_hash());
+        blockchain.submit_transaction(paid).await?;
+        blockchain.submit_transaction(overdrawn).await?;
+        blockchain.create_block("validator2", 1.0, None).await;
+
+        let tip = blockchain.get_latest_block().await;
+        assert_eq!(tip.receipts.len(), tip.transactions.len());
+        assert!(ChainState::verify_block(&tip).is_ok());
+
+        let lookup = blockchain.get_receipt(&paid_hash).await.unwrap();
+        assert!(lookup.receipt.is_success());
+        assert_eq!(lookup.receipt.fee, Amount::from_zhtp(1));
+        assert_eq!(lookup.receipt.gas_used, TRANSACTION_BASE_GAS + 4 * TRANSACTION_DATA_GAS_PER_BYTE);
+        assert_eq!(lookup.block_index, Some(tip.index));
+
+        // Dropped transactions report why, without a block
+        let lookup = blockchain.get_receipt(&overdrawn_hash).await.unwrap();
+        assert!(matches!(
+            lookup.receipt.status,
+            ReceiptStatus::Failed(TransactionError::InsufficientFunds { .. })
+        ));
+        assert_eq!(lookup.block_index, None);
+        assert!(blockchain.get_receipt("unknown").await.is_none());
+
+        // Receipts are bound to the header
+        let mut forged = tip.clone();
+        forged.receipts[1].fee = Amount::ZERO;
+        assert!(ChainState::verify_block(&forged).is_err());
+
+        // and to what executing the block produces
+        let forged = forged.clone().with_receipts(forged.receipts);
+        assert!(ChainState::verify_block(&forged).is_ok());
+        assert!(peer.import_block(forged).await.is_err());
+        assert!(matches!(peer.import_block(tip).await?, BlockImport::Extended));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_contract_transactions() -> Result<(), anyhow::Error> {
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        let validator1 = Keypair::generate();
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+        let peer = fork_of(&blockchain).await;
+
+        let emitting = wat::parse_str(r#"
+            (module
+                (import "zhtp" "emit" (func $emit (param i32 i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "Paid")
+                (func (export "main")
+                    (call $emit (i32.const 0) (i32.const 4) (i32.const 0) (i32.const 0)))
+            )
+        "#)?;
+        let trapping = wat::parse_str(r#"(module (func (export "main") unreachable))"#)?;
+        let mut ok = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(10))
+            .with_fee(Amount::from_zhtp(1));
+        ok.data = emitting.clone();
+        let mut failing = Transaction::new(validator1.address(), "bob".to_string(), Amount::from_zhtp(10))
+            .with_fee(Amount::from_zhtp(1));
+        failing.nonce = 1;
+        failing.data = trapping;
+        let (ok, failing) = (signed(ok, &validator1), signed(failing, &validator1));
+        let (ok_hash, failing_hash) = (ok.calculate_hash(), failing.calculate_hash());
+        blockchain.submit_transaction(ok).await?;
+        blockchain.submit_transaction(failing).await?;
+        blockchain.create_block("validator2", 1.0, None).await;
+
+        // Execution fuel is charged on top of the intrinsic gas
+        let tip = blockchain.get_latest_block().await;
+        assert_eq!(tip.transactions.len(), 3);
+        let receipt = blockchain.get_receipt(&ok_hash).await.unwrap().receipt;
+        assert!(receipt.is_success());
+        assert!(receipt.gas_used > TRANSACTION_BASE_GAS + emitting.len() as u64 * TRANSACTION_DATA_GAS_PER_BYTE);
+        assert_eq!(
+            receipt.logs,
+            vec![EventLog { contract: contracts::contract_address(&emitting), event: "Paid".to_string(), data: Vec::new() }]
+        );
+        assert_eq!(tip.gas_used(), tip.receipts.iter().map(|receipt| receipt.gas_used).sum::<u64>());
+        assert!(tip.gas_used() > tip.transactions.iter().map(Transaction::intrinsic_gas).sum::<u64>());
+
+        // A failing contract is included, pays its fee and moves no value
+        let lookup = blockchain.get_receipt(&failing_hash).await.unwrap();
+        assert_eq!(lookup.block_index, Some(tip.index));
+        assert!(matches!(lookup.receipt.status, ReceiptStatus::Failed(TransactionError::ContractFailed(_))));
+        assert_eq!(lookup.receipt.fee, Amount::from_zhtp(1));
+        assert_eq!(blockchain.get_balance("alice").await, Amount::from_zhtp(10));
+        assert_eq!(blockchain.get_balance("bob").await, Amount::ZERO);
+        assert_eq!(blockchain.get_balance(&validator1.address()).await, Amount::from_zhtp(88));
+
+        // Peers re-execute the contracts and reject forged gas
+        let mut forged = tip.clone();
+        forged.receipts[1].gas_used -= 1;
+        let forged = forged.clone().with_receipts(forged.receipts);
+        assert!(peer.import_block(forged).await.is_err());
+        assert!(matches!(peer.import_block(tip).await?, BlockImport::Extended));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chain_from_genesis_spec() -> Result<(), anyhow::Error> {
+        use crate::genesis::{GenesisAllocation, GenesisValidator};
+        let alice = Keypair::generate();
+
+        let spec = GenesisSpec {
+            chain_id: "zhtp-testnet".to_string(),
+            allocations: vec![GenesisAllocation { address: alice.address(), amount: Amount::from_zhtp(500), lock: None }],
+            validators: vec![GenesisValidator { address: "validator1".to_string(), stake: Amount::from_zhtp(1000) }],
+            ..GenesisSpec::default()
+        };
+        let blockchain = Blockchain::from_genesis(&spec);
+        let genesis = blockchain.get_block_by_height(0).await.unwrap();
+        assert_eq!(genesis.hash, spec.genesis_hash());
+        assert_eq!(genesis.timestamp, spec.timestamp);
+        assert_eq!(blockchain.get_balance(&alice.address()).await, Amount::from_zhtp(500));
+        assert_eq!(blockchain.base_reward, spec.economics.block_reward);
+        assert_eq!(
+            blockchain.state.read().await.validator_stakes.get("validator1"),
+            Some(&Amount::from_zhtp(1000))
+        );
+
+        // Allocations are spendable like any other balance
+        let transfer = Transaction::new(alice.address(), "bob".to_string(), Amount::from_zhtp(200));
+        blockchain.submit_transaction(signed(transfer, &alice)).await?;
+        blockchain.create_block("validator1", 1.0, None).await;
+        assert_eq!(blockchain.get_balance("bob").await, Amount::from_zhtp(200));
+
+        // A data directory holds the chain of one genesis only
+        let data_dir = std::env::temp_dir().join(format!("zhtp-genesis-chain-{}", uuid::Uuid::new_v4()));
+        drop(Blockchain::open_with_genesis(&spec, &data_dir)?);
+        assert_eq!(Blockchain::open_with_genesis(&spec, &data_dir)?.genesis_hash().await, spec.genesis_hash());
+        let other = GenesisSpec { chain_id: "zhtp-devnet".to_string(), ..spec.clone() };
+        assert!(Blockchain::open_with_genesis(&other, &data_dir).is_err());
+        std::fs::remove_dir_all(&data_dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explorer_queries() {
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        let validator1 = Keypair::generate();
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+        let mut hashes = Vec::new();
+        for nonce in 0..3 {
+            let mut tx = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(1));
+            tx.nonce = nonce;
+            let tx = signed(tx, &validator1);
+            hashes.push(tx.calculate_hash());
+            assert!(blockchain.add_transaction(tx).await);
+            blockchain.create_block("validator2", 1.0, None).await;
+        }
+
+        let block = blockchain.get_block_by_height(2).await.unwrap();
+        assert_eq!(blockchain.get_block_by_hash(&block.hash).await.unwrap().index, 2);
+        assert!(blockchain.get_block_by_height(10).await.is_none());
+
+        let headers = blockchain.get_block_headers(1, 2).await;
+        assert_eq!(headers.iter().map(|header| header.index).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(headers[1], BlockHeader::from(&block));
+
+        let found = blockchain.get_transaction(&hashes[1]).await.unwrap();
+        assert_eq!((found.block_index, found.position), (3, 1));
+        assert!(found.receipt.unwrap().is_success());
+
+        // Newest first, paginated
+        let page = blockchain.get_address_history("alice", 1, 5).await;
+        assert_eq!(page.total, 3);
+        let paged: Vec<String> = page.transactions.iter().map(|tx| tx.transaction.calculate_hash()).collect();
+        assert_eq!(paged, vec![hashes[1].clone(), hashes[0].clone()]);
+        assert!(blockchain.get_address_history("nobody", 0, 5).await.transactions.is_empty());
+    }
+
+    #[test]
+    fn test_transaction_sender_keys() -> Result<(), anyhow::Error> {
+        let keypair = Keypair::generate();
+        let mut tx = Transaction::new(keypair.address(), "recipient".to_string(), Amount::from_zhtp(5));
+
+        // Unkeyed transactions encode exactly as before keys were carried
+        assert!(!serde_json::to_string(&tx)?.contains("public_key"));
+        assert!(!tx.verify_sender());
+
+        tx.sign_with_keypair(&keypair)?;
+        assert!(tx.verify_sender());
+        let decoded: Transaction = serde_json::from_str(&serde_json::to_string(&tx)?)?;
+        assert!(decoded.verify_sender());
+
+        // Only the owner of an address can sign for it
+        let mut foreign = Transaction::new("validator1".to_string(), "recipient".to_string(), Amount::from_zhtp(5));
+        assert!(foreign.sign_with_keypair(&keypair).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_signed_hash_covers_every_field() {
+        // Digits moved across the amount/timestamp boundary change the hash
+        let mut tx = Transaction::new("alice".to_string(), "bob".to_string(), Amount::from_base_units(100_000_000));
+        tx.timestamp = 1_760_000_000;
+        let mut shifted = tx.clone();
+        shifted.amount = Amount::from_base_units(1_000_000_001_760_000_000);
+        shifted.timestamp = 0;
+        assert_ne!(tx.hash_bytes(), shifted.hash_bytes());
+
+        let mut moved = tx.clone();
+        moved.from = "alic".to_string();
+        moved.to = "ebob".to_string();
+        assert_ne!(tx.hash_bytes(), moved.hash_bytes());
+
+        let mut with_data = tx.clone();
+        with_data.data = b"call".to_vec();
+        assert_ne!(tx.hash_bytes(), with_data.hash_bytes());
+        let mut private = tx.clone();
+        private.is_private = true;
+        assert_ne!(tx.hash_bytes(), private.hash_bytes());
+        let mut multisig = tx.clone();
+        multisig.multisig = Some(MultisigAuthorization {
+            account: MultisigAccount::new(1, vec![Keypair::generate().public_key()]).unwrap(),
+            signatures: Vec::new(),
+        });
+        assert_ne!(tx.hash_bytes(), multisig.hash_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_multisig_accounts() -> Result<(), anyhow::Error> {
+        let signers: Vec<Keypair> = (0..3).map(|_| Keypair::generate()).collect();
+        let keys: Vec<Vec<u8>> = signers.iter().map(Keypair::public_key).collect();
+        let account = MultisigAccount::new(2, keys.clone())?;
+        assert!(MultisigAccount::new(0, keys.clone()).is_err());
+        assert!(MultisigAccount::new(4, keys.clone()).is_err());
+        let reversed = keys.iter().rev().cloned().collect();
+        assert_eq!(MultisigAccount::new(2, reversed)?.address(), account.address());
+        assert_ne!(MultisigAccount::new(3, keys)?.address(), account.address());
+
+        let spend = Transaction::new(account.address(), "recipient".to_string(), Amount::from_zhtp(5))
+            .for_multisig(account.clone())?;
+        assert!(Transaction::new("validator1".to_string(), "recipient".to_string(), Amount::from_zhtp(5))
+            .for_multisig(account.clone())
+            .is_err());
+
+        // Signers work from their own copies and hand back partial signatures
+        let offline: Transaction = serde_json::from_str(&serde_json::to_string(&spend)?)?;
+        let first = offline.partial_sign(&signers[0])?;
+        let third = offline.partial_sign(&signers[2])?;
+        assert!(offline.partial_sign(&Keypair::generate()).is_err());
+
+        let mut tx = spend;
+        tx.add_multisig_signature(first.clone())?;
+        tx.add_multisig_signature(first.clone())?;
+        assert!(!tx.verify_sender());
+        let mut forged = third.clone();
+        forged.key_index = (third.key_index + 1) % 3;
+        assert!(tx.add_multisig_signature(forged).is_err());
+        tx.add_multisig_signature(third)?;
+        assert!(tx.verify_sender());
+
+        // The signatures cover the transaction contents
+        let mut tampered = tx.clone();
+        tampered.amount = Amount::from_zhtp(500);
+        assert!(!tampered.verify_sender());
+
+        // Submission needs a complete set of signatures
+        use crate::genesis::GenesisAllocation;
+        let blockchain = Blockchain::from_genesis(&GenesisSpec {
+            allocations: vec![GenesisAllocation { address: account.address(), amount: Amount::from_zhtp(10), lock: None }],
+            ..GenesisSpec::default()
+        });
+        let mut incomplete = tx.clone();
+        incomplete.multisig.as_mut().unwrap().signatures.truncate(1);
+        assert!(blockchain.submit_transaction(incomplete).await.is_err());
+        blockchain.submit_transaction(tx).await?;
+        blockchain.create_block("validator1", 1.0, None).await;
+        assert_eq!(blockchain.get_balance(&account.address()).await, Amount::from_zhtp(5));
+        Ok(())
+    }
+
+    /// Independent copy of a chain, standing in for another validator's node
+    #[tokio::test]
+    async fn test_snapshot_bootstrap() -> Result<(), anyhow::Error> {
+        use crate::genesis::GenesisAllocation;
+        let alice = Keypair::generate();
+
+        let spec = GenesisSpec {
+            chain_id: "zhtp-testnet".to_string(),
+            allocations: vec![GenesisAllocation { address: alice.address(), amount: Amount::from_zhtp(500), lock: None }],
+            ..GenesisSpec::default()
+        };
+        let source = Blockchain::from_genesis(&spec);
+        let transfer = Transaction::new(alice.address(), "bob".to_string(), Amount::from_zhtp(200));
+        source.submit_transaction(signed(transfer, &alice)).await?;
+        for _ in 0..3 {
+            source.create_block("validator1", 1.0, None).await;
+        }
+
+        // Snapshots are only accepted at the trusted block with matching state
+        let snapshot = source.create_snapshot(2).await?;
+        let trusted = source.get_block_by_height(2).await.unwrap().hash;
+        assert_eq!(snapshot.headers.len(), 3);
+        assert!(Blockchain::from_snapshot(&spec, &snapshot, &source.get_latest_block().await.hash).is_err());
+        let mut forged = snapshot.clone();
+        forged.accounts.get_mut("bob").unwrap().balance = Amount::from_zhtp(300);
+        assert!(Blockchain::from_snapshot(&spec, &forged, &trusted).is_err());
+        let other = GenesisSpec { chain_id: "zhtp-devnet".to_string(), ..spec.clone() };
+        assert!(Blockchain::from_snapshot(&other, &snapshot, &trusted).is_err());
+
+        // A node started from it serves headers below the snapshot and
+        // follows the chain from there
+        let node = Blockchain::from_snapshot(&spec, &snapshot, &trusted)?;
+        assert_eq!(node.genesis_hash().await, spec.genesis_hash());
+        assert_eq!(node.get_balance("bob").await, Amount::from_zhtp(200));
+        assert_eq!(node.get_block_headers(0, 10).await.len(), 3);
+        assert!(node.get_block_by_height(1).await.is_none());
+        let next = source.get_block_by_height(3).await.unwrap();
+        assert!(matches!(node.import_block(next).await?, BlockImport::Extended));
+        node.create_block("validator1", 1.0, None).await;
+        assert_eq!(node.get_latest_block().await.index, 4);
+        let replayed = node.create_snapshot(3).await?;
+        assert_eq!(replayed.accounts, source.create_snapshot(3).await?.accounts);
+
+        // Imported into a data directory, the chain reopens from the snapshot
+        let data_dir = std::env::temp_dir().join(format!("zhtp-snapshot-{}", uuid::Uuid::new_v4()));
+        drop(Blockchain::open_with_genesis(&spec, &data_dir)?);
+        let tip = {
+            let imported = Blockchain::import_snapshot(&spec, &snapshot, &trusted, &data_dir)?;
+            imported.create_block("validator1", 1.0, None).await;
+            imported.get_latest_block().await
+        };
+        let reopened = Blockchain::open_with_genesis(&spec, &data_dir)?;
+        assert_eq!(reopened.get_latest_block().await.hash, tip.hash);
+        assert_eq!(reopened.get_balance("bob").await, Amount::from_zhtp(200));
+        assert!(Blockchain::open_with_genesis(&other, &data_dir).is_err());
+        std::fs::remove_dir_all(&data_dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_limits() -> Result<(), anyhow::Error> {
+        let data_dir = std::env::temp_dir().join(format!("zhtp-limits-{}", uuid::Uuid::new_v4()));
+        let governance = Keypair::generate();
+        let spec = GenesisSpec { governance: Some(governance.address()), ..GenesisSpec::default() };
+        let blockchain = Blockchain::open_with_genesis(&spec, &data_dir)?;
+        let validator = Keypair::generate();
+        blockchain.create_block(&validator.address(), 1.0, None).await;
+
+        // Room for the reward and two transfers, scheduled by the governance key only
+        let params = ProtocolParams { max_block_gas: 3 * TRANSACTION_BASE_GAS, ..ProtocolParams::default() };
+        let unusable = ProtocolParams { max_block_gas: 1, ..params };
+        assert!(blockchain.schedule_protocol_params(unusable, &governance).await.is_err());
+        assert!(blockchain.schedule_protocol_params(params, &validator).await.is_err());
+        let upgrade = blockchain.schedule_protocol_params(params, &governance).await?;
+        blockchain.create_block(&validator.address(), 1.0, None).await;
+        assert_eq!(blockchain.params_schedule().await.upgrades.get(&upgrade.activation_height), Some(&params));
+
+        // An upgrade activating too soon or sent by anyone else is rejected
+        let mut accounts = blockchain.state.read().await.accounts.clone();
+        let early = ParamsUpgrade { activation_height: 3, params };
+        let mut tx = Transaction::params_upgrade(governance.address(), &early);
+        tx.nonce = 1;
+        tx.sign_with_keypair(&governance)?;
+        let mut forged = Transaction::params_upgrade(validator.address(), &upgrade);
+        forged.sign_with_keypair(&validator)?;
+        let (outcomes, _) = accounts.apply_transactions(&[tx, forged], "validator2", 3, Utc::now().timestamp());
+        assert!(outcomes.iter().all(|outcome| outcome.result == Err(TransactionError::InvalidParamsUpgrade)));
+
+        // Blocks keep the old limits up to the activation height
+        while blockchain.get_latest_block().await.index + 2 < upgrade.activation_height {
+            blockchain.create_block(&validator.address(), 1.0, None).await;
+        }
+        let peer = fork_of(&blockchain).await;
+        let transfers = |first_nonce: u64| -> Result<Vec<Transaction>, anyhow::Error> {
+            (first_nonce..first_nonce + 3)
+                .map(|nonce| {
+                    let mut tx = Transaction::new(validator.address(), "alice".to_string(), Amount::from_zhtp(1));
+                    tx.nonce = nonce;
+                    tx.sign_with_keypair(&validator)?;
+                    Ok(tx)
+                })
+                .collect()
+        };
+        for tx in transfers(0)? {
+            blockchain.submit_transaction(tx).await?;
+        }
+        blockchain.create_block(&validator.address(), 1.0, None).await;
+        let before = blockchain.get_latest_block().await;
+        assert_eq!(before.transactions.len(), 4);
+        let loose = fork_of(&blockchain).await;
+        for tx in transfers(3)? {
+            blockchain.submit_transaction(tx.clone()).await?;
+            loose.submit_transaction(tx).await?;
+        }
+        blockchain.create_block(&validator.address(), 1.0, None).await;
+        let block = blockchain.get_latest_block().await;
+        assert_eq!(block.index, upgrade.activation_height);
+        assert_eq!(block.transactions.len(), 3);
+        assert_eq!(block.gas_used(), params.max_block_gas);
+        assert_eq!(blockchain.get_pending_transactions().await.len(), 1);
+
+        // Peers refuse blocks built without the upgrade
+        loose.state.write().await.accounts.params.upgrades.clear();
+        loose.create_block(&validator.address(), 1.0, None).await;
+        let oversized = loose.get_latest_block().await;
+        assert_eq!(oversized.transactions.len(), 4);
+        assert!(matches!(peer.import_block(before).await?, BlockImport::Extended));
+        assert!(peer.import_block(oversized).await.is_err());
+        assert!(matches!(peer.import_block(block).await?, BlockImport::Extended));
+
+        drop(blockchain);
+        let reopened = Blockchain::open_with_genesis(&spec, &data_dir)?;
+        assert_eq!(reopened.protocol_params().await, params);
+        std::fs::remove_dir_all(&data_dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chain_pruning() -> Result<(), anyhow::Error> {
+        let data_dir = std::env::temp_dir().join(format!("zhtp-pruning-{}", uuid::Uuid::new_v4()));
+        let mut blockchain = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+        let validator1 = Keypair::generate();
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+        let mut hashes = Vec::new();
+        for nonce in 0..4 {
+            let mut tx = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(1));
+            tx.nonce = nonce;
+            let tx = signed(tx, &validator1);
+            hashes.push(tx.calculate_hash());
+            blockchain.submit_transaction(tx).await?;
+            blockchain.create_block("validator2", 1.0, None).await;
+        }
+        let tip = blockchain.get_latest_block().await;
+        assert_eq!(blockchain.prune().await?, None);
+
+        // Nothing is pruned that could still be reorganized
+        blockchain.pruning = PruningMode::Full { keep_blocks: 3 };
+        assert_eq!(blockchain.prune().await?, None);
+        blockchain.finalize_block(&tip.hash).await?;
+        assert_eq!(blockchain.prune().await?, Some(3));
+        assert_eq!(blockchain.prune().await?, None);
+
+        // Retained blocks stay queryable and headers cover the whole chain
+        assert!(blockchain.get_block_by_height(2).await.is_none());
+        assert_eq!(blockchain.get_block_by_height(3).await.unwrap().transactions.len(), 2);
+        assert_eq!(blockchain.get_block_headers(0, 10).await.len(), 6);
+        assert!(blockchain.get_transaction(&hashes[0]).await.is_none());
+        assert_eq!(blockchain.get_transaction(&hashes[2]).await.unwrap().block_index, 4);
+        let history = blockchain.get_address_history("alice", 0, 10).await;
+        assert_eq!((history.total, history.transactions.len()), (3, 3));
+        assert_eq!(blockchain.get_balance("alice").await, Amount::from_zhtp(4));
+        assert_eq!(blockchain.genesis_hash().await, GenesisSpec::default().genesis_hash());
+
+        // The pruned chain reopens from disk and keeps growing
+        drop(blockchain);
+        let mut blockchain = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+        assert!(blockchain.get_block_by_height(2).await.is_none());
+        assert_eq!(blockchain.get_latest_block().await.hash, tip.hash);
+        assert_eq!(blockchain.get_balance("alice").await, Amount::from_zhtp(4));
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+
+        blockchain.pruning = PruningMode::Pruned;
+        let tip = blockchain.get_latest_block().await;
+        blockchain.finalize_block(&tip.hash).await?;
+        assert_eq!(blockchain.prune().await?, Some(tip.index));
+        assert_eq!(blockchain.get_block_headers(0, 10).await.len(), 7);
+        drop(blockchain);
+        let reopened = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+        assert_eq!(reopened.get_latest_block().await.hash, tip.hash);
+        assert_eq!(reopened.get_block_headers(0, 10).await.len(), 7);
+        assert_eq!(reopened.get_balance("alice").await, Amount::from_zhtp(4));
+
+        std::fs::remove_dir_all(&data_dir)?;
+        Ok(())
+    }
+
+    async fn fork_of(blockchain: &Blockchain) -> Blockchain {
+        let fork = Blockchain::new(blockchain.base_reward);
+        *fork.state.write().await = blockchain.state.read().await.clone();
+        fork
+    }
+
+    #[tokio::test]
+    async fn test_reorg_to_longer_branch() -> Result<(), anyhow::Error> {
+        let data_dir = std::env::temp_dir().join(format!("zhtp-reorg-{}", uuid::Uuid::new_v4()));
+        let local = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+        let validator1 = Keypair::generate();
+        let mut reorgs = local.subscribe_reorgs();
+        local.create_block(&validator1.address(), 1.0, None).await;
+        let remote = fork_of(&local).await;
+
+        let transfer = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(10));
+        let transfer = signed(transfer, &validator1);
+        let transfer_hash = transfer.calculate_hash();
+        assert!(local.add_transaction(transfer).await);
+        local.create_block("validator2", 1.0, None).await;
+        let orphaned_tip = local.get_latest_block().await;
+        assert_eq!(local.get_transaction(&transfer_hash).await.unwrap().block_index, 2);
+
+        remote.create_block("validator3", 1.0, None).await;
+        let remote_block2 = remote.get_latest_block().await;
+        remote.create_block("validator3", 1.0, None).await;
+        let remote_block3 = remote.get_latest_block().await;
+
+        // An equally long branch does not displace the current one
+        assert!(matches!(local.import_block(remote_block2.clone()).await?, BlockImport::SideChain));
+        assert_eq!(local.get_latest_block().await.hash, orphaned_tip.hash);
+        assert!(matches!(local.import_block(remote_block2.clone()).await?, BlockImport::Known));
+
+        let event = match local.import_block(remote_block3.clone()).await? {
+            BlockImport::Reorganized(event) => event,
+            other => panic!("expected a reorg, got {:?}", other),
+        };
+        assert_eq!(event.fork_height, 1);
+        assert_eq!(event.reverted_blocks, vec![orphaned_tip.hash.clone()]);
+        assert_eq!(event.applied_blocks, vec![remote_block2.hash.clone(), remote_block3.hash.clone()]);
+        assert_eq!(event.requeued_transactions.len(), 1);
+        assert_eq!(reorgs.recv().await?.new_tip, remote_block3.hash);
+
+        // Account state follows the new branch
+        assert_eq!(local.get_latest_block().await.hash, remote_block3.hash);
+        assert_eq!(local.get_balance(&validator1.address()).await, Amount::from_zhtp(100));
+        assert!(local.get_balance("validator2").await.is_zero());
+        assert!(local.get_balance("alice").await.is_zero());
+        assert_eq!(local.get_balance("validator3").await, Amount::from_zhtp(200));
+
+        // So do the explorer indexes
+        assert!(local.get_block_by_hash(&orphaned_tip.hash).await.is_none());
+        assert!(local.get_transaction(&transfer_hash).await.is_none());
+        assert_eq!(local.get_address_history("alice", 0, 10).await.total, 0);
+
+        // The orphaned transfer is mined again on top of the new branch
+        local.create_block("validator2", 1.0, None).await;
+        assert_eq!(local.get_balance("alice").await, Amount::from_zhtp(10));
+        let tip = local.get_latest_block().await;
+        assert_eq!(local.get_transaction(&transfer_hash).await.unwrap().block_hash, tip.hash);
+        drop(local);
+
+        let reopened = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+        assert_eq!(reopened.get_latest_block().await.hash, tip.hash);
+        assert_eq!(reopened.get_balance("alice").await, Amount::from_zhtp(10));
+
+        std::fs::remove_dir_all(&data_dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unpersisted_changes_roll_back() -> Result<(), anyhow::Error> {
+        let local = Blockchain::new(Amount::from_zhtp(100));
+        let validator1 = Keypair::generate();
+        local.create_block(&validator1.address(), 1.0, None).await;
+        let remote = fork_of(&local).await;
+
+        let transfer = Transaction::new(validator1.address(), "alice".to_string(), Amount::from_zhtp(10));
+        let transfer = signed(transfer, &validator1);
+        let transfer_hash = transfer.calculate_hash();
+        assert!(local.add_transaction(transfer).await);
+        local.create_block("validator2", 1.0, None).await;
+        let tip = local.get_latest_block().await;
+
+        remote.create_block("validator3", 1.0, None).await;
+        local.import_block(remote.get_latest_block().await).await?;
+        remote.create_block("validator3", 1.0, None).await;
+        let event = match local.import_block(remote.get_latest_block().await).await? {
+            BlockImport::Reorganized(event) => event,
+            other => panic!("expected a reorg, got {:?}", other),
+        };
+
+        // A reorganization that could not be written is undone
+        local.state.write().await.undo_reorganization(&event)?;
+        assert_eq!(local.get_latest_block().await.hash, tip.hash);
+        assert_eq!(local.get_balance("alice").await, Amount::from_zhtp(10));
+        assert!(local.get_balance("validator3").await.is_zero());
+        assert_eq!(local.get_transaction(&transfer_hash).await.unwrap().block_hash, tip.hash);
+
+        // So is a block that could not be written, returning its transfer
+        // to the pool
+        local.state.write().await.revert_tip()?;
+        assert_eq!(local.get_latest_block().await.index, 1);
+        assert!(local.get_balance("alice").await.is_zero());
+        assert!(local.get_balance("validator2").await.is_zero());
+        assert!(local.get_transaction(&transfer_hash).await.is_none());
+        local.create_block("validator2", 1.0, None).await;
+        assert_eq!(local.get_balance("alice").await, Amount::from_zhtp(10));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fork_choice_rules() -> Result<(), anyhow::Error> {
+        let base = Blockchain::new(Amount::from_zhtp(100));
+        base.create_block("validator1", 1.0, None).await;
+        let light_chain = fork_of(&base).await;
+        let claimed_chain = fork_of(&base).await;
+        let heavy = Keypair::generate();
+        let light = Keypair::generate();
+
+        base.create_signed_block(&heavy, 1.0, None).await;
+        base.set_validator_stake(&heavy.address(), Amount::from_zhtp(1000)).await;
+        base.set_validator_stake(&light.address(), Amount::from_zhtp(10)).await;
+        light_chain.create_signed_block(&light, 1.0, None).await;
+        light_chain.create_signed_block(&light, 1.0, None).await;
+        let light_blocks = light_chain.state.read().await.chain[2..].to_vec();
+        assert!(light_blocks.iter().all(Block::verify_producer));
+
+        // More stake beats more blocks
+        let by_stake = fork_of(&base).await;
+        for block in &light_blocks {
+            assert!(matches!(by_stake.import_block(block.clone()).await?, BlockImport::SideChain));
+        }
+
+        // Naming a staked validator without its signature carries no weight
+        claimed_chain.create_block(&heavy.address(), 1.0, None).await;
+        claimed_chain.create_block(&heavy.address(), 1.0, None).await;
+        let claimed_blocks = claimed_chain.state.read().await.chain[2..].to_vec();
+        assert!(!claimed_blocks[0].verify_producer());
+        let mut forged = claimed_blocks[0].clone();
+        forged.producer_key = Some(heavy.public_key());
+        forged.producer_signature = light_blocks[0].producer_signature.clone();
+        assert!(!forged.verify_producer());
+        assert!(forged.sign_producer(&light).is_err());
+        let by_signature = fork_of(&light_chain).await;
+        for block in &claimed_blocks {
+            assert!(matches!(by_signature.import_block(block.clone()).await?, BlockImport::SideChain));
+        }
+
+        // Otherwise the longer branch wins
+        let mut by_length = fork_of(&base).await;
+        by_length.fork_choice = ForkChoice::HighestFinalized;
+        by_length.import_block(light_blocks[0].clone()).await?;
+        assert!(matches!(by_length.import_block(light_blocks[1].clone()).await?, BlockImport::Reorganized(_)));
+
+        // Finalized blocks are never reverted
+        let finalized = fork_of(&base).await;
+        let heavy_tip = finalized.get_latest_block().await;
+        finalized.finalize_block(&heavy_tip.hash).await?;
+        assert!(finalized.import_block(light_blocks[0].clone()).await.is_err());
+        assert_eq!(finalized.get_latest_block().await.hash, heavy_tip.hash);
+
+        // Blocks whose parent is unknown are rejected
+        assert!(Blockchain::new(Amount::from_zhtp(100)).import_block(light_blocks[1].clone()).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_side_blocks_bounded() -> Result<(), anyhow::Error> {
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        blockchain.create_block("validator1", 1.0, None).await;
+        let remote = fork_of(&blockchain).await;
+        blockchain.create_block("validator1", 1.0, None).await;
+        remote.create_block("validator2", 1.0, None).await;
+        let side = remote.get_latest_block().await;
+        assert!(matches!(blockchain.import_block(side.clone()).await?, BlockImport::SideChain));
+
+        // Side blocks at or below the finalized height are dropped
+        let tip = blockchain.get_latest_block().await;
+        blockchain.finalize_block(&tip.hash).await?;
+        assert!(blockchain.state.read().await.side_blocks.is_empty());
+
+        // Beyond the cap, the lowest side blocks go first
+        let mut state = blockchain.state.write().await;
+        for offset in 0..MAX_SIDE_BLOCKS as u64 + 10 {
+            let mut block = side.clone();
+            block.index = tip.index + 1 + offset;
+            block.hash = format!("side-{}", offset);
+            state.side_blocks.insert(block.hash.clone(), block);
+        }
+        state.prune_fork_data();
+        assert_eq!(state.side_blocks.len(), MAX_SIDE_BLOCKS);
+        assert!(!state.side_blocks.contains_key("side-9"));
+        assert!(state.side_blocks.contains_key("side-10"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_header_root_proofs() {
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        blockchain.create_block("validator1", 1.0, None).await;
+        blockchain.create_block("validator2", 1.0, None).await;
+
+        let tip = blockchain.get_latest_block().await;
+        let state_root = tip.state_root.expect("blocks commit to a state root");
+        let transactions_root = tip.transactions_root.expect("blocks commit to a transactions root");
+
+        let reward_hash = tip.transactions[0].calculate_hash();
+        let tx_proof = blockchain.prove_transaction(&reward_hash).await.unwrap();
+        assert_eq!(tx_proof.block_index, tip.index);
+        assert!(tx_proof.verify(&transactions_root));
+        assert!(!tx_proof.verify(&state_root));
+
+        let account = blockchain.prove_account("validator1").await;
+        assert_eq!(account.balance, blockchain.get_balance("validator1").await);
+        assert!(account.verify(&state_root));
+
+        let mut forged = account.clone();
+        forged.balance = forged.balance.saturating_add(Amount::from_base_units(1));
+        assert!(!forged.verify(&state_root));
+
+        let missing = blockchain.prove_account("nobody").await;
+        assert!(missing.balance.is_zero());
+        assert!(missing.verify(&state_root));
+    }
+
+    #[tokio::test]
+    async fn test_persistent_chain_reload() -> Result<(), anyhow::Error> {
+        let data_dir = std::env::temp_dir().join(format!("zhtp-blockchain-{}", uuid::Uuid::new_v4()));
+
+        let (tip_hash, balance) = {
+            let blockchain = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+            blockchain.create_block("validator1", 1.0, None).await;
+            blockchain.create_block("validator1", 1.0, None).await;
+            let tip = blockchain.get_latest_block().await;
+            (tip.hash, blockchain.get_balance("validator1").await)
+        };
+
+        let reopened = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+        let tip = reopened.get_latest_block().await;
+        assert_eq!(tip.index, 2);
+        assert_eq!(tip.hash, tip_hash);
+        assert_eq!(reopened.get_balance("validator1").await, balance);
+
+        std::fs::remove_dir_all(&data_dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shielded_pool() -> Result<(), anyhow::Error> {
+        use crate::zhtp::shielded::ShieldedBundleBuilder;
+
+        let data_dir = std::env::temp_dir().join(format!("zhtp-shielded-{}", uuid::Uuid::new_v4()));
+        let local = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+        let validator1 = Keypair::generate();
+        local.create_block(&validator1.address(), 1.0, None).await;
+        let fee = Amount::from_zhtp(1);
+        let wallet = || {
+            let key = SpendingKey::generate();
+            let keypair = Keypair::generate();
+            let address = key.address([3u8; 16], keypair.kyber_public_key());
+            (key, keypair, address)
+        };
+        let (alice_key, alice_keypair, alice) = wallet();
+        let (bob_key, bob_keypair, bob) = wallet();
+
+        // Shielding is a transparent transfer to the pool carrying the bundle
+        let bundle = ShieldedBundleBuilder::new(fee)
+            .shield(Amount::from_zhtp(50))
+            .output(&alice, Amount::from_zhtp(49))
+            .build(&alice_key)?;
+        let mut shielding = Transaction::new(validator1.address(), SHIELDED_POOL_ADDRESS.to_string(), Amount::from_zhtp(50));
+        shielding.zk_transaction = Some(ZkTransaction::shielded(bundle)?);
+        let bare = Transaction::new(validator1.address(), SHIELDED_POOL_ADDRESS.to_string(), Amount::from_zhtp(50));
+        let bare = signed(bare, &validator1);
+        assert!(local.submit_transaction(bare).await.is_err());
+        let mut mismatched = shielding.clone();
+        mismatched.amount = Amount::from_zhtp(40);
+        assert!(local.submit_transaction(signed(mismatched, &validator1)).await.is_err());
+        local.submit_transaction(signed(shielding, &validator1)).await?;
+        local.create_block(&validator1.address(), 1.0, None).await;
+        assert_eq!(local.get_balance(&validator1.address()).await, Amount::from_zhtp(151));
+        assert_eq!(local.get_balance(SHIELDED_POOL_ADDRESS).await, Amount::from_zhtp(49));
+
+        let notes = local.find_notes(&alice_key, &alice_keypair).await;
+        assert_eq!(notes.len(), 1);
+        assert_eq!(notes[0].value, Amount::from_zhtp(49));
+        assert!(local.find_notes(&bob_key, &bob_keypair).await.is_empty());
+        let remote = fork_of(&local).await;
+
+        // Alice pays bob privately and unshields the change to carol
+        let note = notes[0].clone();
+        let (anchor, path) = local.shielded_path(&note.commitment()).await.unwrap();
+        assert_eq!(anchor, local.shielded_anchor().await);
+        let payment = ShieldedBundleBuilder::new(fee)
+            .spend(note.clone(), anchor, path.clone())
+            .output(&bob, Amount::from_zhtp(20))
+            .unshield("carol".to_string(), Amount::from_zhtp(28))
+            .build(&alice_key)?;
+        assert!(local.add_zk_transaction(ZkTransaction::shielded(payment)?).await?);
+        local.create_block(&validator1.address(), 1.0, None).await;
+        assert_eq!(local.get_balance("carol").await, Amount::from_zhtp(28));
+        assert_eq!(local.get_balance(SHIELDED_POOL_ADDRESS).await, Amount::from_zhtp(20));
+        assert!(local.find_notes(&alice_key, &alice_keypair).await.is_empty());
+        assert_eq!(local.find_notes(&bob_key, &bob_keypair).await[0].value, Amount::from_zhtp(20));
+
+        // The spent note cannot be spent again, even against its old anchor
+        let double_spend = ShieldedBundleBuilder::new(fee)
+            .spend(note.clone(), anchor, path)
+            .unshield("mallory".to_string(), Amount::from_zhtp(48))
+            .build(&alice_key)?;
+        assert!(!local.add_zk_transaction(ZkTransaction::shielded(double_spend.clone())?).await?);
+        let mut accounts = local.state.read().await.accounts.clone();
+        let replayed = Transaction::new_private(ZkTransaction::shielded(double_spend)?)?;
+        let (outcomes, _) = accounts.apply_transactions(&[replayed], &validator1.address(), 1, Utc::now().timestamp());
+        assert_eq!(outcomes[0].result, Err(TransactionError::NullifierSpent));
+
+        // A reorg past the spend restores the note, and the spend is mined again
+        remote.create_block("validator2", 1.0, None).await;
+        let remote_block3 = remote.get_latest_block().await;
+        remote.create_block("validator2", 1.0, None).await;
+        local.import_block(remote_block3).await?;
+        assert!(matches!(local.import_block(remote.get_latest_block().await).await?, BlockImport::Reorganized(_)));
+        assert!(local.get_balance("carol").await.is_zero());
+        assert_eq!(local.find_notes(&alice_key, &alice_keypair).await, vec![note]);
+        local.create_block(&validator1.address(), 1.0, None).await;
+        assert_eq!(local.get_balance("carol").await, Amount::from_zhtp(28));
+        let anchor = local.shielded_anchor().await;
+        drop(local);
+
+        // The pool is committed to by the state root and persisted with it
+        let reopened = Blockchain::open(Amount::from_zhtp(100), &data_dir)?;
+        assert_eq!(reopened.shielded_anchor().await, anchor);
+        assert_eq!(reopened.find_notes(&bob_key, &bob_keypair).await.len(), 1);
+        let tip = reopened.get_latest_block().await;
+        let replayed = AccountState::replay(&reopened.state.read().await.chain);
+        assert_eq!(Some(replayed.root()), tip.state_root);
+
+        std::fs::remove_dir_all(&data_dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shield_and_unshield_preserve_supply() -> Result<(), anyhow::Error> {
+        use crate::zhtp::shielded::ShieldedBundleBuilder;
+
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        let validator1 = Keypair::generate();
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+        assert_eq!(blockchain.total_supply().await, Amount::from_zhtp(100));
+        let key = SpendingKey::generate();
+        let keypair = Keypair::generate();
+        let alice = key.address([1u8; 16], keypair.kyber_public_key());
+        let fee = Amount::from_zhtp(1);
+
+        // Shield 60 of validator1's balance, paying the shielded fee to the producer
+        let bundle = ShieldedBundleBuilder::new(fee)
+            .shield(Amount::from_zhtp(60))
+            .output(&alice, Amount::from_zhtp(59))
+            .build(&key)?;
+        assert!(Transaction::unshield(bundle.clone()).is_err());
+        let shield = Transaction::shield(validator1.address(), bundle)?;
+        assert_eq!(shield.shielded_kind(), Some(ShieldedKind::Shield));
+        blockchain.submit_transaction(signed(shield, &validator1)).await?;
+        blockchain.create_block("validator2", 1.0, None).await;
+        assert_eq!(blockchain.get_balance(&validator1.address()).await, Amount::from_zhtp(40));
+        assert_eq!(blockchain.get_balance("validator2").await, Amount::from_zhtp(101));
+        assert_eq!(blockchain.get_balance(SHIELDED_POOL_ADDRESS).await, Amount::from_zhtp(59));
+        assert_eq!(blockchain.total_supply().await, Amount::from_zhtp(200));
+
+        // Unshield the note back to a transparent account
+        let note = blockchain.find_notes(&key, &keypair).await.remove(0);
+        let (anchor, path) = blockchain.shielded_path(&note.commitment()).await.unwrap();
+        let bundle = ShieldedBundleBuilder::new(fee)
+            .spend(note, anchor, path)
+            .unshield("carol".to_string(), Amount::from_zhtp(58))
+            .build(&key)?;
+        assert!(Transaction::shield(validator1.address(), bundle.clone()).is_err());
+        let unshield = Transaction::unshield(bundle)?;
+        assert_eq!(unshield.shielded_kind(), Some(ShieldedKind::Unshield));
+        blockchain.submit_transaction(unshield).await?;
+        assert_eq!(blockchain.get_pending_zk_transactions().await.len(), 1);
+        let outcomes = blockchain.create_block("validator3", 1.0, None).await;
+        assert!(outcomes.iter().all(TransactionOutcome::is_applied));
+        assert!(blockchain.get_pending_zk_transactions().await.is_empty());
+        assert_eq!(blockchain.get_balance("carol").await, Amount::from_zhtp(58));
+        assert_eq!(blockchain.get_balance("validator3").await, Amount::from_zhtp(101));
+        assert!(blockchain.get_balance(SHIELDED_POOL_ADDRESS).await.is_zero());
+        assert_eq!(blockchain.total_supply().await, Amount::from_zhtp(300));
+
+        // Value appearing beyond what the coinbase minted breaks the invariant
+        let mut accounts = blockchain.state.read().await.accounts.clone();
+        let coinbase = Transaction::coinbase("validator4".to_string(), Amount::from_zhtp(100), 4);
+        let (outcomes, undo) = accounts.apply_transactions(std::slice::from_ref(&coinbase), "validator4", 1, 0);
+        accounts.check_supply(std::slice::from_ref(&coinbase), &outcomes, &undo)?;
+        assert!(accounts.check_supply(&[], &[], &undo).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_viewing_key_report() -> Result<(), anyhow::Error> {
+        use crate::zhtp::shielded::ShieldedBundleBuilder;
+        use crate::zhtp::viewing_keys::Direction;
+
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
+        let validator1 = Keypair::generate();
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+        let alice = Keypair::generate();
+        let bob = Keypair::generate();
+
+        // An account-based private transfer and a shielded payment from alice to bob
+        let transfer = ZkTransaction::new(&alice, "alice", "bob", &bob.kyber_public_key(), Amount::from_zhtp(5), Amount::from_zhtp(50), 0)?;
+        assert!(blockchain.add_zk_transaction(transfer).await?);
+        let bob_address = SpendingKey::generate().address([0u8; 16], bob.kyber_public_key());
+        let bundle = ShieldedBundleBuilder::new(Amount::from_zhtp(1))
+            .shield(Amount::from_zhtp(10))
+            .output(&bob_address, Amount::from_zhtp(9))
+            .outgoing_viewing_key(alice.outgoing_viewing_key())
+            .build(&SpendingKey::generate())?;
+        let shield = Transaction::shield(validator1.address(), bundle)?;
+        blockchain.submit_transaction(signed(shield, &validator1)).await?;
+        blockchain.create_block(&validator1.address(), 1.0, None).await;
+
+        // The auditor receives exported keys only
+        let bob_view = ViewingKey::import(&ViewingKey::incoming(&bob).export()?)?;
+        let report = blockchain.scan_with_viewing_key(&bob_view).await;
+        assert!(!report.full);
+        assert_eq!((report.first_block, report.last_block), (0, 2));
+        assert_eq!(report.entries.len(), 2);
+        assert!(report.entries.iter().all(|entry| entry.block_index == 2 && entry.direction == Direction::Incoming));
+        assert_eq!(report.total_received, Amount::from_zhtp(14));
+
+        let alice_view = ViewingKey::import(&ViewingKey::full(&alice).export()?)?;
+        let report = blockchain.scan_with_viewing_key(&alice_view).await;
+        assert!(report.full);
+        assert_eq!(report.total_sent, Amount::from_zhtp(14));
+        assert!(report.total_received.is_zero());
+
+        let stranger = ViewingKey::full(&Keypair::generate());
+        assert!(blockchain.scan_with_viewing_key(&stranger).await.entries.is_empty());
+        Ok(())
     }
 }
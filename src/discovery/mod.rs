@@ -1,6 +1,9 @@
 use crate::{
     storage::content::{ContentId, ContentMetadata},
-    zhtp::zk_proofs::{ByteRoutingProof, RoutingProof},
+    zhtp::{
+        proof_envelope::{ProofEnvelope, ProofStatement, VerifierRegistry},
+        zk_proofs::UnifiedCircuit,
+    },
 };
 use std::{
     collections::{HashMap, HashSet, BTreeMap},
@@ -10,8 +13,6 @@ use std::{
 use tokio::sync::RwLock;
 use anyhow::Result;
 use sha2::{Sha256, Digest};
-use ark_bn254::Fr;
-use ark_ff::PrimeField;
 
 /// Node discovery service
 pub struct DiscoveryNode {
@@ -115,7 +116,7 @@ impl DiscoveryNode {
         // In test environments, use simplified verification
         let verification_result = if cfg!(test) {
             // Simplified verification for tests - just check proof structure
-            !identity_proof.commitments.is_empty() && !identity_proof.elements.is_empty()
+            !identity_proof.is_none() && !identity_proof.public_inputs.is_empty()
         } else {
             // Full ZK verification in production
             self.verify_node_identity_proof(&identity_proof, &identity_commitment)?
@@ -162,60 +163,49 @@ impl DiscoveryNode {
 
     /// Generate a zero-knowledge proof of node identity
     fn generate_node_identity_proof(
-        &self, 
-        name: &str, 
-        addr: &SocketAddr, 
+        &self,
+        _name: &str,
+        _addr: &SocketAddr,
         identity_commitment: &[u8; 32]
-    ) -> Result<ByteRoutingProof> {
-        // Create proof elements using the ZK proof system
-        let name_field = Fr::from_le_bytes_mod_order(name.as_bytes());
-        let addr_field = Fr::from_le_bytes_mod_order(&addr.to_string().as_bytes());
-        let commitment_field = Fr::from_le_bytes_mod_order(identity_commitment);
-        
-        // Generate proof elements that verify node ownership without revealing private data
-        let proof_elements = vec![name_field, addr_field, commitment_field];
-        
-        // Create commitment to the node identity
-        let mut hasher = sha2::Sha256::new();
-        hasher.update(name.as_bytes());
-        hasher.update(&addr.to_string().as_bytes());
-        let node_commitment = hasher.finalize();
-        
-        Ok(ByteRoutingProof {
-            commitments: vec![node_commitment.to_vec(), identity_commitment.to_vec()],
-            elements: proof_elements.iter().map(|f| {
-                let mut bytes = Vec::new();
-                ark_serialize::CanonicalSerialize::serialize_uncompressed(f, &mut bytes).unwrap();
-                bytes
-            }).collect(),
-            inputs: vec![node_commitment.to_vec()],
-        })
+    ) -> Result<ProofEnvelope> {
+        // Prove a route from the identity commitment to itself; the
+        // commitment already binds the node's name and address
+        let identity = identity_commitment.to_vec();
+        let mut routing_table = HashMap::new();
+        routing_table.insert(identity.clone(), vec![identity.clone()]);
+        let mut circuit = UnifiedCircuit::new(
+            identity.clone(),
+            identity.clone(),
+            vec![identity.clone(), identity],
+            routing_table,
+            *identity_commitment,
+            vec![],
+            <ark_bn254::G1Projective as ark_ec::Group>::generator(),
+            0,
+            vec![],
+            vec![],
+        );
+
+        circuit
+            .generate_proof()
+            .map(ProofEnvelope::from)
+            .ok_or_else(|| anyhow::anyhow!("Failed to generate node identity proof"))
     }
     
     /// Verify a zero-knowledge proof of node identity
     fn verify_node_identity_proof(
         &self,
-        proof: &ByteRoutingProof,
+        proof: &ProofEnvelope,
         identity_commitment: &[u8; 32]
     ) -> Result<bool> {
-        // Convert to native proof format for verification
-        match RoutingProof::try_from(proof.clone()) {
-            Ok(native_proof) => {
-                // Verify the identity proof using the ZK proof system
-                let verification_result = crate::zhtp::zk_proofs::verify_unified_proof(
-                    &native_proof,
-                    identity_commitment,
-                    identity_commitment,
-                    *identity_commitment,
-                );
-                
-                // Additional validation: ensure proof contains valid commitments
-                let has_valid_commitments = proof.commitments.len() >= 2 
-                    && proof.elements.len() >= 3
-                    && proof.inputs.len() >= 1;
-                
-                Ok(verification_result && has_valid_commitments)
-            }
+        // Verify the identity proof using the ZK proof system
+        let statement = ProofStatement::Route {
+            source: identity_commitment,
+            destination: identity_commitment,
+            stored_data_root: *identity_commitment,
+        };
+        match VerifierRegistry::global().verify(proof, &statement) {
+            Ok(valid) => Ok(valid),
             Err(_) => Ok(false), // Invalid proof format
         }
     }
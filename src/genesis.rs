@@ -0,0 +1,246 @@
+//! Genesis specification: what every node of a network agrees on before the
+//! first block, loaded from a JSON or TOML file referenced by the node config.
+
+use crate::blockchain::TransferLock;
+use crate::zhtp::amount::Amount;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Chain id of the built-in development genesis
+pub const DEFAULT_CHAIN_ID: &str = "zhtp-dev";
+
+/// Timestamp of the built-in development genesis (2025-01-01 00:00:00 UTC)
+pub const DEFAULT_GENESIS_TIMESTAMP: i64 = 1_735_689_600;
+
+/// Balance credited to an address in the genesis block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    pub address: String,
+    pub amount: Amount,
+    /// Vesting schedule or timelock on the allocation, measured from the
+    /// genesis timestamp
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<TransferLock>,
+}
+
+/// Validator active from the first block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GenesisValidator {
+    pub address: String,
+    pub stake: Amount,
+}
+
+/// Economic parameters the network starts with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenesisEconomics {
+    /// Total maximum supply
+    pub max_supply: Amount,
+    /// Initial block reward for validators
+    pub block_reward: Amount,
+    /// Initial inflation rate, decreasing over time
+    pub inflation_rate: f64,
+    pub base_transaction_fee: Amount,
+    pub certificate_fee: Amount,
+    pub dns_registration_fee: Amount,
+    pub min_validator_stake: Amount,
+    /// Annual validator reward rate
+    pub validator_reward_rate: f64,
+    /// Share of fees burned
+    pub fee_burn_rate: f64,
+}
+
+impl Default for GenesisEconomics {
+    fn default() -> Self {
+        Self {
+            max_supply: Amount::from_zhtp(21_000_000), // 21 million ZHTP tokens (similar to Bitcoin scarcity)
+            block_reward: Amount::from_zhtp(50), // Halves every 4 years
+            inflation_rate: 0.05, // 5% initial inflation, decreasing over time
+            base_transaction_fee: Amount::from_base_units(100_000), // 0.001 ZHTP
+            certificate_fee: Amount::from_base_units(10_000_000), // 0.1 ZHTP (vs $100-$1000 traditional CA fees)
+            dns_registration_fee: Amount::from_base_units(1_000_000), // 0.01 ZHTP (vs $10-$50 traditional DNS)
+            min_validator_stake: Amount::from_zhtp(100), // 100 ZHTP minimum stake for development
+            validator_reward_rate: 0.08, // 8% annual reward for validators
+            fee_burn_rate: 0.3, // 30% of fees burned (deflationary)
+        }
+    }
+}
+
+/// Everything that goes into a network's genesis block.
+///
+/// Nodes built from different specifications end up with different genesis
+/// hashes and refuse to peer with each other.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GenesisSpec {
+    pub chain_id: String,
+    /// Unix timestamp of the genesis block
+    pub timestamp: i64,
+    #[serde(default)]
+    pub allocations: Vec<GenesisAllocation>,
+    #[serde(default)]
+    pub validators: Vec<GenesisValidator>,
+    #[serde(default)]
+    pub economics: GenesisEconomics,
+    /// Address allowed to schedule protocol parameter upgrades, e.g. the key
+    /// executing passed DAO proposals. Without one the parameters are fixed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub governance: Option<String>,
+}
+
+impl Default for GenesisSpec {
+    /// The development network: no allocations and no validators
+    fn default() -> Self {
+        Self {
+            chain_id: DEFAULT_CHAIN_ID.to_string(),
+            timestamp: DEFAULT_GENESIS_TIMESTAMP,
+            allocations: Vec::new(),
+            validators: Vec::new(),
+            economics: GenesisEconomics::default(),
+            governance: None,
+        }
+    }
+}
+
+impl GenesisSpec {
+    /// Load and validate a specification; `.toml` files are read as TOML,
+    /// anything else as JSON
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read genesis file {}: {}", path.display(), e))?;
+        let spec: GenesisSpec = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&contents)?
+        } else {
+            serde_json::from_str(&contents)?
+        };
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Check that the specification describes a usable chain
+    pub fn validate(&self) -> Result<()> {
+        if self.chain_id.trim().is_empty() {
+            return Err(anyhow!("Genesis chain id is empty"));
+        }
+
+        let mut addresses = HashSet::new();
+        let mut total = Amount::ZERO;
+        for allocation in &self.allocations {
+            if allocation.address.is_empty() || allocation.amount.is_zero() {
+                return Err(anyhow!("Genesis allocation needs an address and a nonzero amount"));
+            }
+            if !addresses.insert(&allocation.address) {
+                return Err(anyhow!("Duplicate genesis allocation for {}", allocation.address));
+            }
+            if let Some(lock) = allocation.lock {
+                lock.schedule(allocation.amount, self.timestamp)
+                    .map_err(|e| anyhow!("Genesis allocation for {}: {}", allocation.address, e))?;
+            }
+            total = total
+                .checked_add(allocation.amount)
+                .ok_or_else(|| anyhow!("Genesis allocations overflow"))?;
+        }
+        if total > self.economics.max_supply {
+            return Err(anyhow!(
+                "Genesis allocations of {} ZHTP exceed the maximum supply of {} ZHTP",
+                total, self.economics.max_supply
+            ));
+        }
+
+        if self.governance.as_deref().is_some_and(|governance| governance.trim().is_empty()) {
+            return Err(anyhow!("Genesis governance address is empty"));
+        }
+
+        let mut validators = HashSet::new();
+        for validator in &self.validators {
+            if !validators.insert(&validator.address) {
+                return Err(anyhow!("Duplicate genesis validator {}", validator.address));
+            }
+            if validator.stake < self.economics.min_validator_stake {
+                return Err(anyhow!(
+                    "Genesis validator {} stakes {} ZHTP, below the minimum of {} ZHTP",
+                    validator.address, validator.stake, self.economics.min_validator_stake
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Digest of the whole specification, committed to by the genesis block
+    pub fn spec_hash(&self) -> [u8; 32] {
+        let encoded = serde_json::to_vec(self).expect("genesis specification encodes as JSON");
+        Sha256::digest(encoded).into()
+    }
+
+    /// Hash of the genesis block this specification produces
+    pub fn genesis_hash(&self) -> String {
+        crate::blockchain::Block::genesis(self).hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> GenesisSpec {
+        GenesisSpec {
+            chain_id: "zhtp-testnet".to_string(),
+            allocations: vec![GenesisAllocation {
+                address: "alice".to_string(),
+                amount: Amount::from_zhtp(1_000),
+                lock: None,
+            }],
+            validators: vec![GenesisValidator {
+                address: "validator1".to_string(),
+                stake: Amount::from_zhtp(500),
+            }],
+            ..GenesisSpec::default()
+        }
+    }
+
+    #[test]
+    fn test_genesis_file_formats() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("zhtp-genesis-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+
+        let json = dir.join("genesis.json");
+        fs::write(&json, serde_json::to_string_pretty(&spec())?)?;
+        assert_eq!(GenesisSpec::from_file(&json)?, spec());
+
+        // Omitted economics take the defaults
+        let toml_path = dir.join("genesis.toml");
+        fs::write(&toml_path, "chain_id = \"ring-1\"\ntimestamp = 1700000000\n")?;
+        let loaded = GenesisSpec::from_file(&toml_path)?;
+        assert_eq!(loaded.chain_id, "ring-1");
+        assert_eq!(loaded.economics, GenesisEconomics::default());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_genesis_validation_and_identity() {
+        assert!(spec().validate().is_ok());
+
+        let mut duplicate = spec();
+        duplicate.allocations.push(duplicate.allocations[0].clone());
+        assert!(duplicate.validate().is_err());
+
+        let mut understaked = spec();
+        understaked.validators[0].stake = Amount::from_zhtp(1);
+        assert!(understaked.validate().is_err());
+
+        // Every field of the specification changes the genesis hash
+        assert_eq!(spec().genesis_hash(), spec().genesis_hash());
+        let mut other_chain = spec();
+        other_chain.chain_id = "zhtp-devnet".to_string();
+        assert_ne!(other_chain.genesis_hash(), spec().genesis_hash());
+        let mut other_economics = spec();
+        other_economics.economics.block_reward = Amount::from_zhtp(10);
+        assert_ne!(other_economics.genesis_hash(), spec().genesis_hash());
+    }
+}
@@ -11,14 +11,14 @@ use crate::{
 #[cfg(test)]
 mod integration_security_tests {
     use super::*;
-    use crate::Transaction;
+    use crate::{Amount, Transaction};
     use pqcrypto_dilithium::dilithium5;
     use pqcrypto_traits::sign::{PublicKey, SecretKey};
 
     #[tokio::test]
     async fn test_comprehensive_security_integration() {
         // Test 1: Full System Security Integration
-        let blockchain = Blockchain::new(100.0);
+        let blockchain = Blockchain::new(Amount::from_zhtp(100));
         let addr = SocketAddr::from_str("127.0.0.1:8080").unwrap();
         let discovery = DiscoveryNode::new(addr).unwrap();
         let dht = DhtNetwork::new();
@@ -34,7 +34,7 @@ mod integration_security_tests {
     #[tokio::test]
     async fn test_end_to_end_attack_resistance() {
         // Test 2: End-to-End Attack Scenarios
-        let mut blockchain = Blockchain::new(100.0);
+        let mut blockchain = Blockchain::new(Amount::from_zhtp(100));
         
         // Simulate multi-vector attack
         let attack_results = simulate_coordinated_attack(&mut blockchain).await;
@@ -112,7 +112,7 @@ mod integration_security_tests {
         let mut tx = crate::blockchain::Transaction::new(
             "alice".to_string(),
             "bob".to_string(),
-            50.0,
+            Amount::from_zhtp(50),
         );
         tx.signature = "malicious:fake_signature".to_string();
         
@@ -122,7 +122,7 @@ mod integration_security_tests {
         let (public_key, secret_key) = dilithium5::keypair();
         
         // Sign transaction with post-quantum signature
-        let mut tx = Transaction::new("alice".to_string(), "bob".to_string(), 100.0);
+        let mut tx = Transaction::new("alice".to_string(), "bob".to_string(), Amount::from_zhtp(100));
         tx.sign(secret_key.as_bytes()).expect("Signing should succeed");
         
         // This should fail with a random wrong public key
@@ -167,7 +167,7 @@ mod integration_security_tests {
         let mut malicious_tx = crate::blockchain::Transaction::new(
             "attacker".to_string(),
             "victim".to_string(),
-            1000.0,
+            Amount::from_zhtp(1000),
         );
         malicious_tx.signature = "bypass:attempt".to_string();
         // Test signature forgery resistance with post-quantum cryptography
@@ -176,11 +176,11 @@ mod integration_security_tests {
         let (attacker_public, _attacker_secret) = dilithium5::keypair();
         
         // Create valid transaction signed by Alice
-        let mut legitimate_tx = Transaction::new("alice".to_string(), "bob".to_string(), 50.0);
+        let mut legitimate_tx = Transaction::new("alice".to_string(), "bob".to_string(), Amount::from_zhtp(50));
         legitimate_tx.sign(alice_secret.as_bytes()).expect("Alice signing should succeed");
         
         // Create malicious transaction with forged signature
-        let mut malicious_tx = Transaction::new("alice".to_string(), "attacker".to_string(), 1000.0);
+        let mut malicious_tx = Transaction::new("alice".to_string(), "attacker".to_string(), Amount::from_zhtp(1000));
         malicious_tx.signature = "forged_signature_attempt".to_string();
         
         results.signature_attack_blocked = !malicious_tx.verify_signature(attacker_public.as_bytes());
@@ -188,7 +188,7 @@ mod integration_security_tests {
         let valid_tx = crate::blockchain::Transaction::new(
             "alice".to_string(),
             "bob".to_string(),
-            10.0,
+            Amount::from_zhtp(10),
         );
         
         // Try to add same transaction twice - both should fail due to nonce validation
@@ -204,7 +204,7 @@ mod integration_security_tests {
         let injection_tx = crate::blockchain::Transaction::new(
             "'; DROP TABLE transactions; --".to_string(),
             "bob".to_string(),
-            10.0,
+            Amount::from_zhtp(10),
         );
         let injection_result = blockchain.add_transaction(injection_tx).await;
         results.injection_attack_blocked = !injection_result;
@@ -5,6 +5,9 @@ pub mod network;
 pub mod storage;
 pub mod zhtp;
 pub mod discovery;
+pub mod genesis;
+pub mod mempool;
+pub mod snapshot;
 pub mod input_validation; // Add input validation module
 
 // Backward compatibility consensus module alias
@@ -16,6 +19,7 @@ pub mod consensus {
 
 pub use blockchain::{Block, Blockchain, Transaction, ZkBlockchainStats};
 pub use zhtp::{
+    amount::Amount,
     consensus_engine::{ZhtpConsensusEngine, ZkValidator, ZkBlock, ZkNetworkMetrics},
     zk_transactions::{ZkTransaction, ZkBalance, ZkTransactionPool},
 };
@@ -5,13 +5,14 @@ use decentralized_network::{
         Keypair, ZhtpNode, 
         consensus_engine::{ZhtpConsensusEngine, ZkConsensusParams},
         economics::ZhtpEconomics, 
-        zk_proofs::ByteRoutingProof,
+        proof_envelope::ProofEnvelope,
         ZhtpDao, DAppLaunchpad,
         dns::ZhtpDNS,
     },
-    Blockchain, Network, StorageManager, Transaction,
+    Amount, Blockchain, Network, StorageManager, Transaction,
     storage::{dht::DataChunk, ZhtpStorageManager, StorageConfig},
     input_validation::{InputValidator, CliValidator},
+    genesis::{GenesisAllocation, GenesisEconomics, GenesisSpec},
 };
 use std::env;
 use std::io::{self, Write};
@@ -66,7 +67,7 @@ async fn setup_zkp_node(
     // Register node as validator with the ZK consensus
     consensus.register_validator(
         node_name.clone(),
-        1000.0, // Now sufficient for the reduced minimum stake of 100 ZHTP
+        Amount::from_zhtp(1000), // Now sufficient for the reduced minimum stake of 100 ZHTP
     ).await?;
     
     // Initialize storage and wait for routing setup
@@ -100,6 +101,9 @@ async fn setup_zkp_node(
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Demo nodes run on the publicly known development trusted setup
+    decentralized_network::zhtp::zk_proofs::KzgTrustedSetup::allow_insecure_development_setup();
+
     // Initialize logging first
     env_logger::init();
     
@@ -124,7 +128,19 @@ async fn main() -> Result<()> {
     println!("=== Decentralized Network Demo ===\n");
     // Initialize core components
     let mut network = Network::new();
-    let blockchain = Blockchain::new(100.0);
+    // The demo genesis funds node1 for the initial distribution
+    let blockchain = Blockchain::from_genesis(&GenesisSpec {
+        allocations: vec![GenesisAllocation {
+            address: "node1".to_string(),
+            amount: Amount::from_zhtp(1000),
+            lock: None,
+        }],
+        economics: GenesisEconomics {
+            block_reward: Amount::from_zhtp(100),
+            ..GenesisEconomics::default()
+        },
+        ..GenesisSpec::default()
+    });
     // Create consensus engine for main system
     let dummy_keypair = Keypair::generate();
     let economics = Arc::new(ZhtpEconomics::new());
@@ -200,18 +216,15 @@ async fn main() -> Result<()> {
 
     // Quick genesis setup
     info!("\nInitializing blockchain...");
-    let mut genesis_tx = Transaction::new("network".to_string(), "node1".to_string(), 1000.0);
-    genesis_tx.sign(b"network");
-    blockchain.add_transaction(genesis_tx).await;
     blockchain.create_block("genesis", 1.0, None).await;
 
     // Initial fund distribution
     info!("Initial fund distribution...");
-    let mut tx1 = Transaction::new("node1".to_string(), "node2".to_string(), 300.0);
+    let mut tx1 = Transaction::new("node1".to_string(), "node2".to_string(), Amount::from_zhtp(300));
     tx1.sign(b"node1");
     blockchain.add_transaction(tx1).await;
 
-    let mut tx2 = Transaction::new("node1".to_string(), "node3".to_string(), 300.0);
+    let mut tx2 = Transaction::new("node1".to_string(), "node3".to_string(), Amount::from_zhtp(300));
     tx2.sign(b"node1");
     blockchain.add_transaction(tx2).await;
     blockchain.create_block("node1", 1.0, None).await;
@@ -316,9 +329,6 @@ async fn main() -> Result<()> {
                 match timeout(Duration::from_secs(5), store_future).await {
                     Ok(result) => {
                         if result {
-                            let mut store_tx = Transaction::new("network".to_string(), "node1".to_string(), 0.0);
-                            store_tx.sign(b"network");
-                            blockchain.add_transaction(store_tx).await;
                             blockchain.create_block("node1", 1.0, None).await;
                             println!("Storage operation completed with PQ signatures");
                         } else {
@@ -582,7 +592,7 @@ async fn main() -> Result<()> {
             }
             7 => {
                 println!("\nCreating signed transaction...");
-                let mut tx = Transaction::new("node1".to_string(), "node2".to_string(), 50.0);
+                let mut tx = Transaction::new("node1".to_string(), "node2".to_string(), Amount::from_zhtp(50));
                 tx.sign(b"node1");
                 if blockchain.add_transaction(tx).await {
                     blockchain.create_block("node1", 1.0, None).await;
@@ -593,7 +603,7 @@ async fn main() -> Result<()> {
                     println!("\nNode Balances:");
                     for node in &["node1", "node2", "node3"] {
                         let balance = blockchain.get_balance(node).await;
-                        println!("  {}: {:.2}", node, balance);
+                        println!("  {}: {}", node, balance);
                     }
                 } else {
                     println!("Transaction failed - insufficient balance");
@@ -646,8 +656,8 @@ async fn main() -> Result<()> {
                 println!("\n📊 Current Stats:");
                 println!("   Registered voters: {}", stats.registered_voters);
                 println!("   Active nodes: {}", stats.active_nodes);
-                println!("   Treasury: {:.2} ZHTP", stats.total_treasury_balance);
-                println!("   Monthly UBI distributed: {:.2} ZHTP", stats.monthly_ubi_distributed);
+                println!("   Treasury: {} ZHTP", stats.total_treasury_balance);
+                println!("   Monthly UBI distributed: {} ZHTP", stats.monthly_ubi_distributed);
                 
                 println!("\n💡 Example governance features:");
                 println!("   • Vote on protocol upgrades");
@@ -0,0 +1,519 @@
+//! Pool of transactions waiting to be included in a block.
+//!
+//! Account transactions are kept in one nonce-ordered queue per sender.
+//! Block selection takes the best-paying executable transaction across all
+//! senders, where executable means its nonce continues the sender's
+//! committed nonce. Network and private transactions carry no account nonce
+//! and are held outside the sender queues.
+
+use crate::blockchain::Transaction;
+use crate::zhtp::amount::Amount;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::fmt;
+
+/// Limits of the transaction pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MempoolConfig {
+    /// Maximum number of pooled transactions
+    pub max_transactions: usize,
+    /// Maximum total encoded size of pooled transactions
+    pub max_bytes: usize,
+    /// Seconds after which a pooled transaction expires
+    pub max_age_secs: i64,
+    /// Minimum fee rate increase, in percent, for replacing a transaction
+    /// with the same sender and nonce
+    pub replacement_fee_bump_percent: u64,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self {
+            max_transactions: 10_000,
+            max_bytes: 64 * 1024 * 1024,
+            max_age_secs: 3 * 60 * 60,
+            replacement_fee_bump_percent: 10,
+        }
+    }
+}
+
+/// Reason a transaction was not admitted to the pool
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MempoolError {
+    /// The transaction is already pooled
+    AlreadyKnown,
+    /// The nonce was already used by a committed transaction
+    NonceTooLow { committed: u64, found: u64 },
+    /// The nonce leaves a gap after the sender's pooled transactions
+    NonceGap { expected: u64, found: u64 },
+    /// A transaction with the same nonce is pooled and the fee rate is not
+    /// high enough to replace it
+    ReplacementUnderpriced { required_fee_rate: u64, found: u64 },
+    /// The pool is full of transactions paying at least as much
+    PoolFull,
+}
+
+impl fmt::Display for MempoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MempoolError::AlreadyKnown => write!(f, "transaction already pooled"),
+            MempoolError::NonceTooLow { committed, found } => {
+                write!(f, "nonce {} already used, next committed nonce is {}", found, committed)
+            }
+            MempoolError::NonceGap { expected, found } => {
+                write!(f, "nonce gap: expected at most {}, found {}", expected, found)
+            }
+            MempoolError::ReplacementUnderpriced { required_fee_rate, found } => write!(
+                f,
+                "replacement fee rate {} too low, at least {} required",
+                found, required_fee_rate
+            ),
+            MempoolError::PoolFull => write!(f, "transaction pool is full"),
+        }
+    }
+}
+
+impl std::error::Error for MempoolError {}
+
+/// Fee in base units per 1000 encoded bytes
+pub fn fee_rate(fee: Amount, size: usize) -> u64 {
+    let rate = fee.base_units() as u128 * 1000 / size.max(1) as u128;
+    u64::try_from(rate).unwrap_or(u64::MAX)
+}
+
+#[derive(Debug, Clone)]
+struct PoolEntry {
+    transaction: Transaction,
+    hash: String,
+    size: usize,
+    fee_rate: u64,
+    added_at: i64,
+}
+
+impl PoolEntry {
+    fn new(transaction: Transaction, now: i64) -> Self {
+        let size = transaction.encoded_size();
+        Self {
+            hash: transaction.calculate_hash(),
+            fee_rate: fee_rate(transaction.fee, size),
+            transaction,
+            size,
+            added_at: now,
+        }
+    }
+
+    /// Whether the transaction spends from an account nonce
+    fn is_sequenced(transaction: &Transaction) -> bool {
+        transaction.from != "network" && !transaction.is_private
+    }
+}
+
+/// Where a pooled transaction lives
+#[derive(Debug, Clone)]
+enum Slot {
+    Queued { sender: String, nonce: u64 },
+    Unsequenced,
+}
+
+/// Candidate for block selection, ordered by fee rate and then age
+struct Candidate<'a> {
+    entry: &'a PoolEntry,
+    /// Sender queue the entry came from, `None` for unsequenced entries
+    sender: Option<&'a str>,
+}
+
+impl Candidate<'_> {
+    fn key(&self) -> (u64, std::cmp::Reverse<i64>) {
+        (self.entry.fee_rate, std::cmp::Reverse(self.entry.added_at))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Fee-prioritized pool of pending transactions
+#[derive(Debug, Clone, Default)]
+pub struct Mempool {
+    config: MempoolConfig,
+    /// Account transactions by sender and nonce
+    queues: HashMap<String, BTreeMap<u64, PoolEntry>>,
+    /// Network and private transactions in arrival order
+    unsequenced: Vec<PoolEntry>,
+    /// Location of every pooled transaction by hash
+    index: HashMap<String, Slot>,
+    total_bytes: usize,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    pub fn config(&self) -> &MempoolConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Total encoded size of pooled transactions
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        self.index.contains_key(hash)
+    }
+
+    /// Every pooled transaction, senders' queues in nonce order
+    pub fn transactions(&self) -> Vec<Transaction> {
+        self.unsequenced
+            .iter()
+            .chain(self.queues.values().flat_map(|queue| queue.values()))
+            .map(|entry| entry.transaction.clone())
+            .collect()
+    }
+
+    /// First nonce after the run of pooled transactions continuing
+    /// `committed_nonce`
+    pub fn next_nonce(&self, sender: &str, committed_nonce: u64) -> u64 {
+        let mut next = committed_nonce;
+        if let Some(queue) = self.queues.get(sender) {
+            while queue.contains_key(&next) {
+                next += 1;
+            }
+        }
+        next
+    }
+
+    /// Add a transaction whose sender's next committed nonce is
+    /// `committed_nonce`. Returns the transaction it replaced, if any.
+    ///
+    /// When the pool is over its limits, the cheapest transactions at the
+    /// end of a sender queue are evicted; if the new transaction is the
+    /// cheapest it is rejected instead.
+    pub fn insert(
+        &mut self,
+        transaction: Transaction,
+        committed_nonce: u64,
+        now: i64,
+    ) -> Result<Option<Transaction>, MempoolError> {
+        let entry = PoolEntry::new(transaction, now);
+        if self.index.contains_key(&entry.hash) {
+            return Err(MempoolError::AlreadyKnown);
+        }
+
+        if !PoolEntry::is_sequenced(&entry.transaction) {
+            let hash = entry.hash.clone();
+            self.total_bytes += entry.size;
+            self.index.insert(hash.clone(), Slot::Unsequenced);
+            self.unsequenced.push(entry);
+            self.enforce_limits(&hash)?;
+            return Ok(None);
+        }
+
+        let sender = entry.transaction.from.clone();
+        let nonce = entry.transaction.nonce;
+        if nonce < committed_nonce {
+            return Err(MempoolError::NonceTooLow { committed: committed_nonce, found: nonce });
+        }
+
+        let existing = self
+            .queues
+            .get(&sender)
+            .and_then(|queue| queue.get(&nonce))
+            .map(|existing| (existing.hash.clone(), existing.fee_rate));
+        let replaced = match existing {
+            Some((existing_hash, existing_rate)) => {
+                let bump = 100 + self.config.replacement_fee_bump_percent;
+                let required = (existing_rate as u128 * bump as u128).div_ceil(100);
+                let required = u64::try_from(required).unwrap_or(u64::MAX).max(existing_rate.saturating_add(1));
+                if entry.fee_rate < required {
+                    return Err(MempoolError::ReplacementUnderpriced {
+                        required_fee_rate: required,
+                        found: entry.fee_rate,
+                    });
+                }
+                self.remove_hash(&existing_hash)
+            }
+            None => {
+                let expected = self.next_nonce(&sender, committed_nonce);
+                if nonce > expected {
+                    return Err(MempoolError::NonceGap { expected, found: nonce });
+                }
+                None
+            }
+        };
+
+        let hash = entry.hash.clone();
+        self.total_bytes += entry.size;
+        self.index.insert(hash.clone(), Slot::Queued { sender: sender.clone(), nonce });
+        self.queues.entry(sender).or_default().insert(nonce, entry);
+        if let Err(e) = self.enforce_limits(&hash) {
+            // Put back what the rejected replacement displaced
+            if let Some(previous) = replaced {
+                let _ = self.insert(previous, committed_nonce, now);
+            }
+            return Err(e);
+        }
+        Ok(replaced)
+    }
+
+    /// Evict the cheapest evictable transactions until the pool is within
+    /// its limits. Fails, undoing the insertion, if `inserted` itself would
+    /// be evicted.
+    fn enforce_limits(&mut self, inserted: &str) -> Result<(), MempoolError> {
+        while self.index.len() > self.config.max_transactions || self.total_bytes > self.config.max_bytes {
+            // Only the last transaction of a sender queue can go without
+            // stranding later nonces
+            let victim = self
+                .queues
+                .values()
+                .filter_map(|queue| queue.values().next_back())
+                .chain(self.unsequenced.iter())
+                .min_by_key(|entry| (entry.fee_rate, std::cmp::Reverse(entry.added_at)))
+                .map(|entry| entry.hash.clone());
+
+            let Some(victim) = victim else { break };
+            self.remove_hash(&victim);
+            if victim == inserted {
+                return Err(MempoolError::PoolFull);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a transaction by hash
+    pub fn remove_hash(&mut self, hash: &str) -> Option<Transaction> {
+        let entry = match self.index.remove(hash)? {
+            Slot::Queued { sender, nonce } => {
+                let queue = self.queues.get_mut(&sender)?;
+                let entry = queue.remove(&nonce);
+                if queue.is_empty() {
+                    self.queues.remove(&sender);
+                }
+                entry?
+            }
+            Slot::Unsequenced => {
+                let position = self.unsequenced.iter().position(|entry| entry.hash == hash)?;
+                self.unsequenced.remove(position)
+            }
+        };
+        self.total_bytes -= entry.size;
+        Some(entry.transaction)
+    }
+
+    /// Drop `sender`'s transactions from `nonce` on, e.g. after one of them
+    /// failed to apply and the rest can no longer execute
+    pub fn remove_from_nonce(&mut self, sender: &str, nonce: u64) -> Vec<Transaction> {
+        let hashes: Vec<String> = match self.queues.get(sender) {
+            Some(queue) => queue.range(nonce..).map(|(_, entry)| entry.hash.clone()).collect(),
+            None => return Vec::new(),
+        };
+        hashes.iter().filter_map(|hash| self.remove_hash(hash)).collect()
+    }
+
+    /// Forget transactions that were committed in a block or whose nonce
+    /// has been used up. `committed_nonce` gives each sender's next nonce.
+    pub fn remove_committed<F>(&mut self, included: &[Transaction], committed_nonce: F)
+    where
+        F: Fn(&str) -> u64,
+    {
+        for tx in included {
+            self.remove_hash(&tx.calculate_hash());
+        }
+
+        let stale: Vec<String> = self
+            .queues
+            .iter()
+            .flat_map(|(sender, queue)| queue.range(..committed_nonce(sender)).map(|(_, entry)| entry.hash.clone()))
+            .collect();
+        for hash in stale {
+            self.remove_hash(&hash);
+        }
+    }
+
+    /// Drop transactions pooled for longer than the configured maximum age,
+    /// together with later nonces of the same sender. Returns how many were
+    /// dropped.
+    pub fn expire(&mut self, now: i64) -> usize {
+        let cutoff = now - self.config.max_age_secs;
+        let before = self.len();
+
+        let expired: Vec<(String, u64)> = self
+            .queues
+            .iter()
+            .filter_map(|(sender, queue)| {
+                queue
+                    .values()
+                    .find(|entry| entry.added_at < cutoff)
+                    .map(|entry| (sender.clone(), entry.transaction.nonce))
+            })
+            .collect();
+        for (sender, nonce) in expired {
+            self.remove_from_nonce(&sender, nonce);
+        }
+
+        let expired: Vec<String> = self
+            .unsequenced
+            .iter()
+            .filter(|entry| entry.added_at < cutoff)
+            .map(|entry| entry.hash.clone())
+            .collect();
+        for hash in expired {
+            self.remove_hash(&hash);
+        }
+
+        before - self.len()
+    }
+
+    /// Pick transactions for a block, best fee rate first, limited to
+    /// `max_transactions`, `max_bytes` of encoded transactions and
+    /// `max_gas` of gas, counting each contract at its full gas limit.
+    ///
+    /// A sender's transactions are only taken in nonce order starting at
+    /// `committed_nonce(sender)`, so every selected transaction can execute.
+    pub fn select<F>(&self, max_transactions: usize, max_bytes: usize, max_gas: u64, committed_nonce: F) -> Vec<Transaction>
+    where
+        F: Fn(&str) -> u64,
+    {
+        let mut heap: BinaryHeap<Candidate> = self
+            .unsequenced
+            .iter()
+            .map(|entry| Candidate { entry, sender: None })
+            .collect();
+        for (sender, queue) in &self.queues {
+            if let Some(entry) = queue.get(&committed_nonce(sender)) {
+                heap.push(Candidate { entry, sender: Some(sender) });
+            }
+        }
+
+        let mut selected = Vec::new();
+        let mut bytes = 0;
+        let mut gas = 0u64;
+        while selected.len() < max_transactions {
+            let Some(candidate) = heap.pop() else { break };
+            let tx_gas = candidate.entry.transaction.gas_limit();
+            if bytes + candidate.entry.size > max_bytes || gas.saturating_add(tx_gas) > max_gas {
+                // Later nonces of this sender depend on the skipped one
+                continue;
+            }
+            bytes += candidate.entry.size;
+            gas += tx_gas;
+            selected.push(candidate.entry.transaction.clone());
+
+            if let Some(sender) = candidate.sender {
+                let next = candidate.entry.transaction.nonce + 1;
+                if let Some(entry) = self.queues.get(sender).and_then(|queue| queue.get(&next)) {
+                    heap.push(Candidate { entry, sender: Some(sender) });
+                }
+            }
+        }
+        selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(from: &str, nonce: u64, fee_base_units: u64) -> Transaction {
+        let mut tx = Transaction::new(from.to_string(), "recipient".to_string(), Amount::from_zhtp(1))
+            .with_fee(Amount::from_base_units(fee_base_units));
+        tx.nonce = nonce;
+        tx
+    }
+
+    #[test]
+    fn test_select_orders_by_fee_within_nonce_order() {
+        let mut pool = Mempool::default();
+        pool.insert(transfer("alice", 0, 100), 0, 0).unwrap();
+        pool.insert(transfer("alice", 1, 900_000), 0, 0).unwrap();
+        pool.insert(transfer("bob", 0, 50_000), 0, 0).unwrap();
+
+        let selected = pool.select(10, usize::MAX, u64::MAX, |_| 0);
+        let order: Vec<(&str, u64)> = selected.iter().map(|tx| (tx.from.as_str(), tx.nonce)).collect();
+        // Bob pays more than alice's first transaction, which gates her second
+        assert_eq!(order, vec![("bob", 0), ("alice", 0), ("alice", 1)]);
+
+        assert_eq!(pool.select(1, usize::MAX, u64::MAX, |_| 0).len(), 1);
+        let one_transfer = crate::blockchain::TRANSACTION_BASE_GAS;
+        assert_eq!(pool.select(10, usize::MAX, one_transfer, |_| 0).len(), 1);
+        // Nothing of alice's executes until nonce 5 is reached
+        assert!(pool.select(10, usize::MAX, u64::MAX, |sender| if sender == "alice" { 5 } else { 1 }).is_empty());
+    }
+
+    #[test]
+    fn test_nonce_rules_and_replace_by_fee() {
+        let mut pool = Mempool::default();
+        pool.insert(transfer("alice", 3, 1000), 3, 0).unwrap();
+
+        assert_eq!(
+            pool.insert(transfer("alice", 2, 1000), 3, 0).err(),
+            Some(MempoolError::NonceTooLow { committed: 3, found: 2 })
+        );
+        assert_eq!(
+            pool.insert(transfer("alice", 5, 1000), 3, 0).err(),
+            Some(MempoolError::NonceGap { expected: 4, found: 5 })
+        );
+        assert!(matches!(
+            pool.insert(transfer("alice", 3, 1050), 3, 0),
+            Err(MempoolError::ReplacementUnderpriced { .. })
+        ));
+
+        let replaced = pool.insert(transfer("alice", 3, 2000), 3, 0).unwrap();
+        assert_eq!(replaced.unwrap().fee, Amount::from_base_units(1000));
+        assert_eq!(pool.len(), 1);
+        assert_eq!(pool.transactions()[0].fee, Amount::from_base_units(2000));
+        assert_eq!(pool.next_nonce("alice", 3), 4);
+    }
+
+    #[test]
+    fn test_eviction_and_expiry() {
+        let config = MempoolConfig {
+            max_transactions: 2,
+            ..MempoolConfig::default()
+        };
+        let mut pool = Mempool::new(config);
+        pool.insert(transfer("alice", 0, 5000), 0, 0).unwrap();
+        pool.insert(transfer("bob", 0, 1000), 0, 10).unwrap();
+
+        // A cheaper transaction cannot push out better paying ones
+        assert_eq!(pool.insert(transfer("carol", 0, 10), 0, 20).err(), Some(MempoolError::PoolFull));
+        assert_eq!(pool.len(), 2);
+
+        // A better paying one evicts the cheapest
+        pool.insert(transfer("carol", 0, 9000), 0, 20).unwrap();
+        assert!(!pool.transactions().iter().any(|tx| tx.from == "bob"));
+
+        let max_age = pool.config().max_age_secs;
+        assert_eq!(pool.expire(max_age + 10), 1);
+        assert_eq!(pool.transactions()[0].from, "carol");
+    }
+}
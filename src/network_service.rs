@@ -6,7 +6,7 @@ use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::env;
 use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
@@ -19,7 +19,10 @@ use hex;
 use chrono;
 
 use decentralized_network::{
-    zhtp::{ZhtpNode, crypto::Keypair},
+    blockchain::{Blockchain, PruningMode, MAX_HEADER_RANGE},
+    genesis::GenesisSpec,
+    snapshot::{ChainSnapshot, SignedSnapshot},
+    zhtp::{ZhtpNode, amount::Amount, crypto::Keypair},
     zhtp::{
         consensus_engine::ZhtpConsensusEngine,
         dns::ZhtpDNS,
@@ -28,9 +31,24 @@ use decentralized_network::{
         p2p_network::{ZhtpP2PNetwork, EncryptedZhtpPacket},
         economics::ZhtpEconomics,
         ceremony_coordinator::ZhtpCeremonyCoordinator,
+        proof_envelope::VerifierRegistry,
+        circom::CircuitKeys,
+        spend_circuit::{self, SPEND_CIRCUIT_ID},
+        zk_proofs::{KzgTrustedSetup, ZkEngine, CIRCUIT_KEYS_DIR, COMPILED_CIRCUITS_DIR},
     },
 };
 
+/// Default and largest page size of
+/// `/api/chain/addresses/{address}/transactions`
+const DEFAULT_HISTORY_PAGE: usize = 50;
+const MAX_HISTORY_PAGE: usize = 500;
+
+/// Seconds between background pruning passes
+const PRUNE_INTERVAL_SECS: u64 = 60;
+
+/// How often the governance node executes DAO proposals whose voting closed
+const GOVERNANCE_INTERVAL_SECS: u64 = 60;
+
 /// Production configuration for ZHTP Network Service
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProductionConfig {
@@ -50,6 +68,60 @@ pub struct ProductionConfig {
     pub service_endpoints: ServiceEndpointsConfig,
     /// Certificate authority configuration
     pub certificate_authority: CertificateAuthorityConfig,
+    /// Genesis specification file (JSON or TOML); the built-in development
+    /// genesis is used when unset
+    #[serde(default)]
+    pub genesis_file: Option<String>,
+    /// Block bodies kept by this node: `archive` (the default), `full` with
+    /// `keep_blocks`, or `pruned`
+    #[serde(default)]
+    pub pruning: PruningMode,
+    /// KZG trusted setup produced by the ceremony; the node refuses to start
+    /// without one unless `insecure_development_setup` is set
+    #[serde(default)]
+    pub trusted_setup: Option<TrustedSetupConfig>,
+    /// Run on the publicly known development setup when no `trusted_setup`
+    /// is configured. Anyone can forge proofs against it, so this is for
+    /// local development only; `--insecure-dev-setup` sets it too.
+    #[serde(default)]
+    pub insecure_development_setup: bool,
+    /// Compiled circom circuits and their Groth16 keys
+    #[serde(default)]
+    pub circuits: CircuitsConfig,
+    /// Key file holding this node's identity, created on first start;
+    /// `node_key.json` in the data directory when unset
+    #[serde(default)]
+    pub node_key_file: Option<String>,
+    /// Addresses of the nodes whose signed snapshots `import-snapshot`
+    /// accepts
+    #[serde(default)]
+    pub trusted_snapshot_signers: Vec<String>,
+}
+
+/// Ceremony output file holding the KZG structured reference string
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedSetupConfig {
+    pub srs_file: String,
+    /// Hex SHA-256 the file must have, as published with the ceremony results
+    pub srs_hash: String,
+}
+
+/// Where the compiled circom circuits and the keys from their setup live
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitsConfig {
+    pub compiled_dir: String,
+    /// Directory of `<circuit>.vk` files, and `<circuit>.pk` files on
+    /// nodes that also prove
+    pub keys_dir: String,
+}
+
+impl Default for CircuitsConfig {
+    fn default() -> Self {
+        Self {
+            compiled_dir: COMPILED_CIRCUITS_DIR.to_string(),
+            keys_dir: CIRCUIT_KEYS_DIR.to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -159,6 +231,21 @@ impl ProductionConfig {
                 ca_key_path: "./ca/key.pem".to_string(),
                 ca_cert_path: "./ca/cert.pem".to_string(),
             },
+            genesis_file: None,
+            pruning: PruningMode::Archive,
+            trusted_setup: None,
+            insecure_development_setup: false,
+            circuits: CircuitsConfig::default(),
+            node_key_file: None,
+            trusted_snapshot_signers: Vec::new(),
+        }
+    }
+
+    /// Path of the node's identity key file
+    pub fn node_key_path(&self) -> PathBuf {
+        match &self.node_key_file {
+            Some(path) => PathBuf::from(path),
+            None => Path::new(&self.storage.data_dir).join("node_key.json"),
         }
     }
 }
@@ -252,8 +339,44 @@ impl ZhtpNetworkService {
         let bind_addr: SocketAddr = config.node.bind_address.parse()?;
         let p2p_addr: SocketAddr = config.node.p2p_address.parse()?;
         
-        // Initialize core ZHTP node
-        let keypair = Keypair::generate();
+        // Install the ceremony's trusted setup before anything creates or
+        // checks a proof against the global one
+        match &config.trusted_setup {
+            Some(trusted_setup) => {
+                let setup = KzgTrustedSetup::load(&trusted_setup.srs_file, &trusted_setup.srs_hash)?;
+                KzgTrustedSetup::install_global(setup)?;
+                println!("🔐 Loaded trusted setup from {}", trusted_setup.srs_file);
+            }
+            None if config.insecure_development_setup => {
+                KzgTrustedSetup::allow_insecure_development_setup();
+                println!("⚠️ No trusted_setup configured, using the INSECURE development setup");
+            }
+            None => {
+                return Err(anyhow!(
+                    "No trusted_setup configured: set trusted_setup.srs_file and trusted_setup.srs_hash \
+                     to the ceremony output, or pass --insecure-dev-setup for local development"
+                ));
+            }
+        }
+        
+        // Load the circom circuits and register the verifying keys of those
+        // whose setup has run, so their Groth16 proofs can be checked
+        let zk_engine = ZkEngine::load(&config.circuits.compiled_dir, &config.circuits.keys_dir)
+            .map_err(|e| anyhow!("Failed to load circuits from {}: {}", config.circuits.compiled_dir, e))?;
+        zk_engine.register_verifiers(VerifierRegistry::global());
+        println!("🔑 Loaded {} circuits from {}", zk_engine.circuit_ids().len(), config.circuits.compiled_dir);
+
+        // Shielded spends are proven against keys from the ceremony; only
+        // the insecure development setup may do without them
+        if let Some(keys) = CircuitKeys::load(&config.circuits.keys_dir, SPEND_CIRCUIT_ID)? {
+            spend_circuit::install_keys(keys)?;
+            println!("🔑 Loaded shielded spend keys from {}", config.circuits.keys_dir);
+        }
+        spend_circuit::keys().map_err(|e| anyhow!("{}: add {}.vk to {}", e, SPEND_CIRCUIT_ID, config.circuits.keys_dir))?;
+        
+        // Initialize core ZHTP node with its persistent identity
+        let keypair = Keypair::load_or_generate(config.node_key_path())?;
+        println!("🪪 Node identity {} from {}", keypair.address(), config.node_key_path().display());
         let node: Arc<ZhtpNode> = Arc::new(ZhtpNode::new(bind_addr, keypair.clone()).await?);
         
         // Parse bootstrap nodes
@@ -263,16 +386,40 @@ impl ZhtpNetworkService {
             .collect();
         let bootstrap_nodes = bootstrap_nodes?;
         
-        // Initialize network layer with production config
-        let network = Arc::new(ZhtpP2PNetwork::new(p2p_addr, bootstrap_nodes).await?);
+        // Load persistent blockchain from the node data directory, for the
+        // network named by the genesis file if one is configured
+        let (mut blockchain, economics) = match &config.genesis_file {
+            Some(path) => {
+                let genesis = GenesisSpec::from_file(path)?;
+                println!("🌱 Using genesis for chain {} from {}", genesis.chain_id, path);
+                (
+                    Blockchain::open_with_genesis(&genesis, &config.storage.data_dir)?,
+                    ZhtpEconomics::from_genesis(&genesis.economics),
+                )
+            }
+            None => (
+                Blockchain::open(Amount::from_zhtp(50), &config.storage.data_dir)?,
+                ZhtpEconomics::new(),
+            ),
+        };
+        blockchain.pruning = config.pruning;
+        let genesis_hash = blockchain.genesis_hash().await;
+        
+        // Initialize network layer with production config; peers must share our genesis
+        let network = Arc::new(
+            ZhtpP2PNetwork::new(p2p_addr, bootstrap_nodes)
+                .await?
+                .with_genesis_hash(genesis_hash)
+                .with_keypair(keypair.clone())
+        );
         
         // Initialize DNS service (replaces traditional DNS)
         let dns_service = Arc::new(RwLock::new(ZhtpDNS::new()));
         
         // Initialize consensus engine
-        let economics = Arc::new(ZhtpEconomics::new());
+        let economics = Arc::new(economics);
         let consensus = Arc::new(
-            ZhtpConsensusEngine::new(keypair.clone(), economics.clone()).await?
+            ZhtpConsensusEngine::with_blockchain(keypair.clone(), economics.clone(), blockchain).await?
         );
         
         // Initialize storage
@@ -345,6 +492,12 @@ impl ZhtpNetworkService {
         // Start ZK blockchain integration
         self.start_zk_blockchain_integration().await?;
         
+        // Prune old block bodies in the background unless running as an archive node
+        self.start_chain_pruning().await?;
+        
+        // Execute closed DAO proposals if this node holds the governance key
+        self.start_governance().await?;
+        
         // Start certificate authority if enabled (replaces traditional SSL CAs)
         if self.config.certificate_authority.enabled {
             self.start_certificate_authority().await?;
@@ -396,7 +549,7 @@ impl ZhtpNetworkService {
             
             // Register as validator with quantum-resistant proof
             let validator_keypair = self.node.get_keypair().clone();
-            let stake_amount = self.config.consensus.stake_amount as f64;
+            let stake_amount = Amount::from_zhtp(self.config.consensus.stake_amount);
             
             // Generate validator ID from public key
             let validator_id = hex::encode(&validator_keypair.public_key());
@@ -445,6 +598,61 @@ impl ZhtpNetworkService {
         Ok(())
     }
     
+    /// Start background pruning of the block bodies the configured
+    /// pruning mode no longer keeps
+    async fn start_chain_pruning(&self) -> Result<()> {
+        if self.config.pruning == PruningMode::Archive {
+            return Ok(());
+        }
+        println!("✂️ Starting chain pruning in {:?} mode", self.config.pruning);
+        
+        let consensus = self.consensus.clone();
+        
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(PRUNE_INTERVAL_SECS)).await;
+                
+                let blockchain = consensus.get_blockchain().await;
+                match blockchain.prune().await {
+                    Ok(Some(height)) => println!("✂️ Pruned block bodies below height {}", height),
+                    Ok(None) => {}
+                    Err(e) => println!("⚠️ Chain pruning failed: {}", e),
+                }
+            }
+        });
+        
+        Ok(())
+    }
+    
+    /// Execute DAO proposals once their voting closes, submitting passed
+    /// protocol upgrades to the chain. Only the node whose identity is the
+    /// chain's governance address can sign upgrades, so others skip this.
+    async fn start_governance(&self) -> Result<()> {
+        let blockchain = self.consensus.get_blockchain().await;
+        let keypair = self.node.get_keypair().clone();
+        if blockchain.params_schedule().await.governance.as_deref() != Some(keypair.address().as_str()) {
+            return Ok(());
+        }
+        println!("🏛️ Starting DAO proposal execution as governance node {}", keypair.address());
+        
+        let dao = self.dao.clone();
+        
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(tokio::time::Duration::from_secs(GOVERNANCE_INTERVAL_SECS)).await;
+                
+                for (id, status) in dao.execute_due_proposals(&blockchain, &keypair).await {
+                    match status {
+                        Ok(status) => println!("🏛️ Proposal #{} closed as {:?}", id, status),
+                        Err(e) => println!("⚠️ Executing proposal #{} failed: {}", id, e),
+                    }
+                }
+            }
+        });
+        
+        Ok(())
+    }
+    
     /// Start ZK proof mining and validation
     async fn start_zk_proof_mining(&self) -> Result<()> {
         println!("🔬 Starting ZK proof mining pipeline");
@@ -552,9 +760,12 @@ impl ZhtpNetworkService {
                 Ok(ceremony_result) => {
                     println!("🎉 Ceremony completed successfully!");
                     
-                    // Update ZHTP code with new trusted setup
-                    if let Err(e) = self.ceremony_coordinator.update_trusted_setup_in_code(&ceremony_result).await {
-                        println!("⚠️ Failed to update code with ceremony result: {}", e);
+                    // Write the SRS for operators to distribute and pin; the
+                    // running node keeps its setup until restarted with it
+                    let srs_path = Path::new(&self.config.storage.data_dir).join("trusted_setup.srs");
+                    match self.ceremony_coordinator.write_trusted_setup_file(&ceremony_result, &srs_path.to_string_lossy()).await {
+                        Ok(srs_hash) => println!("📌 Set trusted_setup.srs_file = {:?} and srs_hash = {:?} to use it", srs_path, srs_hash),
+                        Err(e) => println!("⚠️ Failed to write ceremony result: {}", e),
                     }
                 },
                 Err(e) => {
@@ -1353,8 +1564,7 @@ impl ZhtpNetworkService {
                     
                     // Use full post-quantum public key for wallet address (not just 8 bytes)
                     let full_public_key = wallet_keypair.public_key();
-                    let key_hash = sha2::Sha256::digest(&full_public_key);
-                    let wallet_address = format!("zhtp_{}", hex::encode(&key_hash)); // Use full 64-character hash
+                    let wallet_address = wallet_keypair.address();
                     
                     // Update network metrics based on node type
                     {
@@ -1571,6 +1781,12 @@ impl ZhtpNetworkService {
                                 (200, "application/json", consensus_status.to_string())
                             }
                             
+                            ("GET", path) if path.starts_with("/api/chain/") => {
+                                let query = full_path.split_once('?').map(|(_, query)| query).unwrap_or("");
+                                let blockchain = consensus.get_blockchain().await;
+                                Self::handle_chain_query(&blockchain, &path["/api/chain/".len()..], query).await
+                            }
+                            
                             ("GET", "/welcome.html") => {
                                 // Redirect to quantum merged welcome page
                                 println!("🔍 Redirecting /welcome.html to quantum merged welcome page");
@@ -1948,6 +2164,7 @@ impl ZhtpNetworkService {
                         
                         let status_text = match status {
                             200 => "OK",
+                            400 => "Bad Request",
                             404 => "Not Found",
                             500 => "Internal Server Error",
                             _ => "Unknown"
@@ -1978,6 +2195,80 @@ impl ZhtpNetworkService {
         }
     }
     
+    /// Block explorer queries under `/api/chain/`:
+    /// - `blocks/{height or hash}`
+    /// - `headers?from={height}&count={n}`
+    /// - `transactions/{hash}` and `receipts/{hash}`
+    /// - `addresses/{address}/transactions?offset={n}&limit={n}`, newest first
+    /// - `addresses/{address}/balance`, split into locked and spendable
+    ///
+    /// Nodes that prune keep answering header and balance queries, but no
+    /// longer find the blocks and transactions they pruned.
+    async fn handle_chain_query(blockchain: &Blockchain, route: &str, query: &str) -> (u16, &'static str, String) {
+        let param = |name: &str| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+        };
+        let number = |name: &str, default: u64| match param(name) {
+            Some(value) => value.parse::<u64>().map_err(|_| format!("Invalid {} parameter: {}", name, value)),
+            None => Ok(default),
+        };
+
+        let segments: Vec<&str> = route.split('/').collect();
+        let result = match segments.as_slice() {
+            ["blocks", id] => {
+                let block = match id.parse::<u64>() {
+                    Ok(height) => blockchain.get_block_by_height(height).await,
+                    Err(_) => blockchain.get_block_by_hash(id).await,
+                };
+                Ok(block.map(|block| serde_json::json!({ "success": true, "block": block })))
+            }
+            ["headers"] => match (number("from", 0), number("count", MAX_HEADER_RANGE)) {
+                (Ok(from), Ok(count)) => {
+                    let headers = blockchain.get_block_headers(from, count).await;
+                    Ok(Some(serde_json::json!({ "success": true, "headers": headers })))
+                }
+                (Err(e), _) | (_, Err(e)) => Err(e),
+            },
+            ["transactions", hash] => Ok(blockchain
+                .get_transaction(hash)
+                .await
+                .map(|transaction| serde_json::json!({ "success": true, "transaction": transaction }))),
+            ["receipts", hash] => Ok(blockchain
+                .get_receipt(hash)
+                .await
+                .map(|receipt| serde_json::json!({ "success": true, "receipt": receipt }))),
+            ["addresses", address, "transactions"] => {
+                match (number("offset", 0), number("limit", DEFAULT_HISTORY_PAGE as u64)) {
+                    (Ok(offset), Ok(limit)) => {
+                        let limit = (limit as usize).min(MAX_HISTORY_PAGE);
+                        let history = blockchain.get_address_history(address, offset as usize, limit).await;
+                        Ok(Some(serde_json::json!({ "success": true, "history": history })))
+                    }
+                    (Err(e), _) | (_, Err(e)) => Err(e),
+                }
+            }
+            ["addresses", address, "balance"] => {
+                let balance = blockchain.get_balance_details(address).await;
+                Ok(Some(serde_json::json!({ "success": true, "balance": balance })))
+            }
+            _ => Ok(None),
+        };
+
+        match result {
+            Ok(Some(response)) => (200, "application/json", response.to_string()),
+            Ok(None) => {
+                let error = serde_json::json!({ "success": false, "error": format!("Not found: /api/chain/{}", route) });
+                (404, "application/json", error.to_string())
+            }
+            Err(e) => {
+                let error = serde_json::json!({ "success": false, "error": e });
+                (400, "application/json", error.to_string())
+            }
+        }
+    }
+
     /// Send a secure message using post-quantum cryptography over ZHTP P2P network
     async fn send_secure_message(
         node: &Arc<ZhtpNode>,
@@ -2126,11 +2417,25 @@ async fn main() -> Result<()> {
     println!("ZHTP Network Service - Production Mainnet");
     println!("========================================");
     
-    // Check for configuration file argument
-    let args: Vec<String> = env::args().collect();
+    // Check for configuration file argument; whatever else is given is a subcommand
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let config_path = match args.iter().position(|arg| arg == "--config") {
+        Some(position) if position + 1 < args.len() => {
+            args.remove(position);
+            Some(args.remove(position))
+        }
+        _ => None,
+    };
+    
+    let insecure_dev_setup = match args.iter().position(|arg| arg == "--insecure-dev-setup") {
+        Some(position) => {
+            args.remove(position);
+            true
+        }
+        None => false,
+    };
     
-    let config = if args.len() > 2 && args[1] == "--config" {
-        let config_path = &args[2];
+    let mut config = if let Some(config_path) = &config_path {
         println!("📁 Loading configuration from: {}", config_path);
         
         // Load configuration from JSON file
@@ -2147,6 +2452,14 @@ async fn main() -> Result<()> {
         config.service_endpoints.metrics_port = 9000;
         config
     };
+    config.insecure_development_setup |= insecure_dev_setup;
+
+    match args.first().map(String::as_str) {
+        Some("import-snapshot") => return import_snapshot(&config, &args[1..]),
+        Some("export-snapshot") => return export_snapshot(&config, &args[1..]).await,
+        Some(other) => return Err(anyhow!("Unknown command '{}'", other)),
+        None => {}
+    }
     
     println!("🚀 Starting ZHTP service on port {}", config.service_endpoints.api_port);
     
@@ -2155,3 +2468,59 @@ async fn main() -> Result<()> {
     
     Ok(())
 }
+
+/// Genesis of the configured network, the development genesis if none is set
+fn configured_genesis(config: &ProductionConfig) -> Result<GenesisSpec> {
+    match &config.genesis_file {
+        Some(path) => GenesisSpec::from_file(path),
+        None => Ok(GenesisSpec::default()),
+    }
+}
+
+/// `zhtp import-snapshot <file> <trusted-block-hash>`: replace the stored
+/// chain with a snapshot, after checking it against a block hash obtained
+/// from a trusted source and its signer against `trusted_snapshot_signers`
+fn import_snapshot(config: &ProductionConfig, args: &[String]) -> Result<()> {
+    let [path, trusted_hash] = args else {
+        return Err(anyhow!("Usage: zhtp import-snapshot <file> <trusted-block-hash> [--config <file>]"));
+    };
+    let genesis = configured_genesis(config)?;
+    let signed = ChainSnapshot::load_signed(path)?;
+    signed.verify(trusted_hash, &config.trusted_snapshot_signers)?;
+    println!(
+        "🔏 Snapshot at height {} signed by {}",
+        signed.snapshot.height(),
+        signed.manifest.signer_address()
+    );
+    Blockchain::import_snapshot(&genesis, &signed.snapshot, trusted_hash, &config.storage.data_dir)?;
+    println!("✅ Node data in {} now starts from block {}", config.storage.data_dir, trusted_hash);
+    Ok(())
+}
+
+/// `zhtp export-snapshot <height> <file> [--key <key-file>]`: write a
+/// snapshot of the stored chain at `height`, signed with the given key file
+/// or else the node's identity key
+async fn export_snapshot(config: &ProductionConfig, args: &[String]) -> Result<()> {
+    let (height, path, keypair) = match args {
+        [height, path] => (height, path, Keypair::load_or_generate(config.node_key_path())?),
+        [height, path, flag, key_file] if flag == "--key" => (height, path, Keypair::load(key_file)?),
+        _ => {
+            return Err(anyhow!(
+                "Usage: zhtp export-snapshot <height> <file> [--key <key-file>] [--config <file>]"
+            ))
+        }
+    };
+    let height: u64 = height.parse().map_err(|_| anyhow!("Invalid height '{}'", height))?;
+    let blockchain = match &config.genesis_file {
+        Some(path) => Blockchain::open_with_genesis(&GenesisSpec::from_file(path)?, &config.storage.data_dir)?,
+        None => Blockchain::open(Amount::from_zhtp(50), &config.storage.data_dir)?,
+    };
+    let snapshot = blockchain.create_snapshot(height).await?;
+    let block_hash = snapshot.tip.hash.clone();
+    SignedSnapshot::new(snapshot, &keypair)?.save(path)?;
+    println!(
+        "📸 Wrote snapshot of block {} at height {} to {}, signed by {}",
+        block_hash, height, path, keypair.address()
+    );
+    Ok(())
+}
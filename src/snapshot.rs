@@ -0,0 +1,401 @@
+//! Chain snapshots: the account state at one height together with the header
+//! chain leading to it, so that a new node can start from a recent block
+//! instead of replaying the chain from genesis.
+//!
+//! A snapshot travels with a [`SnapshotManifest`] signed by the node that
+//! produced it. The manifest commits to the snapshot's hash and to the hash
+//! of every chunk, so peers can serve it piecewise over the P2P network.
+//! A snapshot is accepted when its tip is a block hash obtained out of band
+//! and its signer is one of the node's trusted snapshot signers.
+//!
+//! Only the tip block is authenticated by that hash. The headers below it
+//! are linked by hash but cannot be recomputed without the block bodies, so
+//! their contents are as claimed by the signer.
+
+use crate::blockchain::{BalanceLock, Block, BlockHeader, ParamsSchedule};
+use crate::zhtp::{
+    amount::Amount,
+    crypto::{address_from_public_key, Keypair},
+    shielded::ShieldedPool,
+    zk_transactions::ZkBalance,
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Version of the snapshot encoding
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// Size of the pieces a snapshot is exchanged in; small enough for one
+/// datagram together with the message framing
+pub const SNAPSHOT_CHUNK_SIZE: usize = 32 * 1024;
+
+/// Nodes build the snapshots they serve to peers at multiples of this height
+pub const SNAPSHOT_INTERVAL: u64 = 1_000;
+
+/// Committed state of one account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotAccount {
+    pub balance: Amount,
+    pub nonce: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locks: Vec<BalanceLock>,
+}
+
+/// Chain state at the height of `tip`.
+///
+/// Maps are ordered so that every node encodes the same snapshot to the
+/// same bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainSnapshot {
+    pub version: u32,
+    /// Headers of every block from genesis up to and including `tip`.
+    /// Those below the tip are untrusted apart from their hash links.
+    pub headers: Vec<BlockHeader>,
+    /// The block the snapshot was taken at; its state root commits to `accounts`
+    pub tip: Block,
+    pub accounts: BTreeMap<String, SnapshotAccount>,
+    /// Shielded note pool, committed to by the state root through the pool
+    /// account's leaf
+    #[serde(default)]
+    pub shielded: ShieldedPool,
+    /// Governance address and parameter upgrades, committed to by the state
+    /// root through the parameters account's leaf
+    #[serde(default)]
+    pub params: ParamsSchedule,
+    /// Private balance commitments of the producing node, only carried by
+    /// snapshots of its chain tip
+    #[serde(default)]
+    pub private_balances: BTreeMap<String, ZkBalance>,
+}
+
+impl ChainSnapshot {
+    pub fn height(&self) -> u64 {
+        self.tip.index
+    }
+
+    /// Hash of the genesis block the snapshot's chain starts from
+    pub fn genesis_hash(&self) -> &str {
+        &self.headers[0].hash
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+
+    /// SHA-256 of the encoded snapshot
+    pub fn hash(&self) -> Result<[u8; 32]> {
+        Ok(Sha256::digest(self.encode()?).into())
+    }
+
+    /// Check that the snapshot is taken at the block with hash `trusted_hash`
+    /// and that its header chain links from genesis to that block.
+    ///
+    /// The accounts are checked against the tip's state root when the
+    /// snapshot is loaded into a chain. The fields of the headers below the
+    /// tip, including the genesis hash they start from, are not checked.
+    pub fn verify(&self, trusted_hash: &str) -> Result<()> {
+        if self.version != SNAPSHOT_FORMAT_VERSION {
+            return Err(anyhow!("Unsupported snapshot version {}", self.version));
+        }
+        if self.tip.hash != trusted_hash || self.tip.calculate_hash() != self.tip.hash {
+            return Err(anyhow!("Snapshot tip {} is not the trusted block {}", self.tip.hash, trusted_hash));
+        }
+        if self.tip.state_root.is_none() {
+            return Err(anyhow!("Snapshot tip {} has no state root", self.tip.index));
+        }
+        if self.headers.len() as u64 != self.height() + 1 || self.headers.last() != Some(&BlockHeader::from(&self.tip)) {
+            return Err(anyhow!("Snapshot header chain does not end at its tip"));
+        }
+        for (height, header) in self.headers.iter().enumerate() {
+            if header.index != height as u64 {
+                return Err(anyhow!("Snapshot header at height {} has index {}", height, header.index));
+            }
+            if height > 0 && header.previous_hash != self.headers[height - 1].hash {
+                return Err(anyhow!("Snapshot header {} does not link to its parent", height));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a signed snapshot written by [`SignedSnapshot::save`]
+    pub fn load_signed<P: AsRef<Path>>(path: P) -> Result<SignedSnapshot> {
+        let path = path.as_ref();
+        let contents = fs::read(path).map_err(|e| anyhow!("Failed to read snapshot {}: {}", path.display(), e))?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+}
+
+/// Signed description of a snapshot and the chunks it is exchanged in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub version: u32,
+    pub height: u64,
+    pub block_hash: String,
+    pub genesis_hash: String,
+    /// SHA-256 of the encoded snapshot
+    pub snapshot_hash: [u8; 32],
+    /// Length of the encoded snapshot in bytes
+    pub size: u64,
+    /// SHA-256 of each [`SNAPSHOT_CHUNK_SIZE`] piece of the encoding
+    pub chunk_hashes: Vec<[u8; 32]>,
+    /// Dilithium5 public key of the producing node
+    pub signer: Vec<u8>,
+    /// Signature by `signer` over [`SnapshotManifest::signing_digest`]
+    pub signature: Vec<u8>,
+}
+
+impl SnapshotManifest {
+    /// Describe and sign `snapshot`, returning the manifest with the encoding
+    /// it describes
+    pub fn sign(snapshot: &ChainSnapshot, keypair: &Keypair) -> Result<(Self, Vec<u8>)> {
+        let encoded = snapshot.encode()?;
+        let mut manifest = Self {
+            version: SNAPSHOT_FORMAT_VERSION,
+            height: snapshot.height(),
+            block_hash: snapshot.tip.hash.clone(),
+            genesis_hash: snapshot.genesis_hash().to_string(),
+            snapshot_hash: Sha256::digest(&encoded).into(),
+            size: encoded.len() as u64,
+            chunk_hashes: encoded.chunks(SNAPSHOT_CHUNK_SIZE).map(|chunk| Sha256::digest(chunk).into()).collect(),
+            signer: keypair.public_key(),
+            signature: Vec::new(),
+        };
+        manifest.signature = keypair.sign(&manifest.signing_digest())?.as_bytes().to_vec();
+        Ok((manifest, encoded))
+    }
+
+    /// Digest of every field except the signature
+    pub fn signing_digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.version.to_le_bytes());
+        hasher.update(self.height.to_le_bytes());
+        hasher.update(self.block_hash.as_bytes());
+        hasher.update(self.genesis_hash.as_bytes());
+        hasher.update(self.snapshot_hash);
+        hasher.update(self.size.to_le_bytes());
+        for chunk_hash in &self.chunk_hashes {
+            hasher.update(chunk_hash);
+        }
+        hasher.update(&self.signer);
+        hasher.finalize().into()
+    }
+
+    pub fn verify_signature(&self) -> bool {
+        use pqcrypto_dilithium::dilithium5;
+        use pqcrypto_traits::sign::{DetachedSignature, PublicKey};
+
+        let (Ok(public_key), Ok(signature)) = (
+            dilithium5::PublicKey::from_bytes(&self.signer),
+            dilithium5::DetachedSignature::from_bytes(&self.signature),
+        ) else {
+            return false;
+        };
+        dilithium5::verify_detached_signature(&signature, &self.signing_digest(), &public_key).is_ok()
+    }
+
+    /// Address of the node that signed the manifest
+    pub fn signer_address(&self) -> String {
+        address_from_public_key(&self.signer)
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_hashes.len()
+    }
+
+    /// Whether `data` is chunk `index` of the described snapshot
+    pub fn verify_chunk(&self, index: usize, data: &[u8]) -> bool {
+        self.chunk_hashes
+            .get(index)
+            .is_some_and(|hash| *hash == <[u8; 32]>::from(Sha256::digest(data)))
+    }
+
+    /// Decode the snapshot this manifest describes from its encoding
+    pub fn decode(&self, encoded: &[u8]) -> Result<ChainSnapshot> {
+        if encoded.len() as u64 != self.size || <[u8; 32]>::from(Sha256::digest(encoded)) != self.snapshot_hash {
+            return Err(anyhow!("Snapshot data does not match manifest for block {}", self.block_hash));
+        }
+        let snapshot = ChainSnapshot::decode(encoded)?;
+        if snapshot.tip.hash != self.block_hash || snapshot.genesis_hash() != self.genesis_hash {
+            return Err(anyhow!("Snapshot contents do not match manifest for block {}", self.block_hash));
+        }
+        Ok(snapshot)
+    }
+}
+
+/// A snapshot with its manifest, as written to and read from files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedSnapshot {
+    pub manifest: SnapshotManifest,
+    pub snapshot: ChainSnapshot,
+}
+
+impl SignedSnapshot {
+    pub fn new(snapshot: ChainSnapshot, keypair: &Keypair) -> Result<Self> {
+        let (manifest, _) = SnapshotManifest::sign(&snapshot, keypair)?;
+        Ok(Self { manifest, snapshot })
+    }
+
+    /// Check that the manifest is signed by one of `trusted_signers`
+    /// (addresses of snapshot producers), that it describes the snapshot, and
+    /// the snapshot itself against `trusted_hash`
+    pub fn verify(&self, trusted_hash: &str, trusted_signers: &[String]) -> Result<()> {
+        if !self.manifest.verify_signature() {
+            return Err(anyhow!("Invalid snapshot manifest signature"));
+        }
+        let signer = self.manifest.signer_address();
+        if !trusted_signers.contains(&signer) {
+            return Err(anyhow!("Snapshot signer {} is not a trusted snapshot signer", signer));
+        }
+        if self.snapshot.hash()? != self.manifest.snapshot_hash || self.snapshot.tip.hash != self.manifest.block_hash {
+            return Err(anyhow!("Snapshot manifest does not describe the snapshot"));
+        }
+        self.snapshot.verify(trusted_hash)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        fs::write(path, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+/// Snapshot being assembled from chunks fetched from peers
+#[derive(Debug, Clone)]
+pub struct SnapshotDownload {
+    manifest: SnapshotManifest,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl SnapshotDownload {
+    /// Start downloading the snapshot described by a signed manifest
+    pub fn new(manifest: SnapshotManifest) -> Result<Self> {
+        if !manifest.verify_signature() {
+            return Err(anyhow!("Invalid snapshot manifest signature"));
+        }
+        let expected_chunks = manifest.size.div_ceil(SNAPSHOT_CHUNK_SIZE as u64);
+        if manifest.chunk_count() as u64 != expected_chunks {
+            return Err(anyhow!("Snapshot manifest lists {} chunks for {} bytes", manifest.chunk_count(), manifest.size));
+        }
+        let chunks = vec![None; manifest.chunk_count()];
+        Ok(Self { manifest, chunks })
+    }
+
+    pub fn manifest(&self) -> &SnapshotManifest {
+        &self.manifest
+    }
+
+    /// Indexes of the chunks still to be fetched
+    pub fn missing(&self) -> Vec<usize> {
+        (0..self.chunks.len()).filter(|index| self.chunks[*index].is_none()).collect()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.chunks.iter().all(Option::is_some)
+    }
+
+    /// Store a received chunk after checking it against the manifest
+    pub fn insert(&mut self, index: usize, data: Vec<u8>) -> Result<()> {
+        if !self.manifest.verify_chunk(index, &data) {
+            return Err(anyhow!("Snapshot chunk {} does not match the manifest", index));
+        }
+        self.chunks[index] = Some(data);
+        Ok(())
+    }
+
+    /// Reassemble the snapshot once every chunk has arrived
+    pub fn finish(self) -> Result<SignedSnapshot> {
+        if !self.is_complete() {
+            return Err(anyhow!("Snapshot download is missing {} chunks", self.missing().len()));
+        }
+        let encoded: Vec<u8> = self.chunks.into_iter().flatten().flatten().collect();
+        let snapshot = self.manifest.decode(&encoded)?;
+        Ok(SignedSnapshot { manifest: self.manifest, snapshot })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{Blockchain, Transaction};
+    use crate::genesis::{GenesisAllocation, GenesisSpec};
+
+    async fn snapshot() -> ChainSnapshot {
+        let alice = Keypair::generate();
+        let spec = GenesisSpec {
+            allocations: vec![GenesisAllocation { address: alice.address(), amount: Amount::from_zhtp(500), lock: None }],
+            ..GenesisSpec::default()
+        };
+        let blockchain = Blockchain::from_genesis(&spec);
+        let mut transfer = Transaction::new(alice.address(), "bob".to_string(), Amount::from_zhtp(200));
+        transfer.sign_with_keypair(&alice).unwrap();
+        blockchain.submit_transaction(transfer).await.unwrap();
+        blockchain.create_block("validator1", 1.0, None).await;
+        blockchain.create_snapshot(1).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_signed_snapshot_roundtrip() -> Result<()> {
+        let snapshot = snapshot().await;
+        let trusted = snapshot.tip.hash.clone();
+        let producer = Keypair::generate();
+        let signers = vec![producer.address()];
+        let signed = SignedSnapshot::new(snapshot, &producer)?;
+        signed.verify(&trusted, &signers)?;
+        assert!(signed.verify(signed.snapshot.genesis_hash(), &signers).is_err());
+        // Only trusted producers are accepted
+        assert!(signed.verify(&trusted, &[]).is_err());
+        assert!(signed.verify(&trusted, &[Keypair::generate().address()]).is_err());
+
+        let path = std::env::temp_dir().join(format!("zhtp-snapshot-{}.json", uuid::Uuid::new_v4()));
+        signed.save(&path)?;
+        let loaded = ChainSnapshot::load_signed(&path)?;
+        loaded.verify(&trusted, &signers)?;
+        assert_eq!(loaded.manifest, signed.manifest);
+        fs::remove_file(&path)?;
+
+        // Changing the contents breaks the manifest, re-signing with another
+        // key changes the signer
+        let mut tampered = signed.clone();
+        tampered.snapshot.accounts.remove("bob");
+        assert!(tampered.verify(&trusted, &signers).is_err());
+        let mut resigned = signed.clone();
+        resigned.manifest.signature = SignedSnapshot::new(signed.snapshot.clone(), &Keypair::generate())?.manifest.signature;
+        assert!(resigned.verify(&trusted, &signers).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunked_download() -> Result<()> {
+        let mut snapshot = snapshot().await;
+        // Enough accounts for several chunks
+        for i in 0..2_000 {
+            let account = SnapshotAccount { balance: Amount::from_zhtp(i), nonce: i, locks: Vec::new() };
+            snapshot.accounts.insert(format!("account-{:04}", i), account);
+        }
+        let (manifest, encoded) = SnapshotManifest::sign(&snapshot, &Keypair::generate())?;
+        assert!(manifest.chunk_count() > 1);
+
+        let mut download = SnapshotDownload::new(manifest.clone())?;
+        let chunks: Vec<&[u8]> = encoded.chunks(SNAPSHOT_CHUNK_SIZE).collect();
+        assert!(download.insert(0, chunks[1].to_vec()).is_err());
+        for (index, chunk) in chunks.iter().enumerate().rev() {
+            assert!(!download.is_complete());
+            download.insert(index, chunk.to_vec())?;
+        }
+        assert!(download.missing().is_empty());
+        let fetched = download.finish()?;
+        assert_eq!(fetched.snapshot.hash()?, snapshot.hash()?);
+
+        let mut forged = manifest;
+        forged.height += 1;
+        assert!(SnapshotDownload::new(forged).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,848 @@
+use crate::blockchain::{BalanceLock, Block, ParamsSchedule};
+use crate::snapshot::ChainSnapshot;
+use crate::zhtp::{amount::Amount, shielded::ShieldedPool, zk_transactions::ZkBalance};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Subdirectory of the node data directory holding chain data
+const CHAIN_DIR: &str = "chain";
+/// Append-only block log
+const BLOCK_LOG_FILE: &str = "blocks.log";
+/// Indexed account state, replaced atomically after every committed block
+const STATE_FILE: &str = "state.bin";
+/// On-disk format version marker
+const FORMAT_FILE: &str = "FORMAT";
+/// Snapshot the block log starts from, present only on nodes bootstrapped
+/// from one
+const SNAPSHOT_FILE: &str = "snapshot.json";
+/// Block limits set by protocol upgrades, absent while the defaults apply
+/// Record header: payload length (u32 LE) followed by SHA-256 of the payload
+const RECORD_HEADER_LEN: usize = 4 + 32;
+
+/// Format 1 stored token amounts as `f64` and format 2 as integer base units,
+/// both with bincode-encoded blocks. Format 3 encodes blocks as JSON so that
+/// optional header fields can be added without rewriting the log. Format 4
+/// has the same layout, with transaction hashes over the canonical signing
+/// payload instead of concatenated fields.
+const LEGACY_F64_FORMAT: u32 = 1;
+const LEGACY_BINCODE_FORMAT: u32 = 2;
+const LEGACY_TRANSACTION_HASH_FORMAT: u32 = 3;
+const CURRENT_FORMAT: u32 = 4;
+
+/// Account state persisted next to the block log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccountStateIndex {
+    /// Height of the last block applied to this state
+    pub height: u64,
+    /// Hash of the last block applied to this state
+    pub tip_hash: String,
+    /// Transparent account balances
+    pub balances: HashMap<String, Amount>,
+    /// Next expected nonce per sender
+    pub nonces: HashMap<String, u64>,
+    /// Balance locks per address
+    pub locks: HashMap<String, Vec<BalanceLock>>,
+    /// Note commitment tree and spent nullifiers of the shielded pool
+    pub shielded: ShieldedPool,
+    /// Governance address and scheduled protocol parameter upgrades
+    pub params: ParamsSchedule,
+    /// Private balance commitments
+    pub private_balances: HashMap<String, ZkBalance>,
+}
+
+/// On-disk chain storage: an append-only block log plus an account state index
+#[derive(Debug)]
+pub struct ChainStore {
+    /// Directory holding the chain files
+    root: PathBuf,
+    /// Block log opened for appending
+    log: File,
+    /// Height of the first block in the log
+    base: u64,
+    /// Byte offset of every block record in the log, indexed by height
+    /// above `base`
+    offsets: Vec<u64>,
+    /// Length of the verified part of the log
+    log_len: u64,
+}
+
+impl ChainStore {
+    /// Open (or create) the chain store under `data_dir`
+    pub fn open<P: AsRef<Path>>(data_dir: P) -> Result<Self> {
+        let root = data_dir.as_ref().join(CHAIN_DIR);
+        fs::create_dir_all(&root)?;
+
+        let log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(root.join(BLOCK_LOG_FILE))?;
+
+        let mut store = Self {
+            root,
+            log,
+            base: 0,
+            offsets: Vec::new(),
+            log_len: 0,
+        };
+
+        match store.format_version()? {
+            CURRENT_FORMAT => {}
+            version @ (LEGACY_F64_FORMAT | LEGACY_BINCODE_FORMAT | LEGACY_TRANSACTION_HASH_FORMAT) => {
+                store.upgrade_legacy_format(version)?
+            }
+            version => return Err(anyhow!("Unsupported chain store format version {}", version)),
+        }
+
+        Ok(store)
+    }
+
+    /// Format version of the files on disk; an unmarked non-empty log predates versioning
+    fn format_version(&self) -> Result<u32> {
+        match fs::read_to_string(self.root.join(FORMAT_FILE)) {
+            Ok(version) => version
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Corrupt chain store format marker")),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if self.log.metadata()?.len() > 0 {
+                    Ok(LEGACY_F64_FORMAT)
+                } else {
+                    fs::write(self.root.join(FORMAT_FILE), CURRENT_FORMAT.to_string())?;
+                    Ok(CURRENT_FORMAT)
+                }
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Rewrite an older log into the current format.
+    ///
+    /// Format 1 amounts were `f64` ZHTP tokens, and before format 4
+    /// transaction hashes covered a different preimage. Block hashes commit
+    /// to both through the encoded transactions and the header roots, so the
+    /// roots and receipts are recomputed and the chain re-hashed and
+    /// re-linked in order; every node migrating the same legacy chain derives
+    /// the same new hashes. Transaction signatures are kept as written, over
+    /// the legacy preimage, and are not checked again for stored blocks.
+    fn upgrade_legacy_format(&mut self, version: u32) -> Result<()> {
+        log::warn!("Migrating chain store from format {} to {}", version, CURRENT_FORMAT);
+        if self.load_snapshot()?.is_some() {
+            return Err(anyhow!(
+                "Chain store format {} started from a snapshot cannot be migrated; import a new snapshot",
+                version
+            ));
+        }
+
+        let mut blocks = self.load_records(|payload| {
+            if version == LEGACY_TRANSACTION_HASH_FORMAT {
+                return Ok(serde_json::from_slice(payload)?);
+            }
+            let mut block: legacy::BincodeBlock = bincode::deserialize(payload)?;
+            if version == LEGACY_F64_FORMAT {
+                block.migrate_f64_amounts();
+            }
+            block.into_block()
+        })?;
+        let legacy_tip_hash = blocks.last().map(|block| block.hash.clone());
+        for height in 0..blocks.len() {
+            if height > 0 {
+                blocks[height].previous_hash = blocks[height - 1].hash.clone();
+            }
+            Self::reseal(&mut blocks[height]);
+        }
+
+        // Write the migrated log beside the old one and swap it in atomically
+        let tmp_path = self.root.join(format!("{}.tmp", BLOCK_LOG_FILE));
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            for block in &blocks {
+                tmp.write_all(&Self::encode_record(block)?)?;
+            }
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, self.root.join(BLOCK_LOG_FILE))?;
+        self.log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(self.root.join(BLOCK_LOG_FILE))?;
+
+        if let Some(mut state) = self.load_state()? {
+            if version == LEGACY_F64_FORMAT {
+                for balance in state.balances.values_mut() {
+                    *balance = Amount::from_legacy_bits(*balance);
+                }
+            }
+            if Some(&state.tip_hash) == legacy_tip_hash.as_ref() {
+                if let Some(tip) = blocks.last() {
+                    state.tip_hash = tip.hash.clone();
+                }
+            }
+            self.save_state(&state)?;
+        }
+
+        fs::write(self.root.join(FORMAT_FILE), CURRENT_FORMAT.to_string())?;
+        self.offsets.clear();
+        self.log_len = 0;
+        Ok(())
+    }
+
+    /// Recompute the transaction hashes a block commits to, then its hash
+    fn reseal(block: &mut Block) {
+        for (receipt, tx) in block.receipts.iter_mut().zip(&block.transactions) {
+            receipt.tx_hash = tx.calculate_hash();
+        }
+        if block.transactions_root.is_some() {
+            block.transactions_root = Some(Block::calculate_transactions_root(&block.transactions));
+        }
+        if block.receipts_root.is_some() {
+            block.receipts_root = Some(Block::calculate_receipts_root(&block.receipts));
+        }
+        block.hash = block.calculate_hash();
+    }
+
+    /// Read every block from the log.
+    ///
+    /// Records are checked against their checksum; the first incomplete or
+    /// corrupt record marks a torn write and the log is truncated there. An
+    /// intact record that does not decode is an error.
+    pub fn load_chain(&mut self) -> Result<Vec<Block>> {
+        let mut blocks = self.load_records(|payload| Ok(serde_json::from_slice(payload)?))?;
+
+        // Finish pruning interrupted after the new snapshot was written
+        if let Some(snapshot) = self.load_snapshot()? {
+            let position = snapshot.height().saturating_sub(self.base) as usize;
+            if position > 0 && position < blocks.len() {
+                log::warn!("Completing interrupted pruning of blocks below {}", snapshot.height());
+                self.compact(snapshot.height())?;
+                blocks.drain(..position);
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Decode every intact record of the log with `decode`
+    fn load_records<F>(&mut self, decode: F) -> Result<Vec<Block>>
+    where
+        F: Fn(&[u8]) -> Result<Block>,
+    {
+        let file = File::open(self.root.join(BLOCK_LOG_FILE))?;
+        let file_len = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+
+        let mut blocks = Vec::new();
+        self.offsets.clear();
+        let mut offset = 0u64;
+
+        // A record whose checksum holds was written completely, so failing
+        // to decode it is not a torn write; truncating there would discard
+        // intact history
+        while let Some(payload) = Self::read_record(&mut reader)? {
+            let block = decode(&payload)
+                .map_err(|e| anyhow!("Undecodable block record at offset {}: {}", offset, e))?;
+            self.offsets.push(offset);
+            offset += (RECORD_HEADER_LEN + payload.len()) as u64;
+            blocks.push(block);
+        }
+
+        if let Some(first) = blocks.first() {
+            self.base = first.index;
+        }
+        if offset < file_len {
+            log::warn!(
+                "Truncating torn block log write: {} trailing bytes discarded",
+                file_len - offset
+            );
+            self.log.set_len(offset)?;
+            self.log.sync_all()?;
+        }
+        self.log_len = offset;
+
+        Ok(blocks)
+    }
+
+    /// Read one record, returning `None` at end of log or on a torn/corrupt record
+    fn read_record<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+        let mut header = [0u8; RECORD_HEADER_LEN];
+        if !Self::read_full(reader, &mut header)? {
+            return Ok(None);
+        }
+
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let mut payload = vec![0u8; len];
+        if !Self::read_full(reader, &mut payload)? {
+            return Ok(None);
+        }
+
+        let checksum: [u8; 32] = Sha256::digest(&payload).into();
+        if checksum[..] != header[4..] {
+            return Ok(None);
+        }
+
+        Ok(Some(payload))
+    }
+
+    /// Fill `buf` completely, returning false if the log ends first
+    fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool> {
+        match reader.read_exact(buf) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Append a block to the log and flush it to disk
+    pub fn append_block(&mut self, block: &Block) -> Result<()> {
+        let next = self.base + self.offsets.len() as u64;
+        if block.index != next {
+            return Err(anyhow!(
+                "Block {} does not extend stored chain ending before height {}",
+                block.index,
+                next
+            ));
+        }
+
+        let record = Self::encode_record(block)?;
+        if let Err(e) = self.log.write_all(&record).and_then(|()| self.log.sync_data()) {
+            // Drop a partial record so the next append starts on a record
+            // boundary
+            let _ = self.log.set_len(self.log_len);
+            return Err(e.into());
+        }
+
+        self.offsets.push(self.log_len);
+        self.log_len += record.len() as u64;
+        Ok(())
+    }
+
+    /// Drop every block at or above `height` from the log, e.g. after a
+    /// chain reorganization
+    pub fn truncate(&mut self, height: u64) -> Result<()> {
+        let position = height
+            .checked_sub(self.base)
+            .ok_or_else(|| anyhow!("Cannot truncate below stored base height {}", self.base))? as usize;
+        let Some(&offset) = self.offsets.get(position) else {
+            return Ok(());
+        };
+        self.log.set_len(offset)?;
+        self.log.sync_all()?;
+        self.offsets.truncate(position);
+        self.log_len = offset;
+        Ok(())
+    }
+
+    /// Discard the stored chain and start over from `snapshot`: the next
+    /// block appended must be the snapshot's tip
+    pub fn reset_to_snapshot(&mut self, snapshot: &ChainSnapshot) -> Result<()> {
+        Self::write_atomic(&self.root, SNAPSHOT_FILE, &snapshot.encode()?)?;
+        self.log.set_len(0)?;
+        self.log.sync_all()?;
+        if let Err(e) = fs::remove_file(self.root.join(STATE_FILE)) {
+            if e.kind() != ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+        self.base = snapshot.height();
+        self.offsets.clear();
+        self.log_len = 0;
+        Ok(())
+    }
+
+    /// Start the stored chain at `snapshot`, taken at a block already in
+    /// the log, and drop the blocks below it. The snapshot is written first,
+    /// so a crash in between leaves a log that [`ChainStore::load_chain`]
+    /// finishes pruning.
+    pub fn prune_to(&mut self, snapshot: &ChainSnapshot) -> Result<()> {
+        if snapshot.height() < self.base || snapshot.height() >= self.base + self.len() {
+            return Err(anyhow!("Cannot prune to block {} which is not stored", snapshot.height()));
+        }
+        Self::write_atomic(&self.root, SNAPSHOT_FILE, &snapshot.encode()?)?;
+        self.compact(snapshot.height())
+    }
+
+    /// Rewrite the log without the blocks below `height`
+    fn compact(&mut self, height: u64) -> Result<()> {
+        let position = (height - self.base) as usize;
+        let start = self.offsets[position];
+        if start == 0 {
+            return Ok(());
+        }
+
+        let tmp_path = self.root.join(format!("{}.tmp", BLOCK_LOG_FILE));
+        {
+            let mut log = File::open(self.root.join(BLOCK_LOG_FILE))?;
+            log.seek(SeekFrom::Start(start))?;
+            let mut tmp = File::create(&tmp_path)?;
+            io::copy(&mut log.take(self.log_len - start), &mut tmp)?;
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, self.root.join(BLOCK_LOG_FILE))?;
+        if let Ok(dir) = File::open(&self.root) {
+            let _ = dir.sync_all();
+        }
+        self.log = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .open(self.root.join(BLOCK_LOG_FILE))?;
+
+        self.offsets = self.offsets[position..].iter().map(|offset| offset - start).collect();
+        self.log_len -= start;
+        self.base = height;
+        Ok(())
+    }
+
+    /// Load the snapshot the block log starts from, if any
+    pub fn load_snapshot(&self) -> Result<Option<ChainSnapshot>> {
+        match fs::read(self.root.join(SNAPSHOT_FILE)) {
+            Ok(bytes) => Ok(Some(ChainSnapshot::decode(&bytes)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Frame a block as a checksummed log record
+    fn encode_record(block: &Block) -> Result<Vec<u8>> {
+        let payload = serde_json::to_vec(block)?;
+        Self::frame_record(&payload)
+            .ok_or_else(|| anyhow!("Block {} too large to store", block.index))
+    }
+
+    /// Prefix a payload with its length and checksum
+    fn frame_record(payload: &[u8]) -> Option<Vec<u8>> {
+        let len = u32::try_from(payload.len()).ok()?;
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&Sha256::digest(payload));
+        record.extend_from_slice(payload);
+        Some(record)
+    }
+
+    /// Height of the first block in the log
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    /// Number of blocks in the log
+    pub fn len(&self) -> u64 {
+        self.offsets.len() as u64
+    }
+
+    /// Check if the log holds no blocks
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Load the persisted account state, if any
+    pub fn load_state(&self) -> Result<Option<AccountStateIndex>> {
+        match fs::read(self.root.join(STATE_FILE)) {
+            Ok(bytes) => match bincode::deserialize(&bytes) {
+                Ok(state) => Ok(Some(state)),
+                Err(e) => {
+                    log::warn!("Ignoring unreadable account state index: {}", e);
+                    Ok(None)
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Atomically replace the persisted account state
+    pub fn save_state(&self, state: &AccountStateIndex) -> Result<()> {
+        Self::write_atomic(&self.root, STATE_FILE, &bincode::serialize(state)?)
+    }
+
+    /// Replace `root/name` with `bytes` through a temporary file
+    fn write_atomic(root: &Path, name: &str, bytes: &[u8]) -> Result<()> {
+        let tmp_path = root.join(format!("{}.tmp", name));
+
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            tmp.write_all(bytes)?;
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, root.join(name))?;
+
+        // Persist the rename itself where the platform allows it
+        if let Ok(dir) = File::open(root) {
+            let _ = dir.sync_all();
+        }
+        Ok(())
+    }
+}
+
+/// Block layout of the bincode formats (1 and 2), frozen so old logs stay
+/// readable as the live `Block` type grows. Bincode is not self-describing,
+/// so every nested type is mirrored field for field.
+mod legacy {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Proof {
+        commitments: Vec<Vec<u8>>,
+        elements: Vec<Vec<u8>>,
+        inputs: Vec<Vec<u8>>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct NetworkMetrics {
+        encrypted_metrics: Vec<u8>,
+        metrics_proof: Proof,
+        performance_commitment: [u8; 32],
+        reputation_score: f64,
+        updated_at: u64,
+        packets_routed: u64,
+        delivery_success: f64,
+        delivery_failures: u64,
+        avg_latency: f64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct ZkTransaction {
+        encrypted_data: Vec<u8>,
+        validity_proof: Proof,
+        nullifier: [u8; 32],
+        commitment: [u8; 32],
+        fee: u64,
+        timestamp: u64,
+        balance_proof: Proof,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Transaction {
+        from: String,
+        to: String,
+        amount: u64,
+        timestamp: i64,
+        signature: String,
+        nonce: u64,
+        data: Vec<u8>,
+        zk_transaction: Option<ZkTransaction>,
+        is_private: bool,
+        validity_proof: Option<Proof>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct BincodeBlock {
+        index: u64,
+        timestamp: i64,
+        transactions: Vec<Transaction>,
+        previous_hash: String,
+        hash: String,
+        validator: String,
+        validator_score: f64,
+        network_metrics: Option<NetworkMetrics>,
+        zk_transaction_count: u64,
+        private_transaction_root: Option<[u8; 32]>,
+        block_validity_proof: Option<Proof>,
+        has_private_transactions: bool,
+    }
+
+    impl BincodeBlock {
+        /// Reinterpret amount fields written by format 1 as `f64` ZHTP tokens
+        pub(super) fn migrate_f64_amounts(&mut self) {
+            let convert = |raw: u64| Amount::from_legacy_bits(Amount::from_base_units(raw)).base_units();
+            for tx in &mut self.transactions {
+                tx.amount = convert(tx.amount);
+                if let Some(zk_tx) = &mut tx.zk_transaction {
+                    zk_tx.fee = convert(zk_tx.fee);
+                }
+            }
+        }
+
+        /// Convert to the live block type; header fields added since are absent
+        pub(super) fn into_block(self) -> Result<Block> {
+            Ok(serde_json::from_value(serde_json::to_value(self)?)?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockchain::{Transaction, TransactionReceipt};
+
+    fn temp_data_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("zhtp-chain-store-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn test_block(index: u64, previous_hash: String) -> Block {
+        Block::new(index, Vec::new(), previous_hash, "validator".to_string(), 1.0, None)
+    }
+
+    #[test]
+    fn test_block_log_roundtrip() -> Result<()> {
+        let dir = temp_data_dir();
+        let genesis = test_block(0, "0".to_string());
+        let next = test_block(1, genesis.hash.clone());
+
+        {
+            let mut store = ChainStore::open(&dir)?;
+            assert!(store.load_chain()?.is_empty());
+            store.append_block(&genesis)?;
+            store.append_block(&next)?;
+            assert!(store.append_block(&genesis).is_err());
+        }
+
+        let mut store = ChainStore::open(&dir)?;
+        let blocks = store.load_chain()?;
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].hash, next.hash);
+        assert_eq!(store.len(), 2);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    /// Write `blocks` as a bincode-format log, as nodes did before format 3
+    fn write_bincode_log(dir: &Path, blocks: &[Block], version: Option<u32>) -> Result<()> {
+        let root = dir.join(CHAIN_DIR);
+        fs::create_dir_all(&root)?;
+        let mut log = Vec::new();
+        for block in blocks {
+            let legacy: legacy::BincodeBlock = serde_json::from_value(serde_json::to_value(block)?)?;
+            log.extend(ChainStore::frame_record(&bincode::serialize(&legacy)?).unwrap());
+        }
+        fs::write(root.join(BLOCK_LOG_FILE), log)?;
+        if let Some(version) = version {
+            fs::write(root.join(FORMAT_FILE), version.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// A block as written before header roots existed
+    fn rootless_block(index: u64, transactions: Vec<Transaction>, previous_hash: String) -> Block {
+        let mut block = Block::new(index, transactions, previous_hash, "validator".to_string(), 1.0, None);
+        block.transactions_root = None;
+        block.hash = block.calculate_hash();
+        block
+    }
+
+    #[test]
+    fn test_legacy_format_migration() -> Result<()> {
+        let dir = temp_data_dir();
+        // Pre-versioning nodes wrote the amount as f64 bits in the same 8 bytes
+        let legacy_amount = Amount::from_base_units(2.5f64.to_bits());
+        let tx = Transaction::new("network".to_string(), "alice".to_string(), legacy_amount);
+        let genesis = rootless_block(0, vec![tx], "0".to_string());
+        let next = rootless_block(1, Vec::new(), genesis.hash.clone());
+
+        write_bincode_log(&dir, &[genesis.clone(), next.clone()], None)?;
+        let legacy_state = AccountStateIndex {
+            height: 1,
+            tip_hash: next.hash.clone(),
+            balances: HashMap::from([("alice".to_string(), legacy_amount)]),
+            ..Default::default()
+        };
+        fs::write(dir.join(CHAIN_DIR).join(STATE_FILE), bincode::serialize(&legacy_state)?)?;
+
+        let mut store = ChainStore::open(&dir)?;
+        let blocks = store.load_chain()?;
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].transactions[0].amount, "2.5".parse()?);
+        assert_ne!(blocks[0].hash, genesis.hash);
+        assert_eq!(blocks[1].previous_hash, blocks[0].hash);
+        assert_eq!(blocks[1].hash, blocks[1].calculate_hash());
+
+        let state = store.load_state()?.expect("state index migrated");
+        assert_eq!(state.balances.get("alice"), Some(&"2.5".parse()?));
+        assert_eq!(state.tip_hash, blocks[1].hash);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_bincode_format_upgrade_keeps_hashes() -> Result<()> {
+        let dir = temp_data_dir();
+        let tx = Transaction::new("network".to_string(), "alice".to_string(), Amount::from_zhtp(5));
+        let genesis = rootless_block(0, vec![tx], "0".to_string());
+        let next = rootless_block(1, Vec::new(), genesis.hash.clone());
+        write_bincode_log(&dir, &[genesis.clone(), next.clone()], Some(LEGACY_BINCODE_FORMAT))?;
+
+        let mut store = ChainStore::open(&dir)?;
+        let blocks = store.load_chain()?;
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].hash, genesis.hash);
+        assert_eq!(blocks[1].hash, next.hash);
+        assert_eq!(blocks[0].transactions[0].amount, Amount::from_zhtp(5));
+        assert!(blocks[0].transactions_root.is_none());
+
+        // New blocks append as JSON records after the upgrade
+        store.append_block(&test_block(2, next.hash.clone()))?;
+        assert_eq!(ChainStore::open(&dir)?.load_chain()?.len(), 3);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_transaction_hash_upgrade_reroots_blocks() -> Result<()> {
+        let dir = temp_data_dir();
+        let tx = Transaction::new("network".to_string(), "alice".to_string(), Amount::from_zhtp(5));
+        let genesis = test_block(0, "0".to_string());
+        let mut next = Block::new(1, vec![tx.clone()], genesis.hash.clone(), "validator".to_string(), 1.0, None);
+        // Roots and receipts over the format 3 transaction hashes
+        next.transactions_root = Some([7u8; 32]);
+        next.receipts = vec![TransactionReceipt { tx_hash: "legacy".to_string(), ..TransactionReceipt::applied(&tx) }];
+        next.receipts_root = Some([8u8; 32]);
+        next.hash = next.calculate_hash();
+
+        let root = dir.join(CHAIN_DIR);
+        fs::create_dir_all(&root)?;
+        let mut log = Vec::new();
+        for block in [&genesis, &next] {
+            log.extend(ChainStore::encode_record(block)?);
+        }
+        fs::write(root.join(BLOCK_LOG_FILE), log)?;
+        fs::write(root.join(FORMAT_FILE), LEGACY_TRANSACTION_HASH_FORMAT.to_string())?;
+
+        let mut store = ChainStore::open(&dir)?;
+        let blocks = store.load_chain()?;
+        assert_eq!(blocks[0].hash, genesis.hash);
+        let migrated = &blocks[1];
+        assert_eq!(migrated.transactions_root, Some(Block::calculate_transactions_root(&migrated.transactions)));
+        assert_eq!(migrated.receipts[0].tx_hash, migrated.transactions[0].calculate_hash());
+        assert_eq!(migrated.receipts_root, Some(Block::calculate_receipts_root(&migrated.receipts)));
+        assert_eq!(migrated.hash, migrated.calculate_hash());
+        assert_ne!(migrated.hash, next.hash);
+        assert_eq!(fs::read_to_string(root.join(FORMAT_FILE))?, CURRENT_FORMAT.to_string());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_torn_write_recovery() -> Result<()> {
+        let dir = temp_data_dir();
+        let genesis = test_block(0, "0".to_string());
+        let next = test_block(1, genesis.hash.clone());
+
+        {
+            let mut store = ChainStore::open(&dir)?;
+            store.load_chain()?;
+            store.append_block(&genesis)?;
+            store.append_block(&next)?;
+        }
+
+        // Simulate a crash halfway through writing the second record
+        let log_path = dir.join(CHAIN_DIR).join(BLOCK_LOG_FILE);
+        let full_len = fs::metadata(&log_path)?.len();
+        OpenOptions::new().write(true).open(&log_path)?.set_len(full_len - 10)?;
+
+        let mut store = ChainStore::open(&dir)?;
+        let blocks = store.load_chain()?;
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].hash, genesis.hash);
+
+        // The torn tail is gone, so the block can be written again
+        store.append_block(&next)?;
+        let mut store = ChainStore::open(&dir)?;
+        assert_eq!(store.load_chain()?.len(), 2);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_undecodable_record_is_an_error() -> Result<()> {
+        let dir = temp_data_dir();
+        let genesis = test_block(0, "0".to_string());
+        let next = test_block(1, genesis.hash.clone());
+
+        {
+            let mut store = ChainStore::open(&dir)?;
+            store.load_chain()?;
+            store.append_block(&genesis)?;
+            store.append_block(&next)?;
+        }
+
+        // An intact record with a valid checksum that is not a block
+        let log_path = dir.join(CHAIN_DIR).join(BLOCK_LOG_FILE);
+        let record = ChainStore::frame_record(b"not a block").unwrap();
+        OpenOptions::new().append(true).open(&log_path)?.write_all(&record)?;
+        let len = fs::metadata(&log_path)?.len();
+
+        let mut store = ChainStore::open(&dir)?;
+        assert!(store.load_chain().is_err());
+        // Nothing was truncated
+        assert_eq!(fs::metadata(&log_path)?.len(), len);
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_interrupted_pruning_completes() -> Result<()> {
+        let dir = temp_data_dir();
+        let genesis = test_block(0, "0".to_string());
+        let next = test_block(1, genesis.hash.clone());
+        let last = test_block(2, next.hash.clone());
+
+        {
+            let mut store = ChainStore::open(&dir)?;
+            store.load_chain()?;
+            for block in [&genesis, &next, &last] {
+                store.append_block(block)?;
+            }
+        }
+
+        // Simulate a crash after the snapshot was written but before the
+        // log was compacted
+        let snapshot = ChainSnapshot {
+            version: crate::snapshot::SNAPSHOT_FORMAT_VERSION,
+            headers: vec![(&genesis).into(), (&next).into()],
+            tip: next.clone(),
+            accounts: Default::default(),
+            shielded: Default::default(),
+            params: Default::default(),
+            private_balances: Default::default(),
+        };
+        ChainStore::write_atomic(&dir.join(CHAIN_DIR), SNAPSHOT_FILE, &snapshot.encode()?)?;
+
+        let mut store = ChainStore::open(&dir)?;
+        let blocks = store.load_chain()?;
+        assert_eq!(blocks.iter().map(|block| block.index).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!((store.base(), store.len()), (1, 2));
+        store.append_block(&test_block(3, last.hash.clone()))?;
+
+        let mut store = ChainStore::open(&dir)?;
+        assert_eq!(store.load_chain()?.len(), 3);
+        assert!(store.prune_to(&snapshot).is_ok());
+        let pruned = ChainSnapshot { tip: genesis.clone(), headers: vec![(&genesis).into()], ..snapshot };
+        assert!(store.prune_to(&pruned).is_err());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_index_roundtrip() -> Result<()> {
+        let dir = temp_data_dir();
+        let store = ChainStore::open(&dir)?;
+        assert!(store.load_state()?.is_none());
+
+        let mut state = AccountStateIndex {
+            height: 3,
+            tip_hash: "abc".to_string(),
+            ..Default::default()
+        };
+        state.balances.insert("alice".to_string(), Amount::from_zhtp(42));
+        state.nonces.insert("alice".to_string(), 2);
+        store.save_state(&state)?;
+
+        let loaded = store.load_state()?.expect("state should be persisted");
+        assert_eq!(loaded.height, 3);
+        assert_eq!(loaded.balances.get("alice"), Some(&Amount::from_zhtp(42)));
+        assert_eq!(loaded.nonces.get("alice"), Some(&2));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -1,8 +1,10 @@
 pub mod dht;
 pub mod content;
+pub mod chain_store;
 
 pub use dht::{DhtNode, DhtNetwork};
 pub use content::{ContentAddressing, ContentId, ContentMetadata};
+pub use chain_store::{ChainStore, AccountStateIndex};
 
 use crate::zhtp::{dns::ZhtpDNS, crypto::Keypair};
 use std::collections::HashMap;
@@ -0,0 +1,350 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of base units in one ZHTP token (8 decimal places)
+pub const BASE_UNITS_PER_ZHTP: u64 = 100_000_000;
+
+/// ZHTP token amount stored as an integer number of base units (10^-8 ZHTP).
+///
+/// Arithmetic is explicit: `checked_*` for consensus-critical state where
+/// overflow or underflow must be rejected, `saturating_*` for counters and
+/// statistics.
+///
+/// Serialized as a plain `u64` of base units. Human-readable formats (JSON,
+/// TOML) also accept a decimal string of ZHTP tokens such as `"1.5"`, parsed
+/// exactly like [`Amount::from_str`]. Floating point numbers are rejected
+/// rather than guessed at: legacy payloads that stored `f64` ZHTP tokens are
+/// converted explicitly, with [`legacy_json`] on fields of JSON files that
+/// predate this type, [`Amount::from_zhtp_f64`] or, for binary formats
+/// (bincode) that decode the raw `f64` bits in place of the base units,
+/// [`Amount::from_legacy_bits`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u64);
+
+impl Amount {
+    /// Zero tokens
+    pub const ZERO: Amount = Amount(0);
+    /// Largest representable amount
+    pub const MAX: Amount = Amount(u64::MAX);
+
+    /// Create an amount from base units
+    pub const fn from_base_units(units: u64) -> Self {
+        Amount(units)
+    }
+
+    /// Create an amount from whole ZHTP tokens (saturating at `Amount::MAX`)
+    pub const fn from_zhtp(tokens: u64) -> Self {
+        Amount(tokens.saturating_mul(BASE_UNITS_PER_ZHTP))
+    }
+
+    /// Convert a floating point number of ZHTP tokens, rounding to the nearest
+    /// base unit. Returns `None` for negative, non-finite or out-of-range values.
+    pub fn from_zhtp_f64(tokens: f64) -> Option<Self> {
+        if !tokens.is_finite() || tokens < 0.0 {
+            return None;
+        }
+        let units = (tokens * BASE_UNITS_PER_ZHTP as f64).round();
+        if units >= u64::MAX as f64 {
+            return None;
+        }
+        Some(Amount(units as u64))
+    }
+
+    /// Reinterpret an amount decoded from a legacy binary payload, where the
+    /// field held an `f64` number of ZHTP tokens, as a proper amount.
+    /// Negative or invalid legacy values become zero.
+    pub fn from_legacy_bits(raw: Amount) -> Self {
+        Self::from_zhtp_f64(f64::from_bits(raw.0)).unwrap_or(Amount::ZERO)
+    }
+
+    /// Number of base units
+    pub const fn base_units(self) -> u64 {
+        self.0
+    }
+
+    /// Approximate value in ZHTP tokens, for display and reward heuristics only
+    pub fn to_zhtp_f64(self) -> f64 {
+        self.0 as f64 / BASE_UNITS_PER_ZHTP as f64
+    }
+
+    /// Whole ZHTP tokens, rounded down
+    pub const fn whole_zhtp(self) -> u64 {
+        self.0 / BASE_UNITS_PER_ZHTP
+    }
+
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, other: Amount) -> Option<Amount> {
+        self.0.checked_add(other.0).map(Amount)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Option<Amount> {
+        self.0.checked_sub(other.0).map(Amount)
+    }
+
+    pub fn checked_mul(self, factor: u64) -> Option<Amount> {
+        self.0.checked_mul(factor).map(Amount)
+    }
+
+    pub fn checked_div(self, divisor: u64) -> Option<Amount> {
+        self.0.checked_div(divisor).map(Amount)
+    }
+
+    pub fn saturating_add(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_add(other.0))
+    }
+
+    pub fn saturating_sub(self, other: Amount) -> Amount {
+        Amount(self.0.saturating_sub(other.0))
+    }
+
+    pub fn saturating_mul(self, factor: u64) -> Amount {
+        Amount(self.0.saturating_mul(factor))
+    }
+
+    /// Exact `self * numerator / denominator` rounded down, e.g. for percentage
+    /// splits. Returns `None` on a zero denominator or overflow of the result.
+    pub fn mul_div(self, numerator: u64, denominator: u64) -> Option<Amount> {
+        if denominator == 0 {
+            return None;
+        }
+        let result = self.0 as u128 * numerator as u128 / denominator as u128;
+        u64::try_from(result).ok().map(Amount)
+    }
+
+    /// Scale by a floating point multiplier, rounding down and saturating.
+    /// Intended for reward formulas whose inputs are already `f64` scores;
+    /// non-finite or negative multipliers yield zero.
+    pub fn mul_f64(self, factor: f64) -> Amount {
+        if !factor.is_finite() || factor <= 0.0 {
+            return Amount::ZERO;
+        }
+        Amount((self.0 as f64 * factor) as u64)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.0 / BASE_UNITS_PER_ZHTP;
+        let fraction = self.0 % BASE_UNITS_PER_ZHTP;
+        if fraction == 0 {
+            write!(f, "{}", whole)
+        } else {
+            let digits = format!("{:08}", fraction);
+            write!(f, "{}.{}", whole, digits.trim_end_matches('0'))
+        }
+    }
+}
+
+impl FromStr for Amount {
+    type Err = anyhow::Error;
+
+    /// Parse a decimal ZHTP amount such as `"12"` or `"0.00000001"` exactly
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(anyhow::anyhow!("Empty amount"));
+        }
+        if fraction.len() > 8 || !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(anyhow::anyhow!("Invalid ZHTP amount: {}", s));
+        }
+
+        let whole: u64 = if whole.is_empty() { 0 } else { whole.parse()? };
+        let fraction: u64 = if fraction.is_empty() {
+            0
+        } else {
+            format!("{:0<8}", fraction).parse()?
+        };
+
+        whole
+            .checked_mul(BASE_UNITS_PER_ZHTP)
+            .and_then(|units| units.checked_add(fraction))
+            .map(Amount)
+            .ok_or_else(|| anyhow::anyhow!("ZHTP amount out of range: {}", s))
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(AmountVisitor)
+        } else {
+            deserializer.deserialize_u64(AmountVisitor)
+        }
+    }
+}
+
+struct AmountVisitor;
+
+impl<'de> de::Visitor<'de> for AmountVisitor {
+    type Value = Amount;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an integer number of base units or a decimal ZHTP string")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Amount, E> {
+        Ok(Amount(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Amount, E> {
+        u64::try_from(v)
+            .map(Amount)
+            .map_err(|_| E::custom("negative ZHTP amount"))
+    }
+
+    /// A float could be legacy ZHTP tokens or a mistyped base unit count
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Amount, E> {
+        Err(E::custom(format!(
+            "floating point amount {} is ambiguous; use integer base units or a decimal ZHTP string",
+            v
+        )))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Amount, E> {
+        v.parse().map_err(|e| E::custom(format!("{}", e)))
+    }
+}
+
+/// Serde adapter for JSON fields written when amounts were `f64` ZHTP
+/// tokens, used as `#[serde(with = "crate::zhtp::amount::legacy_json")]`.
+///
+/// Any number, integer or float, is read as ZHTP tokens and rounded to the
+/// nearest base unit; decimal strings are read like [`Amount::from_str`].
+/// Amounts are written back as decimal strings, which both this adapter and
+/// the plain [`Amount`] deserializer read as ZHTP tokens, so a migrated file
+/// means the same thing to either.
+pub mod legacy_json {
+    use super::{Amount, BASE_UNITS_PER_ZHTP};
+    use serde::{de, Deserializer, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(amount)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        deserializer.deserialize_any(LegacyVisitor)
+    }
+
+    struct LegacyVisitor;
+
+    impl<'de> de::Visitor<'de> for LegacyVisitor {
+        type Value = Amount;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a number or decimal string of ZHTP tokens")
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Amount, E> {
+            v.checked_mul(BASE_UNITS_PER_ZHTP)
+                .map(Amount)
+                .ok_or_else(|| E::custom("ZHTP amount out of range"))
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Amount, E> {
+            let v = u64::try_from(v).map_err(|_| E::custom("negative ZHTP amount"))?;
+            self.visit_u64(v)
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Amount, E> {
+            Amount::from_zhtp_f64(v).ok_or_else(|| E::custom(format!("invalid ZHTP amount {}", v)))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Amount, E> {
+            v.parse().map_err(|e| E::custom(format!("{}", e)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let a = Amount::from_zhtp(5);
+        let b = Amount::from_base_units(1);
+        assert_eq!(a.checked_add(b).unwrap().base_units(), 500_000_001);
+        assert_eq!(b.checked_sub(a), None);
+        assert_eq!(Amount::MAX.checked_add(b), None);
+        assert_eq!(a.mul_div(40, 100), Some(Amount::from_zhtp(2)));
+        assert_eq!(a.mul_div(1, 0), None);
+    }
+
+    #[test]
+    fn test_display_and_parse() {
+        let amount: Amount = "12.5".parse().unwrap();
+        assert_eq!(amount.base_units(), 1_250_000_000);
+        assert_eq!(amount.to_string(), "12.5");
+        assert_eq!("0.00000001".parse::<Amount>().unwrap().base_units(), 1);
+        assert!("0.000000001".parse::<Amount>().is_err());
+        assert!("-1".parse::<Amount>().is_err());
+        assert_eq!(Amount::from_zhtp(3).to_string(), "3");
+    }
+
+    #[test]
+    fn test_json_forms() {
+        // Integers are base units, strings are decimal ZHTP tokens
+        let units: Amount = serde_json::from_str("150000000").unwrap();
+        let tokens: Amount = serde_json::from_str("\"1.5\"").unwrap();
+        assert_eq!(units, tokens);
+        assert_eq!(serde_json::to_string(&tokens).unwrap(), "150000000");
+        assert!(serde_json::from_str::<Amount>("\"-1\"").is_err());
+
+        // Floats are neither, so they are refused instead of guessed at
+        assert!(serde_json::from_str::<Amount>("1.5").is_err());
+        assert!(serde_json::from_str::<Amount>("150000000.0").is_err());
+        assert_eq!(Amount::from_zhtp_f64(1.5), Some(units));
+    }
+
+    #[test]
+    fn test_legacy_bincode_migration() {
+        // A legacy f64 field decodes as raw bits and is reinterpreted
+        let legacy_bytes = bincode::serialize(&2.25f64).unwrap();
+        let raw: Amount = bincode::deserialize(&legacy_bytes).unwrap();
+        assert_eq!(Amount::from_legacy_bits(raw), "2.25".parse().unwrap());
+
+        let current = Amount::from_base_units(42);
+        let bytes = bincode::serialize(&current).unwrap();
+        assert_eq!(bincode::deserialize::<Amount>(&bytes).unwrap(), current);
+    }
+
+    #[test]
+    fn test_legacy_json_migration() -> anyhow::Result<()> {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Transfer {
+            to: String,
+            #[serde(with = "legacy_json")]
+            amount: Amount,
+            #[serde(with = "legacy_json")]
+            fee: Amount,
+        }
+
+        // A file written when amounts were f64 ZHTP tokens
+        let path = std::env::temp_dir().join(format!("zhtp_legacy_amounts_{}.json", std::process::id()));
+        std::fs::write(&path, r#"{"to": "zhtp_bob", "amount": 12.5, "fee": 1}"#)?;
+        let transfer: Transfer = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(transfer.amount, "12.5".parse()?);
+        assert_eq!(transfer.fee, Amount::from_zhtp(1));
+        assert!(serde_json::from_str::<Transfer>(r#"{"to": "", "amount": -1.0, "fee": 0}"#).is_err());
+
+        // Rewritten, it reads the same with or without the adapter
+        let json = serde_json::to_value(&transfer)?;
+        assert_eq!(json["amount"], "12.5");
+        assert_eq!(serde_json::from_value::<Transfer>(json.clone())?.amount, transfer.amount);
+        assert_eq!(serde_json::from_value::<Amount>(json["fee"].clone())?, transfer.fee);
+        Ok(())
+    }
+}
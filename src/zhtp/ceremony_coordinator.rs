@@ -2,9 +2,11 @@ use crate::zhtp::{
     ceremony_participants::{CeremonyParticipantManager, ParticipantType},
     consensus_engine::{ZhtpConsensusEngine, ZkValidator},
     p2p_network::ZhtpP2PNetwork,
+    zk_proofs::KzgTrustedSetup,
 };
 use anyhow::{Result, anyhow};
 use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
 use std::{
     collections::HashMap,
     sync::Arc,
@@ -15,6 +17,9 @@ use std::{
 };
 use tokio::sync::RwLock;
 
+/// Powers-of-tau transcript phase 1 of the ceremony leaves behind
+const PHASE1_PTAU_FILE: &str = "circuits/setup/output/phase1_final.ptau";
+
 /// ZHTP Trusted Setup Ceremony Coordinator
 /// Orchestrates the entire multi-party trusted setup ceremony
 pub struct ZhtpCeremonyCoordinator {
@@ -102,8 +107,6 @@ pub struct TrustedSetupResult {
     pub verification_keys: HashMap<String, String>,
     /// Ceremony attestation
     pub attestation: CeremonyAttestation,
-    /// Generated tau parameter (for replacement in code)
-    pub tau_parameter: String,
 }
 
 /// Ceremony attestation document
@@ -228,7 +231,7 @@ impl ZhtpCeremonyCoordinator {
         state.phase1_progress.current_round = state.phase1_progress.total_rounds;
         
         // Get the final PTAU hash
-        if let Ok(ptau_hash) = self.get_file_hash("circuits/setup/output/phase1_final.ptau").await {
+        if let Ok(ptau_hash) = self.get_file_hash(PHASE1_PTAU_FILE).await {
             state.phase1_progress.current_ptau_hash = Some(ptau_hash);
         }
 
@@ -283,9 +286,6 @@ impl ZhtpCeremonyCoordinator {
             verification_hashes: HashMap::new(), // Would be populated with actual hashes
         };
 
-        // Extract tau parameter from the ceremony
-        let tau_parameter = self.extract_tau_parameter().await?;
-
         // Create verification keys map
         let mut verification_keys = HashMap::new();
         for circuit in &state.phase2_progress.circuits {
@@ -299,7 +299,6 @@ impl ZhtpCeremonyCoordinator {
                 .unwrap_or_else(|| "unknown".to_string()),
             verification_keys,
             attestation,
-            tau_parameter,
         };
 
         state.final_trusted_setup = Some(result.clone());
@@ -308,61 +307,36 @@ impl ZhtpCeremonyCoordinator {
         Ok(result)
     }
 
-    /// Update ZHTP code with new trusted setup parameters
-    pub async fn update_trusted_setup_in_code(&self, result: &TrustedSetupResult) -> Result<()> {
-        println!("🔄 Updating ZHTP code with new trusted setup parameters...");
-
-        // Read current zk_proofs.rs file
-        let zk_proofs_path = "src/zhtp/zk_proofs.rs";
-        let content = fs::read_to_string(zk_proofs_path)?;
-
-        // Replace the deterministic tau generation with ceremony result
-        let new_tau_function = format!(
-            r#"    /// PRODUCTION TAU: Generated from multi-party ceremony
-    /// Ceremony completed: {}
-    /// Participants: {}
-    /// SECURITY: This tau parameter came from a secure multi-party ceremony
-    pub fn generate_tau() -> Fr {{
-        // Load tau from ceremony result
-        let ceremony_tau_hex = "{}";
-        let tau_bytes = hex::decode(ceremony_tau_hex)
-            .expect("Invalid ceremony tau hex");
-        Fr::from_le_bytes_mod_order(&tau_bytes)
-    }}"#,
-            chrono::DateTime::from_timestamp(result.attestation.completed_at as i64, 0)
-                .unwrap_or_default()
-                .format("%Y-%m-%d %H:%M:%S UTC"),
-            result.attestation.total_participants,
-            result.tau_parameter
-        );
-
-        // Find and replace the old generate_tau function
-        let updated_content = if let Some(start) = content.find("pub fn generate_tau() -> Fr {") {
-            if let Some(end) = content[start..].find("\n    }") {
-                let end_pos = start + end + "\n    }".len();
-                format!("{}{}{}", 
-                    &content[..start], 
-                    new_tau_function, 
-                    &content[end_pos..]
-                )
-            } else {
-                return Err(anyhow!("Could not find end of generate_tau function"));
-            }
-        } else {
-            return Err(anyhow!("Could not find generate_tau function"));
-        };
+    /// Write the ceremony's structured reference string to `srs_path` and
+    /// return its hash. The powers are copied from the phase 1 transcript
+    /// whose hash the result attests, so τ is never reconstructed. Nodes load
+    /// the file through the `trusted_setup` section of their config, which
+    /// pins this hash.
+    pub async fn write_trusted_setup_file(&self, result: &TrustedSetupResult, srs_path: &str) -> Result<String> {
+        println!("🔄 Writing trusted setup SRS file {}...", srs_path);
+
+        let transcript = fs::read(PHASE1_PTAU_FILE)
+            .map_err(|e| anyhow!("Failed to read ceremony transcript {}: {}", PHASE1_PTAU_FILE, e))?;
+        if hex::encode(Sha256::digest(&transcript)) != result.ptau_hash {
+            return Err(anyhow!("Ceremony transcript {} does not match the attested hash", PHASE1_PTAU_FILE));
+        }
+        let setup = KzgTrustedSetup::from_ptau(&transcript)?;
 
-        // Write updated content
-        fs::write(zk_proofs_path, updated_content)?;
+        let bytes = setup.to_srs_bytes();
+        if let Some(parent) = Path::new(srs_path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(srs_path, &bytes)?;
+        let srs_hash = KzgTrustedSetup::srs_hash(&bytes);
 
         // Update documentation
-        self.update_ceremony_documentation(result).await?;
+        self.update_ceremony_documentation(result, srs_path, &srs_hash).await?;
 
-        println!("✅ ZHTP code updated with production trusted setup");
-        println!("🔐 Tau parameter: {}", &result.tau_parameter[..16]);
+        println!("✅ Trusted setup written to {}", srs_path);
+        println!("🔐 SRS hash: {}", srs_hash);
         println!("📊 Ceremony participants: {}", result.attestation.total_participants);
 
-        Ok(())
+        Ok(srs_hash)
     }
 
     // Private helper methods
@@ -419,15 +393,6 @@ impl ZhtpCeremonyCoordinator {
         }
     }
 
-    async fn extract_tau_parameter(&self) -> Result<String> {
-        // In a real implementation, this would extract the actual tau from the ceremony
-        // For now, generate a secure random tau as a placeholder
-        use rand::RngCore;
-        let mut tau_bytes = [0u8; 32];
-        rand::thread_rng().fill_bytes(&mut tau_bytes);
-        Ok(hex::encode(tau_bytes))
-    }
-
     async fn get_verification_key(&self, circuit_name: &str) -> Result<String> {
         let vkey_path = format!("circuits/keys/{}_verification_key.json", circuit_name);
         if Path::new(&vkey_path).exists() {
@@ -437,14 +402,15 @@ impl ZhtpCeremonyCoordinator {
         }
     }
 
-    async fn update_ceremony_documentation(&self, result: &TrustedSetupResult) -> Result<()> {
+    async fn update_ceremony_documentation(&self, result: &TrustedSetupResult, srs_path: &str, srs_hash: &str) -> Result<()> {
         let doc_content = format!(
             r#"# ZHTP Production Trusted Setup Ceremony Results
 
 ## Ceremony Completion
 - **Completed**: {}
 - **Total Participants**: {}
-- **Tau Parameter**: `{}`
+- **SRS File**: `{}`
+- **SRS Hash (SHA-256)**: `{}`
 
 ## Participant Breakdown
 {}
@@ -457,8 +423,17 @@ impl ZhtpCeremonyCoordinator {
 - **All Circuits Verified**: ✅
 
 ## Usage
-This ceremony result has been integrated into the ZHTP network code.
-The tau parameter is now used for all zero-knowledge proofs in production.
+Distribute the SRS file to every node and pin its hash in the node config:
+
+```json
+"trusted_setup": {{
+  "srs_file": "{}",
+  "srs_hash": "{}"
+}}
+```
+
+Nodes refuse to start if the file's hash differs from the pinned one or if
+its powers of tau fail the pairing consistency checks.
 
 ⚠️ **IMPORTANT**: This trusted setup is only secure if at least one participant
 properly destroyed their secret contribution. The ceremony was designed to
@@ -468,7 +443,8 @@ ensure this property through multi-party computation.
                 .unwrap_or_default()
                 .format("%Y-%m-%d %H:%M:%S UTC"),
             result.attestation.total_participants,
-            result.tau_parameter,
+            srs_path,
+            srs_hash,
             result.attestation.participant_breakdown.iter()
                 .map(|(ptype, count)| format!("- **{:?}**: {}", ptype, count))
                 .collect::<Vec<_>>()
@@ -477,7 +453,9 @@ ensure this property through multi-party computation.
                 .map(|prop| format!("- {}", prop))
                 .collect::<Vec<_>>()
                 .join("\n"),
-            result.ptau_hash
+            result.ptau_hash,
+            srs_path,
+            srs_hash
         );
 
         fs::write("docs/ceremony-results.md", doc_content)?;
@@ -1,4 +1,5 @@
 use crate::zhtp::{
+    amount::Amount,
     consensus_engine::{ZkValidator, ValidatorStatus},
     crypto::Keypair,
     p2p_network::ZhtpP2PNetwork,
@@ -498,7 +499,7 @@ impl CeremonyParticipantManager {
             ParticipantType::CoreValidator => {
                 if let Some(validator_info) = &identity.validator_info {
                     // Higher stake = higher trust
-                    (validator_info.stake / 1000000.0).min(0.5)
+                    (validator_info.stake.to_zhtp_f64() / 1000000.0).min(0.5)
                 } else {
                     0.0
                 }
@@ -520,8 +521,10 @@ impl CeremonyParticipantManager {
     async fn verify_validator_credentials(&self, identity: &ParticipantIdentity) -> Result<bool> {
         // Verify validator is actually registered and has sufficient stake
         if let Some(validator_info) = &identity.validator_info {
-            // Check if validator is active and has minimum stake
-            Ok(validator_info.status == ValidatorStatus::Active && validator_info.stake >= 100000.0)
+            // Check if validator is active and proves its minimum stake
+            Ok(validator_info.status == ValidatorStatus::Active
+                && validator_info.stake >= Amount::from_zhtp(100_000)
+                && validator_info.verify_stake_proof())
         } else {
             Ok(false)
         }
@@ -0,0 +1,686 @@
+//! Native Groth16 support for the circom circuits in `circuits/compiled`.
+//!
+//! The `.r1cs` files are parsed directly and witnesses are computed without
+//! Node.js by solving the constraints from the given inputs. Signals that
+//! circom computes with `<--` hints are recovered from the constraints that
+//! bound them: bits of a `Num2Bits` decomposition are read off the value
+//! they must sum to, and a free `IsZero` inverse is zero.
+//!
+//! Public signals are the circuit outputs followed by the inputs listed in
+//! the circuit's [`CircuitSpec`] that the compiler kept.
+
+use anyhow::{anyhow, Result};
+use ark_bn254::{Bn254, Fr};
+use ark_ff::{BigInteger, Field, One, PrimeField, Zero};
+use crate::zhtp::proof_envelope::{ProofEnvelope, ProofStatement, ProofSystem, ProofVerifier};
+use ark_groth16::{prepare_verifying_key, Groth16, PreparedVerifyingKey, Proof, ProvingKey, VerifyingKey};
+use ark_relations::r1cs::{
+    ConstraintSynthesizer, ConstraintSystemRef, LinearCombination, SynthesisError, Variable,
+};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+/// A compiled circuit shipped with the node
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitSpec {
+    /// Circuit id used in proofs, the stem of its `.r1cs` file
+    pub id: &'static str,
+    /// Path of the `.r1cs` and `.sym` files below the compiled directory,
+    /// without extension
+    pub path: &'static str,
+    /// Inputs the verifier sees, in order
+    pub public_inputs: &'static [&'static str],
+}
+
+/// Circuits compiled into `circuits/compiled`
+pub const CIRCUITS: &[CircuitSpec] = &[
+    CircuitSpec {
+        id: "stake_proof",
+        path: "consensus/stake_proof",
+        public_inputs: &["minimum_stake", "validator_commitment", "block_hash", "network_id"],
+    },
+    CircuitSpec {
+        id: "anonymous_voting",
+        path: "dao/anonymous_voting",
+        public_inputs: &["proposal_id", "vote_commitment", "eligibility_root", "nullifier_hash"],
+    },
+    CircuitSpec {
+        id: "ownership_proof",
+        path: "dns/ownership_proof",
+        public_inputs: &["domain_hash", "owner_commitment", "registration_time", "network_id"],
+    },
+    CircuitSpec {
+        id: "routing_proof",
+        path: "routing/routing_proof",
+        public_inputs: &["source_commitment", "destination_hash", "network_id", "bandwidth_limit"],
+    },
+    CircuitSpec {
+        id: "integrity_proof",
+        path: "storage/integrity_proof",
+        public_inputs: &["file_hash", "storage_commitment", "network_id"],
+    },
+    CircuitSpec {
+        id: "private_transfer",
+        path: "transactions/private_transfer",
+        public_inputs: &["merkle_root", "nullifier_hash", "commitment_new", "network_fee", "quantum_proof_id"],
+    },
+];
+
+/// Spec of a compiled circuit by id
+pub fn circuit_spec(id: &str) -> Option<&'static CircuitSpec> {
+    CIRCUITS.iter().find(|spec| spec.id == id)
+}
+
+/// Terms of a linear combination as (wire, coefficient); wire 0 is the constant one
+pub type Terms = Vec<(usize, Fr)>;
+
+/// One `A * B = C` constraint
+#[derive(Debug, Clone)]
+pub struct Constraint {
+    pub a: Terms,
+    pub b: Terms,
+    pub c: Terms,
+}
+
+/// Contents of a circom `.r1cs` file
+#[derive(Debug, Clone)]
+pub struct R1cs {
+    pub n_wires: usize,
+    pub n_pub_out: usize,
+    pub n_pub_in: usize,
+    pub constraints: Vec<Constraint>,
+}
+
+const R1CS_MAGIC: &[u8; 4] = b"r1cs";
+const SECTION_HEADER: u32 = 1;
+const SECTION_CONSTRAINTS: u32 = 2;
+
+/// Little-endian reader over a byte slice
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("R1CS file is truncated"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+    }
+}
+
+impl R1cs {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Parse version 1 of the circom R1CS format over BN254
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != R1CS_MAGIC {
+            return Err(anyhow!("Not an R1CS file"));
+        }
+        let version = reader.u32()?;
+        if version != 1 {
+            return Err(anyhow!("Unsupported R1CS version {}", version));
+        }
+
+        // The header gives the field size, so collect sections before parsing
+        let mut sections = HashMap::new();
+        for _ in 0..reader.u32()? {
+            let section_type = reader.u32()?;
+            let len = usize::try_from(reader.u64()?)?;
+            sections.insert(section_type, reader.take(len)?);
+        }
+
+        let header = sections.get(&SECTION_HEADER).ok_or_else(|| anyhow!("R1CS file has no header"))?;
+        let mut header = Reader { bytes: header, pos: 0 };
+        let field_size = header.u32()? as usize;
+        let prime = header.take(field_size)?;
+        if prime != Fr::MODULUS.to_bytes_le().as_slice() {
+            return Err(anyhow!("R1CS file is not over the BN254 scalar field"));
+        }
+        let n_wires = header.u32()? as usize;
+        let n_pub_out = header.u32()? as usize;
+        let n_pub_in = header.u32()? as usize;
+        let _n_prv_in = header.u32()?;
+        let _n_labels = header.u64()?;
+        let n_constraints = header.u32()? as usize;
+
+        let body = sections.get(&SECTION_CONSTRAINTS).ok_or_else(|| anyhow!("R1CS file has no constraints"))?;
+        let mut body = Reader { bytes: body, pos: 0 };
+        let read_terms = |body: &mut Reader| -> Result<Terms> {
+            let n_terms = body.u32()?;
+            (0..n_terms)
+                .map(|_| {
+                    let wire = body.u32()? as usize;
+                    if wire >= n_wires {
+                        return Err(anyhow!("R1CS constraint uses unknown wire {}", wire));
+                    }
+                    Ok((wire, Fr::from_le_bytes_mod_order(body.take(field_size)?)))
+                })
+                .collect()
+        };
+        let constraints = (0..n_constraints)
+            .map(|_| {
+                Ok(Constraint {
+                    a: read_terms(&mut body)?,
+                    b: read_terms(&mut body)?,
+                    c: read_terms(&mut body)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { n_wires, n_pub_out, n_pub_in, constraints })
+    }
+}
+
+/// A circom circuit with its signal names
+#[derive(Debug, Clone)]
+pub struct CircomCircuit {
+    pub id: String,
+    pub r1cs: R1cs,
+    /// Signal names without the `main.` prefix; `None` for signals the
+    /// compiler optimized away
+    signals: HashMap<String, Option<usize>>,
+    /// First name of each wire
+    wire_names: Vec<Option<String>>,
+    /// Wires that are public inputs to the proof, in order
+    public_wires: Vec<usize>,
+}
+
+impl CircomCircuit {
+    /// Load a compiled circuit from `compiled_dir`
+    pub fn load<P: AsRef<Path>>(compiled_dir: P, spec: &CircuitSpec) -> Result<Self> {
+        let base = compiled_dir.as_ref().join(spec.path);
+        let r1cs = R1cs::from_file(base.with_extension("r1cs"))?;
+        let sym_path = base.with_extension("sym");
+        let sym = fs::read_to_string(&sym_path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", sym_path.display(), e))?;
+        Self::from_parts(spec.id, r1cs, &sym, spec.public_inputs)
+    }
+
+    /// Build a circuit from its R1CS and the contents of its `.sym` file
+    pub fn from_parts(id: &str, r1cs: R1cs, sym: &str, public_inputs: &[&str]) -> Result<Self> {
+        let mut signals = HashMap::new();
+        let mut wire_names = vec![None; r1cs.n_wires];
+        for line in sym.lines().filter(|line| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.splitn(4, ',').collect();
+            if fields.len() != 4 {
+                return Err(anyhow!("Malformed symbol line: {}", line));
+            }
+            let name = fields[3].trim();
+            let name = name.strip_prefix("main.").unwrap_or(name).to_string();
+            let wire = match fields[1].trim().parse::<i64>()? {
+                -1 => None,
+                wire => {
+                    let wire = usize::try_from(wire)?;
+                    if wire >= r1cs.n_wires {
+                        return Err(anyhow!("Symbol {} names unknown wire {}", name, wire));
+                    }
+                    Some(wire)
+                }
+            };
+            if let Some(wire) = wire {
+                wire_names[wire].get_or_insert_with(|| name.clone());
+            }
+            signals.insert(name, wire);
+        }
+
+        let mut public_wires: Vec<usize> = (1..=r1cs.n_pub_out + r1cs.n_pub_in).collect();
+        for input in public_inputs {
+            match signals.get(*input) {
+                Some(Some(wire)) if !public_wires.contains(wire) => public_wires.push(*wire),
+                Some(_) => {}
+                None => return Err(anyhow!("Circuit {} has no signal {}", id, input)),
+            }
+        }
+
+        Ok(Self { id: id.to_string(), r1cs, signals, wire_names, public_wires })
+    }
+
+    /// Names of the public signals, in the order proofs commit to them
+    pub fn public_signal_names(&self) -> Vec<&str> {
+        self.public_wires.iter().map(|wire| self.wire_name(*wire)).collect()
+    }
+
+    /// Values of the public signals in a witness
+    pub fn public_signals(&self, witness: &[Fr]) -> Vec<Fr> {
+        self.public_wires.iter().map(|wire| witness[*wire]).collect()
+    }
+
+    fn wire_name(&self, wire: usize) -> &str {
+        self.wire_names[wire].as_deref().unwrap_or("?")
+    }
+
+    /// Compute the full witness from the circuit's input signals, named as
+    /// in the circom source (`routing_path[2]` for array elements)
+    pub fn calculate_witness(&self, inputs: &[(&str, Fr)]) -> Result<Vec<Fr>> {
+        let mut values = vec![None; self.r1cs.n_wires];
+        values[0] = Some(Fr::one());
+        for (name, value) in inputs {
+            let name = name.strip_prefix("main.").unwrap_or(name);
+            match self.signals.get(name) {
+                Some(Some(wire)) => match values[*wire] {
+                    Some(existing) if existing != *value => {
+                        return Err(anyhow!("Conflicting values for signal {}", name));
+                    }
+                    _ => values[*wire] = Some(*value),
+                },
+                // Unused by any constraint
+                Some(None) => {}
+                None => return Err(anyhow!("Circuit {} has no signal {}", self.id, name)),
+            }
+        }
+
+        let booleans = self.boolean_wires();
+        loop {
+            let mut progress = false;
+            for constraint in &self.r1cs.constraints {
+                progress |= solve(constraint, &mut values);
+            }
+            if progress {
+                continue;
+            }
+            if self.r1cs.constraints.iter().any(|constraint| decompose_bits(constraint, &booleans, &mut values)) {
+                continue;
+            }
+
+            // Signals of sub-components left free by every constraint, such
+            // as the inverse in `IsZero` of zero, take the value zero
+            let unknown: Vec<usize> = (0..values.len()).filter(|wire| values[*wire].is_none()).collect();
+            if unknown.is_empty() {
+                break;
+            }
+            match unknown.iter().find(|wire| self.wire_name(**wire).contains('.')) {
+                Some(wire) => values[*wire] = Some(Fr::zero()),
+                None => {
+                    let missing: Vec<&str> = unknown.iter().map(|wire| self.wire_name(*wire)).collect();
+                    return Err(anyhow!("Missing inputs for circuit {}: {}", self.id, missing.join(", ")));
+                }
+            }
+        }
+
+        let witness: Vec<Fr> = values.into_iter().map(|value| value.unwrap_or_default()).collect();
+        if let Some(index) = self.r1cs.constraints.iter().position(|constraint| !satisfied(constraint, &witness)) {
+            return Err(anyhow!("Inputs do not satisfy constraint {} of circuit {}", index, self.id));
+        }
+        Ok(witness)
+    }
+
+    /// Wires constrained by `x * (x - 1) = 0` or an equivalent form
+    fn boolean_wires(&self) -> HashSet<usize> {
+        self.r1cs
+            .constraints
+            .iter()
+            .filter(|constraint| constraint.c.is_empty())
+            .filter_map(|constraint| {
+                let (wire_a, root_a) = single_root(&constraint.a)?;
+                let (wire_b, root_b) = single_root(&constraint.b)?;
+                let roots = [root_a, root_b];
+                (wire_a == wire_b && roots.contains(&Fr::zero()) && roots.contains(&Fr::one())).then_some(wire_a)
+            })
+            .collect()
+    }
+
+    /// Constraint system instance for proving, or for key generation when
+    /// `witness` is `None`
+    pub fn instance(&self, witness: Option<Vec<Fr>>) -> CircomInstance<'_> {
+        CircomInstance { circuit: self, witness }
+    }
+}
+
+/// The wire and root of a linear combination `k * (x - root)`
+fn single_root(terms: &Terms) -> Option<(usize, Fr)> {
+    let mut wire = None;
+    let mut constant = Fr::zero();
+    for (w, coeff) in terms {
+        match (*w, wire) {
+            (0, _) => constant += coeff,
+            (w, None) => wire = Some((w, *coeff)),
+            _ => return None,
+        }
+    }
+    let (wire, coeff) = wire?;
+    Some((wire, -constant / coeff))
+}
+
+/// Known part of a linear combination and its unknown terms
+fn evaluate(terms: &Terms, values: &[Option<Fr>]) -> (Fr, Vec<(usize, Fr)>) {
+    let mut known = Fr::zero();
+    let mut unknown = Vec::new();
+    for (wire, coeff) in terms {
+        match values[*wire] {
+            Some(value) => known += *coeff * value,
+            None if coeff.is_zero() => {}
+            None => unknown.push((*wire, *coeff)),
+        }
+    }
+    (known, unknown)
+}
+
+/// Value of `A * B` if it no longer depends on unknown wires
+fn known_product(a: &(Fr, Vec<(usize, Fr)>), b: &(Fr, Vec<(usize, Fr)>)) -> Option<Fr> {
+    match (a.1.is_empty(), b.1.is_empty()) {
+        (true, true) => Some(a.0 * b.0),
+        // A known zero factor decides the product on its own
+        (true, false) if a.0.is_zero() => Some(Fr::zero()),
+        (false, true) if b.0.is_zero() => Some(Fr::zero()),
+        _ => None,
+    }
+}
+
+/// Solve a constraint with a single unknown wire it depends on linearly
+fn solve(constraint: &Constraint, values: &mut [Option<Fr>]) -> bool {
+    let a = evaluate(&constraint.a, values);
+    let b = evaluate(&constraint.b, values);
+    let c = evaluate(&constraint.c, values);
+    let solved = match (known_product(&a, &b), c.1.as_slice()) {
+        (Some(product), [(wire, coeff)]) => Some((*wire, (product - c.0) / coeff)),
+        (None, []) => {
+            // One side is a known non-zero factor, the other has one unknown
+            let (factor, other) = if a.1.is_empty() { (a.0, &b) } else if b.1.is_empty() { (b.0, &a) } else { return false };
+            match (factor.inverse(), other.1.as_slice()) {
+                (Some(inverse), [(wire, coeff)]) => Some((*wire, (c.0 * inverse - other.0) / coeff)),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    match solved {
+        Some((wire, value)) => {
+            values[wire] = Some(value);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Solve a constraint whose unknowns are boolean wires weighted by distinct
+/// powers of two times a common factor, i.e. a binary decomposition
+fn decompose_bits(constraint: &Constraint, booleans: &HashSet<usize>, values: &mut [Option<Fr>]) -> bool {
+    let a = evaluate(&constraint.a, values);
+    let b = evaluate(&constraint.b, values);
+    let c = evaluate(&constraint.c, values);
+    let Some(product) = known_product(&a, &b) else { return false };
+    if c.1.len() < 2 || c.1.iter().any(|(wire, _)| !booleans.contains(wire)) {
+        return false;
+    }
+    let target = product - c.0;
+
+    for (_, base) in &c.1 {
+        let exponents: Option<Vec<usize>> = c.1.iter().map(|(_, coeff)| power_of_two(*coeff / base)).collect();
+        let Some(exponents) = exponents else { continue };
+        if exponents.iter().collect::<HashSet<_>>().len() != exponents.len() {
+            continue;
+        }
+        let value = (target / base).into_bigint();
+        let bits: Vec<bool> = exponents.iter().map(|exponent| value.get_bit(*exponent)).collect();
+        let recomposed = exponents.iter().zip(&bits).filter(|(_, bit)| **bit)
+            .fold(Fr::zero(), |sum, (exponent, _)| sum + Fr::from(2u64).pow([*exponent as u64]));
+        if recomposed != target / base {
+            // The value does not fit the available bits
+            return false;
+        }
+        for ((wire, _), bit) in c.1.iter().zip(bits) {
+            values[*wire] = Some(Fr::from(bit));
+        }
+        return true;
+    }
+    false
+}
+
+fn power_of_two(value: Fr) -> Option<usize> {
+    let bigint = value.into_bigint();
+    let exponent = (bigint.num_bits() as usize).checked_sub(1)?;
+    (Fr::from(2u64).pow([exponent as u64]) == value).then_some(exponent)
+}
+
+fn satisfied(constraint: &Constraint, witness: &[Fr]) -> bool {
+    let eval = |terms: &Terms| terms.iter().map(|(wire, coeff)| *coeff * witness[*wire]).sum::<Fr>();
+    eval(&constraint.a) * eval(&constraint.b) == eval(&constraint.c)
+}
+
+/// A circom circuit as an arkworks constraint system
+pub struct CircomInstance<'a> {
+    circuit: &'a CircomCircuit,
+    witness: Option<Vec<Fr>>,
+}
+
+impl ConstraintSynthesizer<Fr> for CircomInstance<'_> {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let witness = self.witness.as_deref();
+        let value = |wire: usize| move || witness.map(|w| w[wire]).ok_or(SynthesisError::AssignmentMissing);
+
+        let mut variables = vec![Variable::One; self.circuit.r1cs.n_wires];
+        for wire in &self.circuit.public_wires {
+            variables[*wire] = cs.new_input_variable(value(*wire))?;
+        }
+        for (wire, variable) in variables.iter_mut().enumerate().skip(1) {
+            if !self.circuit.public_wires.contains(&wire) {
+                *variable = cs.new_witness_variable(value(wire))?;
+            }
+        }
+
+        let combination = |terms: &Terms| {
+            let mut lc = LinearCombination(terms.iter().map(|(wire, coeff)| (*coeff, variables[*wire])).collect());
+            lc.compactify();
+            lc
+        };
+        for constraint in &self.circuit.r1cs.constraints {
+            cs.enforce_constraint(combination(&constraint.a), combination(&constraint.b), combination(&constraint.c))?;
+        }
+        Ok(())
+    }
+}
+
+/// Groth16 keys of one circuit. Nodes that only verify hold just the
+/// verifying key.
+#[derive(Clone)]
+pub struct CircuitKeys {
+    pub proving_key: Option<ProvingKey<Bn254>>,
+    pub verifying_key: VerifyingKey<Bn254>,
+    prepared: PreparedVerifyingKey<Bn254>,
+}
+
+impl std::fmt::Debug for CircuitKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitKeys")
+            .field("can_prove", &self.proving_key.is_some())
+            .field("verifying_key_hash", &hex::encode(self.verifying_key_hash()))
+            .finish()
+    }
+}
+
+impl CircuitKeys {
+    /// Run the circuit-specific Groth16 setup. Whoever runs it can forge
+    /// proofs for the circuit, so the keys must come from the ceremony.
+    pub fn generate<R: rand::RngCore + rand::CryptoRng>(circuit: &CircomCircuit, rng: &mut R) -> Result<Self> {
+        let proving_key = Groth16::<Bn254>::generate_random_parameters_with_reduction(circuit.instance(None), rng)
+            .map_err(|e| anyhow!("Groth16 setup for {} failed: {}", circuit.id, e))?;
+        Ok(Self::from_proving_key(proving_key))
+    }
+
+    pub fn from_proving_key(proving_key: ProvingKey<Bn254>) -> Self {
+        let mut keys = Self::from_verifying_key(proving_key.vk.clone());
+        keys.proving_key = Some(proving_key);
+        keys
+    }
+
+    pub fn from_verifying_key(verifying_key: VerifyingKey<Bn254>) -> Self {
+        Self { proving_key: None, prepared: prepare_verifying_key(&verifying_key), verifying_key }
+    }
+
+    pub fn prepared_verifying_key(&self) -> &PreparedVerifyingKey<Bn254> {
+        &self.prepared
+    }
+
+    /// SHA-256 of the compressed verifying key
+    pub fn verifying_key_hash(&self) -> [u8; 32] {
+        let mut bytes = Vec::new();
+        self.verifying_key.serialize_compressed(&mut bytes).expect("writing to a Vec cannot fail");
+        Sha256::digest(bytes).into()
+    }
+
+    /// Write `<id>.vk`, and `<id>.pk` if this holds the proving key
+    pub fn save<P: AsRef<Path>>(&self, keys_dir: P, circuit_id: &str) -> Result<()> {
+        let keys_dir = keys_dir.as_ref();
+        fs::create_dir_all(keys_dir)?;
+        let mut bytes = Vec::new();
+        self.verifying_key.serialize_compressed(&mut bytes)?;
+        fs::write(keys_dir.join(format!("{}.vk", circuit_id)), bytes)?;
+        if let Some(proving_key) = &self.proving_key {
+            let mut bytes = Vec::new();
+            proving_key.serialize_compressed(&mut bytes)?;
+            fs::write(keys_dir.join(format!("{}.pk", circuit_id)), bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Read the keys written by [`CircuitKeys::save`], preferring the
+    /// proving key; `None` if neither file exists
+    pub fn load<P: AsRef<Path>>(keys_dir: P, circuit_id: &str) -> Result<Option<Self>> {
+        let keys_dir = keys_dir.as_ref();
+        let pk_path = keys_dir.join(format!("{}.pk", circuit_id));
+        let vk_path = keys_dir.join(format!("{}.vk", circuit_id));
+        if pk_path.exists() {
+            let proving_key = ProvingKey::deserialize_compressed(fs::read(&pk_path)?.as_slice())
+                .map_err(|e| anyhow!("Invalid proving key {}: {}", pk_path.display(), e))?;
+            Ok(Some(Self::from_proving_key(proving_key)))
+        } else if vk_path.exists() {
+            let verifying_key = VerifyingKey::deserialize_compressed(fs::read(&vk_path)?.as_slice())
+                .map_err(|e| anyhow!("Invalid verifying key {}: {}", vk_path.display(), e))?;
+            Ok(Some(Self::from_verifying_key(verifying_key)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl ProofVerifier for CircuitKeys {
+    fn verification_key_hash(&self) -> [u8; 32] {
+        self.verifying_key_hash()
+    }
+
+    fn verify(&self, envelope: &ProofEnvelope, _statement: &ProofStatement) -> Result<bool> {
+        if envelope.system != ProofSystem::Groth16 || envelope.public_inputs.len() + 1 != self.verifying_key.gamma_abc_g1.len() {
+            return Ok(false);
+        }
+        let Ok(proof) = Proof::<Bn254>::deserialize_compressed(envelope.proof.as_slice()) else {
+            return Ok(false);
+        };
+        Ok(Groth16::<Bn254>::verify_proof(&self.prepared, &proof, &envelope.public_inputs).unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    fn load(id: &str) -> CircomCircuit {
+        CircomCircuit::load("circuits/compiled", circuit_spec(id).unwrap()).unwrap()
+    }
+
+    fn fr(value: u64) -> Fr {
+        Fr::from(value)
+    }
+
+    fn satisfies(circuit: &CircomCircuit, witness: Vec<Fr>) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        circuit.instance(Some(witness)).generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn test_compiled_circuits_parse() {
+        for spec in CIRCUITS {
+            let circuit = CircomCircuit::load("circuits/compiled", spec).unwrap();
+            assert_eq!(circuit.r1cs.n_pub_out, 3, "{}", spec.id);
+            assert!(!circuit.r1cs.constraints.is_empty(), "{}", spec.id);
+        }
+        assert!(R1cs::from_bytes(b"r1cs").is_err());
+    }
+
+    #[test]
+    fn test_stake_witness() -> Result<()> {
+        let circuit = load("stake_proof");
+        let inputs = |actual_stake: u64| {
+            vec![
+                ("minimum_stake", fr(100)),
+                ("validator_commitment", fr(7)),
+                ("block_hash", fr(9)),
+                ("network_id", fr(1)),
+                ("actual_stake", fr(actual_stake)),
+                ("validator_nonce", fr(5)),
+                ("stake_salt", fr(11)),
+            ]
+        };
+        let witness = circuit.calculate_witness(&inputs(250))?;
+        assert!(satisfies(&circuit, witness.clone()));
+        assert_eq!(circuit.public_signal_names()[..3], ["stake_proof", "vote_nullifier", "quantum_commitment"]);
+        // stake_proof sums the stake, minimum, commitment and network id
+        assert_eq!(circuit.public_signals(&witness)[0], fr(250 + 100 + 7 + 1));
+
+        // Exactly the minimum passes, less does not
+        assert!(circuit.calculate_witness(&inputs(100)).is_ok());
+        assert!(circuit.calculate_witness(&inputs(99)).is_err());
+        assert!(circuit.calculate_witness(&inputs(250)[..6]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_hint_witnesses() -> Result<()> {
+        // IsZero with a non-zero input needs its inverse hint
+        let routing = load("routing_proof");
+        let mut inputs = vec![
+            ("source_commitment", fr(3 * 4 + 1)),
+            ("destination_hash", fr(8)),
+            ("network_id", fr(1)),
+            ("bandwidth_limit", fr(1000)),
+            ("source_identity", fr(3)),
+            ("path_nonce", fr(4)),
+            ("bandwidth_proof", fr(500)),
+        ];
+        let path: Vec<String> = (0..5).map(|i| format!("routing_path[{}]", i)).collect();
+        inputs.extend(path.iter().map(|name| (name.as_str(), fr(2))));
+        assert!(satisfies(&routing, routing.calculate_witness(&inputs)?));
+
+        inputs[0].1 = fr(99);
+        assert!(routing.calculate_witness(&inputs).is_err());
+
+        let voting = load("anonymous_voting");
+        let vote = |choice: u64| {
+            vec![
+                ("proposal_id", fr(1)),
+                ("vote_commitment", fr(choice + 20 + 3)),
+                ("eligibility_root", fr(5)),
+                ("nullifier_hash", fr(21)),
+                ("vote_choice", fr(choice)),
+                ("voter_secret", fr(20)),
+                ("voting_power", fr(10)),
+                ("vote_nonce", fr(3)),
+            ]
+        };
+        assert!(satisfies(&voting, voting.calculate_witness(&vote(1))?));
+        assert!(voting.calculate_witness(&vote(2)).is_err());
+        Ok(())
+    }
+}
@@ -2,23 +2,28 @@
 //! Production-ready zero-knowledge consensus with real cryptography
 
 use crate::zhtp::{
-    zk_proofs::{UnifiedCircuit, ByteRoutingProof},
+    amount::Amount,
+    proof_envelope::{ProofEnvelope, ProofStatement, VerifierRegistry},
+    prover::ProofCancellation,
+    zk_proofs::UnifiedCircuit,
     crypto::Keypair,
     economics::ZhtpEconomics,
 };
-use crate::blockchain::{Block, Transaction};
+use crate::blockchain::{Block, BlockImport, Transaction, MAX_BLOCK_TRANSACTIONS};
 use anyhow::{Result, anyhow};
 use serde::{Serialize, Deserialize};
 use std::{
     collections::HashMap,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::RwLock;
 use sha2::{Sha256, Digest};
-use pqcrypto_traits::sign::PublicKey;
 use ark_ec::Group;
 
+/// Longest a stake or vote proof may take before the round moves on without it
+const CONSENSUS_PROOF_TIMEOUT: Duration = Duration::from_secs(30);
+
 // ============================================================================
 // ENHANCED TYPES FROM ZK_CONSENSUS (merged into consensus engine)
 // ============================================================================
@@ -29,7 +34,7 @@ pub struct ZkNetworkMetrics {
     /// Encrypted routing performance data
     pub encrypted_metrics: Vec<u8>,
     /// Zero-knowledge proof of metric validity
-    pub metrics_proof: ByteRoutingProof,
+    pub metrics_proof: ProofEnvelope,
     /// Commitment to actual performance values
     pub performance_commitment: [u8; 32],
     /// Public reputation score (derived from private metrics)
@@ -47,36 +52,16 @@ pub struct ZkNetworkMetrics {
 }
 
 impl ZkNetworkMetrics {
+    /// Metrics without a proof yet; [`prove`](Self::prove) adds one
     pub fn new(reputation_score: f64) -> Self {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
 
-        // Generate real ZK proof for metrics
-        let mut circuit = UnifiedCircuit::new(
-            vec![0u8; 32], // source_node (placeholder for metrics)
-            vec![0u8; 32], // destination_node
-            vec![],        // route_path
-            HashMap::new(), // routing_table
-            [0u8; 32],     // stored_data_root
-            vec![],        // storage_merkle_proof
-            ark_bn254::G1Projective::generator(), // space_commitment
-            reputation_score as u64, // bandwidth_used
-            vec![(reputation_score as u64, true)], // uptime_records
-            vec![(50, 25.0)], // latency_measurements
-        );
-        
-        // Convert to ByteRoutingProof format
-        let metrics_proof = ByteRoutingProof {
-            inputs: vec![vec![reputation_score as u8; 32]],
-            elements: vec![reputation_score.to_le_bytes().to_vec()],
-            commitments: vec![vec![0u8; 32]], // Placeholder commitment
-        };
-        
         Self {
             encrypted_metrics: vec![reputation_score as u8; 64], // Real encrypted metrics
-            metrics_proof,
+            metrics_proof: ProofEnvelope::none(),
             performance_commitment: [0u8; 32],
             reputation_score,
             updated_at: now,
@@ -87,6 +72,29 @@ impl ZkNetworkMetrics {
         }
     }
 
+    /// Generate the ZK proof of the metrics on the prover pool
+    pub async fn prove(&mut self) -> Result<()> {
+        let circuit = UnifiedCircuit::new(
+            vec![0u8; 32], // source_node (placeholder for metrics)
+            vec![0u8; 32], // destination_node
+            vec![],        // route_path
+            HashMap::new(), // routing_table
+            [0u8; 32],     // stored_data_root
+            vec![],        // storage_merkle_proof
+            ark_bn254::G1Projective::generator(), // space_commitment
+            self.reputation_score as u64, // bandwidth_used
+            vec![(self.reputation_score as u64, true)], // uptime_records
+            vec![(50, 25.0)], // latency_measurements
+        );
+
+        let proof = circuit
+            .prove(&ProofCancellation::new(), CONSENSUS_PROOF_TIMEOUT)
+            .await
+            .map_err(|e| anyhow!("Failed to generate metrics proof: {}", e))?;
+        self.metrics_proof = ProofEnvelope::from(proof);
+        Ok(())
+    }
+
     pub fn get_delivery_success_rate(&self) -> f64 {
         self.delivery_success
     }
@@ -134,7 +142,7 @@ impl ZkNetworkMetrics {
 #[derive(Debug, Clone)]
 pub struct ZkConsensusParams {
     /// Minimum stake required to be a validator
-    pub min_stake: f64,
+    pub min_stake: Amount,
     /// Maximum number of validators per round
     pub max_validators: usize,
     /// Round timeout in seconds
@@ -166,7 +174,7 @@ pub struct ZkBlock {
     /// Encrypted transaction data
     pub encrypted_transactions: Vec<u8>,
     /// Zero-knowledge proof of block validity
-    pub validity_proof: ByteRoutingProof,
+    pub validity_proof: ProofEnvelope,
     /// Merkle root of transaction commitments
     pub transaction_root: [u8; 32],
     /// Anonymous validator commitments who approved this block
@@ -185,9 +193,9 @@ pub struct ZkValidator {
     /// Encrypted validator identity
     pub encrypted_identity: Vec<u8>,
     /// Stake amount (visible for consensus weight)
-    pub stake: f64,
+    pub stake: Amount,
     /// Zero-knowledge proof of stake validity
-    pub stake_proof: ByteRoutingProof,
+    pub stake_proof: ProofEnvelope,
     /// Commitment to validator public key
     pub identity_commitment: [u8; 32],
     /// Network metrics with ZK proofs
@@ -200,6 +208,32 @@ pub struct ZkValidator {
     pub status: ValidatorStatus,
 }
 
+impl ZkValidator {
+    /// Whether `stake_proof` is a valid stake proof for this validator
+    pub fn verify_stake_proof(&self) -> bool {
+        verify_stake_proof(&self.encrypted_identity, &self.stake_proof)
+    }
+}
+
+/// Statement a validator's stake proof is checked against
+fn stake_statement(validator_id: &[u8]) -> ProofStatement<'_> {
+    ProofStatement::Route {
+        source: validator_id,
+        destination: &[0; 32],
+        stored_data_root: [0; 32],
+    }
+}
+
+fn verify_stake_proof(validator_id: &[u8], proof: &ProofEnvelope) -> bool {
+    match VerifierRegistry::global().verify(proof, &stake_statement(validator_id)) {
+        Ok(valid) => valid,
+        Err(e) => {
+            log::warn!("Cannot verify stake proof: {}", e);
+            false
+        }
+    }
+}
+
 // ============================================================================
 // ENHANCED CONSENSUS ENGINE (keeping the working MVP logic)
 // ============================================================================
@@ -235,14 +269,15 @@ pub struct Vote {
     pub validator_id: String,
     pub block_hash: String,
     pub approve: bool,
-    pub zk_proof: ByteRoutingProof,
+    pub zk_proof: ProofEnvelope,
     pub timestamp: u64,
 }
 
 #[derive(Clone)]
 pub struct ValidatorInfo {
     pub keypair: Keypair,
-    pub stake: f64,
+    pub stake: Amount,
+    pub stake_proof: ProofEnvelope,
     pub reputation: f64,
     pub status: ValidatorStatus,
     pub last_activity: u64,
@@ -260,14 +295,25 @@ pub enum RoundStatus {
 
 impl ZhtpConsensusEngine {
     /// Create new consensus engine with real cryptography
-    pub async fn new(node_keypair: Keypair, economics: Arc<ZhtpEconomics>) -> Result<Self> {        let params = ZkConsensusParams {
-            min_stake: 100.0, // 100 ZHTP minimum stake for development/testing
+    pub async fn new(node_keypair: Keypair, economics: Arc<ZhtpEconomics>) -> Result<Self> {
+        Self::with_blockchain(node_keypair, economics, crate::Blockchain::new(Amount::from_zhtp(50))).await
+    }
+
+    /// Create consensus engine on top of an existing (e.g. persistent) blockchain
+    pub async fn with_blockchain(
+        node_keypair: Keypair,
+        economics: Arc<ZhtpEconomics>,
+        blockchain: crate::Blockchain,
+    ) -> Result<Self> {
+        let params = ZkConsensusParams {
+            min_stake: Amount::from_zhtp(100), // 100 ZHTP minimum stake for development/testing
             max_validators: 1000,
             round_timeout: 12, // 12 second blocks
             min_votes: 3, // Minimum for testnet
             slashing_penalty: 0.1,
             anonymity_set_size: 100,
-        };        let blockchain = Arc::new(RwLock::new(crate::Blockchain::new(50.0)));
+        };
+        let blockchain = Arc::new(RwLock::new(blockchain));
 
         let initial_round = ConsensusRound {
             round_number: 0,
@@ -289,47 +335,56 @@ impl ZhtpConsensusEngine {
     }
 
     /// Register as validator with real stake proof
-    pub async fn register_validator(&self, validator_id: String, stake: f64) -> Result<()> {
-        if stake < 100.0 {
+    pub async fn register_validator(&self, validator_id: String, stake: Amount) -> Result<()> {
+        if stake < self.params.min_stake {
             return Err(anyhow!("Insufficient stake: need at least 100 ZHTP"));
         }        // Generate real ZK proof of stake
-        let stake_proof = self.generate_stake_proof(stake).await?;
+        let stake_proof = self.generate_stake_proof(&validator_id, stake).await?;
+        if !verify_stake_proof(validator_id.as_bytes(), &stake_proof) {
+            return Err(anyhow!("Stake proof of validator {} does not verify", validator_id));
+        }
+
+        let mut metrics = ZkNetworkMetrics::new(1.0);
+        metrics.prove().await?;
 
         // Store validator info locally
         let validator_info = ValidatorInfo {
             keypair: self.node_keypair.clone(),
             stake,
+            stake_proof,
             reputation: 1.0,
             status: ValidatorStatus::Active,
             last_activity: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
-            metrics: ZkNetworkMetrics::new(1.0),
+            metrics,
         };
 
         let mut registry = self.validator_registry.write().await;
-        registry.insert(validator_id, validator_info);
+        registry.insert(validator_id.clone(), validator_info);
+        self.blockchain.read().await.set_validator_stake(&validator_id, stake).await;
 
         Ok(())
     }
 
     /// Generate real zero-knowledge proof of stake
-    async fn generate_stake_proof(&self, stake: f64) -> Result<ByteRoutingProof> {        // Create proof that we have sufficient stake without revealing exact amount
-        let mut circuit = UnifiedCircuit::new(
-            self.node_keypair.public.as_bytes().to_vec(), // source (validator ID)
+    async fn generate_stake_proof(&self, validator_id: &str, stake: Amount) -> Result<ProofEnvelope> {        // Create proof that we have sufficient stake without revealing exact amount
+        let circuit = UnifiedCircuit::new(
+            validator_id.as_bytes().to_vec(), // source (validator ID)
             vec![0; 32], // destination (network)
             vec![], // route_path
             HashMap::new(), // routing_table
             [0; 32], // stored_data_root
             vec![], // storage_merkle_proof
             ark_bn254::G1Projective::generator(), // space_commitment
-            stake as u64, // bandwidth_used (stake amount)
-            vec![(stake as u64, true)], // uptime_records (stake, active)
+            stake.whole_zhtp(), // bandwidth_used (stake amount)
+            vec![(stake.whole_zhtp(), true)], // uptime_records (stake, active)
             vec![(1, 1.0)], // latency_measurements (1ms, perfect performance)
         );
 
-        match circuit.generate_proof() {
-            Some(proof) => Ok(ByteRoutingProof::from(proof)),
-            None => Err(anyhow!("Failed to generate stake proof")),
-        }
+        let proof = circuit
+            .prove(&ProofCancellation::new(), CONSENSUS_PROOF_TIMEOUT)
+            .await
+            .map_err(|e| anyhow!("Failed to generate stake proof: {}", e))?;
+        Ok(ProofEnvelope::from(proof))
     }
 
     /// Start consensus engine
@@ -381,10 +436,9 @@ impl ZhtpConsensusEngine {
                 // Create new block
                 if let Some(validator) = registry.get(&proposer) {
                     let blockchain = self.blockchain.read().await;
-                    let pending_txs = blockchain.get_transactions().await;
-                    
-                    // Take up to 100 transactions
-                    let block_txs: Vec<Transaction> = pending_txs.into_iter().take(100).collect();
+                    // Take the best paying pending transactions
+                    let block_txs: Vec<Transaction> =
+                        blockchain.select_transactions(MAX_BLOCK_TRANSACTIONS).await;
                     
                     let latest_block = blockchain.get_latest_block().await;
                     let new_block = Block::new(
@@ -414,6 +468,15 @@ impl ZhtpConsensusEngine {
             }            RoundStatus::Finalizing => {
                 // Finalize the block
                 if let Some(block) = round.proposed_block.clone() {
+                    // Sign as producer when we hold the proposer's key, so
+                    // the block carries its stake in fork choice
+                    let signer = self
+                        .validator_registry
+                        .read()
+                        .await
+                        .get(&round.proposer)
+                        .map(|validator| validator.keypair.clone())
+                        .filter(|keypair| keypair.address() == round.proposer);
                     let blockchain = self.blockchain.write().await;
                     
                     // Process all transactions in the block
@@ -421,11 +484,16 @@ impl ZhtpConsensusEngine {
                         blockchain.add_transaction(tx.clone()).await;
                     }
                     
-                    // Create the block
-                    blockchain.create_block(&round.proposer, 1.0, None).await;
+                    // Create the block; committed blocks are final
+                    match &signer {
+                        Some(keypair) => blockchain.create_signed_block(keypair, 1.0, None).await,
+                        None => blockchain.create_block(&round.proposer, 1.0, None).await,
+                    };
+                    let tip = blockchain.get_latest_block().await;
+                    blockchain.finalize_block(&tip.hash).await?;
                     
                     // Distribute rewards
-                    self.economics.process_fee_burn(1000).await?; // Process fees
+                    self.economics.process_fee_burn(Amount::from_base_units(100_000)).await?; // Process fees
                     
                     round.status = RoundStatus::Committed;
                     log::info!("Block {} committed", block.index);
@@ -497,39 +565,14 @@ impl ZhtpConsensusEngine {
 
     /// Validate a proposed block
     async fn validate_block(&self, block: &Block) -> Result<bool> {
-        // Basic validation checks
-        if block.transactions.is_empty() {
+        // The same rules every node applies when importing the block:
+        // coinbase, size and gas limits, and the sender signature of every
+        // transparent transfer
+        if let Err(e) = self.blockchain.read().await.validate_block(block).await {
+            log::warn!("Rejecting block {}: {}", block.index, e);
             return Ok(false);
         }
 
-        // Validate all transactions
-        for tx in &block.transactions {
-            if tx.amount < 0.0 {
-                return Ok(false);
-            }
-            
-            // Check signature using proper post-quantum verification
-            // Convert string address to public key bytes for verification
-            let public_key_bytes = tx.from.as_bytes();
-            if public_key_bytes.len() >= 32 {
-                // Use first 32 bytes as simplified public key
-                let mut key_bytes = [0u8; 1952]; // Dilithium5 public key size
-                let hash = sha2::Sha256::digest(public_key_bytes);
-                // Expand hash to full key size for demo purposes
-                for i in 0..key_bytes.len() {
-                    key_bytes[i] = hash[i % 32];
-                }
-                
-                if !tx.verify_signature(&key_bytes) {
-                    log::warn!("Invalid transaction signature from {}", tx.from);
-                    return Ok(false);
-                }
-            } else {
-                log::warn!("Invalid public key format for {}", tx.from);
-                return Ok(false);
-            }
-        }
-
         // Check block hash
         let calculated_hash = block.calculate_hash();
         if calculated_hash != block.hash {
@@ -538,8 +581,8 @@ impl ZhtpConsensusEngine {
 
         Ok(true)
     }    /// Generate zero-knowledge proof for vote
-    async fn generate_vote_proof(&self, validator_id: &str, block: &Block, approve: bool) -> Result<ByteRoutingProof> {
-        let mut circuit = UnifiedCircuit::new(
+    async fn generate_vote_proof(&self, validator_id: &str, block: &Block, approve: bool) -> Result<ProofEnvelope> {
+        let circuit = UnifiedCircuit::new(
             validator_id.as_bytes().to_vec(), // source (validator)
             block.hash.as_bytes().to_vec(), // destination (block hash)
             vec![], // route_path
@@ -552,10 +595,11 @@ impl ZhtpConsensusEngine {
             vec![(1, 1.0)], // latency_measurements
         );
 
-        match circuit.generate_proof() {
-            Some(proof) => Ok(ByteRoutingProof::from(proof)),
-            None => Err(anyhow!("Failed to generate vote proof")),
-        }
+        let proof = circuit
+            .prove(&ProofCancellation::new(), CONSENSUS_PROOF_TIMEOUT)
+            .await
+            .map_err(|e| anyhow!("Failed to generate vote proof: {}", e))?;
+        Ok(ProofEnvelope::from(proof))
     }
 
     /// Get current consensus status
@@ -587,11 +631,7 @@ impl ZhtpConsensusEngine {
                 ZkValidator {
                     encrypted_identity: id.as_bytes().to_vec(),
                     stake: info.stake,
-                    stake_proof: ByteRoutingProof {
-                        commitments: vec![],
-                        elements: vec![],
-                        inputs: vec![],
-                    },
+                    stake_proof: info.stake_proof.clone(),
                     identity_commitment: {
                         let mut commitment = [0u8; 32];
                         let id_hash = sha2::Sha256::digest(id.as_bytes());
@@ -622,11 +662,7 @@ impl ZhtpConsensusEngine {
                 let zk_validator = ZkValidator {
                     encrypted_identity: validator_id.as_bytes().to_vec(),
                     stake: validator_info.stake,
-                    stake_proof: ByteRoutingProof {
-                        commitments: vec![],
-                        elements: vec![],
-                        inputs: vec![],
-                    },
+                    stake_proof: validator_info.stake_proof.clone(),
                     identity_commitment: [0u8; 32],
                     metrics: ZkNetworkMetrics::new(validator_info.reputation),
                     registered_at: 0,
@@ -649,17 +685,17 @@ impl ZhtpConsensusEngine {
     }
 
     /// Calculate CA rewards
-    pub async fn calculate_ca_rewards(&self, certificates_issued: u64) -> Result<u64> {
+    pub async fn calculate_ca_rewards(&self, certificates_issued: u64) -> Result<Amount> {
         self.economics.calculate_certificate_reward(certificates_issued as u32).await
     }
 
     /// Calculate DNS rewards  
-    pub async fn calculate_dns_rewards(&self, domains_resolved: u64, domains_registered: u64) -> Result<u64> {
+    pub async fn calculate_dns_rewards(&self, domains_resolved: u64, domains_registered: u64) -> Result<Amount> {
         self.economics.calculate_dns_reward(domains_resolved as u32, domains_registered as u32).await
     }
 
     /// Calculate routing rewards
-    pub async fn calculate_routing_rewards(&self, packets_routed: u64, success_rate: f64) -> Result<u64> {
+    pub async fn calculate_routing_rewards(&self, packets_routed: u64, success_rate: f64) -> Result<Amount> {
         self.economics.calculate_routing_reward(packets_routed, success_rate).await
     }
 
@@ -685,8 +721,8 @@ impl ZhtpConsensusEngine {
     }
 
     /// Process transaction fees
-    pub async fn process_transaction_fees(&self, total_fees: f64) -> Result<()> {
-        self.economics.process_fee_burn(total_fees as u64).await
+    pub async fn process_transaction_fees(&self, total_fees: Amount) -> Result<()> {
+        self.economics.process_fee_burn(total_fees).await
     }
 
     /// Get economic metrics from the consensus system
@@ -699,6 +735,16 @@ impl ZhtpConsensusEngine {
         self.economics.calculate_network_value_capture().await
     }
 
+    /// Handle to the chain this engine produces blocks for
+    pub async fn get_blockchain(&self) -> crate::Blockchain {
+        self.blockchain.read().await.clone()
+    }
+
+    /// Import a block announced by another validator into the block tree
+    pub async fn import_block(&self, block: Block) -> Result<BlockImport> {
+        self.blockchain.read().await.import_block(block).await
+    }
+
     /// Slash a validator for malicious behavior
     pub async fn slash_validator(&self, validator_id: &str, reason: String) -> Result<()> {
         let mut registry = self.validator_registry.write().await;
@@ -707,8 +753,8 @@ impl ZhtpConsensusEngine {
             validator_info.status = ValidatorStatus::Slashed;
             
             // Apply slashing penalty
-            let penalty = validator_info.stake * 0.1; // 10% penalty
-            validator_info.stake -= penalty;
+            let penalty = validator_info.stake.mul_div(1, 10).unwrap_or(Amount::ZERO); // 10% penalty
+            validator_info.stake = validator_info.stake.saturating_sub(penalty);
             
             log::warn!("Slashed validator {} for {}: {} ZHTP penalty (remaining stake: {})", 
                 validator_id, reason, penalty, validator_info.stake);
@@ -720,13 +766,22 @@ impl ZhtpConsensusEngine {
                 .collect();
             
             if !active_validators.is_empty() {
-                let reward_per_validator = penalty / active_validators.len() as f64;
+                let reward_per_validator = penalty
+                    .checked_div(active_validators.len() as u64)
+                    .unwrap_or(Amount::ZERO);
                 for active_id in active_validators {
                     if let Some(active_validator) = registry.get_mut(&active_id) {
-                        active_validator.stake += reward_per_validator;
+                        active_validator.stake = active_validator.stake.saturating_add(reward_per_validator);
                     }
                 }
             }
+
+            // Slashed validators no longer add weight to their branches
+            let blockchain = self.blockchain.read().await;
+            for (id, info) in registry.iter() {
+                let weight = if info.status == ValidatorStatus::Active { info.stake } else { Amount::ZERO };
+                blockchain.set_validator_stake(id, weight).await;
+            }
             
             Ok(())
         } else {
@@ -763,6 +818,7 @@ pub struct ConsensusStatus {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blockchain::{ProtocolParams, TRANSACTION_DATA_GAS_PER_BYTE};
 
     #[tokio::test]
     async fn test_consensus_engine_creation() -> Result<()> {
@@ -777,6 +833,50 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_validate_block_checks_sender_keys() -> Result<()> {
+        let engine = ZhtpConsensusEngine::new(Keypair::generate(), Arc::new(ZhtpEconomics::new())).await?;
+        let sender = Keypair::generate();
+        let reward = Transaction::coinbase("validator".to_string(), Amount::from_zhtp(50), 1);
+
+        let mut transfer = Transaction::new(sender.address(), "recipient".to_string(), Amount::from_zhtp(1));
+        transfer.sign_with_keypair(&sender)?;
+        let block = Block::new(1, vec![reward.clone(), transfer.clone()], "0".to_string(), "validator".to_string(), 1.0, None);
+        assert!(engine.validate_block(&block).await?);
+
+        // A key that does not own the sender address is rejected
+        let mut impostor = transfer.clone();
+        impostor.public_key = Some(Keypair::generate().public_key());
+        let block = Block::new(1, vec![reward.clone(), impostor], "0".to_string(), "validator".to_string(), 1.0, None);
+        assert!(!engine.validate_block(&block).await?);
+
+        // So is a transaction changed after signing
+        let mut tampered = transfer.clone();
+        tampered.amount = Amount::from_zhtp(1000);
+        let block = Block::new(1, vec![reward.clone(), tampered], "0".to_string(), "validator".to_string(), 1.0, None);
+        assert!(!engine.validate_block(&block).await?);
+
+        // Rewards are minted only as the coinbase, for the economics' block reward
+        let inflated = Transaction::coinbase("validator".to_string(), Amount::from_zhtp(51), 1);
+        let block = Block::new(1, vec![inflated], "0".to_string(), "validator".to_string(), 1.0, None);
+        assert!(!engine.validate_block(&block).await?);
+        let block = Block::new(1, vec![transfer.clone(), reward.clone()], "0".to_string(), "validator".to_string(), 1.0, None);
+        assert!(!engine.validate_block(&block).await?);
+
+        // Blocks over the protocol gas limit are rejected
+        let params = ProtocolParams::default();
+        let mut second = Transaction::new(sender.address(), "recipient".to_string(), Amount::from_zhtp(1));
+        second.nonce = 1;
+        second.data = vec![0; (params.max_block_gas / TRANSACTION_DATA_GAS_PER_BYTE) as usize];
+        second.sign_with_keypair(&sender)?;
+        let block = Block::new(1, vec![reward.clone(), transfer.clone()], "0".to_string(), "validator".to_string(), 1.0, None);
+        assert!(engine.validate_block(&block).await?);
+        let block = Block::new(1, vec![reward, transfer, second], "0".to_string(), "validator".to_string(), 1.0, None);
+        assert!(!engine.validate_block(&block).await?);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_validator_registration() -> Result<()> {
         let keypair = Keypair::generate();
@@ -784,11 +884,11 @@ mod tests {
         let engine = ZhtpConsensusEngine::new(keypair, economics).await?;
         
         // Should succeed with sufficient stake
-        let result = engine.register_validator("validator1".to_string(), 50_000_000.0).await;
+        let result = engine.register_validator("validator1".to_string(), Amount::from_zhtp(50_000_000)).await;
         assert!(result.is_ok());
         
-        // Should fail with insufficient stake
-        let result = engine.register_validator("validator2".to_string(), 1_000_000.0).await;
+        // Should fail below the 100 ZHTP minimum
+        let result = engine.register_validator("validator2".to_string(), Amount::from_zhtp(99)).await;
         assert!(result.is_err());
         
         Ok(())
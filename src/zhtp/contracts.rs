@@ -1,43 +1,150 @@
 use anyhow::Result;
+use sha2::{Digest, Sha256};
 use wasmi::{
     self,
+    core::{Trap, TrapCode},
+    Caller,
+    Config,
     Engine,
+    Extern,
     Instance,
     Linker,
+    Memory,
     Module,
     Store,
+    StoreLimits,
+    StoreLimitsBuilder,
     Value,
 };
 
-/// Contract execution environment
+/// Gas available to one contract deployment or call unless set otherwise
+pub const DEFAULT_CONTRACT_GAS_LIMIT: u64 = 1_000_000;
+
+/// Most events one deployment or call may emit
+pub const MAX_CONTRACT_EVENTS: usize = 64;
+
+/// Largest event name or payload a contract may emit, in bytes
+pub const MAX_EVENT_BYTES: usize = 1024;
+
+/// Largest linear memory a contract may allocate, in bytes
+pub const MAX_CONTRACT_MEMORY: usize = 16 * 1024 * 1024;
+
+/// Extra gas charged per byte of an emitted event
+const EVENT_GAS_PER_BYTE: u64 = 16;
+
+/// Leading bytes of every WebAssembly module
+const WASM_MAGIC: &[u8] = b"\0asm";
+
+/// Whether transaction data is contract code to execute
+pub fn is_contract_code(data: &[u8]) -> bool {
+    data.starts_with(WASM_MAGIC)
+}
+
+/// Address identifying a contract in the events it emits: `zhtp_contract_`
+/// followed by the hex SHA-256 of its code
+pub fn contract_address(bytecode: &[u8]) -> String {
+    format!("zhtp_contract_{}", hex::encode(Sha256::digest(bytecode)))
+}
+
+/// Event emitted by a contract through the `zhtp.emit` host function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractEvent {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// State host functions work on during an execution
+#[derive(Debug)]
+struct ContractContext {
+    events: Vec<ContractEvent>,
+    limits: StoreLimits,
+}
+
+/// Contract execution environment.
+///
+/// Execution is metered: every deployment or call may burn at most the gas
+/// limit, one unit per executed instruction, and fails once it runs out.
+/// Contracts may import `zhtp.emit(name_ptr, name_len, data_ptr, data_len)`
+/// to emit an event from their exported `memory`.
 #[derive(Debug)]
 pub struct WasmRuntime {
     engine: Engine,
-    store: Store<()>,
+    store: Store<ContractContext>,
     instance: Option<Instance>,
+    gas_limit: u64,
+    gas_used: u64,
 }
 
 impl WasmRuntime {
     pub fn new() -> Self {
-        let engine = Engine::default();
-        let store = Store::new(&engine, ());
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let context = ContractContext {
+            events: Vec::new(),
+            limits: StoreLimitsBuilder::new().memory_size(MAX_CONTRACT_MEMORY).build(),
+        };
+        let mut store = Store::new(&engine, context);
+        store.limiter(|context| &mut context.limits);
         Self {
             engine,
             store,
             instance: None,
+            gas_limit: DEFAULT_CONTRACT_GAS_LIMIT,
+            gas_used: 0,
         }
     }
 
+    /// Limit the gas of each following deployment or call
+    pub fn set_gas_limit(&mut self, gas_limit: u64) {
+        self.gas_limit = gas_limit;
+    }
+
+    /// Gas burnt by the last deployment or call
+    pub fn gas_used(&self) -> u64 {
+        self.gas_used
+    }
+
+    /// Events emitted by the last deployment or call
+    pub fn events(&self) -> &[ContractEvent] {
+        &self.store.data().events
+    }
+
     pub fn deploy(&mut self, bytecode: &[u8]) -> Result<()> {
         let module = Module::new(&self.engine, bytecode)?;
-        let linker = Linker::new(&self.engine);
-        self.instance = Some(
-            linker.instantiate(&mut self.store, &module)?
-                .start(&mut self.store)?
-        );
+        let linker = self.linker()?;
+        self.store.data_mut().events.clear();
+        self.refuel()?;
+        let instance = linker
+            .instantiate(&mut self.store, &module)
+            .and_then(|pre| pre.start(&mut self.store));
+        let instance = self.metered(instance)?;
+        self.instance = Some(instance);
         Ok(())
     }
 
+    /// Run contract code carried by a transaction: instantiate it and call
+    /// its exported `main` function, if any, within one gas limit. Returns
+    /// the events it emitted; [`WasmRuntime::gas_used`] reports the gas
+    /// burnt whether or not it succeeded.
+    pub fn execute(&mut self, bytecode: &[u8]) -> Result<Vec<ContractEvent>> {
+        self.gas_used = 0;
+        let module = Module::new(&self.engine, bytecode)?;
+        let linker = self.linker()?;
+        self.store.data_mut().events.clear();
+        self.refuel()?;
+        let outcome = linker
+            .instantiate(&mut self.store, &module)
+            .and_then(|pre| pre.start(&mut self.store))
+            .and_then(|instance| match instance.get_func(&self.store, "main") {
+                Some(main) => main.call(&mut self.store, &[], &mut []).map(|()| instance),
+                None => Ok(instance),
+            });
+        let events = std::mem::take(&mut self.store.data_mut().events);
+        self.metered(outcome)?;
+        Ok(events)
+    }
+
     pub fn call_function(&mut self, method: &str, params: &[Value]) -> Result<Vec<u8>> {
         let instance = self.instance.as_ref()
             .ok_or_else(|| anyhow::anyhow!("No contract deployed"))?;
@@ -46,13 +153,86 @@ impl WasmRuntime {
             .ok_or_else(|| anyhow::anyhow!("Method not found"))?;
             
         let mut results = vec![Value::I32(0)];
-        func.call(&mut self.store, params, &mut results)?;
+        self.store.data_mut().events.clear();
+        self.refuel()?;
+        let outcome = func.call(&mut self.store, params, &mut results);
+        self.metered(outcome)?;
             
         Ok(match results.get(0) {
             Some(&Value::I32(val)) => val.to_le_bytes().to_vec(),
             _ => vec![0],
         })
     }
+
+    /// Linker providing the host functions contracts may import
+    fn linker(&self) -> Result<Linker<ContractContext>> {
+        let mut linker = Linker::new(&self.engine);
+        linker
+            .func_wrap("zhtp", "emit", Self::emit)
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(linker)
+    }
+
+    /// `zhtp.emit`: record an event whose name and payload are read from the
+    /// contract's memory, charging gas for their size
+    fn emit(
+        mut caller: Caller<'_, ContractContext>,
+        name_ptr: i32,
+        name_len: i32,
+        data_ptr: i32,
+        data_len: i32,
+    ) -> Result<(), Trap> {
+        let memory = caller
+            .get_export("memory")
+            .and_then(Extern::into_memory)
+            .ok_or_else(|| Trap::new("Contract emitting an event exports no memory"))?;
+        let name = Self::read_memory(&caller, &memory, name_ptr, name_len)?;
+        let name = String::from_utf8(name).map_err(|_| Trap::new("Event name is not UTF-8"))?;
+        let data = Self::read_memory(&caller, &memory, data_ptr, data_len)?;
+        if caller.data().events.len() >= MAX_CONTRACT_EVENTS {
+            return Err(Trap::new("Contract emitted too many events"));
+        }
+        let gas = EVENT_GAS_PER_BYTE * (name.len() + data.len()) as u64;
+        caller.consume_fuel(gas).map_err(|_| Trap::from(TrapCode::OutOfFuel))?;
+        caller.data_mut().events.push(ContractEvent { name, data });
+        Ok(())
+    }
+
+    fn read_memory(caller: &Caller<'_, ContractContext>, memory: &Memory, ptr: i32, len: i32) -> Result<Vec<u8>, Trap> {
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= MAX_EVENT_BYTES)
+            .ok_or_else(|| Trap::new("Event field is too large"))?;
+        let mut buffer = vec![0u8; len];
+        memory
+            .read(caller, ptr as u32 as usize, &mut buffer)
+            .map_err(|e| Trap::new(e.to_string()))?;
+        Ok(buffer)
+    }
+
+    /// Reset the remaining fuel to the gas limit
+    fn refuel(&mut self) -> Result<()> {
+        let remaining = self.remaining_fuel()?;
+        self.store.consume_fuel(remaining).map_err(|e| anyhow::anyhow!("{}", e))?;
+        self.store.add_fuel(self.gas_limit).map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok(())
+    }
+
+    fn remaining_fuel(&mut self) -> Result<u64> {
+        self.store.consume_fuel(0).map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    /// Record the gas burnt by an execution, reporting exhausted gas as such
+    fn metered<T>(&mut self, outcome: Result<T, wasmi::Error>) -> Result<T> {
+        self.gas_used = self.gas_limit - self.remaining_fuel()?;
+        match outcome {
+            Err(wasmi::Error::Trap(trap)) if matches!(trap.trap_code(), Some(TrapCode::OutOfFuel)) => {
+                self.gas_used = self.gas_limit;
+                Err(anyhow::anyhow!("Contract ran out of gas (limit {})", self.gas_limit))
+            }
+            outcome => Ok(outcome?),
+        }
+    }
 }
 
 impl Default for WasmRuntime {
@@ -94,4 +274,60 @@ mod tests {
         assert_eq!(i32::from_le_bytes(result.try_into().unwrap()), 42);
         Ok(())
     }
+
+    #[test]
+    fn test_contract_gas_limit() -> Result<()> {
+        let mut runtime = WasmRuntime::new();
+        let wasm = wat::parse_str(r#"
+            (module
+                (func (export "spin") (result i32)
+                    (loop $forever (br $forever))
+                    i32.const 0)
+                (func (export "one") (result i32)
+                    i32.const 1)
+            )
+        "#)?;
+        runtime.deploy(&wasm)?;
+
+        // An endless loop stops once its gas is spent
+        runtime.set_gas_limit(10_000);
+        let error = runtime.call_function("spin", &[]).unwrap_err();
+        assert!(error.to_string().contains("out of gas"));
+        assert_eq!(runtime.gas_used(), 10_000);
+
+        // and the next call starts with a fresh allowance
+        assert_eq!(runtime.call_function("one", &[])?, 1i32.to_le_bytes().to_vec());
+        assert!(runtime.gas_used() > 0 && runtime.gas_used() < 10_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_contract_events() -> Result<()> {
+        let wasm = wat::parse_str(r#"
+            (module
+                (import "zhtp" "emit" (func $emit (param i32 i32 i32 i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "Greeted")
+                (data (i32.const 16) "hello")
+                (func (export "main")
+                    (call $emit (i32.const 0) (i32.const 7) (i32.const 16) (i32.const 5)))
+            )
+        "#)?;
+        assert!(is_contract_code(&wasm));
+        assert!(!is_contract_code(b"memo"));
+
+        let mut runtime = WasmRuntime::new();
+        let events = runtime.execute(&wasm)?;
+        assert_eq!(events, vec![ContractEvent { name: "Greeted".to_string(), data: b"hello".to_vec() }]);
+        assert!(runtime.gas_used() >= EVENT_GAS_PER_BYTE * 12);
+
+        // Execution is deterministic, and a failing contract keeps no events
+        let gas_used = runtime.gas_used();
+        assert_eq!(WasmRuntime::new().execute(&wasm)?, events);
+        runtime.set_gas_limit(gas_used - 1);
+        assert!(runtime.execute(&wasm).is_err());
+        assert_eq!(runtime.gas_used(), gas_used - 1);
+        assert!(runtime.events().is_empty());
+        Ok(())
+    }
 }
\ No newline at end of file
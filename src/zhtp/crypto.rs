@@ -9,10 +9,22 @@ use pqcrypto_traits::{
     kem::{PublicKey as _, SecretKey as _, SharedSecret as _, Ciphertext as _},
 };
 use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 const KEY_ROTATION_INTERVAL: u64 = 24 * 60 * 60; // 24 hours in seconds
 
+/// Prefix of account addresses derived from Dilithium public keys
+pub const ADDRESS_PREFIX: &str = "zhtp_";
+
+/// Account address owned by a Dilithium public key: `zhtp_` followed by the
+/// hex SHA-256 of the key
+pub fn address_from_public_key(public_key: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    format!("{}{}", ADDRESS_PREFIX, hex::encode(Sha256::digest(public_key)))
+}
+
 /// Secure wrapper for secret key material that automatically zeroizes on drop
 struct SecureSecretKey {
     // Store raw key bytes instead of the library types for better security control
@@ -162,12 +174,82 @@ pub struct KeyStatus {
 pub struct Signature(Vec<u8>);
 
 /// Encapsulated key package
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct KeyPackage {
     kyber_ciphertext: Vec<u8>,
     timestamp: u64,
 }
 
+/// Data encrypted to a single Kyber768 public key: a fresh encapsulation plus
+/// the payload sealed under its shared secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SealedData {
+    package: KeyPackage,
+    ciphertext: Vec<u8>,
+}
+
+/// Read-only key opening whatever is sealed to a keypair's Kyber key: the
+/// Kyber key pair without the Dilithium signing key. It reveals what the
+/// keypair receives but cannot sign or spend.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IncomingViewingKey {
+    kyber_public: Vec<u8>,
+    kyber_secret: Vec<u8>,
+}
+
+impl IncomingViewingKey {
+    /// Kyber public key the viewed data is sealed to
+    pub fn kyber_public_key(&self) -> &[u8] {
+        &self.kyber_public
+    }
+
+    /// Decrypt data sealed to the viewed keypair
+    pub fn open_sealed(&self, sealed: &SealedData) -> Result<Vec<u8>> {
+        let secret = kyber768::SecretKey::from_bytes(&self.kyber_secret)
+            .map_err(|_| anyhow!("Invalid Kyber secret key"))?;
+        open_with(sealed, &secret)
+    }
+}
+
+impl Drop for IncomingViewingKey {
+    fn drop(&mut self) {
+        for byte in self.kyber_secret.iter_mut() {
+            *byte = 0;
+        }
+    }
+}
+
+impl std::fmt::Debug for IncomingViewingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IncomingViewingKey")
+            .field("kyber_public_len", &self.kyber_public.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Symmetric key a keypair encrypts its own copy of outgoing data under,
+/// derived from its Kyber secret key. Holders can read what the keypair sent.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutgoingViewingKey([u8; 32]);
+
+impl OutgoingViewingKey {
+    /// Encrypt data for later reading with this key
+    pub fn seal(&self, data: &[u8]) -> Result<Vec<u8>> {
+        aead_encrypt(data, &self.0)
+    }
+
+    /// Decrypt data sealed with this key
+    pub fn open(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        aead_decrypt(ciphertext, &self.0)
+    }
+}
+
+impl std::fmt::Debug for OutgoingViewingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("OutgoingViewingKey(..)")
+    }
+}
+
 impl Signature {
     pub fn empty() -> Self {
         Signature(Vec::new())
@@ -248,21 +330,62 @@ impl Keypair {
     /// Encapsulate a shared secret using Kyber
     pub fn encapsulate_key(&self) -> Result<(Vec<u8>, KeyPackage)> {
         self.check_rotation()?;
+        Ok(Self::encapsulate_to(&self.kyber_public))
+    }
 
+    /// Encapsulate a fresh shared secret to another party's Kyber public key
+    pub fn encapsulate_for(kyber_public_key: &[u8]) -> Result<(Vec<u8>, KeyPackage)> {
+        let public = kyber768::PublicKey::from_bytes(kyber_public_key)
+            .map_err(|_| anyhow!("Invalid Kyber public key"))?;
+        Ok(Self::encapsulate_to(&public))
+    }
+
+    fn encapsulate_to(public: &kyber768::PublicKey) -> (Vec<u8>, KeyPackage) {
         // Perform key encapsulation
-        let (shared_secret, ciphertext) = kyber768::encapsulate(&self.kyber_public);
+        let (shared_secret, ciphertext) = kyber768::encapsulate(public);
         
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
 
-        Ok((
+        (
             Vec::from(shared_secret.as_bytes()),
             KeyPackage {
                 kyber_ciphertext: Vec::from(ciphertext.as_bytes()),
                 timestamp: now,
             }
+        )
+    }
+
+    /// Encrypt data so that only the holder of `kyber_public_key` can read it
+    pub fn seal_for(kyber_public_key: &[u8], data: &[u8]) -> Result<SealedData> {
+        let (shared_secret, package) = Self::encapsulate_for(kyber_public_key)?;
+        Ok(SealedData {
+            package,
+            ciphertext: aead_encrypt(data, &shared_secret)?,
+        })
+    }
+
+    /// Decrypt data sealed to this keypair; fails for data sealed to anyone else
+    pub fn open_sealed(&self, sealed: &SealedData) -> Result<Vec<u8>> {
+        self.check_rotation()?;
+        open_with(sealed, &self.secure_secrets.get_kyber()?)
+    }
+
+    /// Viewing key that decrypts what is sealed to this keypair
+    pub fn incoming_viewing_key(&self) -> IncomingViewingKey {
+        IncomingViewingKey {
+            kyber_public: self.kyber_public_key(),
+            kyber_secret: self.secure_secrets.kyber_secret_bytes.clone(),
+        }
+    }
+
+    /// Viewing key for this keypair's copies of what it sends
+    pub fn outgoing_viewing_key(&self) -> OutgoingViewingKey {
+        OutgoingViewingKey(blake3::derive_key(
+            "ZHTP outgoing viewing key v1",
+            &self.secure_secrets.kyber_secret_bytes,
         ))
     }
 
@@ -319,11 +442,21 @@ impl Keypair {
         PublicKeyTrait::as_bytes(&self.public).to_vec()
     }
 
+    /// Account address owned by this keypair
+    pub fn address(&self) -> String {
+        address_from_public_key(&self.public_key())
+    }
+
     /// Get the Kyber public key for key exchange  
     pub fn get_kyber_public(&self) -> &kyber768::PublicKey {
         &self.kyber_public
     }
 
+    /// Kyber public key bytes others encrypt to
+    pub fn kyber_public_key(&self) -> Vec<u8> {
+        self.kyber_public.as_bytes().to_vec()
+    }
+
     /// Derive shared secret from another party's Kyber public key using encapsulation
     pub fn derive_shared_secret(&self, other_public: &kyber768::PublicKey) -> Result<[u8; 32]> {
         self.check_rotation()?;
@@ -382,55 +515,12 @@ impl Keypair {
 
     /// Encrypt data using ChaCha20-Poly1305 with a shared secret
     pub fn encrypt_data(&self, data: &[u8], shared_secret: &[u8]) -> Result<Vec<u8>> {
-        use chacha20poly1305::{ChaCha20Poly1305, KeyInit, AeadInPlace, Nonce};
-        use rand::RngCore;
-        
-        // Derive key from shared secret using BLAKE3
-        let key_hash = self.hash_message(shared_secret);
-        let cipher = ChaCha20Poly1305::new_from_slice(&key_hash)
-            .map_err(|_| anyhow!("Failed to create cipher"))?;
-        
-        // Generate random nonce
-        let mut nonce_bytes = [0u8; 12];
-        rand::thread_rng().fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
-        
-        // Prepare data for encryption
-        let mut buffer = data.to_vec();
-        
-        // Encrypt in place
-        cipher.encrypt_in_place(nonce, b"", &mut buffer)
-            .map_err(|_| anyhow!("Encryption failed"))?;
-        
-        // Prepend nonce to encrypted data
-        let mut result = nonce_bytes.to_vec();
-        result.extend_from_slice(&buffer);
-        
-        Ok(result)
+        aead_encrypt(data, shared_secret)
     }
 
     /// Decrypt data using ChaCha20-Poly1305 with a shared secret
     pub fn decrypt_data(&self, encrypted_data: &[u8], shared_secret: &[u8]) -> Result<Vec<u8>> {
-        use chacha20poly1305::{ChaCha20Poly1305, KeyInit, AeadInPlace, Nonce};
-        
-        if encrypted_data.len() < 12 {
-            return Err(anyhow!("Encrypted data too short"));
-        }
-        
-        // Derive key from shared secret using BLAKE3
-        let key_hash = self.hash_message(shared_secret);
-        let cipher = ChaCha20Poly1305::new_from_slice(&key_hash)
-            .map_err(|_| anyhow!("Failed to create cipher"))?;
-        
-        // Extract nonce and encrypted data
-        let nonce = Nonce::from_slice(&encrypted_data[..12]);
-        let mut buffer = encrypted_data[12..].to_vec();
-        
-        // Decrypt in place
-        cipher.decrypt_in_place(nonce, b"", &mut buffer)
-            .map_err(|_| anyhow!("Decryption failed"))?;
-        
-        Ok(buffer)
+        aead_decrypt(encrypted_data, shared_secret)
     }
 
     /// Get the secret key bytes for this keypair (RESTRICTED - only for blockchain integration)
@@ -446,6 +536,157 @@ impl Keypair {
     pub fn can_sign_for(&self, public_key_bytes: &[u8]) -> bool {
         self.public_key() == public_key_bytes
     }
+
+    /// Write the keypair to a new key file at `path`, readable only by its
+    /// owner on Unix. An existing file is never overwritten.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        use std::io::Write;
+
+        let path = path.as_ref();
+        let key_file = KeyFile {
+            dilithium_public: hex::encode(self.public_key()),
+            dilithium_secret: hex::encode(&self.secure_secrets.dilithium_secret_bytes),
+            kyber_public: hex::encode(self.kyber_public_key()),
+            kyber_secret: hex::encode(&self.secure_secrets.kyber_secret_bytes),
+        };
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options
+            .open(path)
+            .map_err(|e| anyhow!("Failed to create key file {}: {}", path.display(), e))?;
+        file.write_all(&serde_json::to_vec_pretty(&key_file)?)?;
+        file.sync_all()?;
+        Ok(())
+    }
+
+    /// Read a keypair written by [`Keypair::save`]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read(path).map_err(|e| anyhow!("Failed to read key file {}: {}", path.display(), e))?;
+        let key_file: KeyFile = serde_json::from_slice(&contents)
+            .map_err(|e| anyhow!("Invalid key file {}: {}", path.display(), e))?;
+        let decode = |value: &str| hex::decode(value).map_err(|_| anyhow!("Invalid key encoding in {}", path.display()));
+
+        let public = PublicKey::from_bytes(&decode(&key_file.dilithium_public)?)
+            .map_err(|_| anyhow!("Invalid Dilithium public key in {}", path.display()))?;
+        let kyber_public = kyber768::PublicKey::from_bytes(&decode(&key_file.kyber_public)?)
+            .map_err(|_| anyhow!("Invalid Kyber public key in {}", path.display()))?;
+        let secure_secrets = SecureSecretKey {
+            dilithium_secret_bytes: decode(&key_file.dilithium_secret)?,
+            kyber_secret_bytes: decode(&key_file.kyber_secret)?,
+        };
+        secure_secrets.get_dilithium()?;
+        secure_secrets.get_kyber()?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let keypair = Keypair {
+            public,
+            kyber_public,
+            secure_secrets,
+            created_at: now,
+            rotation_due: now + KEY_ROTATION_INTERVAL,
+        };
+
+        // The secret key must belong to the stored public key
+        let probe = b"zhtp key file check";
+        if !keypair.verify(probe, &keypair.sign(probe)?)? {
+            return Err(anyhow!("Secret key in {} does not match its public key", path.display()));
+        }
+        Ok(keypair)
+    }
+
+    /// Load the keypair at `path`, generating and saving a new one there if
+    /// the file does not exist yet
+    pub fn load_or_generate<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Self::load(path);
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let keypair = Self::generate();
+        keypair.save(path)?;
+        Ok(keypair)
+    }
+}
+
+/// Keypair as stored in a key file, every key hex encoded
+#[derive(Serialize, Deserialize)]
+struct KeyFile {
+    dilithium_public: String,
+    dilithium_secret: String,
+    kyber_public: String,
+    kyber_secret: String,
+}
+
+/// Decapsulate a sealed payload's key with a Kyber secret key and decrypt it
+fn open_with(sealed: &SealedData, kyber_secret: &kyber768::SecretKey) -> Result<Vec<u8>> {
+    let ct = kyber768::Ciphertext::from_bytes(&sealed.package.kyber_ciphertext)
+        .map_err(|_| anyhow!("Invalid Kyber ciphertext"))?;
+    let shared_secret = kyber768::decapsulate(&ct, kyber_secret);
+    aead_decrypt(&sealed.ciphertext, shared_secret.as_bytes())
+}
+
+/// ChaCha20-Poly1305 under a BLAKE3-derived key, nonce prepended
+fn aead_encrypt(data: &[u8], shared_secret: &[u8]) -> Result<Vec<u8>> {
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit, AeadInPlace, Nonce};
+    use rand::RngCore;
+    
+    // Derive key from shared secret using BLAKE3
+    let key_hash: [u8; 32] = blake3::hash(shared_secret).into();
+    let cipher = ChaCha20Poly1305::new_from_slice(&key_hash)
+        .map_err(|_| anyhow!("Failed to create cipher"))?;
+    
+    // Generate random nonce
+    let mut nonce_bytes = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce_bytes);
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    
+    // Prepare data for encryption
+    let mut buffer = data.to_vec();
+    
+    // Encrypt in place
+    cipher.encrypt_in_place(nonce, b"", &mut buffer)
+        .map_err(|_| anyhow!("Encryption failed"))?;
+    
+    // Prepend nonce to encrypted data
+    let mut result = nonce_bytes.to_vec();
+    result.extend_from_slice(&buffer);
+    
+    Ok(result)
+}
+
+/// Inverse of [`aead_encrypt`]
+fn aead_decrypt(encrypted_data: &[u8], shared_secret: &[u8]) -> Result<Vec<u8>> {
+    use chacha20poly1305::{ChaCha20Poly1305, KeyInit, AeadInPlace, Nonce};
+    
+    if encrypted_data.len() < 12 {
+        return Err(anyhow!("Encrypted data too short"));
+    }
+    
+    // Derive key from shared secret using BLAKE3
+    let key_hash: [u8; 32] = blake3::hash(shared_secret).into();
+    let cipher = ChaCha20Poly1305::new_from_slice(&key_hash)
+        .map_err(|_| anyhow!("Failed to create cipher"))?;
+    
+    // Extract nonce and encrypted data
+    let nonce = Nonce::from_slice(&encrypted_data[..12]);
+    let mut buffer = encrypted_data[12..].to_vec();
+    
+    // Decrypt in place
+    cipher.decrypt_in_place(nonce, b"", &mut buffer)
+        .map_err(|_| anyhow!("Decryption failed"))?;
+    
+    Ok(buffer)
 }
 
 #[cfg(test)]
@@ -466,6 +707,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_key_file_roundtrip() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("zhtp_key_{}.json", uuid::Uuid::new_v4()));
+        let keypair = Keypair::load_or_generate(&path)?;
+
+        // The same identity comes back and can still sign
+        let loaded = Keypair::load_or_generate(&path)?;
+        assert_eq!(loaded.address(), keypair.address());
+        assert_eq!(loaded.kyber_public_key(), keypair.kyber_public_key());
+        let signature = loaded.sign(b"snapshot manifest")?;
+        assert!(keypair.verify(b"snapshot manifest", &signature)?);
+
+        // An existing key file is never replaced
+        assert!(Keypair::generate().save(&path).is_err());
+        assert_eq!(Keypair::load(&path)?.address(), keypair.address());
+
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
     #[test]
     fn test_key_encapsulation() -> Result<()> {
         let alice_keypair = Keypair::generate();
@@ -483,6 +744,36 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_sealed_data() -> Result<()> {
+        let bob = Keypair::generate();
+        let eve = Keypair::generate();
+
+        let sealed = Keypair::seal_for(&bob.kyber_public_key(), b"for bob only")?;
+        assert_eq!(bob.open_sealed(&sealed)?, b"for bob only");
+        assert!(eve.open_sealed(&sealed).is_err());
+        assert!(Keypair::seal_for(&[0u8; 7], b"x").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_viewing_keys() -> Result<()> {
+        let bob = Keypair::generate();
+        let sealed = Keypair::seal_for(&bob.kyber_public_key(), b"for bob only")?;
+        let incoming = bob.incoming_viewing_key();
+        assert_eq!(incoming.kyber_public_key(), bob.kyber_public_key());
+        assert_eq!(incoming.open_sealed(&sealed)?, b"for bob only");
+        assert!(Keypair::generate().incoming_viewing_key().open_sealed(&sealed).is_err());
+
+        let outgoing = bob.outgoing_viewing_key();
+        assert_eq!(outgoing, bob.clone().outgoing_viewing_key());
+        assert_eq!(outgoing.open(&outgoing.seal(b"sent by bob")?)?, b"sent by bob");
+        assert!(Keypair::generate().outgoing_viewing_key().open(&outgoing.seal(b"x")?).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_different_keypairs() -> Result<()> {
         let keypair1 = Keypair::generate();
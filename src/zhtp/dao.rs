@@ -1,9 +1,11 @@
 use crate::zhtp::{
+    amount::Amount,
     crypto::Keypair,
-    zk_proofs::{ByteRoutingProof, RoutingProof},
+    proof_envelope::{ProofEnvelope, ProofStatement, VerifierRegistry},
     dns::ZhtpDNS,
     economics::ZhtpEconomics,
 };
+use crate::blockchain::{Blockchain, ProtocolParams};
 use crate::storage::ZhtpStorageManager;
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
@@ -43,19 +45,19 @@ pub struct ZhtpDao {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaoTreasury {
     /// Total treasury balance in ZHTP tokens
-    pub total_balance: u64,
+    pub total_balance: Amount,
     /// UBI fund allocation (40% of fees)
-    pub ubi_fund: u64,
+    pub ubi_fund: Amount,
     /// Healthcare fund (20% of fees)
-    pub healthcare_fund: u64,
+    pub healthcare_fund: Amount,
     /// Education fund (15% of fees)
-    pub education_fund: u64,
+    pub education_fund: Amount,
     /// Housing fund (15% of fees)
-    pub housing_fund: u64,
+    pub housing_fund: Amount,
     /// Infrastructure fund (10% of fees)
-    pub infrastructure_fund: u64,
+    pub infrastructure_fund: Amount,
     /// Emergency reserve fund
-    pub emergency_reserve: u64,
+    pub emergency_reserve: Amount,
     /// Monthly fund allocation history
     pub allocation_history: Vec<MonthlyAllocation>,
 }
@@ -64,11 +66,11 @@ pub struct DaoTreasury {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MonthlyAllocation {
     pub month: u64, // Unix timestamp
-    pub ubi_distributed: u64,
-    pub healthcare_spent: u64,
-    pub education_spent: u64,
-    pub housing_spent: u64,
-    pub infrastructure_spent: u64,
+    pub ubi_distributed: Amount,
+    pub healthcare_spent: Amount,
+    pub education_spent: Amount,
+    pub housing_spent: Amount,
+    pub infrastructure_spent: Amount,
     pub beneficiaries_count: u64,
 }
 
@@ -78,7 +80,7 @@ pub struct ZkIdentity {
     /// Anonymous identity commitment
     pub identity_commitment: [u8; 32],
     /// Proof of personhood (prevents Sybil attacks)
-    pub personhood_proof: ByteRoutingProof,
+    pub personhood_proof: ProofEnvelope,
     /// Voting power based on network contribution
     pub voting_power: u64,
     /// Registration timestamp
@@ -100,11 +102,7 @@ impl ZkIdentity {
         hasher.update(user_id.as_bytes());
         let identity_commitment = hasher.finalize().into();
         
-        let personhood_proof = ByteRoutingProof {
-            inputs: vec![vec![1u8; 32]],
-            elements: vec![vec![0u8; 64]],
-            commitments: vec![vec![0u8; 32]],
-        };
+        let personhood_proof = ProofEnvelope::none();
         
         Ok(Self {
             identity_commitment,
@@ -140,7 +138,10 @@ pub struct GovernanceProposal {
     /// Required quorum percentage
     pub quorum_required: f64,
     /// Funds requested (if applicable)
-    pub funds_requested: Option<u64>,
+    pub funds_requested: Option<Amount>,
+    /// Block limits a protocol upgrade puts in force once passed
+    #[serde(default)]
+    pub protocol_params: Option<ProtocolParams>,
 }
 
 /// Types of governance proposals
@@ -173,7 +174,7 @@ pub struct VoteTally {
 }
 
 /// Proposal execution status
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ProposalStatus {
     Active,
     Passed,
@@ -189,7 +190,7 @@ pub struct VotingRecord {
     /// Anonymous vote commitments (cannot be traced to individuals)
     pub vote_commitments: Vec<[u8; 32]>,
     /// ZK proof of valid voting
-    pub validity_proof: ByteRoutingProof,
+    pub validity_proof: ProofEnvelope,
     /// Vote tally
     pub final_tally: VoteTally,
 }
@@ -198,7 +199,7 @@ pub struct VotingRecord {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UbiSystem {
     /// Monthly UBI amount per eligible person
-    pub monthly_ubi_amount: u64,
+    pub monthly_ubi_amount: Amount,
     /// Total registered beneficiaries
     pub registered_beneficiaries: u64,
     /// UBI distribution history
@@ -213,10 +214,10 @@ pub struct UbiSystem {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UbiDistribution {
     pub month: u64,
-    pub amount_per_person: u64,
-    pub total_distributed: u64,
+    pub amount_per_person: Amount,
+    pub total_distributed: Amount,
     pub beneficiaries_count: u64,
-    pub distribution_proof: ByteRoutingProof,
+    pub distribution_proof: ProofEnvelope,
 }
 
 /// UBI eligibility criteria
@@ -236,17 +237,17 @@ pub struct UbiEligibility {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeIncentiveProgram {
     /// Base reward for running a node (per month)
-    pub base_node_reward: u64,
+    pub base_node_reward: Amount,
     /// Performance multipliers
     pub performance_multipliers: HashMap<String, f64>,
     /// Onboarding bonus for new nodes
-    pub onboarding_bonus: u64,
+    pub onboarding_bonus: Amount,
     /// Minimum uptime requirement (percentage)
     pub min_uptime_requirement: f64,
     /// Active node count
     pub active_nodes: u64,
     /// Total rewards distributed
-    pub total_rewards_distributed: u64,
+    pub total_rewards_distributed: Amount,
 }
 
 /// DAO configuration settings
@@ -255,7 +256,7 @@ pub struct DaoConfig {
     /// DAO domain name in ZHTP DNS (e.g., "dao.zhtp")
     pub dao_domain: String,
     /// Minimum tokens required to submit a proposal
-    pub min_proposal_stake: u64,
+    pub min_proposal_stake: Amount,
     /// Voting period duration in seconds
     pub voting_period_seconds: u64,
     /// Minimum participation rate required for quorum
@@ -274,7 +275,7 @@ impl Default for DaoConfig {
     fn default() -> Self {
         Self {
             dao_domain: "dao.zhtp".to_string(),
-            min_proposal_stake: 1000, // 1000 ZHTP tokens
+            min_proposal_stake: Amount::from_zhtp(1000), // 1000 ZHTP tokens
             voting_period_seconds: 7 * 24 * 60 * 60, // 7 days
             min_quorum_percentage: 10.0, // 10% participation required
             ubi_distribution_interval: 30 * 24 * 60 * 60, // Monthly
@@ -296,18 +297,18 @@ impl ZhtpDao {
         let config = config.unwrap_or_default();
         
         let treasury = DaoTreasury {
-            total_balance: 0,
-            ubi_fund: 0,
-            healthcare_fund: 0,
-            education_fund: 0,
-            housing_fund: 0,
-            infrastructure_fund: 0,
-            emergency_reserve: 0,
+            total_balance: Amount::ZERO,
+            ubi_fund: Amount::ZERO,
+            healthcare_fund: Amount::ZERO,
+            education_fund: Amount::ZERO,
+            housing_fund: Amount::ZERO,
+            infrastructure_fund: Amount::ZERO,
+            emergency_reserve: Amount::ZERO,
             allocation_history: Vec::new(),
         };
 
         let ubi_system = UbiSystem {
-            monthly_ubi_amount: 1000_000, // 1000 ZHTP tokens per month
+            monthly_ubi_amount: Amount::from_zhtp(1000), // 1000 ZHTP tokens per month
             registered_beneficiaries: 0,
             distribution_history: Vec::new(),
             eligibility_criteria: UbiEligibility {
@@ -320,17 +321,17 @@ impl ZhtpDao {
         };
 
         let node_incentives = NodeIncentiveProgram {
-            base_node_reward: 500_000, // 500 ZHTP per month
+            base_node_reward: Amount::from_zhtp(500), // 500 ZHTP per month
             performance_multipliers: HashMap::from([
                 ("uptime_99".to_string(), 1.5),
                 ("high_bandwidth".to_string(), 1.3),
                 ("low_latency".to_string(), 1.2),
                 ("storage_provider".to_string(), 1.4),
             ]),
-            onboarding_bonus: 100_000, // 100 ZHTP for new nodes
+            onboarding_bonus: Amount::from_zhtp(100), // 100 ZHTP for new nodes
             min_uptime_requirement: 95.0, // 95% uptime required
             active_nodes: 0,
-            total_rewards_distributed: 0,
+            total_rewards_distributed: Amount::ZERO,
         };
 
         let dao = Self {
@@ -369,6 +370,12 @@ impl ZhtpDao {
 
     /// Submit a governance proposal with ZHTP storage
     pub async fn submit_proposal(&self, proposal: GovernanceProposal) -> Result<u64> {
+        if let Some(params) = &proposal.protocol_params {
+            if !matches!(proposal.proposal_type, ProposalType::ProtocolUpgrade) {
+                return Err(anyhow::anyhow!("Only protocol upgrades can change protocol parameters"));
+            }
+            params.validate()?;
+        }
         let mut proposals = self.proposals.write().await;
         let proposal_id = proposals.len() as u64 + 1;
         
@@ -414,7 +421,7 @@ impl ZhtpDao {
         proposal_id: u64,
         voter_identity: &[u8; 32],
         vote: Vote,
-        vote_proof: ByteRoutingProof,
+        vote_proof: ProofEnvelope,
     ) -> Result<()> {
         // Verify voter eligibility
         let registry = self.identity_registry.read().await;
@@ -443,18 +450,94 @@ impl ZhtpDao {
         Ok(())
     }
 
+    /// Close voting on a proposal past its deadline. It passes with quorum
+    /// and more yes than no votes; a passed protocol upgrade is executed by
+    /// submitting its parameters to `blockchain` as an upgrade signed by
+    /// `governance`, the chain's governance key. The upgrade takes effect
+    /// once the chain reaches its activation height.
+    pub async fn execute_proposal(
+        &self,
+        proposal_id: u64,
+        blockchain: &Blockchain,
+        governance: &Keypair,
+    ) -> Result<ProposalStatus> {
+        let eligible_power: u64 = self.identity_registry.read().await.values().map(|identity| identity.voting_power).sum();
+        let mut proposals = self.proposals.write().await;
+        let proposal = proposals.get_mut(&proposal_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown proposal #{}", proposal_id))?;
+        if proposal.status != ProposalStatus::Active {
+            return Err(anyhow::anyhow!("Proposal #{} is already {:?}", proposal_id, proposal.status));
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        if now < proposal.voting_deadline {
+            return Err(anyhow::anyhow!("Voting on proposal #{} is still open", proposal_id));
+        }
+
+        let tally = &mut proposal.vote_tally;
+        tally.participation_rate = match eligible_power {
+            0 => 0.0,
+            power => tally.total_voting_power as f64 * 100.0 / power as f64,
+        };
+        let quorum = proposal.quorum_required.max(self.config.min_quorum_percentage);
+        if tally.participation_rate < quorum || tally.yes_votes <= tally.no_votes {
+            proposal.status = ProposalStatus::Rejected;
+            println!("❌ Proposal #{} rejected", proposal_id);
+            return Ok(ProposalStatus::Rejected);
+        }
+
+        // An upgrade that cannot be submitted leaves the proposal active
+        proposal.status = ProposalStatus::Passed;
+        if let (ProposalType::ProtocolUpgrade, Some(params)) = (&proposal.proposal_type, proposal.protocol_params) {
+            let upgrade = match blockchain.schedule_protocol_params(params, governance).await {
+                Ok(upgrade) => upgrade,
+                Err(e) => {
+                    proposal.status = ProposalStatus::Active;
+                    return Err(e);
+                }
+            };
+            proposal.status = ProposalStatus::Executed;
+            println!(
+                "⚙️ Protocol upgrade #{} executed: blocks limited to {} bytes and {} gas from height {}",
+                proposal_id, params.max_block_bytes, params.max_block_gas, upgrade.activation_height
+            );
+        }
+        Ok(proposal.status.clone())
+    }
+
+    /// Execute every active proposal whose voting deadline has passed,
+    /// returning the status each ended up with
+    pub async fn execute_due_proposals(
+        &self,
+        blockchain: &Blockchain,
+        governance: &Keypair,
+    ) -> Vec<(u64, Result<ProposalStatus>)> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        let mut due: Vec<u64> = self.proposals.read().await
+            .values()
+            .filter(|proposal| proposal.status == ProposalStatus::Active && proposal.voting_deadline <= now)
+            .map(|proposal| proposal.id)
+            .collect();
+        due.sort_unstable();
+        let mut executed = Vec::with_capacity(due.len());
+        for id in due {
+            executed.push((id, self.execute_proposal(id, blockchain, governance).await));
+        }
+        executed
+    }
+
     /// Process transaction fee for DAO treasury
-    pub async fn process_transaction_fee(&self, fee_amount: u64) -> Result<()> {
+    pub async fn process_transaction_fee(&self, fee_amount: Amount) -> Result<()> {
         let mut treasury = self.treasury.write().await;
+        let share = |percent: u64| fee_amount.mul_div(percent, 100).unwrap_or(Amount::ZERO);
         
         // Allocate fees to different funds
-        treasury.ubi_fund += (fee_amount * 40) / 100; // 40% to UBI
-        treasury.healthcare_fund += (fee_amount * 20) / 100; // 20% to healthcare
-        treasury.education_fund += (fee_amount * 15) / 100; // 15% to education
-        treasury.housing_fund += (fee_amount * 15) / 100; // 15% to housing
-        treasury.infrastructure_fund += (fee_amount * 10) / 100; // 10% to infrastructure
+        treasury.ubi_fund = treasury.ubi_fund.saturating_add(share(40)); // 40% to UBI
+        treasury.healthcare_fund = treasury.healthcare_fund.saturating_add(share(20)); // 20% to healthcare
+        treasury.education_fund = treasury.education_fund.saturating_add(share(15)); // 15% to education
+        treasury.housing_fund = treasury.housing_fund.saturating_add(share(15)); // 15% to housing
+        treasury.infrastructure_fund = treasury.infrastructure_fund.saturating_add(share(10)); // 10% to infrastructure
         
-        treasury.total_balance += fee_amount;
+        treasury.total_balance = treasury.total_balance.saturating_add(fee_amount);
         
         Ok(())
     }
@@ -464,21 +547,19 @@ impl ZhtpDao {
         let mut ubi_system = self.ubi_system.write().await;
         let mut treasury = self.treasury.write().await;
         
-        let total_distribution = ubi_system.monthly_ubi_amount * ubi_system.registered_beneficiaries;
+        let total_distribution = ubi_system.monthly_ubi_amount
+            .checked_mul(ubi_system.registered_beneficiaries)
+            .ok_or_else(|| anyhow::anyhow!("UBI distribution amount overflows"))?;
         
-        if treasury.ubi_fund >= total_distribution {
-            treasury.ubi_fund -= total_distribution;
+        if let Some(remaining) = treasury.ubi_fund.checked_sub(total_distribution) {
+            treasury.ubi_fund = remaining;
             
             let distribution = UbiDistribution {
                 month: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
                 amount_per_person: ubi_system.monthly_ubi_amount,
                 total_distributed: total_distribution,
                 beneficiaries_count: ubi_system.registered_beneficiaries,
-                distribution_proof: ByteRoutingProof {
-                    commitments: vec![total_distribution.to_le_bytes().to_vec()],
-                    elements: vec![ubi_system.registered_beneficiaries.to_le_bytes().to_vec()],
-                    inputs: vec![],
-                },
+                distribution_proof: ProofEnvelope::none(),
             };
             
             ubi_system.distribution_history.push(distribution);
@@ -496,9 +577,9 @@ impl ZhtpDao {
         let mut treasury = self.treasury.write().await;
         
         // Give onboarding bonus
-        if treasury.infrastructure_fund >= incentives.onboarding_bonus {
-            treasury.infrastructure_fund -= incentives.onboarding_bonus;
-            incentives.total_rewards_distributed += incentives.onboarding_bonus;
+        if let Some(remaining) = treasury.infrastructure_fund.checked_sub(incentives.onboarding_bonus) {
+            treasury.infrastructure_fund = remaining;
+            incentives.total_rewards_distributed = incentives.total_rewards_distributed.saturating_add(incentives.onboarding_bonus);
             incentives.active_nodes += 1;
             
             println!("🎉 Node {} registered! Onboarding bonus: {} ZHTP", 
@@ -520,7 +601,7 @@ impl ZhtpDao {
         DaoStats {
             total_treasury_balance: treasury.total_balance,
             ubi_fund_balance: treasury.ubi_fund,
-            monthly_ubi_distributed: ubi_system.monthly_ubi_amount * ubi_system.registered_beneficiaries,
+            monthly_ubi_distributed: ubi_system.monthly_ubi_amount.saturating_mul(ubi_system.registered_beneficiaries),
             registered_voters: registry.len() as u64,
             active_nodes: incentives.active_nodes,
             total_node_rewards: incentives.total_rewards_distributed,
@@ -530,58 +611,50 @@ impl ZhtpDao {
     pub async fn get_treasury_status(&self) -> Result<DaoTreasury> {
         // Return a simplified treasury status
         Ok(DaoTreasury {
-            total_balance: 1_000_000,
-            ubi_fund: 500_000,
-            healthcare_fund: 100_000,
-            education_fund: 100_000,
-            housing_fund: 100_000,
-            infrastructure_fund: 100_000,
-            emergency_reserve: 100_000,
+            total_balance: Amount::from_zhtp(1000),
+            ubi_fund: Amount::from_zhtp(500),
+            healthcare_fund: Amount::from_zhtp(100),
+            education_fund: Amount::from_zhtp(100),
+            housing_fund: Amount::from_zhtp(100),
+            infrastructure_fund: Amount::from_zhtp(100),
+            emergency_reserve: Amount::from_zhtp(100),
             allocation_history: Vec::new(),
         })    }
 
     /// Helper functions for proof verification
-    async fn verify_personhood_proof(&self, proof: &ByteRoutingProof) -> Result<bool> {
-        // Convert ByteRoutingProof to RoutingProof and verify
-        match RoutingProof::try_from(proof.clone()) {
-            Ok(native_proof) => {
-                // Verify personhood proof - this should validate unique human identity
-                let valid = crate::zhtp::zk_proofs::verify_unified_proof(
-                    &native_proof,
-                    b"personhood", // Standard source for personhood proofs
-                    b"verified",   // Standard destination for verified identity
-                    [1u8; 32]     // Non-zero root for personhood verification
-                );
-                Ok(valid)
-            }
-            Err(_) => {
-                log::warn!("Failed to convert personhood proof to RoutingProof");
+    async fn verify_personhood_proof(&self, proof: &ProofEnvelope) -> Result<bool> {
+        // Verify personhood proof - this should validate unique human identity
+        let statement = ProofStatement::Route {
+            source: b"personhood",      // Standard source for personhood proofs
+            destination: b"verified",   // Standard destination for verified identity
+            stored_data_root: [1u8; 32], // Non-zero root for personhood verification
+        };
+        match VerifierRegistry::global().verify(proof, &statement) {
+            Ok(valid) => Ok(valid),
+            Err(e) => {
+                log::warn!("Cannot verify personhood proof: {}", e);
                 Ok(false)
             }
         }
     }
 
-    async fn verify_vote_proof(&self, proof: &ByteRoutingProof, voter: &[u8; 32], proposal_id: u64) -> Result<bool> {
-        // Convert ByteRoutingProof to RoutingProof and verify
-        match RoutingProof::try_from(proof.clone()) {
-            Ok(native_proof) => {
-                // Create unique source/destination from voter and proposal
-                let mut source = [0u8; 8];
-                source.copy_from_slice(&voter[0..8]);
-                let mut dest = [0u8; 8];
-                dest.copy_from_slice(&proposal_id.to_le_bytes());
-                
-                // Verify vote proof prevents double voting
-                let valid = crate::zhtp::zk_proofs::verify_unified_proof(
-                    &native_proof,
-                    &source,
-                    &dest,
-                    *voter // Use voter identity as data root
-                );
-                Ok(valid)
-            }
-            Err(_) => {
-                log::warn!("Failed to convert vote proof to RoutingProof");
+    async fn verify_vote_proof(&self, proof: &ProofEnvelope, voter: &[u8; 32], proposal_id: u64) -> Result<bool> {
+        // Create unique source/destination from voter and proposal
+        let mut source = [0u8; 8];
+        source.copy_from_slice(&voter[0..8]);
+        let mut dest = [0u8; 8];
+        dest.copy_from_slice(&proposal_id.to_le_bytes());
+
+        // Verify vote proof prevents double voting
+        let statement = ProofStatement::Route {
+            source: &source,
+            destination: &dest,
+            stored_data_root: *voter, // Use voter identity as data root
+        };
+        match VerifierRegistry::global().verify(proof, &statement) {
+            Ok(valid) => Ok(valid),
+            Err(e) => {
+                log::warn!("Cannot verify vote proof: {}", e);
                 Ok(false)
             }
         }
@@ -687,12 +760,12 @@ pub enum Vote {
 /// DAO statistics for transparency
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DaoStats {
-    pub total_treasury_balance: u64,
-    pub ubi_fund_balance: u64,
-    pub monthly_ubi_distributed: u64,
+    pub total_treasury_balance: Amount,
+    pub ubi_fund_balance: Amount,
+    pub monthly_ubi_distributed: Amount,
     pub registered_voters: u64,
     pub active_nodes: u64,
-    pub total_node_rewards: u64,
+    pub total_node_rewards: Amount,
 }
 
 #[cfg(test)]
@@ -714,7 +787,7 @@ mod tests {
         
         let dao = ZhtpDao::new(dns_service, storage_manager, economics, None).await.unwrap();
         let stats = dao.get_dao_stats().await;
-        assert_eq!(stats.total_treasury_balance, 0);
+        assert_eq!(stats.total_treasury_balance, Amount::ZERO);
         assert_eq!(stats.registered_voters, 0);
     }
 
@@ -732,12 +805,85 @@ mod tests {
         let economics = Arc::new(crate::zhtp::economics::ZhtpEconomics::new());
         
         let dao = ZhtpDao::new(dns_service, storage_manager, economics, None).await.unwrap();
-        dao.process_transaction_fee(1000).await.unwrap();
+        dao.process_transaction_fee(Amount::from_zhtp(1000)).await.unwrap();
         
         let treasury = dao.treasury.read().await;
-        assert_eq!(treasury.ubi_fund, 400); // 40% of 1000
-        assert_eq!(treasury.healthcare_fund, 200); // 20% of 1000
-        assert_eq!(treasury.education_fund, 150); // 15% of 1000
+        assert_eq!(treasury.ubi_fund, Amount::from_zhtp(400)); // 40% of 1000
+        assert_eq!(treasury.healthcare_fund, Amount::from_zhtp(200)); // 20% of 1000
+        assert_eq!(treasury.education_fund, Amount::from_zhtp(150)); // 15% of 1000
+    }
+
+    #[tokio::test]
+    async fn test_protocol_upgrade_sets_block_limits() -> Result<()> {
+        let dns_service = Arc::new(RwLock::new(crate::zhtp::dns::ZhtpDNS::new()));
+        let keypair = crate::zhtp::crypto::Keypair::generate();
+        let storage_manager = Arc::new(crate::storage::ZhtpStorageManager::new(
+            dns_service.clone(),
+            crate::storage::StorageConfig::default(),
+            keypair,
+        ).await);
+        let economics = Arc::new(crate::zhtp::economics::ZhtpEconomics::new());
+        let config = DaoConfig { store_proposals_on_chain: false, ..DaoConfig::default() };
+        let dao = ZhtpDao::new(dns_service, storage_manager, economics, Some(config)).await?;
+        let governance = crate::zhtp::crypto::Keypair::generate();
+        let spec = crate::genesis::GenesisSpec { governance: Some(governance.address()), ..Default::default() };
+        let blockchain = Blockchain::from_genesis(&spec);
+
+        // One registered voter holding all voting power
+        let voter = ZkIdentity::new("voter".to_string()).await?;
+        let voting_power = voter.voting_power;
+        dao.identity_registry.write().await.insert("voter".to_string(), voter);
+
+        let params = ProtocolParams { max_block_bytes: 64 * 1024, max_block_gas: 500_000 };
+        let proposal = |protocol_params| GovernanceProposal {
+            id: 0,
+            title: "Smaller blocks".to_string(),
+            description: String::new(),
+            proposal_type: ProposalType::ProtocolUpgrade,
+            proposer: [0; 32],
+            voting_deadline: 0,
+            vote_tally: VoteTally {
+                yes_votes: 0,
+                no_votes: 0,
+                abstain_votes: 0,
+                total_voting_power: 0,
+                participation_rate: 0.0,
+            },
+            status: ProposalStatus::Active,
+            quorum_required: 50.0,
+            funds_requested: None,
+            protocol_params,
+        };
+        let unusable = ProtocolParams { max_block_gas: 1, ..params };
+        assert!(dao.submit_proposal(proposal(Some(unusable))).await.is_err());
+
+        // Without votes the upgrade misses its quorum
+        let ignored = dao.submit_proposal(proposal(Some(params))).await?;
+        let executed = dao.execute_due_proposals(&blockchain, &governance).await;
+        assert!(matches!(executed.as_slice(), [(id, Ok(ProposalStatus::Rejected))] if *id == ignored));
+        assert!(blockchain.get_pending_transactions().await.is_empty());
+
+        let id = dao.submit_proposal(proposal(Some(params))).await?;
+        if let Some(proposal) = dao.proposals.write().await.get_mut(&id) {
+            proposal.vote_tally.yes_votes = voting_power;
+            proposal.vote_tally.total_voting_power = voting_power;
+        }
+        // Only the governance key can execute the upgrade
+        let stranger = crate::zhtp::crypto::Keypair::generate();
+        assert!(dao.execute_proposal(id, &blockchain, &stranger).await.is_err());
+        assert_eq!(dao.execute_proposal(id, &blockchain, &governance).await?, ProposalStatus::Executed);
+        assert!(dao.execute_proposal(id, &blockchain, &governance).await.is_err());
+
+        // The upgrade is committed by the next block and activates later
+        assert_eq!(blockchain.get_pending_transactions().await.len(), 1);
+        blockchain.create_block("validator1", 1.0, None).await;
+        let schedule = blockchain.params_schedule().await;
+        let (&activation_height, &scheduled) = schedule.upgrades.iter().next().expect("upgrade was committed");
+        assert_eq!(scheduled, params);
+        assert!(activation_height > 1 + crate::blockchain::PARAMS_ACTIVATION_DELAY);
+        assert_eq!(blockchain.protocol_params().await, ProtocolParams::default());
+        assert_eq!(schedule.params_at(activation_height), params);
+        Ok(())
     }
 
     #[tokio::test]
@@ -756,7 +902,7 @@ mod tests {
         let dao = ZhtpDao::new(dns_service, storage_manager, economics, None).await.unwrap();
         
         // Add some funds first
-        dao.process_transaction_fee(1000000).await.unwrap();
+        dao.process_transaction_fee(Amount::from_zhtp(1000)).await.unwrap();
         
         dao.register_node("test_node_1".to_string(), keypair).await.unwrap();
         
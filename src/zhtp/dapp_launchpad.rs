@@ -1,4 +1,4 @@
-use crate::zhtp::zk_proofs::ByteRoutingProof;
+use crate::zhtp::proof_envelope::ProofEnvelope;
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
 use std::{
@@ -60,7 +60,7 @@ pub struct DeveloperInfo {
     /// Display name (optional)
     pub display_name: Option<String>,
     /// ZK proof of identity (prevents spam)
-    pub identity_proof: ByteRoutingProof,
+    pub identity_proof: ProofEnvelope,
     /// Developer reputation score
     pub reputation: f64,
     /// Contact methods (encrypted)
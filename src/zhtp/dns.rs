@@ -1,5 +1,7 @@
 use crate::zhtp::{
-    zk_proofs::{ByteRoutingProof},
+    proof_envelope::{ProofEnvelope, ProofStatement, VerifierRegistry},
+    prover::ProofCancellation,
+    zk_proofs::UnifiedCircuit,
     crypto::{Keypair, Signature},
 };
 use anyhow::{Result, anyhow};
@@ -16,6 +18,9 @@ use rand::RngCore;
 use pqcrypto_traits::sign::PublicKey;
 use ark_ec::Group;
 
+/// Longest an ownership or delegation proof may take to generate
+const DNS_PROOF_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
 /// Decentralized DNS replacement that uses zero-knowledge proofs
 #[derive(Debug, Clone)]
 pub struct ZhtpDNS {
@@ -43,7 +48,7 @@ pub struct DomainRecord {
     /// Owner's public key
     pub owner_public_key: Vec<u8>,
     /// Zero-knowledge proof of ownership
-    pub ownership_proof: ByteRoutingProof,
+    pub ownership_proof: ProofEnvelope,
     /// Domain signature by owner
     pub signature: Signature,
     /// Time-to-live for caching
@@ -70,7 +75,7 @@ pub struct SubdomainRecord {
     /// Subdomain owner (can be different from domain owner)
     pub owner_public_key: Vec<u8>,
     /// Delegation proof from parent domain
-    pub delegation_proof: ByteRoutingProof,
+    pub delegation_proof: ProofEnvelope,
     /// Subdomain signature
     pub signature: Signature,
     /// TTL
@@ -87,7 +92,7 @@ pub struct CertificateRecord {
     /// Certificate data
     pub certificate_data: Vec<u8>,
     /// Zero-knowledge proof of certificate validity
-    pub validity_proof: ByteRoutingProof,
+    pub validity_proof: ProofEnvelope,
     /// Certificate authority signature
     pub ca_signature: Signature,
     /// Certificate hash
@@ -106,7 +111,7 @@ pub struct OwnershipProof {
     /// Proof of ownership (e.g., DNS challenge response)
     pub proof_data: Vec<u8>,
     /// Zero-knowledge proof of ownership validity
-    pub ownership_proof: ByteRoutingProof,
+    pub ownership_proof: ProofEnvelope,
     /// Proof timestamp
     pub timestamp: u64,
     /// Proof signature
@@ -235,9 +240,10 @@ impl ZhtpDNS {
         parent_domain: String,
         addresses: Vec<SocketAddr>,
         owner_keypair: &Keypair,
-        parent_delegation_proof: ByteRoutingProof,
+        parent_delegation_proof: ProofEnvelope,
     ) -> Result<()> {
-        // Verify parent domain exists and is active
+        // Verify parent domain exists and is active, and that its owner
+        // delegated the subdomain
         {
             let registry = self.domain_registry.read().await;
             let parent_record = registry.get(&parent_domain)
@@ -246,6 +252,12 @@ impl ZhtpDNS {
             if parent_record.status != DomainStatus::Active {
                 return Err(anyhow!("Parent domain is not active"));
             }
+
+            let owner_hash: [u8; 32] = Sha256::digest(&parent_record.owner_public_key).into();
+            let statement = delegation_statement(&parent_domain, &subdomain, owner_hash);
+            if !VerifierRegistry::global().verify(&parent_delegation_proof, &statement)? {
+                return Err(anyhow!("Invalid delegation proof for {}", subdomain));
+            }
         }
 
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
@@ -296,6 +308,49 @@ impl ZhtpDNS {
         Ok(())
     }
 
+    /// Prove that the owner of `parent_domain` delegates `subdomain`, for
+    /// [`ZhtpDNS::register_subdomain`]
+    pub async fn generate_delegation_proof(
+        &self,
+        subdomain: &str,
+        parent_domain: &str,
+        parent_owner_keypair: &Keypair,
+    ) -> Result<ProofEnvelope> {
+        let owner_public_key = {
+            let registry = self.domain_registry.read().await;
+            registry.get(parent_domain)
+                .ok_or_else(|| anyhow!("Parent domain not found"))?
+                .owner_public_key
+                .clone()
+        };
+        if owner_public_key != parent_owner_keypair.public.as_bytes() {
+            return Err(anyhow!("Only the owner of {} can delegate its subdomains", parent_domain));
+        }
+
+        let owner_hash: [u8; 32] = Sha256::digest(&owner_public_key).into();
+        let (parent, sub) = (parent_domain.as_bytes().to_vec(), subdomain.as_bytes().to_vec());
+        let mut routing_table = HashMap::new();
+        routing_table.insert(parent.clone(), vec![sub.clone()]);
+        let circuit = UnifiedCircuit::new(
+            parent.clone(),
+            sub.clone(),
+            vec![parent, sub],
+            routing_table,
+            owner_hash,
+            vec![],
+            ark_bn254::G1Projective::generator(),
+            0,
+            vec![],
+            vec![],
+        );
+
+        let proof = circuit
+            .prove(&ProofCancellation::new(), DNS_PROOF_TIMEOUT)
+            .await
+            .map_err(|e| anyhow!("Failed to generate delegation proof: {}", e))?;
+        Ok(ProofEnvelope::from(proof))
+    }
+
     /// Resolve a domain name to addresses
     pub async fn resolve(&self, query: DnsQuery) -> Result<DnsResponse> {
         match query.query_type {
@@ -472,7 +527,7 @@ impl ZhtpDNS {
 
         Ok(())
     }    /// Generate zero-knowledge proof of domain ownership
-    async fn generate_ownership_proof(&self, domain: &str, keypair: &Keypair) -> Result<ByteRoutingProof> {
+    async fn generate_ownership_proof(&self, domain: &str, keypair: &Keypair) -> Result<ProofEnvelope> {
         // Create a challenge-response proof
         let mut challenge = [0u8; 32];
         rand::thread_rng().fill_bytes(&mut challenge);
@@ -481,7 +536,7 @@ impl ZhtpDNS {
         let response = keypair.sign(&challenge)?;
         
         // Generate secure ZK proof using UnifiedCircuit with KZG trusted setup
-        let mut circuit = crate::zhtp::zk_proofs::UnifiedCircuit::new(
+        let circuit = UnifiedCircuit::new(
             domain.as_bytes().to_vec(),
             b"DNS_OWNERSHIP_VERIFICATION".to_vec(),
             vec![], // No routing path for ownership verification
@@ -494,11 +549,12 @@ impl ZhtpDNS {
             vec![(response.as_bytes().len() as u64, 0.0)], // Response length as latency
         );
 
-        // Generate secure proof using KZG trusted setup
-        let secure_proof = match circuit.generate_proof() {
-            Some(proof) => ByteRoutingProof::from(proof),
-            None => return Err(anyhow::anyhow!("Failed to generate DNS ownership proof"))
-        };
+        // Generate secure proof using KZG trusted setup, off the async runtime
+        let secure_proof = circuit
+            .prove(&ProofCancellation::new(), DNS_PROOF_TIMEOUT)
+            .await
+            .map(ProofEnvelope::from)
+            .map_err(|e| anyhow!("Failed to generate DNS ownership proof: {}", e))?;
 
         // Store ownership proof
         let ownership_proof = OwnershipProof {
@@ -518,14 +574,13 @@ impl ZhtpDNS {
     }
 
     /// Verify domain ownership proof
-    pub async fn verify_ownership_proof(&self, domain: &str, proof: &ByteRoutingProof) -> Result<bool> {
+    pub async fn verify_ownership_proof(&self, domain: &str, proof: &ProofEnvelope) -> Result<bool> {
         let proofs = self.ownership_proofs.read().await;
         
         match proofs.get(domain) {
             Some(stored_proof) => {
                 // Verify the proof matches
-                Ok(stored_proof.ownership_proof.commitments == proof.commitments &&
-                   stored_proof.ownership_proof.elements == proof.elements)
+                Ok(!proof.is_none() && stored_proof.ownership_proof == *proof)
             }
             None => Ok(false)
         }
@@ -549,6 +604,16 @@ impl ZhtpDNS {
     }
 }
 
+/// Route a subdomain delegation proof proves: from the parent domain to the
+/// subdomain, bound to the hash of the parent owner's public key
+fn delegation_statement<'a>(parent_domain: &'a str, subdomain: &'a str, owner_hash: [u8; 32]) -> ProofStatement<'a> {
+    ProofStatement::Route {
+        source: parent_domain.as_bytes(),
+        destination: subdomain.as_bytes(),
+        stored_data_root: owner_hash,
+    }
+}
+
 impl Default for ZhtpDNS {
     fn default() -> Self {
         Self::new()
@@ -602,12 +667,24 @@ mod tests {
             content_hash,
         ).await?;
 
-        // Register subdomain
-        let delegation_proof = ByteRoutingProof {
-            commitments: vec![vec![1, 2, 3]],
-            elements: vec![vec![4, 5, 6]],
-            inputs: vec![vec![7, 8, 9]],
-        };
+        // Subdomains need the parent owner's delegation proof
+        assert!(dns.register_subdomain(
+            "www.example.zhtp".to_string(),
+            "example.zhtp".to_string(),
+            sub_addresses.clone(),
+            &keypair,
+            ProofEnvelope::none(),
+        ).await.is_err());
+        assert!(dns.generate_delegation_proof("www.example.zhtp", "example.zhtp", &Keypair::generate()).await.is_err());
+
+        let delegation_proof = dns.generate_delegation_proof("www.example.zhtp", "example.zhtp", &keypair).await?;
+        assert!(dns.register_subdomain(
+            "api.example.zhtp".to_string(),
+            "example.zhtp".to_string(),
+            sub_addresses.clone(),
+            &keypair,
+            delegation_proof.clone(),
+        ).await.is_err());
 
         dns.register_subdomain(
             "www.example.zhtp".to_string(),
@@ -1,4 +1,6 @@
 pub use crate::zhtp::consensus_engine::{ZkValidator, ZkNetworkMetrics, ValidatorStatus};
+use crate::genesis::GenesisEconomics;
+use crate::zhtp::amount::Amount;
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
 use std::{
@@ -27,32 +29,32 @@ pub struct ZhtpEconomics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenSupply {
     /// Total maximum supply (21 million ZHTP tokens)
-    pub max_supply: u64,
+    pub max_supply: Amount,
     /// Currently circulating supply
-    pub circulating_supply: u64,
+    pub circulating_supply: Amount,
     /// Tokens locked in consensus staking
-    pub staked_tokens: u64,
+    pub staked_tokens: Amount,
     /// Tokens reserved for protocol development
-    pub protocol_reserve: u64,
+    pub protocol_reserve: Amount,
     /// Tokens allocated for ecosystem growth
-    pub ecosystem_allocation: u64,
+    pub ecosystem_allocation: Amount,
     /// Current inflation rate (decreases over time)
     pub inflation_rate: f64,
     /// Block reward for validators
-    pub block_reward: u64,
+    pub block_reward: Amount,
 }
 
 /// Economic parameters that govern the ZHTP economy
 #[derive(Debug, Clone)]
 pub struct EconomicParams {
-    /// Base transaction fee (in ZHTP tokens)
-    pub base_transaction_fee: u64,
+    /// Base transaction fee
+    pub base_transaction_fee: Amount,
     /// Certificate issuance fee (replacing CA fees)
-    pub certificate_fee: u64,
+    pub certificate_fee: Amount,
     /// DNS registration fee (replacing traditional DNS)
-    pub dns_registration_fee: u64,
+    pub dns_registration_fee: Amount,
     /// Validator minimum stake requirement
-    pub min_validator_stake: u64,
+    pub min_validator_stake: Amount,
     /// Validator reward percentage
     pub validator_reward_rate: f64,
     /// Network fee burn rate (deflationary mechanism)
@@ -65,16 +67,16 @@ pub struct EconomicParams {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RevenueStreams {
     /// Revenue from certificate issuance (replacing CAs like DigiCert, Comodo)
-    pub certificate_revenue: u64,
+    pub certificate_revenue: Amount,
     /// Revenue from DNS services (replacing traditional DNS providers)
-    pub dns_revenue: u64,
+    pub dns_revenue: Amount,
     /// Revenue from secure routing (replacing VPN services)
-    pub routing_revenue: u64,
+    pub routing_revenue: Amount,
     /// Revenue from storage services (replacing cloud storage)
-    pub storage_revenue: u64,
+    pub storage_revenue: Amount,
     /// Revenue from compute services (replacing cloud compute)
-    pub compute_revenue: u64,
-    /// Total network value captured
+    pub compute_revenue: Amount,
+    /// Total network value captured (USD, not ZHTP)
     pub total_network_value: u64,
 }
 
@@ -82,30 +84,30 @@ pub struct RevenueStreams {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RewardPool {
     /// Available rewards for validators
-    pub validator_rewards: u64,
+    pub validator_rewards: Amount,
     /// Available rewards for routing nodes
-    pub routing_rewards: u64,
+    pub routing_rewards: Amount,
     /// Available rewards for storage providers
-    pub storage_rewards: u64,
+    pub storage_rewards: Amount,
     /// Available rewards for certificate authorities
-    pub ca_rewards: u64,
+    pub ca_rewards: Amount,
     /// Available rewards for DNS providers
-    pub dns_rewards: u64,
+    pub dns_rewards: Amount,
     /// Performance-based bonus pool
-    pub performance_bonus: u64,
+    pub performance_bonus: Amount,
 }
 
 /// Dynamic fee market that adjusts based on network usage
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FeeMarket {
     /// Current base fee per transaction
-    pub base_fee: u64,
+    pub base_fee: Amount,
     /// Current certificate issuance fee
-    pub certificate_fee: u64,
+    pub certificate_fee: Amount,
     /// Current DNS registration fee
-    pub dns_fee: u64,
+    pub dns_fee: Amount,
     /// Current routing fee per hop
-    pub routing_fee: u64,
+    pub routing_fee: Amount,
     /// Network congestion multiplier
     pub congestion_multiplier: f64,
     /// Fee history for market analysis
@@ -116,7 +118,7 @@ pub struct FeeMarket {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FeeSnapshot {
     pub timestamp: u64,
-    pub base_fee: u64,
+    pub base_fee: Amount,
     pub network_utilization: f64,
     pub transaction_volume: u64,
 }
@@ -125,11 +127,11 @@ pub struct FeeSnapshot {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidatorEconomics {
     /// Validator stake amount
-    pub stake: u64,
+    pub stake: Amount,
     /// Accumulated rewards
-    pub total_rewards: u64,
+    pub total_rewards: Amount,
     /// Penalties from slashing
-    pub total_penalties: u64,
+    pub total_penalties: Amount,
     /// Net validator profit
     pub net_profit: i64,
     /// Return on stake (ROI)
@@ -142,11 +144,11 @@ pub struct ValidatorEconomics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CertificateEconomics {
     /// Revenue from certificate issuance
-    pub issuance_revenue: u64,
+    pub issuance_revenue: Amount,
     /// Costs for certificate validation
-    pub validation_costs: u64,
+    pub validation_costs: Amount,
     /// Revenue from certificate renewal
-    pub renewal_revenue: u64,
+    pub renewal_revenue: Amount,
     /// Market share in certificate space
     pub market_share: f64,
     /// Traditional CA revenue being replaced
@@ -157,11 +159,11 @@ pub struct CertificateEconomics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DnsEconomics {
     /// Revenue from domain registration
-    pub registration_revenue: u64,
+    pub registration_revenue: Amount,
     /// Revenue from DNS resolution services
-    pub resolution_revenue: u64,
+    pub resolution_revenue: Amount,
     /// Costs for maintaining DNS records
-    pub maintenance_costs: u64,
+    pub maintenance_costs: Amount,
     /// Traditional DNS revenue being replaced
     pub traditional_dns_revenue_replaced: u64,
 }
@@ -180,51 +182,56 @@ pub struct NetworkValueCapture {
 }
 
 impl ZhtpEconomics {
-    /// Initialize the ZHTP economic system
+    /// Initialize the ZHTP economic system with the development parameters
     pub fn new() -> Self {
+        Self::from_genesis(&GenesisEconomics::default())
+    }
+
+    /// Initialize the economic system with the parameters of a network's genesis
+    pub fn from_genesis(genesis: &GenesisEconomics) -> Self {
         let token_supply = TokenSupply {
-            max_supply: 21_000_000, // 21 million ZHTP tokens (similar to Bitcoin scarcity)
-            circulating_supply: 10_000_000, // Start with 10M tokens in circulation for development
-            staked_tokens: 0,
-            protocol_reserve: 4_200_000, // 20% for protocol development
-            ecosystem_allocation: 2_100_000, // 10% for ecosystem growth
-            inflation_rate: 0.05, // 5% initial inflation, decreasing over time
-            block_reward: 50, // Initial block reward, halves every 4 years
+            max_supply: genesis.max_supply,
+            circulating_supply: Amount::from_zhtp(10_000_000), // Start with 10M tokens in circulation for development
+            staked_tokens: Amount::ZERO,
+            protocol_reserve: genesis.max_supply.mul_div(20, 100).unwrap_or(Amount::ZERO), // 20% for protocol development
+            ecosystem_allocation: genesis.max_supply.mul_div(10, 100).unwrap_or(Amount::ZERO), // 10% for ecosystem growth
+            inflation_rate: genesis.inflation_rate,
+            block_reward: genesis.block_reward,
         };
 
         let params = EconomicParams {
-            base_transaction_fee: 1000, // 0.001 ZHTP
-            certificate_fee: 100_000, // 0.1 ZHTP (vs $100-$1000 traditional CA fees)
-            dns_registration_fee: 10_000, // 0.01 ZHTP (vs $10-$50 traditional DNS)
-            min_validator_stake: 100, // 100 ZHTP minimum stake for development
-            validator_reward_rate: 0.08, // 8% annual reward for validators
-            fee_burn_rate: 0.3, // 30% of fees burned (deflationary)
+            base_transaction_fee: genesis.base_transaction_fee,
+            certificate_fee: genesis.certificate_fee,
+            dns_registration_fee: genesis.dns_registration_fee,
+            min_validator_stake: genesis.min_validator_stake,
+            validator_reward_rate: genesis.validator_reward_rate,
+            fee_burn_rate: genesis.fee_burn_rate,
             security_deposit_multiplier: 2.0, // 2x deposit for economic security
         };
 
         let revenue_streams = RevenueStreams {
-            certificate_revenue: 0,
-            dns_revenue: 0,
-            routing_revenue: 0,
-            storage_revenue: 0,
-            compute_revenue: 0,
+            certificate_revenue: Amount::ZERO,
+            dns_revenue: Amount::ZERO,
+            routing_revenue: Amount::ZERO,
+            storage_revenue: Amount::ZERO,
+            compute_revenue: Amount::ZERO,
             total_network_value: 0,
         };
 
         let reward_pool = RewardPool {
-            validator_rewards: 1_000_000, // Initial reward pool
-            routing_rewards: 500_000,
-            storage_rewards: 500_000,
-            ca_rewards: 250_000,
-            dns_rewards: 250_000,
-            performance_bonus: 100_000,
+            validator_rewards: Amount::from_zhtp(1_000_000), // Initial reward pool
+            routing_rewards: Amount::from_zhtp(500_000),
+            storage_rewards: Amount::from_zhtp(500_000),
+            ca_rewards: Amount::from_zhtp(250_000),
+            dns_rewards: Amount::from_zhtp(250_000),
+            performance_bonus: Amount::from_zhtp(100_000),
         };
 
         let fee_market = FeeMarket {
             base_fee: params.base_transaction_fee,
             certificate_fee: params.certificate_fee,
             dns_fee: params.dns_registration_fee,
-            routing_fee: 100, // Base routing fee per hop
+            routing_fee: Amount::from_base_units(10_000), // 0.0001 ZHTP base routing fee per hop
             congestion_multiplier: 1.0,
             fee_history: Vec::new(),
         };
@@ -244,30 +251,30 @@ impl ZhtpEconomics {
         validator: &ZkValidator,
         blocks_validated: u32,
         performance_score: f64,
-    ) -> Result<u64> {
+    ) -> Result<Amount> {
         let annual_reward_rate = self.params.validator_reward_rate;
-        let base_reward = (validator.stake as f64 * annual_reward_rate) as u64;
+        let base_reward = validator.stake.mul_f64(annual_reward_rate);
         
         // Performance multiplier (0.5x to 2.0x based on performance)
         let performance_multiplier = 0.5 + (performance_score * 1.5);
         
-        // Block validation bonus
-        let block_bonus = blocks_validated as u64 * 10;
+        // Block validation bonus (0.00001 ZHTP per block)
+        let block_bonus = Amount::from_base_units(1_000).saturating_mul(blocks_validated as u64);
         
-        let total_reward = ((base_reward as f64 * performance_multiplier) as u64) + block_bonus;
+        let total_reward = base_reward.mul_f64(performance_multiplier).saturating_add(block_bonus);
         
         Ok(total_reward)
     }
 
     /// Calculate certificate issuance rewards (replacing traditional CA revenue)
-    pub async fn calculate_certificate_reward(&self, certificates_issued: u32) -> Result<u64> {
-        let base_reward_per_cert = 1000; // Base reward for issuing a certificate
-        let total_reward = certificates_issued as u64 * base_reward_per_cert;
+    pub async fn calculate_certificate_reward(&self, certificates_issued: u32) -> Result<Amount> {
+        let base_reward_per_cert = Amount::from_base_units(100_000); // 0.001 ZHTP for issuing a certificate
+        let total_reward = base_reward_per_cert.saturating_mul(certificates_issued as u64);
         
         // Update revenue tracking
         {
             let mut revenue = self.revenue_streams.write().await;
-            revenue.certificate_revenue += total_reward;
+            revenue.certificate_revenue = revenue.certificate_revenue.saturating_add(total_reward);
             
             // Traditional CA revenue replaced (average $200 per certificate)
             let traditional_revenue_replaced = certificates_issued as u64 * 200_000; // $200 in ZHTP tokens
@@ -278,15 +285,15 @@ impl ZhtpEconomics {
     }
 
     /// Calculate DNS service rewards (replacing traditional DNS revenue)
-    pub async fn calculate_dns_reward(&self, domains_resolved: u32, domains_registered: u32) -> Result<u64> {
-        let resolution_reward = domains_resolved as u64 * 10; // Small reward per resolution
-        let registration_reward = domains_registered as u64 * 1000; // Larger reward per registration
-        let total_reward = resolution_reward + registration_reward;
+    pub async fn calculate_dns_reward(&self, domains_resolved: u32, domains_registered: u32) -> Result<Amount> {
+        let resolution_reward = Amount::from_base_units(1_000).saturating_mul(domains_resolved as u64); // Small reward per resolution
+        let registration_reward = Amount::from_base_units(100_000).saturating_mul(domains_registered as u64); // Larger reward per registration
+        let total_reward = resolution_reward.saturating_add(registration_reward);
         
         // Update revenue tracking
         {
             let mut revenue = self.revenue_streams.write().await;
-            revenue.dns_revenue += total_reward;
+            revenue.dns_revenue = revenue.dns_revenue.saturating_add(total_reward);
             
             // Traditional DNS revenue replaced (average $15 per domain per year)
             let traditional_revenue_replaced = domains_registered as u64 * 15_000; // $15 in ZHTP tokens
@@ -297,15 +304,15 @@ impl ZhtpEconomics {
     }
 
     /// Calculate routing rewards for packet forwarding
-    pub async fn calculate_routing_reward(&self, packets_routed: u64, success_rate: f64) -> Result<u64> {
-        let base_reward_per_packet = 1; // Base reward per packet routed
+    pub async fn calculate_routing_reward(&self, packets_routed: u64, success_rate: f64) -> Result<Amount> {
+        let base_reward_per_packet = Amount::from_base_units(100); // Base reward per packet routed
         let success_multiplier = success_rate; // Multiply by success rate
-        let total_reward = (packets_routed as f64 * base_reward_per_packet as f64 * success_multiplier) as u64;
+        let total_reward = base_reward_per_packet.saturating_mul(packets_routed).mul_f64(success_multiplier);
         
         // Update revenue tracking
         {
             let mut revenue = self.revenue_streams.write().await;
-            revenue.routing_revenue += total_reward;
+            revenue.routing_revenue = revenue.routing_revenue.saturating_add(total_reward);
         }
         
         Ok(total_reward)
@@ -325,9 +332,9 @@ impl ZhtpEconomics {
         };
         
         // Update current fees
-        fee_market.base_fee = (self.params.base_transaction_fee as f64 * fee_market.congestion_multiplier) as u64;
-        fee_market.certificate_fee = (self.params.certificate_fee as f64 * fee_market.congestion_multiplier) as u64;
-        fee_market.dns_fee = (self.params.dns_registration_fee as f64 * fee_market.congestion_multiplier) as u64;
+        fee_market.base_fee = self.params.base_transaction_fee.mul_f64(fee_market.congestion_multiplier);
+        fee_market.certificate_fee = self.params.certificate_fee.mul_f64(fee_market.congestion_multiplier);
+        fee_market.dns_fee = self.params.dns_registration_fee.mul_f64(fee_market.congestion_multiplier);
         
         // Record fee snapshot
         let snapshot = FeeSnapshot {
@@ -345,13 +352,13 @@ impl ZhtpEconomics {
         
         Ok(())
     }    /// Burn tokens to create deflationary pressure
-    pub async fn burn_tokens(&self, amount: u64) -> Result<()> {
+    pub async fn burn_tokens(&self, amount: Amount) -> Result<()> {
         let mut supply = self.token_supply.write().await;
         
         // Only burn what's available to prevent errors during testnet
         let burn_amount = amount.min(supply.circulating_supply);
-        if burn_amount > 0 {
-            supply.circulating_supply -= burn_amount;
+        if !burn_amount.is_zero() {
+            supply.circulating_supply = supply.circulating_supply.saturating_sub(burn_amount);
             println!("Burned {} ZHTP tokens. New circulating supply: {}", burn_amount, supply.circulating_supply);
         } else {
             println!("No tokens available to burn (circulating: {})", supply.circulating_supply);
@@ -361,20 +368,21 @@ impl ZhtpEconomics {
     }
 
     /// Process fee burning (deflationary mechanism)
-    pub async fn process_fee_burn(&self, total_fees: u64) -> Result<()> {
-        let burn_amount = (total_fees as f64 * self.params.fee_burn_rate) as u64;
+    pub async fn process_fee_burn(&self, total_fees: Amount) -> Result<()> {
+        let burn_amount = total_fees.mul_f64(self.params.fee_burn_rate).min(total_fees);
         self.burn_tokens(burn_amount).await?;
         
         // Remaining fees go to reward pools
-        let reward_amount = total_fees - burn_amount;
+        let reward_amount = total_fees.saturating_sub(burn_amount);
+        let share = |divisor: u64| reward_amount.checked_div(divisor).unwrap_or(Amount::ZERO);
         let mut reward_pool = self.reward_pool.write().await;
         
         // Distribute remaining fees across different reward categories
-        reward_pool.validator_rewards += reward_amount / 2; // 50% to validators
-        reward_pool.routing_rewards += reward_amount / 4; // 25% to routing
-        reward_pool.storage_rewards += reward_amount / 8; // 12.5% to storage
-        reward_pool.ca_rewards += reward_amount / 16; // 6.25% to CAs
-        reward_pool.dns_rewards += reward_amount / 16; // 6.25% to DNS
+        reward_pool.validator_rewards = reward_pool.validator_rewards.saturating_add(share(2)); // 50% to validators
+        reward_pool.routing_rewards = reward_pool.routing_rewards.saturating_add(share(4)); // 25% to routing
+        reward_pool.storage_rewards = reward_pool.storage_rewards.saturating_add(share(8)); // 12.5% to storage
+        reward_pool.ca_rewards = reward_pool.ca_rewards.saturating_add(share(16)); // 6.25% to CAs
+        reward_pool.dns_rewards = reward_pool.dns_rewards.saturating_add(share(16)); // 6.25% to DNS
         
         Ok(())
     }
@@ -422,7 +430,7 @@ impl ZhtpEconomics {
             circulating_supply: supply.circulating_supply,
             staked_tokens: supply.staked_tokens,
             current_inflation_rate: supply.inflation_rate,
-            total_value_locked: supply.staked_tokens * 1000, // Assuming 1 ZHTP = $1000
+            total_value_locked: supply.staked_tokens.whole_zhtp().saturating_mul(1000), // Assuming 1 ZHTP = $1000
             network_revenue: revenue.total_network_value,
             average_transaction_fee: fee_market.base_fee,
             validator_apr: self.params.validator_reward_rate,
@@ -434,13 +442,15 @@ impl ZhtpEconomics {
 /// Current economic metrics for the network
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EconomicMetrics {
-    pub total_supply: u64,
-    pub circulating_supply: u64,
-    pub staked_tokens: u64,
+    pub total_supply: Amount,
+    pub circulating_supply: Amount,
+    pub staked_tokens: Amount,
     pub current_inflation_rate: f64,
+    /// USD value of staked tokens
     pub total_value_locked: u64,
+    /// USD value captured from traditional infrastructure
     pub network_revenue: u64,
-    pub average_transaction_fee: u64,
+    pub average_transaction_fee: Amount,
     pub validator_apr: f64,
     pub fee_burn_rate: f64,
 }
@@ -454,8 +464,9 @@ mod tests {
         let economics = ZhtpEconomics::new();
         let metrics = economics.get_economic_metrics().await?;
         
-        assert_eq!(metrics.total_supply, 21_000_000);
-        assert_eq!(metrics.circulating_supply, 0);
+        assert_eq!(metrics.total_supply, Amount::from_zhtp(21_000_000));
+        // Development networks start with 10M tokens in circulation
+        assert_eq!(metrics.circulating_supply, Amount::from_zhtp(10_000_000));
         assert!(metrics.validator_apr > 0.0);
         
         Ok(())
@@ -467,12 +478,8 @@ mod tests {
         
         // Create a mock validator
         let validator = ZkValidator {            encrypted_identity: vec![1, 2, 3],
-            stake: 1_000_000.0, // 1 million ZHTP staked
-            stake_proof: crate::zhtp::zk_proofs::ByteRoutingProof {
-                inputs: vec![],
-                elements: vec![],
-                commitments: vec![],
-            },
+            stake: Amount::from_zhtp(1_000_000), // 1 million ZHTP staked
+            stake_proof: crate::zhtp::proof_envelope::ProofEnvelope::none(),
             identity_commitment: [0u8; 32],
             metrics: ZkNetworkMetrics::new(0.9),
             registered_at: 0,
@@ -481,7 +488,7 @@ mod tests {
         };
         
         let reward = economics.calculate_validator_reward(&validator, 100, 0.95).await?;
-        assert!(reward > 0);
+        assert!(!reward.is_zero());
         
         Ok(())
     }
@@ -491,10 +498,10 @@ mod tests {
         let economics = ZhtpEconomics::new();
         
         let reward = economics.calculate_certificate_reward(10).await?;
-        assert_eq!(reward, 10_000); // 10 certificates * 1000 reward each
+        assert_eq!(reward, "0.01".parse()?); // 10 certificates * 0.001 ZHTP each
         
         let revenue = economics.revenue_streams.read().await;
-        assert_eq!(revenue.certificate_revenue, 10_000);
+        assert_eq!(revenue.certificate_revenue, "0.01".parse()?);
         
         Ok(())
     }
@@ -520,13 +527,13 @@ mod tests {
         // First add some tokens to circulation
         {
             let mut supply = economics.token_supply.write().await;
-            supply.circulating_supply = 1_000_000;
+            supply.circulating_supply = Amount::from_zhtp(1_000_000);
         }
         
-        economics.burn_tokens(100_000).await?;
+        economics.burn_tokens(Amount::from_zhtp(100_000)).await?;
         
         let supply = economics.token_supply.read().await;
-        assert_eq!(supply.circulating_supply, 900_000);
+        assert_eq!(supply.circulating_supply, Amount::from_zhtp(900_000));
         
         Ok(())
     }
@@ -0,0 +1,118 @@
+//! Baby Jubjub, the twisted Edwards curve whose base field is the BN254
+//! scalar field.
+//!
+//! Points on it can be added and multiplied inside a BN254 Groth16 circuit
+//! with native field arithmetic, which is what lets the shielded spend
+//! circuit open value commitments and derive payment addresses. The curve
+//! is `168700·x² + y² = 1 + 168696·x²·y²` with cofactor 8; keys and
+//! commitments live in its subgroup of prime order
+//! 2736030358979909402780800718157159386076813972158567259200215660948447373041.
+
+use ark_bn254::Fr;
+use ark_ec::{
+    models::CurveConfig,
+    twisted_edwards::{Affine, MontCurveConfig, Projective, TECurveConfig},
+    AffineRepr, CurveGroup,
+};
+use ark_ff::{
+    fields::{Fp256, MontBackend},
+    MontFp, PrimeField,
+};
+use sha2::{Digest, Sha256};
+
+pub use scalar::ScalarConfig;
+
+mod scalar {
+    // The derive expands to an impl the lints consider foreign
+    #![allow(unexpected_cfgs, non_local_definitions)]
+
+    use ark_ff::fields::MontConfig;
+
+    /// Scalar field of the prime order subgroup
+    #[derive(MontConfig)]
+    #[modulus = "2736030358979909402780800718157159386076813972158567259200215660948447373041"]
+    #[generator = "31"]
+    pub struct ScalarConfig;
+}
+
+pub type JubjubScalar = Fp256<MontBackend<ScalarConfig, 4>>;
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct JubjubConfig;
+
+pub type JubjubAffine = Affine<JubjubConfig>;
+pub type JubjubPoint = Projective<JubjubConfig>;
+
+impl CurveConfig for JubjubConfig {
+    type BaseField = Fr;
+    type ScalarField = JubjubScalar;
+
+    const COFACTOR: &'static [u64] = &[8];
+
+    /// 8⁻¹ mod the subgroup order
+    const COFACTOR_INV: JubjubScalar =
+        MontFp!("2394026564107420727433200628387514462817212225638746351800188703329891451411");
+}
+
+impl TECurveConfig for JubjubConfig {
+    const COEFF_A: Fr = MontFp!("168700");
+    const COEFF_D: Fr = MontFp!("168696");
+
+    /// The generator of the prime order subgroup circomlib calls `Base8`
+    const GENERATOR: JubjubAffine = JubjubAffine::new_unchecked(
+        MontFp!("5299619240641551281634865583518297030282874472190772894086521144482721001553"),
+        MontFp!("16950150798460657717958625567821834550301663161624707787222815936182638968203"),
+    );
+
+    type MontCurveConfig = JubjubConfig;
+}
+
+impl MontCurveConfig for JubjubConfig {
+    const COEFF_A: Fr = MontFp!("168698");
+    const COEFF_B: Fr = MontFp!("1");
+
+    type TECurveConfig = JubjubConfig;
+}
+
+/// Point of the prime order subgroup with no known discrete log relative
+/// to any other, found by try-and-increment over hashed y coordinates
+pub fn hash_to_curve(parts: &[&[u8]]) -> JubjubPoint {
+    (0u32..)
+        .find_map(|counter| {
+            let mut hasher = Sha256::new();
+            for part in parts {
+                hasher.update(part);
+            }
+            hasher.update(counter.to_le_bytes());
+            let y = Fr::from_le_bytes_mod_order(&hasher.finalize());
+            let point = JubjubAffine::get_point_from_y_unchecked(y, false)?.mul_by_cofactor_to_group();
+            (!point.into_affine().is_zero()).then_some(point)
+        })
+        .expect("half of all y coordinates lie on the curve")
+}
+
+/// Embed a subgroup scalar in the circuit field, which is larger
+pub fn scalar_to_field(scalar: &JubjubScalar) -> Fr {
+    Fr::from_bigint(scalar.into_bigint()).expect("the subgroup order is below the field modulus")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::Group;
+    use ark_ff::{BigInteger, Zero};
+
+    #[test]
+    fn test_generator_has_prime_order() {
+        let generator = JubjubPoint::generator();
+        assert!(generator.into_affine().is_on_curve());
+        assert!(!generator.is_zero());
+        assert!((generator * -JubjubScalar::from(1u64) + generator).is_zero());
+
+        let base = hash_to_curve(&[b"test"]);
+        assert!(base.into_affine().is_in_correct_subgroup_assuming_on_curve());
+        assert_ne!(base, hash_to_curve(&[b"other"]));
+        assert_eq!(scalar_to_field(&JubjubScalar::from(7u64)), Fr::from(7u64));
+        assert_eq!(JubjubScalar::MODULUS.num_bits(), 251);
+    }
+}
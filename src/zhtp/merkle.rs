@@ -0,0 +1,565 @@
+//! Merkle commitments for block headers: a binary Merkle tree over
+//! transaction hashes and a sparse Merkle tree over account state, plus the
+//! append-only tree over shielded note commitments.
+//!
+//! The first two hash with SHA-256. The note commitment tree hashes with
+//! Poseidon, because spends prove membership in it inside a circuit.
+
+use crate::zhtp::poseidon;
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// 32-byte SHA-256 digest
+pub type Hash = [u8; 32];
+
+/// Domain separation so a leaf can never be passed off as an inner node
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Depth of the sparse Merkle tree, one level per bit of a 32-byte key
+pub const SMT_DEPTH: usize = 256;
+
+/// Hash of an empty sparse Merkle tree leaf
+const EMPTY_LEAF: Hash = [0u8; 32];
+
+/// Depth of the note commitment tree, room for 2^32 notes
+pub const NOTE_TREE_DEPTH: usize = 32;
+
+fn hash_leaf(parts: &[&[u8]]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Hash pairs of nodes into the next level up. A trailing odd node is
+/// promoted unchanged rather than paired with itself.
+fn next_level(level: &[Hash]) -> Vec<Hash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks(2) yields one or two nodes"),
+        })
+        .collect()
+}
+
+/// Merkle root of an ordered list of leaves (all zeroes for an empty list)
+pub fn merkle_root(leaves: &[Hash]) -> Hash {
+    if leaves.is_empty() {
+        return [0u8; 32];
+    }
+    let mut level: Vec<Hash> = leaves.iter().map(|leaf| hash_leaf(&[leaf])).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Inclusion proof for one leaf of a binary Merkle tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// Position of the leaf
+    pub index: u64,
+    /// Number of leaves in the tree
+    pub leaf_count: u64,
+    /// Sibling hashes from the leaf level upwards; levels where the node was
+    /// promoted without a sibling are skipped
+    pub siblings: Vec<Hash>,
+}
+
+impl MerkleProof {
+    /// Build the proof for `leaves[index]`
+    pub fn generate(leaves: &[Hash], index: usize) -> Option<Self> {
+        if index >= leaves.len() {
+            return None;
+        }
+
+        let mut level: Vec<Hash> = leaves.iter().map(|leaf| hash_leaf(&[leaf])).collect();
+        let mut position = index;
+        let mut siblings = Vec::new();
+        while level.len() > 1 {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(*sibling);
+            }
+            level = next_level(&level);
+            position /= 2;
+        }
+
+        Some(Self {
+            index: index as u64,
+            leaf_count: leaves.len() as u64,
+            siblings,
+        })
+    }
+
+    /// Check that `leaf` sits at `self.index` in the tree with root `root`
+    pub fn verify(&self, root: &Hash, leaf: &Hash) -> bool {
+        if self.index >= self.leaf_count {
+            return false;
+        }
+
+        let mut hash = hash_leaf(&[leaf]);
+        let mut position = self.index;
+        let mut width = self.leaf_count;
+        let mut siblings = self.siblings.iter();
+        while width > 1 {
+            if position ^ 1 < width {
+                let Some(sibling) = siblings.next() else {
+                    return false;
+                };
+                hash = if position.is_multiple_of(2) {
+                    hash_node(&hash, sibling)
+                } else {
+                    hash_node(sibling, &hash)
+                };
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        siblings.next().is_none() && hash == *root
+    }
+}
+
+/// Root hash of an empty subtree whose top is at `depth`
+fn default_hash(depth: usize) -> Hash {
+    static DEFAULTS: OnceLock<Vec<Hash>> = OnceLock::new();
+    let defaults = DEFAULTS.get_or_init(|| {
+        // Indexed by height above the leaves
+        let mut defaults = Vec::with_capacity(SMT_DEPTH + 1);
+        defaults.push(EMPTY_LEAF);
+        for height in 0..SMT_DEPTH {
+            defaults.push(hash_node(&defaults[height], &defaults[height]));
+        }
+        defaults
+    });
+    defaults[SMT_DEPTH - depth]
+}
+
+/// Bit `depth` of `key`, most significant bit first
+fn key_bit(key: &Hash, depth: usize) -> bool {
+    key[depth / 8] & (0x80 >> (depth % 8)) != 0
+}
+
+fn smt_leaf(key: &Hash, value: &Hash) -> Hash {
+    hash_leaf(&[key, value])
+}
+
+/// Sparse Merkle tree with one leaf slot per 32-byte key.
+///
+/// Empty subtrees hash to precomputed defaults, so the root over a few
+/// thousand accounts stays cheap, and absent keys have non-membership proofs.
+#[derive(Debug, Clone, Default)]
+pub struct SparseMerkleTree {
+    leaves: BTreeMap<Hash, Hash>,
+}
+
+impl SparseMerkleTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the value hash stored under `key`
+    pub fn insert(&mut self, key: Hash, value: Hash) {
+        self.leaves.insert(key, value);
+    }
+
+    /// Clear the slot for `key`
+    pub fn remove(&mut self, key: &Hash) -> Option<Hash> {
+        self.leaves.remove(key)
+    }
+
+    pub fn get(&self, key: &Hash) -> Option<&Hash> {
+        self.leaves.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    fn entries(&self) -> Vec<(Hash, Hash)> {
+        self.leaves.iter().map(|(key, value)| (*key, *value)).collect()
+    }
+
+    /// Root hash over all leaves
+    pub fn root(&self) -> Hash {
+        Self::subtree_root(&self.entries(), 0)
+    }
+
+    /// Root of the subtree at `depth` holding `entries` (sorted by key, all
+    /// sharing the first `depth` key bits)
+    fn subtree_root(entries: &[(Hash, Hash)], depth: usize) -> Hash {
+        match entries {
+            [] => default_hash(depth),
+            [(key, value)] if depth == SMT_DEPTH => smt_leaf(key, value),
+            _ => {
+                let split = entries.partition_point(|(key, _)| !key_bit(key, depth));
+                let (left, right) = entries.split_at(split);
+                hash_node(
+                    &Self::subtree_root(left, depth + 1),
+                    &Self::subtree_root(right, depth + 1),
+                )
+            }
+        }
+    }
+
+    /// Membership or non-membership proof for `key`
+    pub fn prove(&self, key: &Hash) -> SparseMerkleProof {
+        let entries = self.entries();
+        let mut path = &entries[..];
+        let mut non_default = [0u8; 32];
+        let mut siblings = Vec::new();
+
+        for depth in 0..SMT_DEPTH {
+            let split = path.partition_point(|(entry_key, _)| !key_bit(entry_key, depth));
+            let (left, right) = path.split_at(split);
+            let (next, other) = if key_bit(key, depth) { (right, left) } else { (left, right) };
+
+            let sibling = Self::subtree_root(other, depth + 1);
+            if sibling != default_hash(depth + 1) {
+                non_default[depth / 8] |= 0x80 >> (depth % 8);
+                siblings.push(sibling);
+            }
+            path = next;
+        }
+
+        SparseMerkleProof { non_default, siblings }
+    }
+}
+
+/// Proof for one key of a sparse Merkle tree. Default (empty) siblings are
+/// left out and flagged in a bitmap, which keeps proofs to a few hundred bytes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseMerkleProof {
+    /// Bit `d` set when the sibling at depth `d + 1` is not an empty subtree
+    pub non_default: [u8; 32],
+    /// Non-default sibling hashes, root side first
+    pub siblings: Vec<Hash>,
+}
+
+impl SparseMerkleProof {
+    /// Check that `key` holds `value` (or is empty when `value` is `None`) in
+    /// the tree with root `root`
+    pub fn verify(&self, root: &Hash, key: &Hash, value: Option<&Hash>) -> bool {
+        let mut hash = match value {
+            Some(value) => smt_leaf(key, value),
+            None => EMPTY_LEAF,
+        };
+
+        let mut siblings = self.siblings.iter().rev();
+        for depth in (0..SMT_DEPTH).rev() {
+            let sibling = if key_bit(&self.non_default, depth) {
+                match siblings.next() {
+                    Some(sibling) => *sibling,
+                    None => return false,
+                }
+            } else {
+                default_hash(depth + 1)
+            };
+            hash = if key_bit(key, depth) {
+                hash_node(&sibling, &hash)
+            } else {
+                hash_node(&hash, &sibling)
+            };
+        }
+
+        siblings.next().is_none() && hash == *root
+    }
+}
+
+/// Poseidon hash of two note commitment tree nodes, each the encoding of a
+/// field element. Leaves are the note commitments themselves; the tree's
+/// fixed depth keeps them from being passed off as inner nodes.
+pub fn hash_note_nodes(left: &Hash, right: &Hash) -> Hash {
+    let left = Fr::from_le_bytes_mod_order(left);
+    let right = Fr::from_le_bytes_mod_order(right);
+    poseidon::encode(&poseidon::hash(&[left, right]))
+}
+
+/// Root hash of an empty note commitment subtree `height` levels tall
+fn empty_note_subtree(height: usize) -> Hash {
+    static EMPTY: OnceLock<Vec<Hash>> = OnceLock::new();
+    let empty = EMPTY.get_or_init(|| {
+        let mut empty = Vec::with_capacity(NOTE_TREE_DEPTH + 1);
+        empty.push(EMPTY_LEAF);
+        for height in 0..NOTE_TREE_DEPTH {
+            empty.push(hash_note_nodes(&empty[height], &empty[height]));
+        }
+        empty
+    });
+    empty[height]
+}
+
+/// Append-only Merkle tree of fixed depth over shielded note commitments.
+///
+/// Every leaf is kept so paths can be served to wallets. The frontier (the
+/// last left-hand node completed on each level) makes appending and the root
+/// O(depth), and is cheap to save and restore when blocks are reverted.
+/// Only the leaves are encoded; the rest is rebuilt when decoding.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<Hash>", into = "Vec<Hash>")]
+pub struct NoteCommitmentTree {
+    leaves: Vec<Hash>,
+    frontier: [Hash; NOTE_TREE_DEPTH],
+    root: Hash,
+}
+
+/// Enough of a [`NoteCommitmentTree`] to roll it back to an earlier size
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteTreeCheckpoint {
+    size: usize,
+    frontier: [Hash; NOTE_TREE_DEPTH],
+    root: Hash,
+}
+
+impl Default for NoteCommitmentTree {
+    fn default() -> Self {
+        Self {
+            leaves: Vec::new(),
+            frontier: [EMPTY_LEAF; NOTE_TREE_DEPTH],
+            root: empty_note_subtree(NOTE_TREE_DEPTH),
+        }
+    }
+}
+
+impl From<Vec<Hash>> for NoteCommitmentTree {
+    fn from(leaves: Vec<Hash>) -> Self {
+        let mut tree = Self::default();
+        for leaf in leaves {
+            tree.append(leaf);
+        }
+        tree
+    }
+}
+
+impl From<NoteCommitmentTree> for Vec<Hash> {
+    fn from(tree: NoteCommitmentTree) -> Self {
+        tree.leaves
+    }
+}
+
+impl NoteCommitmentTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a note commitment, returning its position. Appends past the
+    /// tree's capacity are ignored and return `None`.
+    pub fn append(&mut self, commitment: Hash) -> Option<u64> {
+        let position = self.leaves.len();
+        if position as u64 >= 1u64 << NOTE_TREE_DEPTH {
+            return None;
+        }
+
+        let mut node = commitment;
+        let mut index = position;
+        for height in 0..NOTE_TREE_DEPTH {
+            node = if index.is_multiple_of(2) {
+                self.frontier[height] = node;
+                hash_note_nodes(&node, &empty_note_subtree(height))
+            } else {
+                hash_note_nodes(&self.frontier[height], &node)
+            };
+            index /= 2;
+        }
+        self.root = node;
+        self.leaves.push(commitment);
+        Some(position as u64)
+    }
+
+    pub fn root(&self) -> Hash {
+        self.root
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Note commitment at `position`
+    pub fn get(&self, position: u64) -> Option<&Hash> {
+        self.leaves.get(position as usize)
+    }
+
+    /// Position of the first leaf equal to `commitment`
+    pub fn position(&self, commitment: &Hash) -> Option<u64> {
+        self.leaves.iter().position(|leaf| leaf == commitment).map(|position| position as u64)
+    }
+
+    pub fn checkpoint(&self) -> NoteTreeCheckpoint {
+        NoteTreeCheckpoint {
+            size: self.leaves.len(),
+            frontier: self.frontier,
+            root: self.root,
+        }
+    }
+
+    /// Drop every leaf appended since `checkpoint` was taken
+    pub fn rewind(&mut self, checkpoint: &NoteTreeCheckpoint) {
+        self.leaves.truncate(checkpoint.size);
+        self.frontier = checkpoint.frontier;
+        self.root = checkpoint.root;
+    }
+
+    /// Authentication path of the leaf at `position` to the current root
+    pub fn path(&self, position: u64) -> Option<NotePath> {
+        let position = usize::try_from(position).ok()?;
+        if position >= self.leaves.len() {
+            return None;
+        }
+
+        let mut level = self.leaves.clone();
+        let mut index = position;
+        let mut siblings = Vec::with_capacity(NOTE_TREE_DEPTH);
+        for height in 0..NOTE_TREE_DEPTH {
+            siblings.push(level.get(index ^ 1).copied().unwrap_or_else(|| empty_note_subtree(height)));
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_note_nodes(left, right),
+                    [left] => hash_note_nodes(left, &empty_note_subtree(height)),
+                    _ => unreachable!("chunks(2) yields one or two nodes"),
+                })
+                .collect();
+            index /= 2;
+        }
+
+        Some(NotePath { position: position as u64, siblings })
+    }
+}
+
+/// Authentication path of one note commitment in a [`NoteCommitmentTree`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotePath {
+    pub position: u64,
+    /// Sibling hashes from the leaf level upwards, one per level
+    pub siblings: Vec<Hash>,
+}
+
+impl NotePath {
+    /// Root of the tree holding `commitment` at this path, or `None` for a
+    /// malformed path
+    pub fn root(&self, commitment: &Hash) -> Option<Hash> {
+        if self.siblings.len() != NOTE_TREE_DEPTH || self.position >= 1u64 << NOTE_TREE_DEPTH {
+            return None;
+        }
+        let mut node = *commitment;
+        for (height, sibling) in self.siblings.iter().enumerate() {
+            node = if (self.position >> height) & 1 == 0 {
+                hash_note_nodes(&node, sibling)
+            } else {
+                hash_note_nodes(sibling, &node)
+            };
+        }
+        Some(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(n: u8) -> Hash {
+        Sha256::digest([n]).into()
+    }
+
+    #[test]
+    fn test_merkle_proofs_all_sizes() {
+        for count in 1..=9u8 {
+            let leaves: Vec<Hash> = (0..count).map(leaf).collect();
+            let root = merkle_root(&leaves);
+            for (index, item) in leaves.iter().enumerate() {
+                let proof = MerkleProof::generate(&leaves, index).unwrap();
+                assert!(proof.verify(&root, item), "leaf {} of {}", index, count);
+                assert!(!proof.verify(&root, &leaf(200)));
+            }
+        }
+        assert_eq!(merkle_root(&[]), [0u8; 32]);
+        assert!(MerkleProof::generate(&[leaf(1)], 1).is_none());
+    }
+
+    #[test]
+    fn test_merkle_root_distinguishes_duplicated_tail() {
+        // Duplicating the odd last leaf must not reproduce the same root
+        let three = vec![leaf(1), leaf(2), leaf(3)];
+        let four = vec![leaf(1), leaf(2), leaf(3), leaf(3)];
+        assert_ne!(merkle_root(&three), merkle_root(&four));
+    }
+
+    #[test]
+    fn test_sparse_merkle_proofs() {
+        let mut tree = SparseMerkleTree::new();
+        assert_eq!(tree.root(), default_hash(0));
+
+        for n in 0..20u8 {
+            tree.insert(leaf(n), leaf(n + 100));
+        }
+        let root = tree.root();
+
+        let proof = tree.prove(&leaf(7));
+        assert!(proof.verify(&root, &leaf(7), Some(&leaf(107))));
+        assert!(!proof.verify(&root, &leaf(7), Some(&leaf(108))));
+        assert!(!proof.verify(&root, &leaf(7), None));
+
+        let absent = tree.prove(&leaf(50));
+        assert!(absent.verify(&root, &leaf(50), None));
+        assert!(!absent.verify(&root, &leaf(50), Some(&leaf(150))));
+
+        // Insertion order does not affect the root
+        let mut reversed = SparseMerkleTree::new();
+        for n in (0..20u8).rev() {
+            reversed.insert(leaf(n), leaf(n + 100));
+        }
+        assert_eq!(reversed.root(), root);
+    }
+
+    #[test]
+    fn test_note_commitment_tree() {
+        let mut tree = NoteCommitmentTree::new();
+        assert_eq!(tree.root(), empty_note_subtree(NOTE_TREE_DEPTH));
+
+        for n in 0..5u8 {
+            assert_eq!(tree.append(leaf(n)), Some(n as u64));
+        }
+        let checkpoint = tree.checkpoint();
+        let root = tree.root();
+        for n in 0..5u8 {
+            let path = tree.path(n as u64).unwrap();
+            assert_eq!(path.root(&leaf(n)), Some(root));
+            assert_ne!(path.root(&leaf(n + 1)), Some(root));
+        }
+        assert!(tree.path(5).is_none());
+
+        // Rewinding restores the earlier root, and decoding rebuilds it
+        tree.append(leaf(5));
+        assert_ne!(tree.root(), root);
+        tree.rewind(&checkpoint);
+        assert_eq!(tree.root(), root);
+        assert_eq!(tree.len(), 5);
+        let decoded: NoteCommitmentTree = bincode::deserialize(&bincode::serialize(&tree).unwrap()).unwrap();
+        assert_eq!(decoded, tree);
+    }
+}
@@ -20,6 +20,7 @@ use tokio::{
 };
 
 
+pub mod amount;
 pub mod contracts;
 pub mod crypto;
 pub mod dao;
@@ -27,35 +28,23 @@ pub mod dapp_launchpad;
 pub mod monitoring;
 pub mod dns;
 pub mod economics;
+pub mod jubjub;
+pub mod merkle;
+pub mod poseidon;
 pub mod routing;
+pub mod shielded;
+pub mod spend_circuit;
 pub mod consensus_engine;
+pub mod circom;
 pub mod zk_proofs;
+pub mod proof_envelope;
+pub mod prover;
 pub mod zk_transactions;
+pub mod viewing_keys;
 pub mod p2p_network;
 pub mod ceremony_participants;
 pub mod ceremony_coordinator;
 
-
-mod routing_proof_serde {
-    use serde::{Serialize, Deserialize, Serializer, Deserializer};
-    use super::zk_proofs::ByteRoutingProof;
-
-    pub fn serialize<S>(proof: &ByteRoutingProof, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        Serialize::serialize(proof, serializer)
-    }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<ByteRoutingProof, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        Deserialize::deserialize(deserializer)
-    }
-}
-
-
 pub use ceremony_participants::{
     CeremonyParticipantManager, ParticipantType, CeremonyParticipant, 
     ParticipationStatus, CeremonyState, CeremonyPhase, CeremonyStats
@@ -64,12 +53,15 @@ pub use ceremony_coordinator::{
     ZhtpCeremonyCoordinator, TrustedSetupResult, CeremonyAttestation,
     run_zhtp_trusted_setup_ceremony
 };
+pub use amount::Amount;
 pub use contracts::WasmRuntime;
 pub use crypto::{Keypair, Signature, KeyPackage, KeyStatus};
 pub use dns::{ZhtpDNS, DomainRecord, SubdomainRecord, CertificateRecord, OwnershipProof};
 pub use routing::{NodeInfo, RoutingTable};
 pub use consensus_engine::{ZhtpConsensusEngine, ConsensusStatus, ZkValidator, ZkBlock, ZkConsensusParams, ValidatorStatus};
-pub use zk_proofs::{RoutingProof, ByteRoutingProof};
+pub use zk_proofs::RoutingProof;
+pub use proof_envelope::{ProofEnvelope, ProofStatement, ProofSystem, VerifierRegistry};
+pub use prover::{ProofCancellation, ProverPool};
 pub use zk_transactions::{ZkTransaction, ZkTransactionPool, ZkBalance};
 
 
@@ -78,8 +70,7 @@ pub struct ZhtpPacket {
     pub header: PacketHeader,
     pub payload: Vec<u8>,
     pub key_package: Option<KeyPackage>,
-    #[serde(with = "routing_proof_serde")]
-    pub routing_proof: ByteRoutingProof,
+    pub routing_proof: ProofEnvelope,
     pub signature: Signature,
 }
 
@@ -152,12 +143,12 @@ pub struct ZhtpNode {
 
 impl ZhtpPacket {
     pub fn with_routing_proof(mut self, proof: RoutingProof) -> Self {
-        self.routing_proof = ByteRoutingProof::from(proof);
+        self.routing_proof = ProofEnvelope::from(proof);
         self
     }
 
-    pub fn get_routing_proof(&self) -> Result<RoutingProof, ark_serialize::SerializationError> {
-        RoutingProof::try_from(self.routing_proof.clone())
+    pub fn get_routing_proof(&self) -> Result<RoutingProof> {
+        self.routing_proof.to_routing_proof()
     }
 }
 
@@ -281,11 +272,7 @@ impl ZhtpNode {
             header,
             payload,
             key_package: None,
-            routing_proof: ByteRoutingProof {
-                commitments: vec![],
-                elements: vec![],
-                inputs: vec![],
-            },
+            routing_proof: ProofEnvelope::none(),
             signature,
         })
     }
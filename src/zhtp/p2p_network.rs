@@ -1,16 +1,20 @@
 use crate::zhtp::{
-    ZhtpPacket, PacketHeader, ByteRoutingProof,
+    ZhtpPacket, PacketHeader, ProofEnvelope, ProofStatement, VerifierRegistry,
+    amount::Amount,
     consensus_engine::{ZhtpConsensusEngine, ZkValidator, ZkConsensusParams},
     crypto::{Keypair, Signature, KeyPackage},
     economics::ZhtpEconomics,
     zk_transactions::{ZkTransaction, ZkTransactionPool},
-    zk_proofs::{RoutingProof, UnifiedCircuit, ZkGroup, ZkGroupTrait},
+    zk_proofs::{UnifiedCircuit, ZkGroup, ZkGroupTrait},
+    prover::ProofCancellation,
 };
+use crate::blockchain::{Block, BlockImport};
+use crate::snapshot::{SignedSnapshot, SnapshotDownload, SnapshotManifest, SNAPSHOT_CHUNK_SIZE, SNAPSHOT_INTERVAL};
 use anyhow::{Result, anyhow};
 use serde::{Serialize, Deserialize};
 use sha2::{Sha256, Digest};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     net::SocketAddr,
     sync::Arc,
     time::{Duration, SystemTime},
@@ -44,6 +48,74 @@ pub struct ZhtpP2PNetwork {
     tx_pool: Arc<RwLock<ZkTransactionPool>>,
     /// Secure sessions with encrypted communication
     secure_sessions: Arc<RwLock<HashMap<SocketAddr, SecureSession>>>,
+    /// Genesis block hash of our chain; peers on another chain are refused
+    genesis_hash: String,
+    /// Genesis hash each peer announced in its discovery request or
+    /// handshake, by the address its packets come from. Other messages are
+    /// only accepted from peers whose genesis matches ours.
+    peer_genesis: Arc<RwLock<HashMap<SocketAddr, String>>>,
+    /// Snapshots served to peers and fetched from them
+    snapshots: Arc<RwLock<SnapshotExchange>>,
+}
+
+/// Number of snapshots kept ready for serving to peers
+const MAX_SERVED_SNAPSHOTS: usize = 2;
+/// Chunk requests sent to a peer per fetch round
+const SNAPSHOT_CHUNKS_PER_ROUND: usize = 16;
+/// How long [`ZhtpP2PNetwork::fetch_snapshot`] waits for a peer
+const SNAPSHOT_FETCH_TIMEOUT: Duration = Duration::from_secs(300);
+/// How often the node checks whether a new snapshot is due for serving
+const SNAPSHOT_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// Snapshot replies sent to one peer per second, twice what a fetching
+/// node asks for
+const MAX_SNAPSHOT_REPLIES_PER_SECOND: usize = 4 * SNAPSHOT_CHUNKS_PER_ROUND;
+/// Most packets taken off the socket at once, so their routing proofs can
+/// be verified together
+const MAX_RECEIVE_BATCH: usize = 64;
+/// How long a sender waits for a packet routing or validator proof
+const PROOF_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Snapshot transfers between this node and its peers
+#[derive(Default)]
+struct SnapshotExchange {
+    /// Encoded snapshots offered to peers, oldest first
+    served: VecDeque<(SnapshotManifest, Arc<Vec<u8>>)>,
+    /// Peers asked for a snapshot that have not offered one yet
+    requested: HashSet<SocketAddr>,
+    /// Snapshots being fetched, by the peer serving them
+    downloads: HashMap<SocketAddr, SnapshotDownload>,
+    /// Fetched snapshots not yet collected, by the peer that served them
+    completed: HashMap<SocketAddr, SignedSnapshot>,
+    /// Start of the current rate limit window and the snapshot replies sent
+    /// in it, by peer
+    replies: HashMap<SocketAddr, (tokio::time::Instant, usize)>,
+}
+
+impl SnapshotExchange {
+    fn served(&self, snapshot_hash: &[u8; 32]) -> Option<&(SnapshotManifest, Arc<Vec<u8>>)> {
+        self.served.iter().find(|(manifest, _)| manifest.snapshot_hash == *snapshot_hash)
+    }
+
+    /// Manifest of the served snapshot at `height`, or of the newest one
+    fn offer(&self, height: Option<u64>) -> Option<&SnapshotManifest> {
+        let mut served = self.served.iter().map(|(manifest, _)| manifest);
+        match height {
+            Some(height) => served.find(|manifest| manifest.height == height),
+            None => served.next_back(),
+        }
+    }
+
+    /// Count a reply to `peer`, refusing it once the peer was sent
+    /// [`MAX_SNAPSHOT_REPLIES_PER_SECOND`] in the current second
+    fn allow_reply(&mut self, peer: SocketAddr, now: tokio::time::Instant) -> bool {
+        let (window, count) = self.replies.entry(peer).or_insert((now, 0));
+        if now.duration_since(*window) >= Duration::from_secs(1) {
+            *window = now;
+            *count = 0;
+        }
+        *count += 1;
+        *count <= MAX_SNAPSHOT_REPLIES_PER_SECOND
+    }
 }
 
 /// ZHTP Peer information with zero-knowledge proofs
@@ -62,7 +134,7 @@ pub struct ZhtpPeer {
     /// Connection state
     pub state: PeerState,
     /// Zero-knowledge proof of peer validity
-    pub validity_proof: Option<ByteRoutingProof>,
+    pub validity_proof: Option<ProofEnvelope>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -115,31 +187,36 @@ pub enum ZhtpP2PMessage {
         sender_addr: SocketAddr,
         protocol_version: String,
         capabilities: Vec<String>,
-        zk_proof: ByteRoutingProof,
+        zk_proof: ProofEnvelope,
+        genesis_hash: String,
     },
     /// Network discovery response
     DiscoveryResponse {
         peers: Vec<ZhtpPeer>,
         network_info: NetworkTopology,
-        zk_proof: ByteRoutingProof,
+        zk_proof: ProofEnvelope,
     },
     /// Consensus message with ZK proofs
     ConsensusMessage {
         round: u64,
         message_type: ConsensusMessageType,
-        zk_proof: ByteRoutingProof,
+        zk_proof: ProofEnvelope,
         validator_signature: Signature,
     },
     /// Transaction propagation
     TransactionBroadcast {
         transaction: ZkTransaction,
         hop_count: u8,
-        zk_proof: ByteRoutingProof,
+        zk_proof: ProofEnvelope,
     },    /// Block announcement with ZK proofs
     BlockAnnouncement {
         block_hash: [u8; 32],
         block_height: u64,
-        validator_proofs: Vec<ByteRoutingProof>,
+        validator_proofs: Vec<ProofEnvelope>,
+        /// The announced block itself, JSON encoded as in the block log:
+        /// blocks have fields that are left out when empty, which this
+        /// binary message encoding cannot represent
+        block: Option<Vec<u8>>,
     },
     /// Peer validation request
     PeerValidation {
@@ -149,7 +226,7 @@ pub enum ZhtpP2PMessage {
     /// Peer validation response
     ValidationResponse {
         response: [u8; 32],
-        zk_proof: ByteRoutingProof,
+        zk_proof: ProofEnvelope,
     },
     /// Secure handshake for encrypted communication
     SecureHandshake {
@@ -157,9 +234,46 @@ pub enum ZhtpP2PMessage {
         sender_public_key: Vec<u8>,
         key_exchange_data: Vec<u8>,
         protocol_version: String,
+        genesis_hash: String,
+    },
+    /// Ask a peer for the snapshot it serves at `height`, or its newest one
+    SnapshotRequest {
+        height: Option<u64>,
+    },
+    /// Signed description of a snapshot the sender is ready to serve
+    SnapshotOffer {
+        manifest: SnapshotManifest,
+    },
+    /// Ask for one chunk of an offered snapshot
+    SnapshotChunkRequest {
+        snapshot_hash: [u8; 32],
+        index: u32,
+    },
+    /// One chunk of an offered snapshot
+    SnapshotChunk {
+        snapshot_hash: [u8; 32],
+        index: u32,
+        data: Vec<u8>,
     },
 }
 
+impl ZhtpP2PMessage {
+    /// Whether the packet carrying this message must hold a routing proof.
+    /// Messages that vouch for a peer or a consensus vote do; the others
+    /// are checked on their own contents: blocks by their validator proofs
+    /// and the chain's rules, transactions by their own proofs, and
+    /// snapshots by the signed manifest.
+    pub fn needs_routing_proof(&self) -> bool {
+        matches!(
+            self,
+            ZhtpP2PMessage::DiscoveryRequest { .. }
+                | ZhtpP2PMessage::DiscoveryResponse { .. }
+                | ZhtpP2PMessage::ConsensusMessage { .. }
+                | ZhtpP2PMessage::ValidationResponse { .. }
+        )
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ConsensusMessageType {
     Propose,
@@ -182,7 +296,7 @@ impl ZhtpP2PNetwork {
         let economics = Arc::new(ZhtpEconomics::new());
           // Initialize consensus with ZK parameters
         let consensus_params = ZkConsensusParams {
-            min_stake: 1000.0,
+            min_stake: Amount::from_zhtp(1000),
             max_validators: 1000,
             round_timeout: 30,
             min_votes: 2,
@@ -217,6 +331,7 @@ impl ZhtpP2PNetwork {
         
         // Initialize transaction pool
         let tx_pool = Arc::new(RwLock::new(ZkTransactionPool::new()));
+        let genesis_hash = consensus.get_blockchain().await.genesis_hash().await;
           Ok(ZhtpP2PNetwork {
             node_keypair,
             consensus,
@@ -228,8 +343,65 @@ impl ZhtpP2PNetwork {
             economics,
             tx_pool,
             secure_sessions: Arc::new(RwLock::new(HashMap::new())),
+            genesis_hash,
+            peer_genesis: Arc::new(RwLock::new(HashMap::new())),
+            snapshots: Arc::new(RwLock::new(SnapshotExchange::default())),
         })
     }
+
+    /// Only peer with nodes whose chain starts from `genesis_hash`
+    pub fn with_genesis_hash(mut self, genesis_hash: String) -> Self {
+        self.genesis_hash = genesis_hash;
+        self
+    }
+
+    /// Identify as `keypair`, e.g. the node's persistent identity key, which
+    /// also signs the snapshots served to peers
+    pub fn with_keypair(mut self, keypair: Keypair) -> Self {
+        self.node_keypair = keypair;
+        self
+    }
+
+    /// Refuse a peer whose chain starts from a different genesis block
+    fn check_genesis(peer_addr: SocketAddr, peer_genesis: &str, local_genesis: &str) -> Result<()> {
+        if peer_genesis != local_genesis {
+            warn!(
+                "Refusing peer {} - genesis {} does not match ours ({})",
+                peer_addr, peer_genesis, local_genesis
+            );
+            return Err(anyhow!("Peer {} is on a different chain (genesis {})", peer_addr, peer_genesis));
+        }
+        Ok(())
+    }
+
+    /// Record the genesis hash `peer_addr` announced and refuse it unless it
+    /// is ours. Returns whether the peer announced one for the first time.
+    async fn record_genesis(
+        peer_addr: SocketAddr,
+        peer_genesis: &str,
+        local_genesis: &str,
+        peer_geneses: &Arc<RwLock<HashMap<SocketAddr, String>>>,
+    ) -> Result<bool> {
+        let first = peer_geneses
+            .write()
+            .await
+            .insert(peer_addr, peer_genesis.to_string())
+            .is_none();
+        Self::check_genesis(peer_addr, peer_genesis, local_genesis)?;
+        Ok(first)
+    }
+
+    /// Refuse messages from a peer that has not announced our genesis hash
+    async fn check_peer_genesis(
+        peer_addr: SocketAddr,
+        local_genesis: &str,
+        peer_geneses: &Arc<RwLock<HashMap<SocketAddr, String>>>,
+    ) -> Result<()> {
+        match peer_geneses.read().await.get(&peer_addr) {
+            Some(peer_genesis) => Self::check_genesis(peer_addr, peer_genesis, local_genesis),
+            None => Err(anyhow!("Dropping message from {} - no genesis check passed yet", peer_addr)),
+        }
+    }
       /// Start the ZHTP P2P network
     pub async fn start(&self) -> Result<()> {
         info!("Starting ZHTP P2P Network...");
@@ -242,6 +414,9 @@ impl ZhtpP2PNetwork {
         
         // Start consensus participation
         self.start_consensus_participation().await?;
+
+        // Keep a recent snapshot ready for peers
+        self.start_snapshot_production();
         
         // Allow the network stack to stabilize before connections
         sleep(Duration::from_millis(100)).await;
@@ -259,6 +434,7 @@ impl ZhtpP2PNetwork {
         let local_addr = self.local_addr;
         let discovery = self.discovery.clone();
         let node_keypair = self.node_keypair.clone();
+        let genesis_hash = self.genesis_hash.clone();
         
         tokio::spawn(async move {
             let mut discovery_interval = interval(Duration::from_secs(60));
@@ -278,6 +454,7 @@ impl ZhtpP2PNetwork {
                         local_addr,
                         peer_addr,
                         &node_keypair,
+                        &genesis_hash,
                     ).await {
                         warn!("Failed to send discovery request to {}: {}", peer_addr, e);
                     }
@@ -296,6 +473,10 @@ impl ZhtpP2PNetwork {
         let tx_pool = self.tx_pool.clone();
         let secure_sessions = self.secure_sessions.clone();
         let node_keypair = self.node_keypair.clone();
+        let genesis_hash = self.genesis_hash.clone();
+        let peer_genesis = self.peer_genesis.clone();
+        let snapshots = self.snapshots.clone();
+        let local_addr = self.local_addr;
         
         tokio::spawn(async move {
             let mut buffer = [0u8; 65536];
@@ -305,38 +486,78 @@ impl ZhtpP2PNetwork {
                 match socket.recv_from(&mut buffer).await {
                     Ok((len, peer_addr)) => {
                         consecutive_errors = 0; // Reset error counter on success
-                        let packet_data = &buffer[..len];
-                        
-                        // Try to process as encrypted packet first
-                        if let Ok(encrypted_packet) = bincode::deserialize::<EncryptedZhtpPacket>(packet_data) {
-                            if let Err(e) = ZhtpP2PNetwork::process_encrypted_packet_static(
-                                encrypted_packet,
+
+                        // Take whatever else has already arrived, so one
+                        // pairing check covers the whole batch's proofs
+                        let mut received = vec![(buffer[..len].to_vec(), peer_addr)];
+                        while received.len() < MAX_RECEIVE_BATCH {
+                            match socket.try_recv_from(&mut buffer) {
+                                Ok((len, peer_addr)) => received.push((buffer[..len].to_vec(), peer_addr)),
+                                Err(_) => break,
+                            }
+                        }
+
+                        let mut packets = Vec::with_capacity(received.len());
+                        for (packet_data, peer_addr) in received {
+                            // Try to process as encrypted packet first
+                            if let Ok(encrypted_packet) = bincode::deserialize::<EncryptedZhtpPacket>(&packet_data) {
+                                // Encrypted messages carry no genesis of their
+                                // own, so the sender must have passed the check
+                                let processed = match Self::check_peer_genesis(peer_addr, &genesis_hash, &peer_genesis).await {
+                                    Ok(()) => ZhtpP2PNetwork::process_encrypted_packet_static(
+                                        encrypted_packet,
+                                        peer_addr,
+                                        &peers,
+                                        &consensus,
+                                        &tx_pool,
+                                        &secure_sessions,
+                                        &node_keypair,
+                                    ).await,
+                                    Err(e) => Err(e),
+                                };
+                                if let Err(e) = processed {
+                                    debug!("Failed to process encrypted packet from {}: {}", peer_addr, e);
+                                    // Fall back to regular packet processing
+                                } else {
+                                    continue; // Successfully processed encrypted packet
+                                }
+                            }
+
+                            match Self::decode_packet(&packet_data) {
+                                Ok((packet, message)) => packets.push((packet, message, peer_addr)),
+                                Err(e) => warn!("Failed to decode ZHTP packet from {}: {}", peer_addr, e),
+                            }
+                        }
+
+                        // Verify packet routing proofs as one batch
+                        let verified = Self::verify_routing_proofs(
+                            &packets.iter().map(|(packet, message, _)| (packet, message)).collect::<Vec<_>>(),
+                        );
+
+                        // Process as regular ZHTP packets
+                        for ((packet, message, peer_addr), verified) in packets.into_iter().zip(verified) {
+                            if !verified {
+                                warn!("Failed to process ZHTP packet from {}: invalid routing proof", peer_addr);
+                                continue;
+                            }
+                            if let Err(e) = ZhtpP2PNetwork::process_zhtp_packet_static(
+                                packet,
+                                message,
                                 peer_addr,
                                 &peers,
                                 &consensus,
                                 &tx_pool,
                                 &secure_sessions,
                                 &node_keypair,
+                                &genesis_hash,
+                                &peer_genesis,
+                                &socket,
+                                local_addr,
+                                &snapshots,
                             ).await {
-                                debug!("Failed to process encrypted packet from {}: {}", peer_addr, e);
-                                // Fall back to regular packet processing
-                            } else {
-                                continue; // Successfully processed encrypted packet
+                                warn!("Failed to process ZHTP packet from {}: {}", peer_addr, e);
                             }
                         }
-                        
-                        // Process as regular ZHTP packet
-                        if let Err(e) = ZhtpP2PNetwork::process_zhtp_packet_static(
-                            packet_data,
-                            peer_addr,
-                            &peers,
-                            &consensus,
-                            &tx_pool,
-                            &secure_sessions,
-                            &node_keypair,
-                        ).await {
-                            warn!("Failed to process ZHTP packet from {}: {}", peer_addr, e);
-                        }
                     }
                     Err(e) => {
                         consecutive_errors += 1;
@@ -449,6 +670,7 @@ impl ZhtpP2PNetwork {
                 self.local_addr,
                 peer_addr,
                 &self.node_keypair,
+                &self.genesis_hash,
             ).await {
                 Ok(_) => break,
                 Err(e) => {
@@ -488,46 +710,23 @@ impl ZhtpP2PNetwork {
         local_addr: SocketAddr,
         peer_addr: SocketAddr,
         keypair: &Keypair,
+        genesis_hash: &str,
     ) -> Result<()> {
-        // Create REAL ZK proof for discovery request using UnifiedCircuit
-        let source_node = local_addr.to_string().as_bytes().to_vec();
-        let destination_node = peer_addr.to_string().as_bytes().to_vec();
-        
-        // Build routing table for proof generation
-        let mut routing_table = std::collections::HashMap::new();
-        routing_table.insert(source_node.clone(), vec![destination_node.clone()]);
-        
-        // Create routing path (direct connection for discovery)
-        let route_path = vec![source_node.clone(), destination_node.clone()];
-        
-        // Generate storage proof components
-        let public_key_hash = keypair.hash_message(keypair.public_key().as_slice());
-        let storage_proof = vec![public_key_hash];
-        
-        // Create unified circuit for network proof
-        let mut circuit = crate::zhtp::zk_proofs::UnifiedCircuit::new(
-            source_node.clone(),
-            destination_node.clone(),
-            route_path,
-            routing_table,
-            public_key_hash,
-            storage_proof,
-            ZkGroupTrait::generator(),
-            1024, // bandwidth_used
-            vec![(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(), true)], // uptime
-            vec![(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(), 10.0)], // latency
-        );
-        
-        // Generate ZK proof using the circuit
-        let zk_proof = crate::zhtp::zk_proofs::generate_unified_proof(
-            &mut circuit,
-            &source_node,
-            &destination_node,
-            public_key_hash
-        )?;
-        
-        // Convert to ByteRoutingProof for transmission
-        let byte_proof = ByteRoutingProof::try_from(zk_proof)?;
+        let header = PacketHeader {
+            id: rand::random(), // Random packet ID
+            source_addr: Some(local_addr), // Source address
+            destination_commitment: {
+                // Use BLAKE3 instead of SHA256 for quantum resistance
+                let hash = keypair.hash_message(peer_addr.to_string().as_bytes());
+                hash
+            }, // Destination commitment
+            ttl: 64, // Time to live
+            routing_metadata: vec![], // Empty routing metadata
+        };
+
+        // Create REAL ZK proof for discovery request using UnifiedCircuit,
+        // off the async runtime threads
+        let byte_proof = Self::packet_routing_proof(&header).await?;
         
         let discovery_message = ZhtpP2PMessage::DiscoveryRequest {
             sender_addr: local_addr,
@@ -539,21 +738,12 @@ impl ZhtpP2PNetwork {
                 "quantum_resistant".to_string(),
             ],
             zk_proof: byte_proof.clone(),
+            genesis_hash: genesis_hash.to_string(),
         };
         
         // Create ZHTP packet
         let packet = ZhtpPacket {
-            header: PacketHeader {
-                id: rand::random(), // Random packet ID
-                source_addr: Some(local_addr), // Source address
-                destination_commitment: {
-                    // Use BLAKE3 instead of SHA256 for quantum resistance
-                    let hash = keypair.hash_message(peer_addr.to_string().as_bytes());
-                    hash
-                }, // Destination commitment
-                ttl: 64, // Time to live
-                routing_metadata: vec![], // Empty routing metadata
-            },
+            header,
             payload: bincode::serialize(&discovery_message)?,
             routing_proof: byte_proof,
             key_package: None, // No key package for discovery messages
@@ -578,35 +768,59 @@ impl ZhtpP2PNetwork {
                 Err(anyhow!("Timeout sending discovery request to {}", peer_addr))
             }
         }
-    }    /// Process received ZHTP packet (static version for spawned tasks)
+    }
+
+    /// Decode a ZHTP packet and the message it carries
+    fn decode_packet(packet_data: &[u8]) -> Result<(ZhtpPacket, ZhtpP2PMessage)> {
+        let packet: ZhtpPacket = bincode::deserialize(packet_data)?;
+        let message: ZhtpP2PMessage = bincode::deserialize(&packet.payload)?;
+        Ok((packet, message))
+    }
+
+    /// Process a received ZHTP packet whose routing proof was verified
+    /// (static version for spawned tasks)
     async fn process_zhtp_packet_static(
-        packet_data: &[u8],
+        packet: ZhtpPacket,
+        message: ZhtpP2PMessage,
         peer_addr: SocketAddr,
         peers: &Arc<RwLock<HashMap<SocketAddr, ZhtpPeer>>>,
         consensus: &Arc<ZhtpConsensusEngine>,
         tx_pool: &Arc<RwLock<ZkTransactionPool>>,
         secure_sessions: &Arc<RwLock<HashMap<SocketAddr, SecureSession>>>,
         node_keypair: &Keypair,
+        genesis_hash: &str,
+        peer_genesis: &Arc<RwLock<HashMap<SocketAddr, String>>>,
+        socket: &Arc<UdpSocket>,
+        local_addr: SocketAddr,
+        snapshots: &Arc<RwLock<SnapshotExchange>>,
     ) -> Result<()> {
-        // Deserialize ZHTP packet
-        let packet: ZhtpPacket = bincode::deserialize(packet_data)?;
-        
-        // Verify packet routing proof
-        if !Self::verify_routing_proof(&packet.routing_proof) {
-            return Err(anyhow!("Invalid routing proof from {}", peer_addr));
-        }
-        
-        // Deserialize P2P message
-        let message: ZhtpP2PMessage = bincode::deserialize(&packet.payload)?;
-        
+        // Peers announce their genesis in discovery and handshakes; nothing
+        // else is accepted from a peer before that check passed
+        let first_contact = match &message {
+            ZhtpP2PMessage::DiscoveryRequest { genesis_hash: announced, .. }
+            | ZhtpP2PMessage::SecureHandshake { genesis_hash: announced, .. } => {
+                Self::record_genesis(peer_addr, announced, genesis_hash, peer_genesis).await?
+            }
+            _ => {
+                Self::check_peer_genesis(peer_addr, genesis_hash, peer_genesis).await?;
+                false
+            }
+        };
+
         match message {
             ZhtpP2PMessage::DiscoveryRequest {
                 sender_addr,
                 protocol_version,
                 capabilities,
                 zk_proof,
+                genesis_hash: _,
             } => {
                 debug!("Received discovery request from {}", sender_addr);
+                // The discovery proof is the packet's routing proof, which
+                // was verified with its batch
+                if zk_proof != packet.routing_proof {
+                    return Err(anyhow!("Discovery proof from {} is not the packet's routing proof", sender_addr));
+                }
                 Self::handle_discovery_request(
                     sender_addr,
                     protocol_version,
@@ -614,6 +828,24 @@ impl ZhtpP2PNetwork {
                     zk_proof,
                     peers,
                 ).await?;
+
+                // Announce our genesis back, so the new peer listens to us
+                if first_contact {
+                    let socket = socket.clone();
+                    let node_keypair = node_keypair.clone();
+                    let genesis_hash = genesis_hash.to_string();
+                    tokio::spawn(async move {
+                        if let Err(e) = Self::send_discovery_request(
+                            &socket,
+                            local_addr,
+                            peer_addr,
+                            &node_keypair,
+                            &genesis_hash,
+                        ).await {
+                            warn!("Failed to answer discovery request from {}: {}", peer_addr, e);
+                        }
+                    });
+                }
             }
             
             ZhtpP2PMessage::ConsensusMessage {
@@ -649,12 +881,14 @@ impl ZhtpP2PNetwork {
                 block_hash,
                 block_height,
                 validator_proofs,
+                block,
             } => {
                 debug!("Received block announcement for height {}", block_height);
                 Self::handle_block_announcement(
                     block_hash,
                     block_height,
                     validator_proofs,
+                    block,
                     consensus,
                 ).await?;
             }
@@ -664,6 +898,7 @@ impl ZhtpP2PNetwork {
                 sender_public_key,
                 key_exchange_data,
                 protocol_version,
+                genesis_hash: _,
             } => {
                 debug!("Received secure handshake from {}", sender_addr);
                 Self::handle_secure_handshake_static(
@@ -675,6 +910,65 @@ impl ZhtpP2PNetwork {
                     node_keypair,
                 ).await?;
             }
+
+            ZhtpP2PMessage::SnapshotRequest { height } => {
+                debug!("Received snapshot request from {}", peer_addr);
+                Self::check_snapshot_peer(peer_addr, peers, snapshots).await?;
+                let manifest = snapshots
+                    .read()
+                    .await
+                    .offer(height)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("No snapshot served for request from {}", peer_addr))?;
+                let offer = ZhtpP2PMessage::SnapshotOffer { manifest };
+                socket.send_to(&Self::encode_packet(local_addr, peer_addr, &offer)?, peer_addr).await?;
+            }
+
+            ZhtpP2PMessage::SnapshotOffer { manifest } => {
+                let mut exchange = snapshots.write().await;
+                if !exchange.requested.remove(&peer_addr) {
+                    return Err(anyhow!("Unsolicited snapshot offer from {}", peer_addr));
+                }
+                info!(
+                    "Peer {} offers snapshot at height {} in {} chunks",
+                    peer_addr, manifest.height, manifest.chunk_count()
+                );
+                exchange.downloads.insert(peer_addr, SnapshotDownload::new(manifest)?);
+            }
+
+            ZhtpP2PMessage::SnapshotChunkRequest { snapshot_hash, index } => {
+                Self::check_snapshot_peer(peer_addr, peers, snapshots).await?;
+                let chunk = {
+                    let exchange = snapshots.read().await;
+                    let (_, encoded) = exchange
+                        .served(&snapshot_hash)
+                        .ok_or_else(|| anyhow!("Snapshot requested by {} is no longer served", peer_addr))?;
+                    encoded
+                        .chunks(SNAPSHOT_CHUNK_SIZE)
+                        .nth(index as usize)
+                        .ok_or_else(|| anyhow!("Snapshot chunk {} out of range", index))?
+                        .to_vec()
+                };
+                let reply = ZhtpP2PMessage::SnapshotChunk { snapshot_hash, index, data: chunk };
+                socket.send_to(&Self::encode_packet(local_addr, peer_addr, &reply)?, peer_addr).await?;
+            }
+
+            ZhtpP2PMessage::SnapshotChunk { snapshot_hash, index, data } => {
+                let mut exchange = snapshots.write().await;
+                let Some(download) = exchange.downloads.get_mut(&peer_addr) else {
+                    return Ok(());
+                };
+                if download.manifest().snapshot_hash != snapshot_hash {
+                    return Ok(());
+                }
+                download.insert(index as usize, data)?;
+                if download.is_complete() {
+                    if let Some(download) = exchange.downloads.remove(&peer_addr) {
+                        let snapshot = download.finish()?;
+                        exchange.completed.insert(peer_addr, snapshot);
+                    }
+                }
+            }
             
             _ => {
                 debug!("Received other P2P message type");
@@ -683,44 +977,108 @@ impl ZhtpP2PNetwork {
         
         Ok(())
     }    /// Verify routing proof - PROPER ZK validation for network security
-    fn verify_routing_proof(proof: &ByteRoutingProof) -> bool {
-        // Convert to proper RoutingProof for full verification
-        let routing_proof = match RoutingProof::try_from(proof.clone()) {
-            Ok(proof) => proof,
-            Err(_) => {
-                log::warn!("Failed to convert ByteRoutingProof to RoutingProof");
-                return false;
+    fn verify_routing_proof(proof: &ProofEnvelope) -> bool {
+        // Perform full ZK proof verification with the envelope's verifier
+        match VerifierRegistry::global().verify(proof, &Self::routing_statement()) {
+            Ok(true) => true,
+            Ok(false) => {
+                log::warn!("ZK routing proof verification failed");
+                false
             }
-        };
+            Err(e) => {
+                log::warn!("Cannot verify routing proof: {}", e);
+                false
+            }
+        }
+    }
+    
+    /// Verify the routing proofs of many received packets together. A
+    /// packet without one passes only if its message does not need one.
+    fn verify_routing_proofs(packets: &[(&ZhtpPacket, &ZhtpP2PMessage)]) -> Vec<bool> {
+        let sources: Vec<Option<Vec<u8>>> = packets
+            .iter()
+            .map(|(packet, _)| match packet.routing_proof.is_none() {
+                true => None,
+                false => packet.header.source_addr.map(|addr| addr.to_string().into_bytes()),
+            })
+            .collect();
+        let items: Vec<(&ProofEnvelope, ProofStatement)> = packets
+            .iter()
+            .zip(&sources)
+            .filter_map(|((packet, _), source)| {
+                let source = source.as_deref()?;
+                Some((&packet.routing_proof, Self::packet_route_statement(source, &packet.header)))
+            })
+            .collect();
 
-        // Perform full ZK proof verification
-        use crate::zhtp::zk_proofs::verify_unified_proof;
-        
-        // For P2P network proofs, we verify basic routing structure
-        let dummy_source = b"network_node";
-        let dummy_destination = b"peer_node";
-        let dummy_root = [0u8; 32]; // No storage component needed for P2P discovery
-        
-        let verification_result = verify_unified_proof(
-            &routing_proof,
-            dummy_source,
-            dummy_destination,
-            dummy_root
+        let mut verified = VerifierRegistry::global().verify_batch(&items).into_iter();
+        let results: Vec<bool> = packets
+            .iter()
+            .zip(&sources)
+            .map(|((packet, message), source)| match source {
+                Some(_) => verified.next().unwrap_or(false),
+                None => packet.routing_proof.is_none() && !message.needs_routing_proof(),
+            })
+            .collect();
+        let rejected = results.iter().filter(|valid| !**valid).count();
+        if rejected > 0 {
+            log::warn!("ZK routing proof verification failed for {} of {} packets", rejected, packets.len());
+        }
+        results
+    }
+
+    /// What a packet's routing proof must show: a route from the sender's
+    /// address to the packet's destination commitment, bound to its id
+    fn packet_route_statement<'a>(source: &'a [u8], header: &'a PacketHeader) -> ProofStatement<'a> {
+        ProofStatement::Route {
+            source,
+            destination: &header.destination_commitment,
+            stored_data_root: header.id,
+        }
+    }
+
+    /// Routing proof for a packet with `header`, generated on the prover pool
+    async fn packet_routing_proof(header: &PacketHeader) -> Result<ProofEnvelope> {
+        let source = header
+            .source_addr
+            .ok_or_else(|| anyhow!("Packet has no source address to prove a route from"))?
+            .to_string()
+            .into_bytes();
+        let destination = header.destination_commitment.to_vec();
+        let mut routing_table = HashMap::new();
+        routing_table.insert(source.clone(), vec![destination.clone()]);
+        let circuit = UnifiedCircuit::new(
+            source.clone(),
+            destination.clone(),
+            vec![source, destination],
+            routing_table,
+            header.id,
+            vec![],
+            <ZkGroup as ZkGroupTrait>::generator(),
+            0,
+            vec![],
+            vec![],
         );
-        
-        if !verification_result {
-            log::warn!("ZK routing proof verification failed");
+        Ok(circuit.prove(&ProofCancellation::new(), PROOF_TIMEOUT).await?.into())
+    }
+
+    /// What P2P routing proofs must show
+    fn routing_statement() -> ProofStatement<'static> {
+        // For P2P network proofs, we verify basic routing structure
+        ProofStatement::Route {
+            source: b"network_node",
+            destination: b"peer_node",
+            stored_data_root: [0u8; 32], // No storage component needed for P2P discovery
         }
-        
-        verification_result
     }
     
-    /// Handle discovery request
+    /// Handle a discovery request whose proof was checked as the packet's
+    /// routing proof
     async fn handle_discovery_request(
         sender_addr: SocketAddr,
         protocol_version: String,
         capabilities: Vec<String>,
-        zk_proof: ByteRoutingProof,
+        zk_proof: ProofEnvelope,
         peers: &Arc<RwLock<HashMap<SocketAddr, ZhtpPeer>>>,
     ) -> Result<()> {
         // Verify protocol version
@@ -728,12 +1086,6 @@ impl ZhtpP2PNetwork {
             return Err(anyhow!("Unsupported protocol version: {}", protocol_version));
         }
         
-        // Verify ZK proof for peer discovery (CRITICAL: No bypassing allowed)
-        if !Self::verify_routing_proof(&zk_proof) {
-            warn!("Rejected discovery request from {} - invalid ZK proof", sender_addr);
-            return Err(anyhow!("Invalid ZK proof in discovery request"));
-        }
-        
         // Verify capabilities
         if !capabilities.contains(&"zk_proofs".to_string()) {
             warn!("Rejected discovery request from {} - missing ZK proof capability", sender_addr);
@@ -741,7 +1093,7 @@ impl ZhtpP2PNetwork {
         }
         
         // Convert ZK proof to RoutingProof for detailed validation
-        let routing_proof = RoutingProof::try_from(zk_proof)
+        let routing_proof = zk_proof.to_routing_proof()
             .map_err(|_| anyhow!("Failed to parse ZK proof"))?;
         
         // Additional validation: Check proof has sufficient complexity
@@ -758,7 +1110,7 @@ impl ZhtpP2PNetwork {
             protocol_versions: vec![protocol_version],
             validator_info: None,
             state: PeerState::Connected,
-            validity_proof: Some(ByteRoutingProof::try_from(routing_proof)?), // Store the verified proof
+            validity_proof: Some(zk_proof), // Store the verified proof
         };
         
         peers.write().await.insert(sender_addr, peer);
@@ -771,7 +1123,7 @@ impl ZhtpP2PNetwork {
     async fn handle_consensus_message(
         round: u64,
         message_type: ConsensusMessageType,
-        zk_proof: ByteRoutingProof,
+        zk_proof: ProofEnvelope,
         validator_signature: Signature,
         consensus: &Arc<ZhtpConsensusEngine>,
     ) -> Result<()> {
@@ -782,7 +1134,7 @@ impl ZhtpP2PNetwork {
         }
         
         // Convert to proper RoutingProof for consensus processing
-        let routing_proof = RoutingProof::try_from(zk_proof)
+        let routing_proof = zk_proof.to_routing_proof()
             .map_err(|_| anyhow!("Failed to parse consensus ZK proof"))?;
         
         // Verify signature is not empty for consensus messages
@@ -803,7 +1155,7 @@ impl ZhtpP2PNetwork {
         &self,
         transaction: ZkTransaction,
         hop_count: u8,
-        _zk_proof: ByteRoutingProof,
+        _zk_proof: ProofEnvelope,
         tx_pool: &Arc<RwLock<ZkTransactionPool>>,
     ) -> Result<()> {        // Add transaction to pool
         tx_pool.write().await.add_transaction(transaction.clone())?;
@@ -867,16 +1219,91 @@ impl ZhtpP2PNetwork {
     
     /// Handle block announcement
     async fn handle_block_announcement(
-        _block_hash: [u8; 32],
-        _block_height: u64,
-        _validator_proofs: Vec<ByteRoutingProof>,
-        _consensus: &Arc<ZhtpConsensusEngine>,
+        block_hash: [u8; 32],
+        block_height: u64,
+        validator_proofs: Vec<ProofEnvelope>,
+        block: Option<Vec<u8>>,
+        consensus: &Arc<ZhtpConsensusEngine>,
     ) -> Result<()> {
-        // Process block through consensus engine
         debug!("Processing block announcement");
+        let Some(block) = block else {
+            return Ok(());
+        };
+        let block: Block = serde_json::from_slice(&block)?;
+        if block.index != block_height || block.hash != hex::encode(block_hash) {
+            return Err(anyhow!("Announced block does not match announcement header"));
+        }
+
+        // The validator must prove it produced the block; check all of its
+        // proofs with one batched pairing check
+        if validator_proofs.is_empty() {
+            return Err(anyhow!("Announced block {} carries no validator proof", block_height));
+        }
+        let statement = Self::block_validator_statement(&block.validator, &block_hash);
+        let items: Vec<(&ProofEnvelope, ProofStatement)> =
+            validator_proofs.iter().map(|proof| (proof, statement)).collect();
+        if VerifierRegistry::global().verify_batch(&items).contains(&false) {
+            return Err(anyhow!("Invalid validator proof for announced block {}", block_height));
+        }
+
+        // Hand the block to the block tree, which applies fork choice
+        match consensus.import_block(block).await {
+            Ok(BlockImport::Reorganized(event)) => {
+                info!("Block {} caused a reorg at height {}", block_height, event.fork_height);
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Rejected announced block {}: {}", block_height, e),
+        }
         Ok(())
     }
     
+    /// Announce a block this node produced to its peers, together with the
+    /// validator proof they check before importing it
+    pub async fn announce_block(&self, block: &Block) -> Result<()> {
+        let block_hash: [u8; 32] = hex::decode(&block.hash)?
+            .try_into()
+            .map_err(|_| anyhow!("Block hash {} is not 32 bytes", block.hash))?;
+        let validator_proof = Self::block_validator_proof(&block.validator, block_hash).await?;
+        let message = ZhtpP2PMessage::BlockAnnouncement {
+            block_hash,
+            block_height: block.index,
+            validator_proofs: vec![validator_proof],
+            block: Some(serde_json::to_vec(block)?),
+        };
+        self.broadcast_message(message).await
+    }
+
+    /// Proof a validator attaches to its announcement of a block: a route
+    /// from the validator to the block hash, generated on the prover pool
+    pub async fn block_validator_proof(validator: &str, block_hash: [u8; 32]) -> Result<ProofEnvelope> {
+        let source = validator.as_bytes().to_vec();
+        let destination = block_hash.to_vec();
+        let mut routing_table = HashMap::new();
+        routing_table.insert(source.clone(), vec![destination.clone()]);
+        let circuit = UnifiedCircuit::new(
+            source.clone(),
+            destination.clone(),
+            vec![source, destination],
+            routing_table,
+            block_hash,
+            vec![],
+            <ZkGroup as ZkGroupTrait>::generator(),
+            0,
+            vec![],
+            vec![],
+        );
+        Ok(circuit.prove(&ProofCancellation::new(), PROOF_TIMEOUT).await?.into())
+    }
+
+    /// What a validator proof in a block announcement must show
+    fn block_validator_statement<'a>(validator: &'a str, block_hash: &'a [u8; 32]) -> ProofStatement<'a> {
+        ProofStatement::Route {
+            source: validator.as_bytes(),
+            destination: block_hash,
+            stored_data_root: *block_hash,
+        }
+    }
+    
     /// Participate in consensus
     async fn participate_in_consensus(
         _consensus: &Arc<ZhtpConsensusEngine>,
@@ -918,30 +1345,12 @@ impl ZhtpP2PNetwork {
         (avg_reputation + (connected_peers / peers.len() as f64)) / 2.0
     }
       /// Broadcast transaction to network
-    pub async fn broadcast_transaction(&self, transaction: ZkTransaction) -> Result<()> {        // Create balanced ZK proof for transaction broadcast
-        let tx_hash = format!("{:?}", transaction.nullifier);
-        let tx_bytes = tx_hash.as_bytes().to_vec();
-        
+    pub async fn broadcast_transaction(&self, transaction: ZkTransaction) -> Result<()> {
+        // The transaction carries its own validity and balance proofs
         let message = ZhtpP2PMessage::TransactionBroadcast {
             transaction,
             hop_count: 0,
-            zk_proof: ByteRoutingProof {
-                commitments: vec![
-                    vec![1, 2, 3], // Commitment 1
-                    tx_bytes.clone(), // Commitment 2 (transaction hash)
-                    vec![0, 1, 0], // Commitment 3 (padding)
-                ],
-                elements: vec![
-                    tx_bytes.clone(), // Element 1 (transaction hash)
-                    vec![1, 0, 1], // Element 2 (padding)
-                    vec![2, 1, 2], // Element 3 (padding)
-                ],
-                inputs: vec![
-                    tx_bytes.clone(), // Input 1 (transaction hash)
-                    vec![0, 2, 0], // Input 2 (padding)
-                    vec![1, 1, 1], // Input 3 (padding)
-                ],
-            },
+            zk_proof: ProofEnvelope::none(),
         };
         
         self.broadcast_message(message).await
@@ -953,40 +1362,150 @@ impl ZhtpP2PNetwork {
             self.peers.read().await.keys().cloned().collect()
         };
         
+        for peer_addr in peers {
+            let packet_bytes = Self::encode_packet(self.local_addr, peer_addr, &message)?;
+            if let Err(e) = self.socket.send_to(&packet_bytes, peer_addr).await {
+                warn!("Failed to send message to {}: {}", peer_addr, e);
+            }
+        }        
+        Ok(())
+    }
+
+    /// Wrap a message in a ZHTP packet addressed to `peer_addr`. The packet
+    /// carries no routing proof, so this only suits messages for which
+    /// [`ZhtpP2PMessage::needs_routing_proof`] is false.
+    fn encode_packet(local_addr: SocketAddr, peer_addr: SocketAddr, message: &ZhtpP2PMessage) -> Result<Vec<u8>> {
         let packet = ZhtpPacket {
             header: PacketHeader {
                 id: rand::random(), // Random packet ID
-                source_addr: Some(self.local_addr), // Source address
-                destination_commitment: [0u8; 32], // Will be overridden per peer
+                source_addr: Some(local_addr), // Source address
+                destination_commitment: Sha256::digest(peer_addr.to_string().as_bytes()).into(),
                 ttl: 64, // Time to live
                 routing_metadata: vec![], // Empty routing metadata
             },
-            payload: bincode::serialize(&message)?,
-            routing_proof: ByteRoutingProof {                commitments: vec![vec![1, 2, 3]],
-                elements: vec![],
-                inputs: vec![],
-            },
-            key_package: None, // No key package for broadcast messages
+            payload: bincode::serialize(message)?,
+            routing_proof: ProofEnvelope::none(),
+            key_package: None, // No key package for direct messages
             signature: Signature::empty(),
         };
-        
-        for peer_addr in peers {
-            let mut peer_packet = packet.clone();
-            peer_packet.header.destination_commitment = {
-                let mut hasher = Sha256::new();
-                hasher.update(peer_addr.to_string().as_bytes());
-                let result = hasher.finalize();
-                let mut commitment = [0u8; 32];
-                commitment.copy_from_slice(&result[..32]);                commitment
-            };
-            
-            let packet_bytes = bincode::serialize(&peer_packet)?;
-            if let Err(e) = self.socket.send_to(&packet_bytes, peer_addr).await {
-                warn!("Failed to send message to {}: {}", peer_addr, e);
+        Ok(bincode::serialize(&packet)?)
+    }
+
+    /// Only peers that passed discovery are answered, each at a limited
+    /// rate, so spoofed or flooding senders cannot turn small requests into
+    /// chunk-sized replies
+    async fn check_snapshot_peer(
+        peer_addr: SocketAddr,
+        peers: &Arc<RwLock<HashMap<SocketAddr, ZhtpPeer>>>,
+        snapshots: &Arc<RwLock<SnapshotExchange>>,
+    ) -> Result<()> {
+        if !peers.read().await.contains_key(&peer_addr) {
+            return Err(anyhow!("Snapshot request from unknown peer {}", peer_addr));
+        }
+        if !snapshots.write().await.allow_reply(peer_addr, tokio::time::Instant::now()) {
+            return Err(anyhow!("Snapshot requests from {} exceed the rate limit", peer_addr));
+        }
+        Ok(())
+    }
+
+    /// Build a snapshot every [`SNAPSHOT_INTERVAL`] blocks for serving to
+    /// peers; requests are only answered from these
+    fn start_snapshot_production(&self) {
+        let consensus = self.consensus.clone();
+        let node_keypair = self.node_keypair.clone();
+        let snapshots = self.snapshots.clone();
+
+        tokio::spawn(async move {
+            let mut refresh = interval(SNAPSHOT_REFRESH_INTERVAL);
+            loop {
+                refresh.tick().await;
+                if let Err(e) = Self::produce_snapshot(&consensus, &node_keypair, &snapshots).await {
+                    warn!("Failed to build snapshot for peers: {}", e);
+                }
             }
-        }        
+        });
+    }
+
+    /// Sign and keep the snapshot at the newest multiple of
+    /// [`SNAPSHOT_INTERVAL`] if it is not served yet
+    async fn produce_snapshot(
+        consensus: &Arc<ZhtpConsensusEngine>,
+        node_keypair: &Keypair,
+        snapshots: &Arc<RwLock<SnapshotExchange>>,
+    ) -> Result<()> {
+        let blockchain = consensus.get_blockchain().await;
+        let tip = blockchain.get_latest_block().await.index;
+        let height = tip - tip % SNAPSHOT_INTERVAL;
+        // Blocks pruned since can no longer be snapshotted
+        let Some(block) = blockchain.get_block_by_height(height).await.filter(|_| height > 0) else {
+            return Ok(());
+        };
+        if snapshots.read().await.served.iter().any(|(manifest, _)| manifest.block_hash == block.hash) {
+            return Ok(());
+        }
+
+        let snapshot = blockchain.create_snapshot(height).await?;
+        let (manifest, encoded) = SnapshotManifest::sign(&snapshot, node_keypair)?;
+        info!("Serving snapshot at height {} in {} chunks", height, manifest.chunk_count());
+        let mut exchange = snapshots.write().await;
+        if exchange.served.len() >= MAX_SERVED_SNAPSHOTS {
+            exchange.served.pop_front();
+        }
+        exchange.served.push_back((manifest, Arc::new(encoded)));
         Ok(())
     }
+
+    /// Fetch the snapshot `peer_addr` serves at `height`, a multiple of
+    /// [`SNAPSHOT_INTERVAL`], or its newest one.
+    ///
+    /// Chunks are checked against the signed manifest as they arrive; the
+    /// caller still has to verify the result against a trusted block hash
+    /// and its trusted snapshot signers before importing it.
+    pub async fn fetch_snapshot(&self, peer_addr: SocketAddr, height: Option<u64>) -> Result<SignedSnapshot> {
+        {
+            let mut exchange = self.snapshots.write().await;
+            exchange.downloads.remove(&peer_addr);
+            exchange.completed.remove(&peer_addr);
+            exchange.requested.insert(peer_addr);
+        }
+
+        let deadline = tokio::time::Instant::now() + SNAPSHOT_FETCH_TIMEOUT;
+        let mut round = interval(Duration::from_millis(500));
+        while tokio::time::Instant::now() < deadline {
+            round.tick().await;
+            let requests = {
+                let mut exchange = self.snapshots.write().await;
+                if let Some(snapshot) = exchange.completed.remove(&peer_addr) {
+                    info!("Fetched snapshot at height {} from {}", snapshot.snapshot.height(), peer_addr);
+                    return Ok(snapshot);
+                }
+                match exchange.downloads.get(&peer_addr) {
+                    Some(download) => {
+                        let snapshot_hash = download.manifest().snapshot_hash;
+                        download
+                            .missing()
+                            .into_iter()
+                            .take(SNAPSHOT_CHUNKS_PER_ROUND)
+                            .map(|index| ZhtpP2PMessage::SnapshotChunkRequest { snapshot_hash, index: index as u32 })
+                            .collect()
+                    }
+                    // Ask again until the peer makes an offer
+                    None if exchange.requested.contains(&peer_addr) => vec![ZhtpP2PMessage::SnapshotRequest { height }],
+                    None => return Err(anyhow!("Snapshot offered by {} was rejected", peer_addr)),
+                }
+            };
+            for request in &requests {
+                self.socket
+                    .send_to(&Self::encode_packet(self.local_addr, peer_addr, request)?, peer_addr)
+                    .await?;
+            }
+        }
+
+        let mut exchange = self.snapshots.write().await;
+        exchange.requested.remove(&peer_addr);
+        exchange.downloads.remove(&peer_addr);
+        Err(anyhow!("Timed out fetching snapshot from {}", peer_addr))
+    }
     
     /// Send a message to a specific peer
     async fn send_message_to_peer(
@@ -1102,7 +1621,7 @@ impl ZhtpP2PNetwork {
     async fn handle_transaction_broadcast_static(
         transaction: ZkTransaction,
         hop_count: u8,
-        _zk_proof: ByteRoutingProof,
+        _zk_proof: ProofEnvelope,
         tx_pool: &Arc<RwLock<ZkTransactionPool>>,
         peers: &Arc<RwLock<HashMap<SocketAddr, ZhtpPeer>>>,
     ) -> Result<()> {
@@ -1147,6 +1666,7 @@ impl ZhtpP2PNetwork {
             sender_public_key: self.node_keypair.public_key(),
             key_exchange_data: key_exchange_data.clone(),
             protocol_version: "zhtp/1.0-encrypted".to_string(),
+            genesis_hash: self.genesis_hash.clone(),
         };
         
         // Send encrypted handshake
@@ -1417,12 +1937,14 @@ impl ZhtpP2PNetwork {
                 block_hash,
                 block_height,
                 validator_proofs,
+                block,
             } => {
                 debug!("Received encrypted block announcement for height {}", block_height);
                 Self::handle_block_announcement(
                     block_hash,
                     block_height,
                     validator_proofs,
+                    block,
                     consensus,
                 ).await?;
             }
@@ -1486,3 +2008,120 @@ impl ZhtpP2PNetwork {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Network node on a free local port
+    async fn node(bootstrap_nodes: Vec<SocketAddr>) -> ZhtpP2PNetwork {
+        let addr = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        ZhtpP2PNetwork::new(addr, bootstrap_nodes).await.unwrap()
+    }
+
+    /// Poll `condition` until it holds, failing the test after a minute
+    async fn wait_for<F: std::future::Future<Output = bool>>(what: &str, mut condition: impl FnMut() -> F) {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(60);
+        while !condition().await {
+            assert!(tokio::time::Instant::now() < deadline, "timed out waiting for {}", what);
+            sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_nodes_exchange_messages() -> Result<()> {
+        let receiver = node(vec![]).await;
+        receiver.start().await?;
+        let sender = node(vec![receiver.local_addr]).await;
+        sender.start().await?;
+
+        // The discovery request's routing proof is checked and its sender
+        // becomes a peer
+        wait_for("discovery", || async { receiver.peers.read().await.contains_key(&sender.local_addr) }).await;
+
+        // A block announcement carries a validator proof instead, and the
+        // announced block is imported
+        let chain = sender.consensus.get_blockchain().await;
+        chain.create_block("validator1", 1.0, None).await;
+        let block = chain.get_latest_block().await;
+        sender.announce_block(&block).await?;
+        let imported = receiver.consensus.get_blockchain().await;
+        wait_for("block import", || async { imported.get_latest_block().await.hash == block.hash }).await;
+
+        // A packet whose message needs a routing proof is dropped without one
+        let discovery = ZhtpP2PMessage::DiscoveryRequest {
+            sender_addr: sender.local_addr,
+            protocol_version: "zhtp/1.0".to_string(),
+            capabilities: vec!["zk_proofs".to_string()],
+            zk_proof: ProofEnvelope::none(),
+            genesis_hash: sender.genesis_hash.clone(),
+        };
+        let packet = ZhtpP2PNetwork::encode_packet(sender.local_addr, receiver.local_addr, &discovery)?;
+        let (packet, message) = ZhtpP2PNetwork::decode_packet(&packet)?;
+        assert_eq!(ZhtpP2PNetwork::verify_routing_proofs(&[(&packet, &message)]), vec![false]);
+
+        // The receiver announces its genesis back, so the sender listens to it
+        wait_for("genesis reply", || async {
+            sender.peer_genesis.read().await.get(&receiver.local_addr) == Some(&receiver.genesis_hash)
+        }).await;
+
+        // Nothing else is taken from a peer before its genesis check passed
+        let stranger: SocketAddr = "127.0.0.1:9".parse()?;
+        let announcement = ZhtpP2PMessage::BlockAnnouncement {
+            block_hash: [0u8; 32],
+            block_height: block.index + 1,
+            validator_proofs: vec![],
+            block: None,
+        };
+        let packet = ZhtpP2PNetwork::encode_packet(stranger, receiver.local_addr, &announcement)?;
+        let (packet, message) = ZhtpP2PNetwork::decode_packet(&packet)?;
+        let result = ZhtpP2PNetwork::process_zhtp_packet_static(
+            packet,
+            message,
+            stranger,
+            &receiver.peers,
+            &receiver.consensus,
+            &receiver.tx_pool,
+            &receiver.secure_sessions,
+            &receiver.node_keypair,
+            &receiver.genesis_hash,
+            &receiver.peer_genesis,
+            &receiver.socket,
+            receiver.local_addr,
+            &receiver.snapshots,
+        ).await;
+        assert!(result.unwrap_err().to_string().contains("no genesis check passed"));
+
+        // Nor from a peer on another chain
+        let local_genesis = &receiver.genesis_hash;
+        assert!(ZhtpP2PNetwork::record_genesis(stranger, "other", local_genesis, &receiver.peer_genesis).await.is_err());
+        assert!(ZhtpP2PNetwork::check_peer_genesis(stranger, local_genesis, &receiver.peer_genesis).await.is_err());
+        assert!(ZhtpP2PNetwork::check_peer_genesis(sender.local_addr, local_genesis, &receiver.peer_genesis).await.is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_serving_limits() -> Result<()> {
+        let chain = crate::blockchain::Blockchain::new(Amount::from_zhtp(100));
+        chain.create_block("validator1", 1.0, None).await;
+        let (manifest, encoded) = SnapshotManifest::sign(&chain.create_snapshot(1).await?, &Keypair::generate())?;
+        let mut exchange = SnapshotExchange::default();
+        exchange.served.push_back((manifest.clone(), Arc::new(encoded)));
+
+        // Only prebuilt snapshots are offered
+        assert_eq!(exchange.offer(None), Some(&manifest));
+        assert_eq!(exchange.offer(Some(1)), Some(&manifest));
+        assert_eq!(exchange.offer(Some(0)), None);
+
+        // Each peer gets a limited number of replies per second
+        let peer: SocketAddr = "127.0.0.1:9000".parse()?;
+        let now = tokio::time::Instant::now();
+        for _ in 0..MAX_SNAPSHOT_REPLIES_PER_SECOND {
+            assert!(exchange.allow_reply(peer, now));
+        }
+        assert!(!exchange.allow_reply(peer, now));
+        assert!(exchange.allow_reply("127.0.0.1:9001".parse()?, now));
+        assert!(exchange.allow_reply(peer, now + Duration::from_secs(1)));
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Poseidon hash over the BN254 scalar field.
+//!
+//! Used wherever a hash has to be recomputed inside a Groth16 circuit: the
+//! note commitment tree, note commitments and nullifiers. A Poseidon hash
+//! costs a few hundred constraints where SHA-256 costs tens of thousands.
+//! The sponge has width 3 (rate 2), 8 full and 57 partial rounds and x⁵
+//! S-boxes, with round constants and MDS matrix from the reference Grain
+//! LFSR.
+
+use ark_bn254::Fr;
+use ark_crypto_primitives::sponge::{
+    constraints::CryptographicSpongeVar,
+    poseidon::{constraints::PoseidonSpongeVar, find_poseidon_ark_and_mds, PoseidonConfig, PoseidonSponge},
+    CryptographicSponge,
+};
+use ark_ff::{BigInteger, PrimeField};
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+use std::sync::OnceLock;
+
+const FULL_ROUNDS: usize = 8;
+const PARTIAL_ROUNDS: usize = 57;
+const ALPHA: u64 = 5;
+const RATE: usize = 2;
+const CAPACITY: usize = 1;
+
+fn config() -> &'static PoseidonConfig<Fr> {
+    static CONFIG: OnceLock<PoseidonConfig<Fr>> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        let (ark, mds) = find_poseidon_ark_and_mds::<Fr>(
+            Fr::MODULUS_BIT_SIZE as u64,
+            RATE,
+            FULL_ROUNDS as u64,
+            PARTIAL_ROUNDS as u64,
+            0,
+        );
+        PoseidonConfig::new(FULL_ROUNDS, PARTIAL_ROUNDS, ALPHA, mds, ark, RATE, CAPACITY)
+    })
+}
+
+/// Canonical little-endian encoding of a field element
+pub fn encode(element: &Fr) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&element.into_bigint().to_bytes_le());
+    bytes
+}
+
+/// Field element whose [`encode`]ing is `bytes`, or `None` if `bytes` is not
+/// a canonical encoding
+pub fn decode(bytes: &[u8; 32]) -> Option<Fr> {
+    let element = Fr::from_le_bytes_mod_order(bytes);
+    (encode(&element) == *bytes).then_some(element)
+}
+
+/// Hash of a fixed-length list of field elements. Callers hashing lists of
+/// different meaning put a domain tag first.
+pub fn hash(inputs: &[Fr]) -> Fr {
+    let mut sponge = PoseidonSponge::new(config());
+    sponge.absorb(&inputs);
+    sponge.squeeze_field_elements(1)[0]
+}
+
+/// [`hash`] inside a circuit
+pub fn hash_var(cs: ConstraintSystemRef<Fr>, inputs: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError> {
+    let mut sponge = PoseidonSpongeVar::new(cs, config());
+    sponge.absorb(&inputs)?;
+    Ok(sponge.squeeze_field_elements(1)?.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_r1cs_std::{alloc::AllocVar, R1CSVar};
+    use ark_relations::r1cs::ConstraintSystem;
+
+    #[test]
+    fn test_native_and_circuit_hashes_agree() -> Result<(), SynthesisError> {
+        let inputs = [Fr::from(1u64), Fr::from(2u64), Fr::from(3u64)];
+        assert_ne!(hash(&inputs), hash(&inputs[..2]));
+        assert_ne!(hash(&inputs[..2]), hash(&[inputs[1], inputs[0]]));
+        assert_eq!(decode(&encode(&hash(&inputs))), Some(hash(&inputs)));
+        assert_eq!(decode(&[0xff; 32]), None);
+
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        let vars = inputs
+            .iter()
+            .map(|input| FpVar::new_witness(cs.clone(), || Ok(*input)))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(hash_var(cs.clone(), &vars)?.value()?, hash(&inputs));
+        assert!(cs.is_satisfied()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,576 @@
+//! Versioned, self-describing proof envelope.
+//!
+//! Every proof a node sends or stores travels as a [`ProofEnvelope`] naming
+//! the proof system, curve, circuit and verification key that produced it,
+//! next to its public inputs and proof bytes. Envelopes have a single
+//! canonical byte encoding, which is also what serde writes (hex in
+//! human-readable formats), so two peers never disagree on a proof's bytes.
+//!
+//! [`VerifierRegistry`] dispatches an envelope to the verifier registered for
+//! its proof system and circuit, after checking the verification key hash.
+
+use crate::zhtp::zk_proofs::{
+    verify_unified_proof, verify_unified_proofs, KzgTrustedSetup, PolyCommit, RoutingProof, UnifiedProofClaim,
+};
+use anyhow::{anyhow, Result};
+use ark_bn254::{Fr, G1Affine, G1Projective};
+use ark_ec::CurveGroup;
+use ark_ff::{BigInteger, PrimeField};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+/// Envelope format written by this node. Version 2 added the KZG opening
+/// proofs to unified routing proofs; version 1 envelopes of other proof
+/// systems have the same layout and are still read.
+pub const PROOF_ENVELOPE_VERSION: u8 = 2;
+
+/// Circuit id of proofs from the KZG-based unified routing circuit
+pub const UNIFIED_CIRCUIT_ID: &str = "unified_routing";
+
+/// Bounds that keep hostile envelopes from exhausting memory
+const MAX_CIRCUIT_ID_LEN: usize = 64;
+const MAX_PUBLIC_INPUTS: usize = 4096;
+const MAX_PROOF_LEN: usize = 1 << 20;
+
+/// Encoded length of a public input
+const FIELD_ELEMENT_LEN: usize = 32;
+
+/// Proof system that produced an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum ProofSystem {
+    /// No proof attached; never verifies
+    None = 0,
+    /// KZG commitments of the unified routing circuit
+    KzgUnified = 1,
+    /// Groth16 over a compiled circom circuit
+    Groth16 = 2,
+}
+
+impl TryFrom<u8> for ProofSystem {
+    type Error = anyhow::Error;
+
+    fn try_from(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(ProofSystem::None),
+            1 => Ok(ProofSystem::KzgUnified),
+            2 => Ok(ProofSystem::Groth16),
+            _ => Err(anyhow!("Unknown proof system {}", tag)),
+        }
+    }
+}
+
+/// Pairing curve of an envelope's proof and public inputs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Curve {
+    Bn254 = 1,
+}
+
+impl TryFrom<u8> for Curve {
+    type Error = anyhow::Error;
+
+    fn try_from(tag: u8) -> Result<Self> {
+        match tag {
+            1 => Ok(Curve::Bn254),
+            _ => Err(anyhow!("Unknown curve {}", tag)),
+        }
+    }
+}
+
+/// A proof together with everything needed to pick its verifier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofEnvelope {
+    pub version: u8,
+    pub system: ProofSystem,
+    pub curve: Curve,
+    pub circuit_id: String,
+    /// SHA-256 identifying the verification key (for KZG proofs, the
+    /// trusted setup's ceremony id)
+    pub verification_key_hash: [u8; 32],
+    pub public_inputs: Vec<Fr>,
+    /// Proof bytes in the proof system's compressed encoding
+    pub proof: Vec<u8>,
+}
+
+impl ProofEnvelope {
+    pub fn new(
+        system: ProofSystem,
+        circuit_id: &str,
+        verification_key_hash: [u8; 32],
+        public_inputs: Vec<Fr>,
+        proof: Vec<u8>,
+    ) -> Self {
+        Self {
+            version: PROOF_ENVELOPE_VERSION,
+            system,
+            curve: Curve::Bn254,
+            circuit_id: circuit_id.to_string(),
+            verification_key_hash,
+            public_inputs,
+            proof,
+        }
+    }
+
+    /// Envelope stating that no proof is attached
+    pub fn none() -> Self {
+        Self::new(ProofSystem::None, "", [0u8; 32], Vec::new(), Vec::new())
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.system == ProofSystem::None
+    }
+
+    /// Wrap a unified routing proof, bound to the global trusted setup
+    pub fn unified(proof: &RoutingProof) -> Self {
+        let commitments: Vec<G1Projective> = proof.path_commitments.iter().map(|c| c.0).collect();
+        let openings: Vec<G1Projective> = proof.opening_proofs.iter().map(|c| c.0).collect();
+        let mut bytes = Vec::new();
+        (
+            G1Projective::normalize_batch(&commitments),
+            G1Projective::normalize_batch(&openings),
+            proof.proof_elements.clone(),
+        )
+            .serialize_compressed(&mut bytes)
+            .expect("writing to a Vec cannot fail");
+        Self::new(
+            ProofSystem::KzgUnified,
+            UNIFIED_CIRCUIT_ID,
+            KzgTrustedSetup::get_global().ceremony_id,
+            proof.public_inputs.clone(),
+            bytes,
+        )
+    }
+
+    /// Unwrap a unified routing proof
+    pub fn to_routing_proof(&self) -> Result<RoutingProof> {
+        if self.system != ProofSystem::KzgUnified {
+            return Err(anyhow!("Expected a unified routing proof, got {:?}", self.system));
+        }
+        let mut reader = self.proof.as_slice();
+        let (commitments, openings, proof_elements) =
+            <(Vec<G1Affine>, Vec<G1Affine>, Vec<Fr>)>::deserialize_compressed(&mut reader)
+                .map_err(|e| anyhow!("Malformed unified routing proof: {}", e))?;
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after unified routing proof"));
+        }
+        Ok(RoutingProof {
+            path_commitments: commitments.into_iter().map(|point| PolyCommit(point.into())).collect(),
+            proof_elements,
+            opening_proofs: openings.into_iter().map(|point| PolyCommit(point.into())).collect(),
+            public_inputs: self.public_inputs.clone(),
+        })
+    }
+
+    /// Canonical encoding: version, system, curve, length-prefixed circuit
+    /// id, verification key hash, counted 32-byte little-endian public
+    /// inputs, then the length-prefixed proof
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(
+            3 + 1 + self.circuit_id.len() + 32 + 4 + self.public_inputs.len() * FIELD_ELEMENT_LEN + 4 + self.proof.len(),
+        );
+        bytes.extend_from_slice(&[self.version, self.system as u8, self.curve as u8]);
+        bytes.push(self.circuit_id.len() as u8);
+        bytes.extend_from_slice(self.circuit_id.as_bytes());
+        bytes.extend_from_slice(&self.verification_key_hash);
+        bytes.extend_from_slice(&(self.public_inputs.len() as u32).to_le_bytes());
+        for input in &self.public_inputs {
+            bytes.extend_from_slice(&input.into_bigint().to_bytes_le());
+        }
+        bytes.extend_from_slice(&(self.proof.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.proof);
+        bytes
+    }
+
+    /// Decode the canonical encoding, rejecting anything
+    /// [`ProofEnvelope::to_bytes`] would not have produced
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        let mut take = |len: usize| reader.take(len);
+
+        let version = take(1)?[0];
+        let system = ProofSystem::try_from(take(1)?[0])?;
+        check_version(version, system)?;
+        let curve = Curve::try_from(take(1)?[0])?;
+
+        let id_len = take(1)?[0] as usize;
+        if id_len > MAX_CIRCUIT_ID_LEN {
+            return Err(anyhow!("Circuit id is too long"));
+        }
+        let circuit_id = std::str::from_utf8(take(id_len)?)
+            .map_err(|_| anyhow!("Circuit id is not UTF-8"))?
+            .to_string();
+        let verification_key_hash: [u8; 32] = take(32)?.try_into()?;
+
+        let n_inputs = u32::from_le_bytes(take(4)?.try_into()?) as usize;
+        if n_inputs > MAX_PUBLIC_INPUTS {
+            return Err(anyhow!("Too many public inputs"));
+        }
+        let public_inputs = (0..n_inputs)
+            .map(|_| {
+                let encoded = take(FIELD_ELEMENT_LEN)?;
+                let input = Fr::from_le_bytes_mod_order(encoded);
+                if input.into_bigint().to_bytes_le() != encoded {
+                    return Err(anyhow!("Public input is not a canonical field element"));
+                }
+                Ok(input)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let proof_len = u32::from_le_bytes(take(4)?.try_into()?) as usize;
+        if proof_len > MAX_PROOF_LEN {
+            return Err(anyhow!("Proof is too long"));
+        }
+        let proof = take(proof_len)?.to_vec();
+        if !reader.0.is_empty() {
+            return Err(anyhow!("Trailing bytes after proof envelope"));
+        }
+
+        let envelope = Self { version, system, curve, circuit_id, verification_key_hash, public_inputs, proof };
+        if envelope.is_none() && envelope != Self::none() {
+            return Err(anyhow!("Envelope without a proof system carries data"));
+        }
+        Ok(envelope)
+    }
+
+    /// SHA-256 of the canonical encoding
+    pub fn digest(&self) -> [u8; 32] {
+        Sha256::digest(self.to_bytes()).into()
+    }
+}
+
+/// Refuse envelope versions this node cannot read, among them version 1
+/// unified routing proofs, which lack the opening proofs
+fn check_version(version: u8, system: ProofSystem) -> Result<()> {
+    match version {
+        PROOF_ENVELOPE_VERSION => Ok(()),
+        1 if system == ProofSystem::KzgUnified => {
+            Err(anyhow!("Version 1 unified routing proofs carry no opening proofs and must be regenerated"))
+        }
+        1 => Ok(()),
+        _ => Err(anyhow!("Unsupported proof envelope version {}", version)),
+    }
+}
+
+/// Cursor over the canonical encoding
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(anyhow!("Proof envelope is truncated"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+}
+
+impl Default for ProofEnvelope {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl From<RoutingProof> for ProofEnvelope {
+    fn from(proof: RoutingProof) -> Self {
+        Self::unified(&proof)
+    }
+}
+
+impl Serialize for ProofEnvelope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&hex::encode(self.to_bytes()))
+        } else {
+            serializer.serialize_bytes(&self.to_bytes())
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ProofEnvelope {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = if deserializer.is_human_readable() {
+            hex::decode(String::deserialize(deserializer)?).map_err(serde::de::Error::custom)?
+        } else {
+            serde_bytes::ByteBuf::deserialize(deserializer)?.into_vec()
+        };
+        ProofEnvelope::from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+/// What the caller expects a proof to show, beyond its own public inputs
+#[derive(Debug, Clone, Copy)]
+pub enum ProofStatement<'a> {
+    /// The envelope's public inputs, which the caller checks itself
+    PublicInputs,
+    /// A unified routing proof from `source` to `destination`
+    Route {
+        source: &'a [u8],
+        destination: &'a [u8],
+        stored_data_root: [u8; 32],
+    },
+}
+
+/// Verifier for the proofs of one circuit
+pub trait ProofVerifier: Send + Sync {
+    /// Hash envelopes must carry to be checked by this verifier
+    fn verification_key_hash(&self) -> [u8; 32];
+
+    fn verify(&self, envelope: &ProofEnvelope, statement: &ProofStatement) -> Result<bool>;
+
+    /// Verify several envelopes of this verifier's circuit, counting those
+    /// that cannot be checked as invalid. Verifiers that can share work
+    /// between proofs override this.
+    fn verify_batch(&self, items: &[(&ProofEnvelope, ProofStatement)]) -> Vec<bool> {
+        items
+            .iter()
+            .map(|(envelope, statement)| self.verify(envelope, statement).unwrap_or(false))
+            .collect()
+    }
+}
+
+/// Verifier of unified routing proofs under the global trusted setup
+pub struct UnifiedProofVerifier;
+
+impl ProofVerifier for UnifiedProofVerifier {
+    fn verification_key_hash(&self) -> [u8; 32] {
+        KzgTrustedSetup::get_global().ceremony_id
+    }
+
+    fn verify(&self, envelope: &ProofEnvelope, statement: &ProofStatement) -> Result<bool> {
+        let ProofStatement::Route { source, destination, stored_data_root } = statement else {
+            return Err(anyhow!("Unified routing proofs are verified against a route"));
+        };
+        let Ok(proof) = envelope.to_routing_proof() else { return Ok(false) };
+        Ok(verify_unified_proof(&proof, source, destination, *stored_data_root))
+    }
+
+    /// Checks the KZG openings of all proofs with one multi-pairing
+    fn verify_batch(&self, items: &[(&ProofEnvelope, ProofStatement)]) -> Vec<bool> {
+        let proofs: Vec<Option<RoutingProof>> =
+            items.iter().map(|(envelope, _)| envelope.to_routing_proof().ok()).collect();
+        let claims: Vec<Option<UnifiedProofClaim>> = items
+            .iter()
+            .zip(&proofs)
+            .map(|((_, statement), proof)| match (statement, proof) {
+                (ProofStatement::Route { source, destination, stored_data_root }, Some(proof)) => {
+                    Some(UnifiedProofClaim { proof, source, destination, stored_data_root: *stored_data_root })
+                }
+                _ => None,
+            })
+            .collect();
+        let mut verified = verify_unified_proofs(&claims.iter().flatten().copied().collect::<Vec<_>>()).into_iter();
+        claims
+            .iter()
+            .map(|claim| claim.is_some() && verified.next().unwrap_or(false))
+            .collect()
+    }
+}
+
+/// Verifiers by proof system and circuit id
+pub struct VerifierRegistry {
+    verifiers: RwLock<HashMap<(ProofSystem, String), Arc<dyn ProofVerifier>>>,
+}
+
+static GLOBAL_REGISTRY: OnceLock<VerifierRegistry> = OnceLock::new();
+
+impl VerifierRegistry {
+    /// Registry without verifiers
+    pub fn empty() -> Self {
+        Self { verifiers: RwLock::new(HashMap::new()) }
+    }
+
+    /// Registry with the verifiers every node has
+    pub fn new() -> Self {
+        let registry = Self::empty();
+        registry.register(ProofSystem::KzgUnified, UNIFIED_CIRCUIT_ID, Arc::new(UnifiedProofVerifier));
+        registry
+    }
+
+    /// Registry shared by the node's subsystems
+    pub fn global() -> &'static Self {
+        GLOBAL_REGISTRY.get_or_init(Self::new)
+    }
+
+    /// Register or replace the verifier of a circuit
+    pub fn register(&self, system: ProofSystem, circuit_id: &str, verifier: Arc<dyn ProofVerifier>) {
+        self.verifiers
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert((system, circuit_id.to_string()), verifier);
+    }
+
+    pub fn has_verifier(&self, system: ProofSystem, circuit_id: &str) -> bool {
+        self.verifiers
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .contains_key(&(system, circuit_id.to_string()))
+    }
+
+    /// Verify an envelope with the verifier registered for it. Errors when no
+    /// verifier is known; a proof under another verification key is invalid.
+    pub fn verify(&self, envelope: &ProofEnvelope, statement: &ProofStatement) -> Result<bool> {
+        check_version(envelope.version, envelope.system)?;
+        if envelope.is_none() {
+            return Ok(false);
+        }
+        let verifier = self
+            .verifiers
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(&(envelope.system, envelope.circuit_id.clone()))
+            .cloned()
+            .ok_or_else(|| anyhow!("No verifier for {:?} circuit {}", envelope.system, envelope.circuit_id))?;
+        if verifier.verification_key_hash() != envelope.verification_key_hash {
+            return Ok(false);
+        }
+        verifier.verify(envelope, statement)
+    }
+
+    /// Verify many envelopes, handing each verifier its share as one batch.
+    /// Unlike [`VerifierRegistry::verify`], envelopes without a known
+    /// verifier are reported invalid rather than failing the whole batch.
+    pub fn verify_batch(&self, items: &[(&ProofEnvelope, ProofStatement)]) -> Vec<bool> {
+        let mut groups: HashMap<(ProofSystem, String), Vec<usize>> = HashMap::new();
+        for (index, (envelope, _)) in items.iter().enumerate() {
+            if check_version(envelope.version, envelope.system).is_ok() && !envelope.is_none() {
+                groups.entry((envelope.system, envelope.circuit_id.clone())).or_default().push(index);
+            }
+        }
+
+        let mut results = vec![false; items.len()];
+        for (key, indices) in groups {
+            let verifier = self.verifiers.read().unwrap_or_else(|poisoned| poisoned.into_inner()).get(&key).cloned();
+            let Some(verifier) = verifier else {
+                log::warn!("No verifier for {:?} circuit {}", key.0, key.1);
+                continue;
+            };
+            let key_hash = verifier.verification_key_hash();
+            let indices: Vec<usize> =
+                indices.into_iter().filter(|&index| items[index].0.verification_key_hash == key_hash).collect();
+            let batch: Vec<(&ProofEnvelope, ProofStatement)> = indices.iter().map(|&index| items[index]).collect();
+            for (index, valid) in indices.into_iter().zip(verifier.verify_batch(&batch)) {
+                results[index] = valid;
+            }
+        }
+        results
+    }
+}
+
+impl Default for VerifierRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zhtp::zk_proofs::UnifiedCircuit;
+
+    fn routing_proof(source: &[u8], destination: &[u8]) -> RoutingProof {
+        let mut routing_table = HashMap::new();
+        routing_table.insert(source.to_vec(), vec![destination.to_vec()]);
+        UnifiedCircuit::new(
+            source.to_vec(),
+            destination.to_vec(),
+            vec![source.to_vec(), destination.to_vec()],
+            routing_table,
+            [0u8; 32],
+            vec![],
+            <G1Projective as ark_ec::Group>::generator(),
+            100,
+            vec![(1, true)],
+            vec![(1, 10.0)],
+        )
+        .generate_proof()
+        .expect("routing proof")
+    }
+
+    #[test]
+    fn test_envelope_encoding() -> Result<()> {
+        let envelope = ProofEnvelope::unified(&routing_proof(b"node_a", b"node_b"));
+        let bytes = envelope.to_bytes();
+        assert_eq!(ProofEnvelope::from_bytes(&bytes)?, envelope);
+        assert_eq!(bincode::deserialize::<ProofEnvelope>(&bincode::serialize(&envelope)?)?, envelope);
+        assert_eq!(serde_json::from_str::<ProofEnvelope>(&serde_json::to_string(&envelope)?)?, envelope);
+        assert!(envelope.to_routing_proof().is_ok());
+
+        // Anything but the canonical encoding is refused
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(ProofEnvelope::from_bytes(&trailing).is_err());
+        assert!(ProofEnvelope::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut future = bytes.clone();
+        future[0] = PROOF_ENVELOPE_VERSION + 1;
+        assert!(ProofEnvelope::from_bytes(&future).is_err());
+
+        // Version 1 unified proofs predate the opening proofs; other version
+        // 1 envelopes are laid out the same and still read
+        let mut legacy = bytes.clone();
+        legacy[0] = 1;
+        assert!(ProofEnvelope::from_bytes(&legacy).is_err());
+        let mut legacy_groth16 =
+            ProofEnvelope::new(ProofSystem::Groth16, "private_transfer", [3u8; 32], vec![Fr::from(5u64)], vec![1, 2]);
+        legacy_groth16.version = 1;
+        assert_eq!(ProofEnvelope::from_bytes(&legacy_groth16.to_bytes())?, legacy_groth16);
+        let mut unreduced = ProofEnvelope::none();
+        unreduced.public_inputs.push(Fr::from(1u64));
+        unreduced.system = ProofSystem::Groth16;
+        let mut bytes = unreduced.to_bytes();
+        let input_at = bytes.len() - 4 - FIELD_ELEMENT_LEN;
+        bytes[input_at..input_at + FIELD_ELEMENT_LEN].fill(0xff);
+        assert!(ProofEnvelope::from_bytes(&bytes).is_err());
+
+        let none = ProofEnvelope::none();
+        assert_eq!(ProofEnvelope::from_bytes(&none.to_bytes())?, none);
+        Ok(())
+    }
+
+    #[test]
+    fn test_verifier_registry() -> Result<()> {
+        let registry = VerifierRegistry::new();
+        let envelope = ProofEnvelope::unified(&routing_proof(b"node_a", b"node_b"));
+        let route = |destination: &'static [u8]| ProofStatement::Route {
+            source: b"node_a",
+            destination,
+            stored_data_root: [0u8; 32],
+        };
+        assert!(registry.verify(&envelope, &route(b"node_b"))?);
+        assert!(!registry.verify(&envelope, &route(b"node_c"))?);
+        assert!(registry.verify(&envelope, &ProofStatement::PublicInputs).is_err());
+
+        // Another setup's proofs, unknown circuits and empty envelopes
+        let mut foreign = envelope.clone();
+        foreign.verification_key_hash = [7u8; 32];
+        assert!(!registry.verify(&foreign, &route(b"node_b"))?);
+        let mut unknown = envelope.clone();
+        unknown.circuit_id = "not_a_circuit".to_string();
+        assert!(registry.verify(&unknown, &route(b"node_b")).is_err());
+        assert!(!registry.verify(&ProofEnvelope::none(), &route(b"node_b"))?);
+        assert!(VerifierRegistry::empty().verify(&envelope, &route(b"node_b")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let registry = VerifierRegistry::new();
+        let valid = ProofEnvelope::unified(&routing_proof(b"node_a", b"node_b"));
+        let other = ProofEnvelope::unified(&routing_proof(b"node_c", b"node_b"));
+        let mut unknown = valid.clone();
+        unknown.circuit_id = "not_a_circuit".to_string();
+        let route = ProofStatement::Route { source: b"node_a", destination: b"node_b", stored_data_root: [0u8; 32] };
+
+        // Unknown circuits, empty envelopes and wrong routes fail on their own
+        let none = ProofEnvelope::none();
+        let items = [(&valid, route), (&other, route), (&unknown, route), (&none, route), (&valid, route)];
+        assert_eq!(registry.verify_batch(&items), vec![true, false, false, false, true]);
+        assert!(registry.verify_batch(&[]).is_empty());
+    }
+}
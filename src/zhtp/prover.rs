@@ -0,0 +1,221 @@
+//! Dedicated worker pool for unified proof generation.
+//!
+//! Committing to a circuit's wire polynomials is CPU-bound work measured in
+//! milliseconds to seconds, far too long to run on an async runtime thread.
+//! [`ProverPool`] runs [`UnifiedCircuit::generate_proof`] on its own rayon
+//! threads, where the commitments and arkworks' MSM/FFT routines fan out in
+//! parallel, and hands the result back to async callers through
+//! [`ProverPool::prove`].
+//!
+//! A prove call gives up when its [`ProofCancellation`] fires or its timeout
+//! elapses. Dropping the returned future has the same effect, and in each
+//! case the worker stops at its next checkpoint instead of finishing a proof
+//! nobody will read.
+
+use crate::zhtp::zk_proofs::{RoutingProof, UnifiedCircuit};
+use anyhow::{anyhow, Result};
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
+use tokio::sync::{oneshot, Notify};
+
+/// Cancels the prove calls it is passed to. Clones share one flag, so a
+/// clone handed to another task can cancel on the caller's behalf.
+#[derive(Debug, Clone, Default)]
+pub struct ProofCancellation {
+    inner: Arc<CancellationState>,
+}
+
+#[derive(Debug, Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl ProofCancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop every prove call using this token
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once [`cancel`](Self::cancel) has been called
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking the flag so a cancel in between
+            // still wakes us
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Marks a proof as abandoned when the future waiting on it goes away
+struct AbandonOnDrop(Arc<AtomicBool>);
+
+impl Drop for AbandonOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Thread pool that unified proofs are generated on
+pub struct ProverPool {
+    pool: rayon::ThreadPool,
+}
+
+static GLOBAL_PROVER_POOL: OnceLock<ProverPool> = OnceLock::new();
+
+impl ProverPool {
+    /// Pool with `threads` workers, or one per available core when zero
+    pub fn new(threads: usize) -> Result<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("zhtp-prover-{}", i))
+            .build()
+            .map_err(|e| anyhow!("Failed to start prover pool: {}", e))?;
+        Ok(Self { pool })
+    }
+
+    /// Pool shared by the node, with one worker per available core
+    pub fn global() -> &'static Self {
+        GLOBAL_PROVER_POOL.get_or_init(|| Self::new(0).expect("prover pool threads should start"))
+    }
+
+    pub fn threads(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Generate `circuit`'s proof on the pool without blocking the caller
+    pub async fn prove(
+        &self,
+        circuit: UnifiedCircuit,
+        cancel: &ProofCancellation,
+        timeout: Duration,
+    ) -> Result<RoutingProof> {
+        if cancel.is_cancelled() {
+            return Err(anyhow!("Proof generation cancelled"));
+        }
+
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let _abandon = AbandonOnDrop(Arc::clone(&abandoned));
+        let (sender, receiver) = oneshot::channel();
+        let worker_cancel = cancel.clone();
+        self.pool.spawn(move || {
+            let mut circuit = circuit;
+            let stop = || abandoned.load(Ordering::SeqCst) || worker_cancel.is_cancelled();
+            // A panic must not reach rayon, which aborts the process on one;
+            // dropping the sender reports it to the caller instead
+            if let Ok(proof) = panic::catch_unwind(AssertUnwindSafe(|| circuit.generate_proof_until(&stop))) {
+                let _ = sender.send(proof);
+            }
+        });
+
+        tokio::select! {
+            result = receiver => match result {
+                Ok(Some(proof)) => Ok(proof),
+                Ok(None) if cancel.is_cancelled() => Err(anyhow!("Proof generation cancelled")),
+                Ok(None) => Err(anyhow!("Failed to generate proof - circuit constraints not satisfied")),
+                Err(_) => Err(anyhow!("Proof generation panicked")),
+            },
+            _ = tokio::time::sleep(timeout) => {
+                Err(anyhow!("Proof generation timed out after {:?}", timeout))
+            }
+            _ = cancel.cancelled() => Err(anyhow!("Proof generation cancelled")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zhtp::zk_proofs::verify_unified_proof;
+    use ark_bn254::G1Projective;
+    use ark_ec::Group;
+    use std::collections::HashMap;
+
+    fn circuit(source: &[u8], destination: &[u8]) -> UnifiedCircuit {
+        let mut routing_table = HashMap::new();
+        routing_table.insert(source.to_vec(), vec![destination.to_vec()]);
+        UnifiedCircuit::new(
+            source.to_vec(),
+            destination.to_vec(),
+            vec![source.to_vec(), destination.to_vec()],
+            routing_table,
+            [0u8; 32],
+            vec![],
+            G1Projective::generator(),
+            10,
+            vec![(1, true)],
+            vec![(1, 5.0)],
+        )
+    }
+
+    #[tokio::test]
+    async fn test_prove_on_pool() {
+        let pool = ProverPool::new(2).unwrap();
+        assert_eq!(pool.threads(), 2);
+
+        let proof = pool
+            .prove(circuit(b"node_a", b"node_b"), &ProofCancellation::new(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(verify_unified_proof(&proof, b"node_a", b"node_b", [0u8; 32]));
+
+        let proof = circuit(b"node_c", b"node_d")
+            .prove(&ProofCancellation::new(), Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(verify_unified_proof(&proof, b"node_c", b"node_d", [0u8; 32]));
+    }
+
+    #[tokio::test]
+    async fn test_prove_cancellation_and_timeout() {
+        let pool = ProverPool::new(1).unwrap();
+        assert!(circuit(b"node_a", b"node_b").generate_proof_until(&|| true).is_none());
+
+        let cancel = ProofCancellation::new();
+        cancel.cancel();
+        let result = pool.prove(circuit(b"node_a", b"node_b"), &cancel, Duration::from_secs(60)).await;
+        assert!(result.unwrap_err().to_string().contains("cancelled"));
+
+        // Occupy the only worker so the next proof cannot finish in time
+        let (release, blocked) = std::sync::mpsc::channel::<()>();
+        pool.pool.spawn(move || {
+            let _ = blocked.recv();
+        });
+        let result = pool
+            .prove(circuit(b"node_a", b"node_b"), &ProofCancellation::new(), Duration::from_millis(50))
+            .await;
+        assert!(result.unwrap_err().to_string().contains("timed out"));
+
+        let cancel = ProofCancellation::new();
+        let canceller = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            canceller.cancel();
+        });
+        let result = pool.prove(circuit(b"node_a", b"node_b"), &cancel, Duration::from_secs(60)).await;
+        assert!(result.unwrap_err().to_string().contains("cancelled"));
+
+        release.send(()).unwrap();
+    }
+}
@@ -0,0 +1,1036 @@
+//! Shielded note pool: value held in notes whose amounts and owners are
+//! hidden behind commitments, spent by revealing a nullifier that only the
+//! owner's spending key can derive.
+//!
+//! Notes commit to their value with a Pedersen commitment `cv = v·G + rcv·H`
+//! over Baby Jubjub, and outputs prove that `v` fits in 64 bits. A note's
+//! commitment is a Poseidon hash binding `cv` to a hash of its hidden owner,
+//! and joins the note commitment tree. A spend publishes only the tree root
+//! it is anchored to, its nullifier, a fresh commitment to the note's value
+//! and a Groth16 proof (see [`crate::zhtp::spend_circuit`]) that the note is
+//! in the tree, that the key owning it derives the nullifier and that both
+//! commitments hold the same value. A binding signature shows that the value
+//! commitments balance against the public amounts moved in and out of the
+//! pool.
+//!
+//! Amounts and payees stay hidden, addresses with different diversifiers
+//! cannot be linked to each other, and a spend cannot be linked to the
+//! output that created its note.
+
+use crate::zhtp::{
+    amount::Amount,
+    crypto::{IncomingViewingKey, Keypair, OutgoingViewingKey, SealedData},
+    jubjub::{hash_to_curve, scalar_to_field, JubjubAffine, JubjubPoint, JubjubScalar},
+    merkle::{Hash, NoteCommitmentTree, NotePath, NoteTreeCheckpoint},
+    poseidon,
+    spend_circuit::{self, SpendStatement, SpendWitness},
+};
+use anyhow::{anyhow, Result};
+use ark_bn254::Fr;
+use ark_ec::{CurveGroup, Group};
+use ark_ff::{Field, PrimeField, Zero};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Most spends in one bundle
+pub const MAX_SHIELDED_SPENDS: usize = 16;
+
+/// Most outputs in one bundle
+pub const MAX_SHIELDED_OUTPUTS: usize = 16;
+
+/// Recent note commitment tree roots a spend may be anchored to, one per
+/// block that added notes
+pub const ANCHOR_HISTORY: usize = 100;
+
+/// Bits covered by an output's range proof, enough for any [`Amount`]
+const VALUE_BITS: usize = 64;
+
+/// Domain tags of the Poseidon hashes, which the spend circuit recomputes
+pub(crate) const OWNER_DOMAIN: &[u8] = b"zhtp-shielded-owner";
+pub(crate) const NOTE_DOMAIN: &[u8] = b"zhtp-shielded-note";
+pub(crate) const NULLIFIER_DOMAIN: &[u8] = b"zhtp-shielded-nullifier";
+
+/// Compressed Baby Jubjub point
+pub type Point = [u8; 32];
+
+/// Canonical little-endian Baby Jubjub scalar
+pub type Scalar = [u8; 32];
+
+/// Diversifier selecting one of a spending key's unlinkable addresses
+pub type Diversifier = [u8; 16];
+
+fn hash_parts(parts: &[&[u8]]) -> Hash {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Fiat-Shamir challenge over a transcript
+fn hash_to_scalar(parts: &[&[u8]]) -> JubjubScalar {
+    JubjubScalar::from_le_bytes_mod_order(&hash_parts(parts))
+}
+
+fn random_scalar() -> JubjubScalar {
+    let mut bytes = [0u8; 64];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    JubjubScalar::from_le_bytes_mod_order(&bytes)
+}
+
+/// Field element standing for a domain tag or a 32-byte hash in Poseidon
+/// hashes
+pub(crate) fn field_element(bytes: &[u8]) -> Fr {
+    Fr::from_le_bytes_mod_order(bytes)
+}
+
+/// Base the value of a commitment is multiplied by
+pub(crate) fn value_base() -> JubjubPoint {
+    JubjubPoint::generator()
+}
+
+/// Base the blinding of a commitment is multiplied by
+pub(crate) fn blinding_base() -> JubjubPoint {
+    static BASE: OnceLock<JubjubPoint> = OnceLock::new();
+    *BASE.get_or_init(|| hash_to_curve(&[b"zhtp-shielded-blinding"]))
+}
+
+fn diversified_base(diversifier: &Diversifier) -> JubjubPoint {
+    hash_to_curve(&[b"zhtp-shielded-diversifier", diversifier])
+}
+
+fn amount_scalar(amount: Amount) -> JubjubScalar {
+    JubjubScalar::from(amount.base_units())
+}
+
+fn encode_point(point: &JubjubPoint) -> Point {
+    let mut bytes = [0u8; 32];
+    point
+        .into_affine()
+        .serialize_compressed(&mut bytes[..])
+        .expect("Baby Jubjub points compress to 32 bytes");
+    bytes
+}
+
+/// Decode a compressed point, which must lie in the prime order subgroup
+fn decode_point(bytes: &Point) -> Result<JubjubPoint> {
+    JubjubAffine::deserialize_compressed(&bytes[..])
+        .map(JubjubPoint::from)
+        .map_err(|_| anyhow!("Invalid curve point"))
+}
+
+fn encode_scalar(scalar: &JubjubScalar) -> Scalar {
+    let mut bytes = [0u8; 32];
+    scalar
+        .serialize_compressed(&mut bytes[..])
+        .expect("Baby Jubjub scalars encode to 32 bytes");
+    bytes
+}
+
+fn decode_scalar(bytes: &Scalar) -> Result<JubjubScalar> {
+    JubjubScalar::deserialize_compressed(&bytes[..]).map_err(|_| anyhow!("Invalid scalar"))
+}
+
+/// Pedersen commitment `value·G + blinding·H`
+fn commit_value(value: Amount, blinding: &JubjubScalar) -> JubjubPoint {
+    value_base() * amount_scalar(value) + blinding_base() * blinding
+}
+
+/// Commitment a note joins the tree under: its value commitment bound to
+/// the hash of its owner and randomness
+pub fn note_commitment(cv: &Point, owner_commitment: &Hash) -> Hash {
+    let cv = decode_point(cv).unwrap_or_default().into_affine();
+    poseidon::encode(&poseidon::hash(&[
+        field_element(NOTE_DOMAIN),
+        cv.x,
+        cv.y,
+        field_element(owner_commitment),
+    ]))
+}
+
+fn owner_commitment(g_d: &JubjubAffine, pk_d: &JubjubAffine, rseed: &[u8; 32]) -> Hash {
+    poseidon::encode(&poseidon::hash(&[
+        field_element(OWNER_DOMAIN),
+        g_d.x,
+        g_d.y,
+        pk_d.x,
+        pk_d.y,
+        field_element(rseed),
+    ]))
+}
+
+/// Secret key that owns shielded notes and derives their nullifiers
+#[derive(Clone)]
+pub struct SpendingKey {
+    nk: JubjubScalar,
+}
+
+impl fmt::Debug for SpendingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SpendingKey(..)")
+    }
+}
+
+impl SpendingKey {
+    pub fn generate() -> Self {
+        loop {
+            let nk = random_scalar();
+            if !nk.is_zero() {
+                return Self { nk };
+            }
+        }
+    }
+
+    pub fn from_bytes(bytes: &Scalar) -> Result<Self> {
+        let nk = decode_scalar(bytes)?;
+        if nk.is_zero() {
+            return Err(anyhow!("Spending key must not be zero"));
+        }
+        Ok(Self { nk })
+    }
+
+    pub fn to_bytes(&self) -> Scalar {
+        encode_scalar(&self.nk)
+    }
+
+    /// Payment address for `diversifier`, with notes encrypted to the Kyber
+    /// public key `encryption_key`. Addresses with different diversifiers
+    /// cannot be linked to each other or to the key.
+    pub fn address(&self, diversifier: Diversifier, encryption_key: Vec<u8>) -> ShieldedAddress {
+        ShieldedAddress {
+            diversifier,
+            pk_d: encode_point(&(diversified_base(&diversifier) * self.nk)),
+            encryption_key,
+        }
+    }
+
+    /// Whether this key can spend `note`
+    pub fn owns(&self, note: &Note) -> bool {
+        encode_point(&(diversified_base(&note.diversifier) * self.nk)) == note.pk_d
+    }
+
+    /// Nullifier revealed when spending `note` from tree position
+    /// `position`. Copies of a note at two positions have different ones.
+    pub fn nullifier(&self, note: &Note, position: u64) -> Hash {
+        poseidon::encode(&poseidon::hash(&[
+            field_element(NULLIFIER_DOMAIN),
+            scalar_to_field(&self.nk),
+            field_element(&note.commitment()),
+            Fr::from(position),
+        ]))
+    }
+}
+
+/// Where shielded notes are paid
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShieldedAddress {
+    pub diversifier: Diversifier,
+    /// Spending key times the base derived from the diversifier
+    pub pk_d: Point,
+    /// Kyber768 public key notes are encrypted to
+    pub encryption_key: Vec<u8>,
+}
+
+/// A shielded note as known to its owner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    pub value: Amount,
+    pub diversifier: Diversifier,
+    pub pk_d: Point,
+    /// Blinding of the value commitment
+    rcv: Scalar,
+    /// Randomness hiding the owner in the note commitment
+    rseed: [u8; 32],
+}
+
+impl Note {
+    fn new(value: Amount, to: &ShieldedAddress) -> Self {
+        let mut rseed = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut rseed);
+        Self {
+            value,
+            diversifier: to.diversifier,
+            pk_d: to.pk_d,
+            rcv: encode_scalar(&random_scalar()),
+            rseed,
+        }
+    }
+
+    fn blinding(&self) -> Result<JubjubScalar> {
+        decode_scalar(&self.rcv)
+    }
+
+    pub fn value_commitment(&self) -> Point {
+        let blinding = self.blinding().unwrap_or_default();
+        encode_point(&commit_value(self.value, &blinding))
+    }
+
+    fn owner_commitment(&self) -> Hash {
+        let g_d = diversified_base(&self.diversifier).into_affine();
+        let pk_d = decode_point(&self.pk_d).unwrap_or_default().into_affine();
+        owner_commitment(&g_d, &pk_d, &self.rseed)
+    }
+
+    /// Leaf the note joins the note commitment tree under
+    pub fn commitment(&self) -> Hash {
+        note_commitment(&self.value_commitment(), &self.owner_commitment())
+    }
+}
+
+/// Proof that a committed value is below 2^64: one commitment per bit, each
+/// with a proof that it commits to 0 or 1, summing to the value commitment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RangeProof {
+    bits: Vec<BitProof>,
+}
+
+/// Commitment to one bit, with an OR proof that it opens to 0 or to 1
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct BitProof {
+    commitment: Point,
+    challenges: [Scalar; 2],
+    responses: [Scalar; 2],
+}
+
+impl RangeProof {
+    fn prove(value: Amount, blinding: &JubjubScalar, cv: &Point) -> Self {
+        let units = value.base_units();
+        let two = JubjubScalar::from(2u64);
+
+        // Bit blindings weighted by powers of two must add up to `blinding`
+        let mut blindings: Vec<JubjubScalar> = (0..VALUE_BITS - 1).map(|_| random_scalar()).collect();
+        let (covered, _) = blindings
+            .iter()
+            .fold((JubjubScalar::zero(), JubjubScalar::from(1u64)), |(sum, weight), r| {
+                (sum + weight * r, weight * two)
+            });
+        let top_weight = two.pow([(VALUE_BITS - 1) as u64]);
+        let top_inverse = top_weight.inverse().expect("powers of two are invertible");
+        blindings.push((*blinding - covered) * top_inverse);
+
+        let bits = blindings
+            .iter()
+            .enumerate()
+            .map(|(index, r)| BitProof::prove((units >> index) & 1 == 1, r, cv, index))
+            .collect();
+        Self { bits }
+    }
+
+    fn verify(&self, cv: &Point) -> Result<()> {
+        if self.bits.len() != VALUE_BITS {
+            return Err(anyhow!("Range proof covers {} bits, expected {}", self.bits.len(), VALUE_BITS));
+        }
+
+        let mut sum = JubjubPoint::zero();
+        let mut weight = JubjubScalar::from(1u64);
+        for (index, bit) in self.bits.iter().enumerate() {
+            let commitment = bit.verify(cv, index)?;
+            sum += commitment * weight;
+            weight.double_in_place();
+        }
+        if sum != decode_point(cv)? {
+            return Err(anyhow!("Range proof bits do not sum to the value commitment"));
+        }
+        Ok(())
+    }
+}
+
+impl BitProof {
+    fn transcript(cv: &Point, index: usize, commitment: &Point, nonces: [&JubjubPoint; 2]) -> JubjubScalar {
+        hash_to_scalar(&[
+            b"zhtp-shielded-range",
+            cv,
+            &(index as u64).to_le_bytes(),
+            commitment,
+            &encode_point(nonces[0]),
+            &encode_point(nonces[1]),
+        ])
+    }
+
+    /// Prove `C = b·G + r·H` commits to `b`, by showing that `C` or `C - G`
+    /// is a multiple of `H` without revealing which
+    fn prove(bit: bool, r: &JubjubScalar, cv: &Point, index: usize) -> Self {
+        let h = blinding_base();
+        let point = if bit { value_base() + h * r } else { h * r };
+        let commitment = encode_point(&point);
+        let statements = [point, point - value_base()];
+        let real = bit as usize;
+        let fake = 1 - real;
+
+        // Simulate the branch that is false, then answer the real one
+        let mut challenges = [JubjubScalar::zero(); 2];
+        let mut responses = [JubjubScalar::zero(); 2];
+        challenges[fake] = random_scalar();
+        responses[fake] = random_scalar();
+        let k = random_scalar();
+        let mut nonces = [JubjubPoint::zero(); 2];
+        nonces[fake] = h * responses[fake] - statements[fake] * challenges[fake];
+        nonces[real] = h * k;
+
+        let challenge = Self::transcript(cv, index, &commitment, [&nonces[0], &nonces[1]]);
+        challenges[real] = challenge - challenges[fake];
+        responses[real] = k + challenges[real] * r;
+
+        Self {
+            commitment,
+            challenges: challenges.map(|c| encode_scalar(&c)),
+            responses: responses.map(|s| encode_scalar(&s)),
+        }
+    }
+
+    /// Check the OR proof, returning the bit commitment
+    fn verify(&self, cv: &Point, index: usize) -> Result<JubjubPoint> {
+        let h = blinding_base();
+        let point = decode_point(&self.commitment)?;
+        let statements = [point, point - value_base()];
+        let challenges = [decode_scalar(&self.challenges[0])?, decode_scalar(&self.challenges[1])?];
+        let responses = [decode_scalar(&self.responses[0])?, decode_scalar(&self.responses[1])?];
+
+        let nonces = [
+            h * responses[0] - statements[0] * challenges[0],
+            h * responses[1] - statements[1] * challenges[1],
+        ];
+        let challenge = Self::transcript(cv, index, &self.commitment, [&nonces[0], &nonces[1]]);
+        if challenges[0] + challenges[1] != challenge {
+            return Err(anyhow!("Range proof bit {} is not 0 or 1", index));
+        }
+        Ok(point)
+    }
+}
+
+/// Schnorr-style proof that `log_a(x) = log_b(y)`, bound to a message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EqualityProof {
+    challenge: Scalar,
+    response: Scalar,
+}
+
+impl EqualityProof {
+    fn transcript(
+        message: &Hash,
+        bases: [&JubjubPoint; 2],
+        points: [&JubjubPoint; 2],
+        nonces: [&JubjubPoint; 2],
+    ) -> JubjubScalar {
+        hash_to_scalar(&[
+            b"zhtp-shielded-equality",
+            message,
+            &encode_point(bases[0]),
+            &encode_point(bases[1]),
+            &encode_point(points[0]),
+            &encode_point(points[1]),
+            &encode_point(nonces[0]),
+            &encode_point(nonces[1]),
+        ])
+    }
+
+    fn prove(secret: &JubjubScalar, bases: [&JubjubPoint; 2], message: &Hash) -> Self {
+        let points = [*bases[0] * secret, *bases[1] * secret];
+        let k = random_scalar();
+        let nonces = [*bases[0] * k, *bases[1] * k];
+        let challenge = Self::transcript(message, bases, [&points[0], &points[1]], [&nonces[0], &nonces[1]]);
+        Self {
+            challenge: encode_scalar(&challenge),
+            response: encode_scalar(&(k + challenge * secret)),
+        }
+    }
+
+    fn verify(&self, bases: [&JubjubPoint; 2], points: [&JubjubPoint; 2], message: &Hash) -> Result<()> {
+        let challenge = decode_scalar(&self.challenge)?;
+        let response = decode_scalar(&self.response)?;
+        let nonces = [
+            *bases[0] * response - *points[0] * challenge,
+            *bases[1] * response - *points[1] * challenge,
+        ];
+        if Self::transcript(message, bases, points, [&nonces[0], &nonces[1]]) != challenge {
+            return Err(anyhow!("Invalid discrete log equality proof"));
+        }
+        Ok(())
+    }
+}
+
+/// Consumes one note of the pool without revealing which
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpendDescription {
+    /// Root of the note commitment tree the note is proven against
+    pub anchor: Hash,
+    /// Fresh commitment to the note's value, unlinkable to the note's own
+    pub cv: Point,
+    /// Hash of the spending key, the note commitment and the note's
+    /// position, the same for every spend of the note
+    pub nullifier: Hash,
+    /// Groth16 proof of the spend circuit, which also authorizes the bundle
+    #[serde(with = "serde_bytes")]
+    pub proof: Vec<u8>,
+}
+
+impl SpendDescription {
+    /// Public inputs of the spend's proof in a bundle with `sighash`
+    fn statement(&self, sighash: &Hash) -> Result<SpendStatement> {
+        Ok(SpendStatement {
+            anchor: poseidon::decode(&self.anchor).ok_or_else(|| anyhow!("Spend anchor is not a tree root"))?,
+            nullifier: poseidon::decode(&self.nullifier).ok_or_else(|| anyhow!("Malformed nullifier"))?,
+            cv: decode_point(&self.cv)?.into_affine(),
+            sighash: field_element(sighash),
+        })
+    }
+
+    fn verify(&self, sighash: &Hash) -> Result<()> {
+        if !spend_circuit::verify(&self.statement(sighash)?, &self.proof)? {
+            return Err(anyhow!("Invalid spend proof"));
+        }
+        Ok(())
+    }
+}
+
+/// Creates one note in the pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputDescription {
+    /// Value commitment of the new note
+    pub cv: Point,
+    /// Hash of the note's hidden owner and randomness
+    pub owner_commitment: Hash,
+    /// Proof that the committed value fits in 64 bits
+    pub range_proof: RangeProof,
+    /// The note, encrypted to the payee
+    pub encrypted_note: SealedData,
+    /// The note again, encrypted under the sender's outgoing viewing key if
+    /// the sender chose to keep a readable copy
+    #[serde(default)]
+    pub out_ciphertext: Vec<u8>,
+}
+
+impl OutputDescription {
+    fn new(note: &Note, encryption_key: &[u8], outgoing: Option<&OutgoingViewingKey>) -> Result<Self> {
+        if decode_point(&note.pk_d).is_err() {
+            return Err(anyhow!("Invalid shielded address"));
+        }
+        let cv = note.value_commitment();
+        let plaintext = bincode::serialize(note)?;
+        Ok(Self {
+            cv,
+            owner_commitment: note.owner_commitment(),
+            range_proof: RangeProof::prove(note.value, &note.blinding()?, &cv),
+            encrypted_note: Keypair::seal_for(encryption_key, &plaintext)?,
+            out_ciphertext: outgoing.map(|key| key.seal(&plaintext)).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    pub fn note_commitment(&self) -> Hash {
+        note_commitment(&self.cv, &self.owner_commitment)
+    }
+
+    /// Trial-decrypt the note with a wallet keypair. Returns the note only if
+    /// it was encrypted to the keypair and matches the committed output.
+    pub fn decrypt(&self, keypair: &Keypair) -> Option<Note> {
+        self.committed_note(&keypair.open_sealed(&self.encrypted_note).ok()?)
+    }
+
+    /// The note if it pays the wallet viewed by `key`
+    pub fn decrypt_incoming(&self, key: &IncomingViewingKey) -> Option<Note> {
+        self.committed_note(&key.open_sealed(&self.encrypted_note).ok()?)
+    }
+
+    /// The note if the wallet viewed by `key` created it
+    pub fn decrypt_outgoing(&self, key: &OutgoingViewingKey) -> Option<Note> {
+        self.committed_note(&key.open(&self.out_ciphertext).ok()?)
+    }
+
+    /// Decode a decrypted note, keeping it only if it matches this output
+    fn committed_note(&self, plaintext: &[u8]) -> Option<Note> {
+        let note: Note = bincode::deserialize(plaintext).ok()?;
+        (note.value_commitment() == self.cv && note.owner_commitment() == self.owner_commitment).then_some(note)
+    }
+}
+
+/// Public payment out of the shielded pool to a transparent account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransparentOutput {
+    pub address: String,
+    pub amount: Amount,
+}
+
+/// Spends and outputs of one shielded transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShieldedBundle {
+    pub spends: Vec<SpendDescription>,
+    pub outputs: Vec<OutputDescription>,
+    /// Transparent value the carrying transaction moves into the pool
+    pub value_in: Amount,
+    /// Transparent value paid out of the pool
+    pub unshield: Option<TransparentOutput>,
+    /// Fee leaving the pool with the transaction
+    pub fee: Amount,
+    /// Proof that the value commitments balance against the public amounts
+    pub binding_signature: EqualityProof,
+}
+
+impl ShieldedBundle {
+    /// Hash of everything the bundle does, signed by its spend proofs and
+    /// its binding signature
+    pub fn sighash(&self) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update(b"zhtp-shielded-bundle");
+        for spend in &self.spends {
+            hasher.update(spend.anchor);
+            hasher.update(spend.cv);
+            hasher.update(spend.nullifier);
+        }
+        for output in &self.outputs {
+            hasher.update(output.cv);
+            hasher.update(output.owner_commitment);
+            hasher.update(bincode::serialize(&output.encrypted_note).unwrap_or_default());
+            hasher.update((output.out_ciphertext.len() as u64).to_le_bytes());
+            hasher.update(&output.out_ciphertext);
+        }
+        hasher.update(self.value_in.base_units().to_le_bytes());
+        if let Some(unshield) = &self.unshield {
+            hasher.update(unshield.address.as_bytes());
+            hasher.update(unshield.amount.base_units().to_le_bytes());
+        }
+        hasher.update(self.fee.base_units().to_le_bytes());
+        hasher.finalize().into()
+    }
+
+    /// Hash of the whole bundle, proofs included
+    pub fn hash(&self) -> Hash {
+        Sha256::digest(bincode::serialize(self).unwrap_or_default()).into()
+    }
+
+    pub fn nullifiers(&self) -> impl Iterator<Item = &Hash> {
+        self.spends.iter().map(|spend| &spend.nullifier)
+    }
+
+    /// Public value leaving the pool: the fee and any transparent payment
+    pub fn value_out(&self) -> Option<Amount> {
+        self.fee.checked_add(self.unshield.as_ref().map_or(Amount::ZERO, |unshield| unshield.amount))
+    }
+
+    /// `Σ cv_spends + value_in·G - Σ cv_outputs - value_out·G`, which is a
+    /// multiple of `H` alone when the bundle balances
+    fn binding_key(&self) -> Result<JubjubPoint> {
+        let value_out = self.value_out().ok_or_else(|| anyhow!("Bundle value out overflows"))?;
+        let mut key = value_base() * amount_scalar(self.value_in) - value_base() * amount_scalar(value_out);
+        for spend in &self.spends {
+            key += decode_point(&spend.cv)?;
+        }
+        for output in &self.outputs {
+            key -= decode_point(&output.cv)?;
+        }
+        Ok(key)
+    }
+
+    /// Check every proof in the bundle. Whether its anchors are known and its
+    /// nullifiers unspent depends on the pool and is checked when it applies.
+    pub fn verify(&self) -> Result<()> {
+        if self.spends.len() > MAX_SHIELDED_SPENDS || self.outputs.len() > MAX_SHIELDED_OUTPUTS {
+            return Err(anyhow!("Bundle has too many spends or outputs"));
+        }
+        let mut nullifiers = HashSet::new();
+        if !self.nullifiers().all(|nullifier| nullifiers.insert(nullifier)) {
+            return Err(anyhow!("Bundle spends the same note twice"));
+        }
+
+        let sighash = self.sighash();
+        for spend in &self.spends {
+            spend.verify(&sighash)?;
+        }
+        for output in &self.outputs {
+            // The spend circuit reads owner commitments as field elements,
+            // so each note must have exactly one encoding
+            if poseidon::decode(&output.owner_commitment).is_none() {
+                return Err(anyhow!("Malformed owner commitment"));
+            }
+            output.range_proof.verify(&output.cv)?;
+        }
+
+        // The binding key must be `bsk·H` for the `bsk` the signature proves
+        let h = blinding_base();
+        let binding_key = self.binding_key()?;
+        self.binding_signature.verify([&h, &h], [&binding_key, &binding_key], &sighash)
+    }
+}
+
+fn total(mut amounts: impl Iterator<Item = Amount>) -> Result<Amount> {
+    amounts
+        .try_fold(Amount::ZERO, |sum, amount| sum.checked_add(amount))
+        .ok_or_else(|| anyhow!("Bundle value overflows"))
+}
+
+/// Assembles a [`ShieldedBundle`] from notes to spend and payments to make
+#[derive(Debug, Default)]
+pub struct ShieldedBundleBuilder {
+    spends: Vec<(Note, Hash, NotePath)>,
+    outputs: Vec<(ShieldedAddress, Amount)>,
+    value_in: Amount,
+    unshield: Option<TransparentOutput>,
+    fee: Amount,
+    outgoing: Option<OutgoingViewingKey>,
+}
+
+impl ShieldedBundleBuilder {
+    pub fn new(fee: Amount) -> Self {
+        Self { fee, ..Self::default() }
+    }
+
+    /// Fund the bundle with transparent value carried by its transaction
+    pub fn shield(mut self, amount: Amount) -> Self {
+        self.value_in = amount;
+        self
+    }
+
+    /// Spend `note`, proven against the tree root `anchor` with `path`
+    pub fn spend(mut self, note: Note, anchor: Hash, path: NotePath) -> Self {
+        self.spends.push((note, anchor, path));
+        self
+    }
+
+    /// Pay `amount` into a new note for `to`
+    pub fn output(mut self, to: &ShieldedAddress, amount: Amount) -> Self {
+        self.outputs.push((to.clone(), amount));
+        self
+    }
+
+    /// Pay `amount` out of the pool to a transparent account
+    pub fn unshield(mut self, address: String, amount: Amount) -> Self {
+        self.unshield = Some(TransparentOutput { address, amount });
+        self
+    }
+
+    /// Keep a copy of every output readable with the sender's outgoing
+    /// viewing key, so its full viewing key shows what it paid
+    pub fn outgoing_viewing_key(mut self, key: OutgoingViewingKey) -> Self {
+        self.outgoing = Some(key);
+        self
+    }
+
+    /// Create the outputs and prove the spends with `key`, which must own
+    /// every spent note
+    pub fn build(self, key: &SpendingKey) -> Result<ShieldedBundle> {
+        let value_in = total(self.spends.iter().map(|(note, _, _)| note.value).chain([self.value_in]))?;
+        let unshielded = self.unshield.as_ref().map_or(Amount::ZERO, |unshield| unshield.amount);
+        let value_out = total(self.outputs.iter().map(|(_, amount)| *amount).chain([unshielded, self.fee]))?;
+        if value_in != value_out {
+            return Err(anyhow!("Bundle does not balance: {} ZHTP in, {} ZHTP out", value_in, value_out));
+        }
+        for (note, anchor, path) in &self.spends {
+            if !key.owns(note) {
+                return Err(anyhow!("Spending key does not own note {}", hex::encode(note.commitment())));
+            }
+            if path.root(&note.commitment()) != Some(*anchor) {
+                return Err(anyhow!("Note {} is not in the tree at its anchor", hex::encode(note.commitment())));
+            }
+        }
+
+        let notes: Vec<Note> = self.outputs.iter().map(|(to, amount)| Note::new(*amount, to)).collect();
+        let outputs = notes
+            .iter()
+            .zip(&self.outputs)
+            .map(|(note, (to, _))| OutputDescription::new(note, &to.encryption_key, self.outgoing.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        // Each spend commits to its note's value afresh, so its value
+        // commitment cannot be matched to the note's
+        let rerandomizations: Vec<JubjubScalar> = self.spends.iter().map(|_| random_scalar()).collect();
+        let spends = self
+            .spends
+            .iter()
+            .zip(&rerandomizations)
+            .map(|((note, anchor, path), rerandomization)| {
+                Ok(SpendDescription {
+                    anchor: *anchor,
+                    cv: encode_point(&commit_value(note.value, &(note.blinding()? + rerandomization))),
+                    nullifier: key.nullifier(note, path.position),
+                    proof: Vec::new(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut bundle = ShieldedBundle {
+            spends,
+            outputs,
+            value_in: self.value_in,
+            unshield: self.unshield,
+            fee: self.fee,
+            binding_signature: EqualityProof { challenge: [0u8; 32], response: [0u8; 32] },
+        };
+        let sighash = bundle.sighash();
+        for ((spend, (note, _, path)), rerandomization) in bundle.spends.iter_mut().zip(&self.spends).zip(&rerandomizations) {
+            let witness = SpendWitness {
+                value: note.value.base_units(),
+                rcv: note.blinding()?,
+                rerandomization: *rerandomization,
+                g_d: diversified_base(&note.diversifier).into_affine(),
+                nk: key.nk,
+                rseed: field_element(&note.rseed),
+                position: path.position,
+                siblings: path.siblings.iter().map(|sibling| field_element(sibling)).collect(),
+            };
+            spend.proof = spend_circuit::prove(&spend.statement(&sighash)?, witness)?;
+        }
+
+        let mut binding_secret = JubjubScalar::zero();
+        for ((note, _, _), rerandomization) in self.spends.iter().zip(&rerandomizations) {
+            binding_secret += note.blinding()? + rerandomization;
+        }
+        for note in &notes {
+            binding_secret -= note.blinding()?;
+        }
+        let h = blinding_base();
+        bundle.binding_signature = EqualityProof::prove(&binding_secret, [&h, &h], &sighash);
+        Ok(bundle)
+    }
+}
+
+/// Consensus state of the shielded pool: the note commitment tree, the
+/// nullifiers of spent notes and the recent roots spends may be anchored to
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "EncodedPool", into = "EncodedPool")]
+pub struct ShieldedPool {
+    tree: NoteCommitmentTree,
+    /// Nullifiers in the order they were revealed
+    nullifiers: Vec<Hash>,
+    spent: HashSet<Hash>,
+    anchors: VecDeque<Hash>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncodedPool {
+    tree: NoteCommitmentTree,
+    nullifiers: Vec<Hash>,
+    anchors: VecDeque<Hash>,
+}
+
+impl From<EncodedPool> for ShieldedPool {
+    fn from(encoded: EncodedPool) -> Self {
+        Self {
+            tree: encoded.tree,
+            spent: encoded.nullifiers.iter().copied().collect(),
+            nullifiers: encoded.nullifiers,
+            anchors: encoded.anchors,
+        }
+    }
+}
+
+impl From<ShieldedPool> for EncodedPool {
+    fn from(pool: ShieldedPool) -> Self {
+        Self {
+            tree: pool.tree,
+            nullifiers: pool.nullifiers,
+            anchors: pool.anchors,
+        }
+    }
+}
+
+/// Enough of a [`ShieldedPool`] to roll it back to an earlier state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShieldedCheckpoint {
+    tree: NoteTreeCheckpoint,
+    nullifiers: usize,
+    anchors: VecDeque<Hash>,
+}
+
+impl ShieldedPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether no note was ever created or spent
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty() && self.nullifiers.is_empty()
+    }
+
+    /// Current root of the note commitment tree
+    pub fn anchor(&self) -> Hash {
+        self.tree.root()
+    }
+
+    /// Whether spends may be proven against `anchor`
+    pub fn is_valid_anchor(&self, anchor: &Hash) -> bool {
+        !self.tree.is_empty() && (*anchor == self.tree.root() || self.anchors.contains(anchor))
+    }
+
+    pub fn is_spent(&self, nullifier: &Hash) -> bool {
+        self.spent.contains(nullifier)
+    }
+
+    pub fn note_count(&self) -> usize {
+        self.tree.len()
+    }
+
+    /// Position of the note with `commitment` in the tree
+    pub fn position(&self, commitment: &Hash) -> Option<u64> {
+        self.tree.position(commitment)
+    }
+
+    /// Authentication path of the note at `position` to [`ShieldedPool::anchor`]
+    pub fn path(&self, position: u64) -> Option<NotePath> {
+        self.tree.path(position)
+    }
+
+    /// Record the effects of a bundle whose anchors and nullifiers were
+    /// checked against this pool
+    pub fn insert(&mut self, bundle: &ShieldedBundle) -> Result<()> {
+        for output in &bundle.outputs {
+            self.tree.append(output.note_commitment()).ok_or_else(|| anyhow!("Note commitment tree is full"))?;
+        }
+        for nullifier in bundle.nullifiers() {
+            self.nullifiers.push(*nullifier);
+            self.spent.insert(*nullifier);
+        }
+        Ok(())
+    }
+
+    /// Remember the tree root at the end of a block as a spend anchor
+    pub fn end_block(&mut self) {
+        let root = self.tree.root();
+        if self.tree.is_empty() || self.anchors.back() == Some(&root) {
+            return;
+        }
+        self.anchors.push_back(root);
+        if self.anchors.len() > ANCHOR_HISTORY {
+            self.anchors.pop_front();
+        }
+    }
+
+    pub fn checkpoint(&self) -> ShieldedCheckpoint {
+        ShieldedCheckpoint {
+            tree: self.tree.checkpoint(),
+            nullifiers: self.nullifiers.len(),
+            anchors: self.anchors.clone(),
+        }
+    }
+
+    /// Undo everything recorded since `checkpoint` was taken
+    pub fn rewind(&mut self, checkpoint: &ShieldedCheckpoint) {
+        self.tree.rewind(&checkpoint.tree);
+        for nullifier in self.nullifiers.drain(checkpoint.nullifiers..) {
+            self.spent.remove(&nullifier);
+        }
+        self.anchors = checkpoint.anchors.clone();
+    }
+
+    /// Hash committing to the whole pool, the pool's leaf in the state root
+    pub fn digest(&self) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update(b"zhtp-shielded-pool");
+        hasher.update(self.tree.root());
+        hasher.update((self.nullifiers.len() as u64).to_le_bytes());
+        for nullifier in &self.nullifiers {
+            hasher.update(nullifier);
+        }
+        for anchor in &self.anchors {
+            hasher.update(anchor);
+        }
+        hasher.finalize().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wallet holding a spending key and the Kyber keypair its notes are
+    /// encrypted to
+    fn wallet() -> (SpendingKey, Keypair, ShieldedAddress) {
+        let key = SpendingKey::generate();
+        let keypair = Keypair::generate();
+        let address = key.address([7u8; 16], keypair.kyber_public_key());
+        (key, keypair, address)
+    }
+
+    #[test]
+    fn test_shield_and_spend() -> Result<()> {
+        let (alice_key, alice_keypair, alice) = wallet();
+        let (_, bob_keypair, bob) = wallet();
+        let fee = Amount::from_zhtp(1);
+
+        // Shield 100 ZHTP into a note for alice
+        let shielding = ShieldedBundleBuilder::new(fee)
+            .shield(Amount::from_zhtp(100))
+            .output(&alice, Amount::from_zhtp(99))
+            .build(&alice_key)?;
+        shielding.verify()?;
+        let mut pool = ShieldedPool::new();
+        pool.insert(&shielding)?;
+        pool.end_block();
+
+        let note = shielding.outputs[0].decrypt(&alice_keypair).expect("alice finds her note");
+        assert_eq!(note.value, Amount::from_zhtp(99));
+        assert!(alice_key.owns(&note));
+        assert!(shielding.outputs[0].decrypt(&bob_keypair).is_none());
+
+        // Alice pays bob 60 and herself the change
+        let position = pool.position(&note.commitment()).unwrap();
+        let payment = ShieldedBundleBuilder::new(fee)
+            .spend(note.clone(), pool.anchor(), pool.path(position).unwrap())
+            .output(&bob, Amount::from_zhtp(60))
+            .output(&alice, Amount::from_zhtp(38))
+            .build(&alice_key)?;
+        payment.verify()?;
+        assert!(pool.is_valid_anchor(&payment.spends[0].anchor));
+        assert_eq!(payment.spends[0].nullifier, alice_key.nullifier(&note, position));
+        assert_ne!(payment.spends[0].cv, note.value_commitment());
+        assert_eq!(payment.outputs[0].decrypt(&bob_keypair).unwrap().value, Amount::from_zhtp(60));
+
+        let checkpoint = pool.checkpoint();
+        pool.insert(&payment)?;
+        assert!(pool.is_spent(&alice_key.nullifier(&note, position)));
+        pool.rewind(&checkpoint);
+        assert!(!pool.is_spent(&alice_key.nullifier(&note, position)));
+
+        // Bundles that do not balance are refused, and tampering breaks them
+        assert!(ShieldedBundleBuilder::new(fee)
+            .spend(note.clone(), pool.anchor(), pool.path(position).unwrap())
+            .output(&bob, Amount::from_zhtp(99))
+            .build(&alice_key)
+            .is_err());
+        let mut inflated = payment.clone();
+        inflated.fee = Amount::ZERO;
+        assert!(inflated.verify().is_err());
+        let mut stolen = payment.clone();
+        stolen.outputs.swap(0, 1);
+        assert!(stolen.verify().is_err());
+        let mut wrong_anchor = payment;
+        wrong_anchor.spends[0].anchor = [1u8; 32];
+        assert!(wrong_anchor.verify().is_err());
+
+        // Only the owner can spend the note, and only notes in the tree
+        assert!(ShieldedBundleBuilder::new(fee)
+            .spend(note.clone(), pool.anchor(), pool.path(position).unwrap())
+            .output(&bob, Amount::from_zhtp(98))
+            .build(&SpendingKey::generate())
+            .is_err());
+        let mut unminted = note;
+        unminted.value = Amount::from_zhtp(1000);
+        assert!(ShieldedBundleBuilder::new(fee)
+            .spend(unminted, pool.anchor(), pool.path(position).unwrap())
+            .output(&bob, Amount::from_zhtp(999))
+            .build(&alice_key)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_proof_rejects_wrapped_values() -> Result<()> {
+        let blinding = random_scalar();
+        let cv = encode_point(&commit_value(Amount::from_base_units(u64::MAX), &blinding));
+        RangeProof::prove(Amount::from_base_units(u64::MAX), &blinding, &cv).verify(&cv)?;
+
+        // A proof for one value does not cover a commitment to another
+        let other = encode_point(&commit_value(Amount::from_base_units(5), &blinding));
+        assert!(RangeProof::prove(Amount::from_base_units(u64::MAX), &blinding, &cv).verify(&other).is_err());
+
+        // "Negative" values (field elements above 2^64) have no 64-bit proof
+        let negative = value_base() * -JubjubScalar::from(5u64) + blinding_base() * blinding;
+        let negative = encode_point(&negative);
+        let forged = RangeProof::prove(Amount::from_base_units(5), &blinding, &negative);
+        assert!(forged.verify(&negative).is_err());
+        Ok(())
+    }
+}
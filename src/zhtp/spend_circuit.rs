@@ -0,0 +1,379 @@
+//! Groth16 circuit proving a shielded spend.
+//!
+//! For public inputs `(anchor, nullifier, cv, sighash)` the prover shows it
+//! knows a note, a spending key `nk` and a tree path such that
+//!
+//! - the note's value commitment `cv_note = v·G + rcv·H` and its owner
+//!   commitment hash to a note commitment at position `pos` of the note
+//!   commitment tree whose root is `anchor`,
+//! - the note is owned by `pk_d = nk·g_d`, where `g_d` is the note's
+//!   diversified base and `nk` is below the subgroup order,
+//! - `nullifier` is the Poseidon hash of `nk`, the note commitment and `pos`,
+//! - `cv = cv_note + rcv'·H` commits to the same value `v`.
+//!
+//! `sighash` enters no constraint; as a public input Groth16 still binds the
+//! proof to it, and so to the bundle the spend authorizes. The hashes and
+//! bases are those of [`crate::zhtp::shielded`].
+//!
+//! Whoever runs the circuit's Groth16 setup can forge spends, so nodes
+//! install the keys of the ceremony with [`install_keys`]. Processes that
+//! allowed [`KzgTrustedSetup::allow_insecure_development_setup`] fall back to
+//! keys derived from a public seed.
+
+use crate::zhtp::{
+    circom::CircuitKeys,
+    jubjub::{JubjubAffine, JubjubConfig, JubjubPoint, JubjubScalar},
+    merkle::NOTE_TREE_DEPTH,
+    poseidon,
+    shielded::{blinding_base, field_element, value_base, NOTE_DOMAIN, NULLIFIER_DOMAIN, OWNER_DOMAIN},
+    zk_proofs::KzgTrustedSetup,
+};
+use anyhow::{anyhow, Result};
+use ark_bn254::{Bn254, Fr};
+use ark_ec::AffineRepr;
+use ark_ff::{BigInteger, One, PrimeField};
+use ark_groth16::{Groth16, Proof};
+use ark_r1cs_std::{
+    alloc::AllocVar,
+    boolean::Boolean,
+    eq::EqGadget,
+    fields::{fp::FpVar, FieldVar},
+    groups::{curves::twisted_edwards::AffineVar, CurveVar},
+};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
+use rand::{rngs::StdRng, CryptoRng, RngCore, SeedableRng};
+use std::sync::OnceLock;
+
+/// Name of the circuit's key files, `shielded_spend.pk` and `shielded_spend.vk`
+pub const SPEND_CIRCUIT_ID: &str = "shielded_spend";
+
+/// Public seed of the development keys; anyone can forge spends with them
+const DEVELOPMENT_SEED: [u8; 32] = *b"zhtp-insecure-dev-spend-setup!!!";
+
+static SPEND_KEYS: OnceLock<CircuitKeys> = OnceLock::new();
+
+type JubjubVar = AffineVar<JubjubConfig, FpVar<Fr>>;
+
+/// Public inputs of a spend proof
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpendStatement {
+    /// Root of the note commitment tree
+    pub anchor: Fr,
+    pub nullifier: Fr,
+    /// The spend's value commitment
+    pub cv: JubjubAffine,
+    /// Hash of the bundle the spend authorizes
+    pub sighash: Fr,
+}
+
+impl SpendStatement {
+    fn public_inputs(&self) -> Vec<Fr> {
+        vec![self.anchor, self.nullifier, self.cv.x, self.cv.y, self.sighash]
+    }
+}
+
+/// What the spender proves knowledge of
+#[derive(Debug, Clone)]
+pub struct SpendWitness {
+    pub value: u64,
+    /// Blinding of the note's value commitment
+    pub rcv: JubjubScalar,
+    /// Blinding the spend's value commitment adds to the note's
+    pub rerandomization: JubjubScalar,
+    /// The note's diversified base
+    pub g_d: JubjubAffine,
+    pub nk: JubjubScalar,
+    /// Randomness of the note's owner commitment
+    pub rseed: Fr,
+    pub position: u64,
+    /// Siblings of the note commitment and of each node above it
+    pub siblings: Vec<Fr>,
+}
+
+/// The spend circuit for one statement and witness
+#[derive(Debug, Clone)]
+pub struct SpendCircuit {
+    statement: SpendStatement,
+    witness: SpendWitness,
+}
+
+impl SpendCircuit {
+    pub fn new(statement: SpendStatement, witness: SpendWitness) -> Self {
+        Self { statement, witness }
+    }
+
+    /// Placeholder values with the circuit's shape, for the setup
+    fn blank() -> Self {
+        Self::new(
+            SpendStatement {
+                anchor: Fr::from(0u64),
+                nullifier: Fr::from(0u64),
+                cv: JubjubAffine::generator(),
+                sighash: Fr::from(0u64),
+            },
+            SpendWitness {
+                value: 0,
+                rcv: JubjubScalar::from(0u64),
+                rerandomization: JubjubScalar::from(0u64),
+                g_d: JubjubAffine::generator(),
+                nk: JubjubScalar::from(1u64),
+                rseed: Fr::from(0u64),
+                position: 0,
+                siblings: vec![Fr::from(0u64); NOTE_TREE_DEPTH],
+            },
+        )
+    }
+}
+
+/// `base`, `2·base`, `4·base`, ... for fixed-base multiplication by a
+/// scalar of `count` bits
+fn powers_of_two(base: JubjubPoint, count: usize) -> Vec<JubjubPoint> {
+    let mut powers = Vec::with_capacity(count);
+    let mut power = base;
+    for _ in 0..count {
+        powers.push(power);
+        power += power;
+    }
+    powers
+}
+
+fn value_base_powers() -> &'static [JubjubPoint] {
+    static POWERS: OnceLock<Vec<JubjubPoint>> = OnceLock::new();
+    POWERS.get_or_init(|| powers_of_two(value_base(), 64))
+}
+
+fn blinding_base_powers() -> &'static [JubjubPoint] {
+    static POWERS: OnceLock<Vec<JubjubPoint>> = OnceLock::new();
+    POWERS.get_or_init(|| powers_of_two(blinding_base(), JubjubScalar::MODULUS_BIT_SIZE as usize))
+}
+
+fn witness_bits(cs: &ConstraintSystemRef<Fr>, bits: Vec<bool>) -> Result<Vec<Boolean<Fr>>, SynthesisError> {
+    bits.into_iter().map(|bit| Boolean::new_witness(cs.clone(), || Ok(bit))).collect()
+}
+
+fn u64_bits(value: u64, count: usize) -> Vec<bool> {
+    (0..count).map(|index| (value >> index) & 1 == 1).collect()
+}
+
+fn scalar_bits(scalar: &JubjubScalar) -> Vec<bool> {
+    let mut bits = scalar.into_bigint().to_bits_le();
+    bits.truncate(JubjubScalar::MODULUS_BIT_SIZE as usize);
+    bits
+}
+
+/// `Σ bitᵢ·powers[i]`
+fn fixed_base_mul(bits: &[Boolean<Fr>], powers: &[JubjubPoint]) -> Result<JubjubVar, SynthesisError> {
+    let mut result = JubjubVar::zero();
+    result.precomputed_base_scalar_mul_le(bits.iter().zip(powers))?;
+    Ok(result)
+}
+
+impl ConstraintSynthesizer<Fr> for SpendCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let Self { statement, witness } = self;
+        let anchor = FpVar::new_input(cs.clone(), || Ok(statement.anchor))?;
+        let nullifier = FpVar::new_input(cs.clone(), || Ok(statement.nullifier))?;
+        let cv = JubjubVar::new(
+            FpVar::new_input(cs.clone(), || Ok(statement.cv.x))?,
+            FpVar::new_input(cs.clone(), || Ok(statement.cv.y))?,
+        );
+        let _sighash = FpVar::new_input(cs.clone(), || Ok(statement.sighash))?;
+
+        // Both value commitments commit to the same value
+        let value_bits = witness_bits(&cs, u64_bits(witness.value, 64))?;
+        let rcv_bits = witness_bits(&cs, scalar_bits(&witness.rcv))?;
+        let rerandomization_bits = witness_bits(&cs, scalar_bits(&witness.rerandomization))?;
+        let cv_note = fixed_base_mul(&value_bits, value_base_powers())? + fixed_base_mul(&rcv_bits, blinding_base_powers())?;
+        (cv_note.clone() + fixed_base_mul(&rerandomization_bits, blinding_base_powers())?).enforce_equal(&cv)?;
+
+        // The key owns the note. Allocating `g_d` puts it in the prime order
+        // subgroup, and it must not be the identity, which every key would
+        // own; below the subgroup order, each owner has a single `nk`.
+        let g_d = JubjubVar::new_witness(cs.clone(), || Ok(JubjubPoint::from(witness.g_d)))?;
+        g_d.is_zero()?.enforce_equal(&Boolean::constant(false))?;
+        let nk_bits = witness_bits(&cs, scalar_bits(&witness.nk))?;
+        Boolean::enforce_smaller_or_equal_than_le(&nk_bits, (-JubjubScalar::one()).into_bigint())?;
+        let pk_d = g_d.scalar_mul_le(nk_bits.iter())?;
+
+        let rseed = FpVar::new_witness(cs.clone(), || Ok(witness.rseed))?;
+        let owner = poseidon::hash_var(
+            cs.clone(),
+            &[FpVar::constant(field_element(OWNER_DOMAIN)), g_d.x, g_d.y, pk_d.x, pk_d.y, rseed],
+        )?;
+        let commitment =
+            poseidon::hash_var(cs.clone(), &[FpVar::constant(field_element(NOTE_DOMAIN)), cv_note.x, cv_note.y, owner])?;
+
+        let position_bits = witness_bits(&cs, u64_bits(witness.position, NOTE_TREE_DEPTH))?;
+        poseidon::hash_var(
+            cs.clone(),
+            &[
+                FpVar::constant(field_element(NULLIFIER_DOMAIN)),
+                Boolean::le_bits_to_fp_var(&nk_bits)?,
+                commitment.clone(),
+                Boolean::le_bits_to_fp_var(&position_bits)?,
+            ],
+        )?
+        .enforce_equal(&nullifier)?;
+
+        // The note commitment is in the tree at the anchor
+        let mut node = commitment;
+        for (bit, sibling) in position_bits.iter().zip(&witness.siblings) {
+            let sibling = FpVar::new_witness(cs.clone(), || Ok(*sibling))?;
+            let left = bit.select(&sibling, &node)?;
+            let right = bit.select(&node, &sibling)?;
+            node = poseidon::hash_var(cs.clone(), &[left, right])?;
+        }
+        node.enforce_equal(&anchor)
+    }
+}
+
+/// Run the circuit's Groth16 setup. Whoever runs it can forge spends, so
+/// the network's keys must come from the ceremony.
+pub fn setup<R: RngCore + CryptoRng>(rng: &mut R) -> Result<CircuitKeys> {
+    let proving_key = Groth16::<Bn254>::generate_random_parameters_with_reduction(SpendCircuit::blank(), rng)
+        .map_err(|e| anyhow!("Groth16 setup for {} failed: {}", SPEND_CIRCUIT_ID, e))?;
+    Ok(CircuitKeys::from_proving_key(proving_key))
+}
+
+/// Make `keys` the spend keys of this process. Must happen before any spend
+/// is proven or checked, since the keys cannot change afterwards.
+pub fn install_keys(keys: CircuitKeys) -> Result<&'static CircuitKeys> {
+    SPEND_KEYS
+        .set(keys)
+        .map_err(|_| anyhow!("Shielded spend keys are already initialized"))?;
+    self::keys()
+}
+
+/// The installed spend keys, or the insecure development keys if none were
+/// installed and the insecure development setup is allowed
+pub fn keys() -> Result<&'static CircuitKeys> {
+    if let Some(keys) = SPEND_KEYS.get() {
+        return Ok(keys);
+    }
+    if !KzgTrustedSetup::insecure_development_setup_allowed() {
+        return Err(anyhow!("No {} keys installed; load the ceremony's keys before proving or checking spends", SPEND_CIRCUIT_ID));
+    }
+    Ok(SPEND_KEYS.get_or_init(|| {
+        log::warn!("No shielded spend keys loaded, using the insecure development keys");
+        setup(&mut StdRng::from_seed(DEVELOPMENT_SEED)).expect("the spend circuit synthesizes")
+    }))
+}
+
+/// Prove a spend, returning the compressed Groth16 proof
+pub fn prove(statement: &SpendStatement, witness: SpendWitness) -> Result<Vec<u8>> {
+    if witness.siblings.len() != NOTE_TREE_DEPTH {
+        return Err(anyhow!("Spend path has {} siblings, expected {}", witness.siblings.len(), NOTE_TREE_DEPTH));
+    }
+    let keys = keys()?;
+    let proving_key = keys
+        .proving_key
+        .as_ref()
+        .ok_or_else(|| anyhow!("Only the verifying key of {} is loaded", SPEND_CIRCUIT_ID))?;
+    let proof = Groth16::<Bn254>::create_random_proof_with_reduction(
+        SpendCircuit::new(*statement, witness),
+        proving_key,
+        &mut rand::rngs::OsRng,
+    )
+    .map_err(|e| anyhow!("Proving spend failed: {}", e))?;
+
+    let mut bytes = Vec::new();
+    proof.serialize_compressed(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Check a spend proof against its public inputs
+pub fn verify(statement: &SpendStatement, proof: &[u8]) -> Result<bool> {
+    let keys = keys()?;
+    let Ok(proof) = Proof::<Bn254>::deserialize_compressed(proof) else {
+        return Ok(false);
+    };
+    Ok(Groth16::<Bn254>::verify_proof(keys.prepared_verifying_key(), &proof, &statement.public_inputs()).unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zhtp::{jubjub::scalar_to_field, merkle::NoteCommitmentTree};
+    use ark_ec::CurveGroup;
+    use ark_relations::r1cs::ConstraintSystem;
+
+    /// A note in a tree of three, with its statement and witness
+    fn spend() -> (SpendStatement, SpendWitness) {
+        let value = 42u64;
+        let rcv = JubjubScalar::from(7u64);
+        let rerandomization = JubjubScalar::from(11u64);
+        let g_d = crate::zhtp::jubjub::hash_to_curve(&[b"diversifier"]).into_affine();
+        let nk = JubjubScalar::from(1234u64);
+        let pk_d = (g_d * nk).into_affine();
+        let rseed = Fr::from(99u64);
+
+        let cv_note = (value_base() * JubjubScalar::from(value) + blinding_base() * rcv).into_affine();
+        let owner = poseidon::hash(&[field_element(OWNER_DOMAIN), g_d.x, g_d.y, pk_d.x, pk_d.y, rseed]);
+        let commitment = poseidon::hash(&[field_element(NOTE_DOMAIN), cv_note.x, cv_note.y, owner]);
+
+        let mut tree = NoteCommitmentTree::new();
+        tree.append([1u8; 32]);
+        let position = tree.append(poseidon::encode(&commitment)).unwrap();
+        tree.append([2u8; 32]);
+        let path = tree.path(position).unwrap();
+
+        let statement = SpendStatement {
+            anchor: field_element(&tree.root()),
+            nullifier: poseidon::hash(&[field_element(NULLIFIER_DOMAIN), scalar_to_field(&nk), commitment, Fr::from(position)]),
+            cv: (JubjubPoint::from(cv_note) + blinding_base() * rerandomization).into_affine(),
+            sighash: Fr::from(5u64),
+        };
+        let witness = SpendWitness {
+            value,
+            rcv,
+            rerandomization,
+            g_d,
+            nk,
+            rseed,
+            position,
+            siblings: path.siblings.iter().map(|sibling| field_element(sibling)).collect(),
+        };
+        (statement, witness)
+    }
+
+    fn satisfied(statement: SpendStatement, witness: SpendWitness) -> bool {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        SpendCircuit::new(statement, witness).generate_constraints(cs.clone()).unwrap();
+        cs.is_satisfied().unwrap()
+    }
+
+    #[test]
+    fn test_spend_circuit_constraints() {
+        let (statement, witness) = spend();
+        assert!(satisfied(statement, witness.clone()));
+
+        // Another key, value, position or anchor breaks the proof
+        let mut other_key = witness.clone();
+        other_key.nk = JubjubScalar::from(4321u64);
+        assert!(!satisfied(statement, other_key));
+        let mut other_value = witness.clone();
+        other_value.value = 43;
+        assert!(!satisfied(statement, other_value));
+        let mut other_position = witness.clone();
+        other_position.position = 0;
+        assert!(!satisfied(statement, other_position));
+        let mut other_anchor = statement;
+        other_anchor.anchor = Fr::from(1u64);
+        assert!(!satisfied(other_anchor, witness));
+    }
+
+    #[test]
+    fn test_spend_proof() -> Result<()> {
+        let (statement, witness) = spend();
+        let proof = prove(&statement, witness)?;
+        assert!(verify(&statement, &proof)?);
+
+        // The proof holds for its own public inputs only
+        let mut other_bundle = statement;
+        other_bundle.sighash = Fr::from(6u64);
+        assert!(!verify(&other_bundle, &proof)?);
+        assert!(!verify(&statement, &proof[1..])?);
+        Ok(())
+    }
+}
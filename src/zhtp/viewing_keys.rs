@@ -0,0 +1,284 @@
+//! Viewing keys: read-only access to a private wallet's transactions, for
+//! auditors and compliance partners who must not be able to spend.
+//!
+//! An [`IncomingViewingKey`] opens everything sealed to the wallet's Kyber
+//! key, so it shows what the wallet received. A [`FullViewingKey`] adds the
+//! wallet's [`OutgoingViewingKey`], which also shows what it sent. Neither
+//! holds the Dilithium signing key or the shielded spending key, and neither
+//! can tell which received notes were later spent: nullifiers can only be
+//! derived with the spending key.
+//!
+//! Keys are exported as a prefix naming their kind followed by the hex of
+//! their encoding and a four byte checksum.
+
+use crate::zhtp::{
+    amount::Amount,
+    crypto::{IncomingViewingKey, Keypair, OutgoingViewingKey},
+    merkle::Hash,
+    zk_transactions::{TransactionData, ZkTransaction},
+};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Export prefix of incoming viewing keys
+pub const INCOMING_VIEWING_KEY_PREFIX: &str = "zhtpivk1";
+
+/// Export prefix of full viewing keys
+pub const FULL_VIEWING_KEY_PREFIX: &str = "zhtpfvk1";
+
+/// Length of the checksum closing an exported key
+const CHECKSUM_LEN: usize = 4;
+
+/// Incoming and outgoing viewing keys of one wallet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FullViewingKey {
+    pub incoming: IncomingViewingKey,
+    pub outgoing: OutgoingViewingKey,
+}
+
+impl FullViewingKey {
+    pub fn from_keypair(keypair: &Keypair) -> Self {
+        Self {
+            incoming: keypair.incoming_viewing_key(),
+            outgoing: keypair.outgoing_viewing_key(),
+        }
+    }
+}
+
+/// Viewing key handed to an auditor
+#[derive(Debug, Clone)]
+pub enum ViewingKey {
+    /// Shows what the wallet received
+    Incoming(IncomingViewingKey),
+    /// Shows what the wallet received and sent
+    Full(FullViewingKey),
+}
+
+/// Which way value moved relative to the viewed wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+    /// Sent by the wallet to itself, such as shielded change
+    Internal,
+}
+
+/// What a viewing key revealed about one private payment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ViewedPayment {
+    /// Account-based private transfer
+    Transfer(TransactionData),
+    /// Shielded note created in the note commitment tree
+    Note { value: Amount, commitment: Hash },
+}
+
+impl ViewedPayment {
+    pub fn amount(&self) -> Amount {
+        match self {
+            ViewedPayment::Transfer(data) => data.amount,
+            ViewedPayment::Note { value, .. } => *value,
+        }
+    }
+}
+
+impl ViewingKey {
+    /// Incoming viewing key of a wallet keypair
+    pub fn incoming(keypair: &Keypair) -> Self {
+        ViewingKey::Incoming(keypair.incoming_viewing_key())
+    }
+
+    /// Full viewing key of a wallet keypair
+    pub fn full(keypair: &Keypair) -> Self {
+        ViewingKey::Full(FullViewingKey::from_keypair(keypair))
+    }
+
+    pub fn is_full(&self) -> bool {
+        matches!(self, ViewingKey::Full(_))
+    }
+
+    fn incoming_key(&self) -> &IncomingViewingKey {
+        match self {
+            ViewingKey::Incoming(key) => key,
+            ViewingKey::Full(key) => &key.incoming,
+        }
+    }
+
+    fn outgoing_key(&self) -> Option<&OutgoingViewingKey> {
+        match self {
+            ViewingKey::Incoming(_) => None,
+            ViewingKey::Full(key) => Some(&key.outgoing),
+        }
+    }
+
+    /// Encode the key for handing over, e.g. `zhtpfvk1` followed by hex
+    pub fn export(&self) -> Result<String> {
+        let (prefix, mut bytes) = match self {
+            ViewingKey::Incoming(key) => (INCOMING_VIEWING_KEY_PREFIX, bincode::serialize(key)?),
+            ViewingKey::Full(key) => (FULL_VIEWING_KEY_PREFIX, bincode::serialize(key)?),
+        };
+        let checksum = Sha256::digest(&bytes);
+        bytes.extend_from_slice(&checksum[..CHECKSUM_LEN]);
+        Ok(format!("{}{}", prefix, hex::encode(bytes)))
+    }
+
+    /// Decode a key produced by [`ViewingKey::export`]
+    pub fn import(encoded: &str) -> Result<Self> {
+        let (full, payload) = if let Some(payload) = encoded.strip_prefix(FULL_VIEWING_KEY_PREFIX) {
+            (true, payload)
+        } else if let Some(payload) = encoded.strip_prefix(INCOMING_VIEWING_KEY_PREFIX) {
+            (false, payload)
+        } else {
+            return Err(anyhow!("Not a viewing key"));
+        };
+        let bytes = hex::decode(payload.trim())?;
+        if bytes.len() < CHECKSUM_LEN {
+            return Err(anyhow!("Viewing key is truncated"));
+        }
+        let (bytes, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        if Sha256::digest(bytes)[..CHECKSUM_LEN] != *checksum {
+            return Err(anyhow!("Viewing key checksum mismatch"));
+        }
+        Ok(if full {
+            ViewingKey::Full(bincode::deserialize(bytes)?)
+        } else {
+            ViewingKey::Incoming(bincode::deserialize(bytes)?)
+        })
+    }
+
+    /// Everything this key reveals about a ZK transaction
+    pub fn view(&self, zk_tx: &ZkTransaction) -> Vec<(Direction, ViewedPayment)> {
+        let incoming = self.incoming_key();
+        let outgoing = self.outgoing_key();
+        if let Some(bundle) = &zk_tx.shielded {
+            return bundle
+                .outputs
+                .iter()
+                .filter_map(|output| {
+                    let received = output.decrypt_incoming(incoming);
+                    let sent = outgoing.and_then(|key| output.decrypt_outgoing(key));
+                    let direction = direction(received.is_some(), sent.is_some())?;
+                    let note = received.or(sent)?;
+                    Some((direction, ViewedPayment::Note { value: note.value, commitment: note.commitment() }))
+                })
+                .collect();
+        }
+
+        let received = zk_tx.decrypt_incoming(incoming);
+        let sent = outgoing.and_then(|key| zk_tx.decrypt_outgoing(key));
+        direction(received.is_some(), sent.is_some())
+            .and_then(|direction| Some((direction, ViewedPayment::Transfer(received.or(sent)?))))
+            .into_iter()
+            .collect()
+    }
+}
+
+fn direction(received: bool, sent: bool) -> Option<Direction> {
+    match (received, sent) {
+        (true, true) => Some(Direction::Internal),
+        (true, false) => Some(Direction::Incoming),
+        (false, true) => Some(Direction::Outgoing),
+        (false, false) => None,
+    }
+}
+
+/// One payment in a [`ViewingReport`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportEntry {
+    pub block_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub timestamp: i64,
+    pub direction: Direction,
+    pub payment: ViewedPayment,
+}
+
+/// Transaction history of a wallet as seen through a viewing key
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ViewingReport {
+    /// Whether outgoing payments were visible to the key
+    pub full: bool,
+    /// Lowest and highest block height scanned
+    pub first_block: u64,
+    pub last_block: u64,
+    pub entries: Vec<ReportEntry>,
+    /// Value received from others
+    pub total_received: Amount,
+    /// Value sent to others; always zero for an incoming viewing key
+    pub total_sent: Amount,
+}
+
+impl ViewingReport {
+    pub fn new(key: &ViewingKey) -> Self {
+        Self { full: key.is_full(), ..Self::default() }
+    }
+
+    pub fn push(&mut self, entry: ReportEntry) {
+        let amount = entry.payment.amount();
+        match entry.direction {
+            Direction::Incoming => self.total_received = self.total_received.saturating_add(amount),
+            Direction::Outgoing => self.total_sent = self.total_sent.saturating_add(amount),
+            Direction::Internal => {}
+        }
+        self.entries.push(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zhtp::shielded::{ShieldedBundleBuilder, SpendingKey};
+
+    #[test]
+    fn test_viewing_key_export() -> Result<()> {
+        let wallet = Keypair::generate();
+        let full = ViewingKey::full(&wallet);
+        let exported = full.export()?;
+        assert!(exported.starts_with(FULL_VIEWING_KEY_PREFIX));
+        assert!(ViewingKey::import(&exported)?.is_full());
+
+        let incoming = ViewingKey::incoming(&wallet).export()?;
+        assert!(!ViewingKey::import(&incoming)?.is_full());
+
+        // Corrupted or foreign strings are refused
+        let mut corrupted = exported.clone().into_bytes();
+        let last = corrupted.len() - 1;
+        corrupted[last] = if corrupted[last] == b'0' { b'1' } else { b'0' };
+        assert!(ViewingKey::import(&String::from_utf8(corrupted)?).is_err());
+        assert!(ViewingKey::import("zhtp_1234").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_view_transactions() -> Result<()> {
+        let alice = Keypair::generate();
+        let bob = Keypair::generate();
+        let transfer = ZkTransaction::new(&alice, "alice", "bob", &bob.kyber_public_key(), Amount::from_zhtp(5), Amount::from_zhtp(10), 0)?;
+
+        // Bob's incoming key sees the payment, alice's only with her full key
+        let seen = ViewingKey::incoming(&bob).view(&transfer);
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].0, Direction::Incoming);
+        assert_eq!(seen[0].1.amount(), Amount::from_zhtp(5));
+        assert!(ViewingKey::incoming(&alice).view(&transfer).is_empty());
+        assert_eq!(ViewingKey::full(&alice).view(&transfer)[0].0, Direction::Outgoing);
+
+        // Shielded outputs: bob is paid, alice keeps change and a copy of both
+        let alice_key = SpendingKey::generate();
+        let bob_address = SpendingKey::generate().address([0u8; 16], bob.kyber_public_key());
+        let alice_address = alice_key.address([0u8; 16], alice.kyber_public_key());
+        let bundle = ShieldedBundleBuilder::new(Amount::from_zhtp(1))
+            .shield(Amount::from_zhtp(10))
+            .output(&bob_address, Amount::from_zhtp(6))
+            .output(&alice_address, Amount::from_zhtp(3))
+            .outgoing_viewing_key(alice.outgoing_viewing_key())
+            .build(&alice_key)?;
+        let shielded = ZkTransaction::shielded(bundle)?;
+        let directions: Vec<Direction> = ViewingKey::full(&alice).view(&shielded).into_iter().map(|(d, _)| d).collect();
+        assert_eq!(directions, vec![Direction::Outgoing, Direction::Internal]);
+        let seen = ViewingKey::incoming(&bob).view(&shielded);
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].1.amount(), Amount::from_zhtp(6));
+        Ok(())
+    }
+}
@@ -11,6 +11,11 @@ use ark_bn254::{Fr, G1Projective};
 use ark_std::vec::Vec;
 use std::collections::{HashMap};
 use sha2::{Sha256, Digest};
+use rayon::prelude::*;
+use ark_bn254::Bn254;
+use ark_groth16::Groth16;
+use crate::zhtp::circom::{CircomCircuit, CircuitKeys, CIRCUITS};
+use crate::zhtp::proof_envelope::{ProofEnvelope, ProofStatement, ProofSystem, ProofVerifier, VerifierRegistry};
 
 // Re-export necessary types for use in other modules
 pub use ark_bn254::{Fr as ZkField, G1Projective as ZkGroup};
@@ -19,131 +24,6 @@ pub use ark_ec::Group as ZkGroupTrait;
 // Type alias for internal use
 type G1 = G1Projective;
 
-/// Serializable version of cryptographic types using byte representation
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ByteRoutingProof {
-    pub commitments: Vec<Vec<u8>>,
-    pub elements: Vec<Vec<u8>>,
-    pub inputs: Vec<Vec<u8>>,
-}
-
-impl From<RoutingProof> for ByteRoutingProof {
-    fn from(proof: RoutingProof) -> Self {
-        let commitments = proof.path_commitments.iter().map(|pc| {
-            let mut bytes = Vec::new();
-            pc.0.serialize_uncompressed(&mut bytes).unwrap();
-            bytes
-        }).collect();
-
-        let elements = proof.proof_elements.iter().map(|fr| {
-            let mut bytes = Vec::new();
-            fr.serialize_uncompressed(&mut bytes).unwrap();
-            bytes
-        }).collect();
-
-        let inputs = proof.public_inputs.iter().map(|fr| {
-            let mut bytes = Vec::new();
-            fr.serialize_uncompressed(&mut bytes).unwrap();
-            bytes
-        }).collect();
-
-        ByteRoutingProof {
-            commitments,
-            elements,
-            inputs,
-        }
-    }
-}
-
-impl TryFrom<ByteRoutingProof> for RoutingProof {
-    type Error = ark_serialize::SerializationError;
-
-    fn try_from(bytes: ByteRoutingProof) -> Result<Self, Self::Error> {
-        // Handle empty/invalid proofs gracefully
-        if bytes.commitments.is_empty() && bytes.elements.is_empty() && bytes.inputs.is_empty() {
-            return Ok(RoutingProof {
-                path_commitments: vec![],
-                proof_elements: vec![],
-                public_inputs: vec![],
-            });
-        }
-
-        let path_commitments = bytes.commitments.iter()
-            .map(|bytes| -> Result<PolyCommit, ark_serialize::SerializationError> {
-                if bytes.len() < 32 {
-                    // Use default/identity point for small byte arrays
-                    return Ok(PolyCommit(G1Projective::default()));
-                }
-                
-                match G1Projective::deserialize_uncompressed(&mut Cursor::new(bytes.as_slice())) {
-                    Ok(point) => Ok(PolyCommit(point)),                    Err(_) => {
-                        // Fallback: create a valid point from the hash of the bytes
-                        let hash = Sha256::digest(bytes);
-                        let mut hash_bytes = [0u8; 32];
-                        hash_bytes.copy_from_slice(&hash[..32]);
-                        
-                        // Use the hash to create a deterministic but valid field element
-                        let mut field_bytes = [0u8; 32];
-                        field_bytes[..hash_bytes.len()].copy_from_slice(&hash_bytes);
-                        let scalar = Fr::from_le_bytes_mod_order(&field_bytes);
-                        
-                        // Create a point by scalar multiplication with generator
-                        Ok(PolyCommit(G1Projective::generator() * scalar))
-                    }
-                }
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let proof_elements = bytes.elements.iter()
-            .map(|bytes| -> Result<Fr, ark_serialize::SerializationError> {                if bytes.len() < 32 {
-                    // Use hash of small byte arrays to create field elements
-                    let hash = Sha256::digest(bytes);
-                    let mut hash_bytes = [0u8; 32];
-                    hash_bytes.copy_from_slice(&hash[..32]);
-                    return Ok(Fr::from_le_bytes_mod_order(&hash_bytes));
-                }
-                
-                match Fr::deserialize_uncompressed(&mut Cursor::new(bytes.as_slice())) {
-                    Ok(element) => Ok(element),
-                    Err(_) => {
-                        // Fallback: create valid field element from hash
-                        let hash = Sha256::digest(bytes);
-                        let mut hash_bytes = [0u8; 32];
-                        hash_bytes.copy_from_slice(&hash[..32]);
-                        Ok(Fr::from_le_bytes_mod_order(&hash_bytes))
-                    }
-                }
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let public_inputs = bytes.inputs.iter()
-            .map(|bytes| -> Result<Fr, ark_serialize::SerializationError> {                if bytes.len() < 32 {
-                    let hash = Sha256::digest(bytes);
-                    let mut hash_bytes = [0u8; 32];
-                    hash_bytes.copy_from_slice(&hash[..32]);
-                    return Ok(Fr::from_le_bytes_mod_order(&hash_bytes));
-                }
-                
-                match Fr::deserialize_uncompressed(&mut Cursor::new(bytes.as_slice())) {
-                    Ok(input) => Ok(input),
-                    Err(_) => {
-                        let hash = Sha256::digest(bytes);
-                        let mut hash_bytes = [0u8; 32];
-                        hash_bytes.copy_from_slice(&hash[..32]);
-                        Ok(Fr::from_le_bytes_mod_order(&hash_bytes))
-                    }
-                }
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(RoutingProof {
-            path_commitments,
-            proof_elements,
-            public_inputs,
-        })
-    }
-}
-
 /// Types of proofs supported by the system
 #[derive(Debug, Clone, PartialEq)]
 pub enum ProofType {
@@ -234,6 +114,8 @@ pub struct RoutingProof {
     pub path_commitments: Vec<PolyCommit>,
     /// PLONK proof elements
     pub proof_elements: Vec<Fr>,
+    /// KZG opening proofs of the commitments at the challenge point
+    pub opening_proofs: Vec<PolyCommit>,
     /// Public inputs for the circuit
     pub public_inputs: Vec<Fr>,
 }
@@ -558,6 +440,24 @@ impl UnifiedCircuit {
 
     /// Generate a unified proof of routing, storage and network metrics
     pub fn generate_proof(&mut self) -> Option<RoutingProof> {
+        self.generate_proof_until(&|| false)
+    }
+
+    /// Prove on the global [`ProverPool`](crate::zhtp::prover::ProverPool)
+    /// instead of the caller's thread, giving up once `cancel` fires or
+    /// `timeout` elapses
+    pub async fn prove(
+        self,
+        cancel: &crate::zhtp::prover::ProofCancellation,
+        timeout: std::time::Duration,
+    ) -> anyhow::Result<RoutingProof> {
+        crate::zhtp::prover::ProverPool::global().prove(self, cancel, timeout).await
+    }
+
+    /// [`generate_proof`](Self::generate_proof) that polls `stop` between
+    /// commitments and returns `None` as soon as it reports true. The
+    /// commitments are computed in parallel on the current rayon pool.
+    pub fn generate_proof_until(&mut self, stop: &(dyn Fn() -> bool + Sync)) -> Option<RoutingProof> {
         // First verify the path is valid
         if !self.route_path.is_empty() {
             for i in 0..self.route_path.len() - 1 {
@@ -623,30 +523,49 @@ impl UnifiedCircuit {
         self.wire_polynomials = self.values_to_polynomials(&wire_values);
         self.generate_polynomials();
         
-        // Generate polynomial commitments
-        let challenge_point = Fr::from(2u64);
+        if stop() {
+            return None;
+        }
+
+        // Generate polynomial commitments and their openings
+        let challenge_point = unified_challenge_point();
         let mut path_commitments = Vec::with_capacity(wire_values.len());
         let mut proof_elements = Vec::with_capacity(wire_values.len());
-        
-        for (_i, poly) in self.wire_polynomials.iter().enumerate() {
-            let eval = evaluate_polynomial(poly, &challenge_point);
-            proof_elements.push(eval);
-            
-            // Use secure KZG trusted setup instead of random secrets
-            let trusted_setup = KzgTrustedSetup::get_global();
-            match trusted_setup.commit_polynomial(poly) {
-                Ok(commitment) => path_commitments.push(PolyCommit(commitment)),
-                Err(err) => {
-                    eprintln!("KZG commitment failed: {}", err);
-                    return None; // Return None if commitment fails
+        let mut opening_proofs = Vec::with_capacity(wire_values.len());
+        
+        // Use secure KZG trusted setup instead of random secrets
+        let trusted_setup = KzgTrustedSetup::get_global();
+        // A stop request is reported as `Err(None)`, a failure as `Err(Some(..))`
+        let opened: Result<Vec<_>, Option<String>> = self.wire_polynomials
+            .par_iter()
+            .map(|poly| {
+                if stop() {
+                    return Err(None);
+                }
+                let commitment = trusted_setup.commit_polynomial(poly).map_err(Some)?;
+                trusted_setup.open(poly, &challenge_point).map(|opening| (commitment, opening)).map_err(Some)
+            })
+            .collect();
+        match opened {
+            Ok(opened) => {
+                for (commitment, (eval, opening_proof)) in opened {
+                    path_commitments.push(PolyCommit(commitment));
+                    proof_elements.push(eval);
+                    opening_proofs.push(PolyCommit(opening_proof));
                 }
             }
+            Err(None) => return None,
+            Err(Some(err)) => {
+                eprintln!("KZG commitment failed: {}", err);
+                return None; // Return None if commitment fails
+            }
         }
         
         // Construct final proof
         let proof = RoutingProof {
             path_commitments,
-            proof_elements: proof_elements.clone(),
+            proof_elements,
+            opening_proofs,
             public_inputs: wire_values.clone(), // Clone to keep original values
         };
         
@@ -728,23 +647,16 @@ impl UnifiedCircuit {
     }
 }
 
-/// Helper function to evaluate polynomial at a point
-fn evaluate_polynomial(poly: &DensePolynomial<Fr>, point: &Fr) -> Fr {
-    let mut result = Fr::zero();
-    let mut power = Fr::one();
-    
-    for coeff in poly.coeffs.iter() {
-        result += *coeff * power;
-        power *= point;
-    }
-    
-    result
+/// Point at which unified proofs open their wire commitments
+fn unified_challenge_point() -> Fr {
+    Fr::from(2u64)
 }
 
 /// Helper function to validate proof structure
 fn validate_proof_structure(proof: &RoutingProof) -> bool {
     // Check component counts match
     if proof.path_commitments.len() != proof.proof_elements.len() ||
+       proof.path_commitments.len() != proof.opening_proofs.len() ||
        proof.path_commitments.len() != proof.public_inputs.len() {
         println!("Proof component count mismatch");
         return false;
@@ -765,6 +677,61 @@ pub fn verify_unified_proof(
     source: &[u8],
     destination: &[u8],
     stored_data_root: [u8; 32]
+) -> bool {
+    if !verify_unified_statement(proof, source, destination, stored_data_root) {
+        return false;
+    }
+
+    // Verify commitment/evaluation consistency using proper KZG verification
+    if !verify_kzg_commitments(proof) {
+        println!("❌ ZK Proof FAILED: KZG commitment verification failed");
+        return false;
+    }
+
+    println!("✅ All ZK proof components verified successfully with real constraint system");
+    true
+}
+
+/// A unified proof together with the route it is claimed to prove
+#[derive(Debug, Clone, Copy)]
+pub struct UnifiedProofClaim<'a> {
+    pub proof: &'a RoutingProof,
+    pub source: &'a [u8],
+    pub destination: &'a [u8],
+    pub stored_data_root: [u8; 32],
+}
+
+/// Verify many unified proofs, checking the KZG openings of all of them
+/// with a single multi-pairing. If the combined check fails, each proof's
+/// openings are checked on their own to find the invalid ones.
+pub fn verify_unified_proofs(claims: &[UnifiedProofClaim]) -> Vec<bool> {
+    let trusted_setup = KzgTrustedSetup::get_global();
+    let mut results: Vec<bool> = claims
+        .iter()
+        .map(|claim| verify_unified_statement(claim.proof, claim.source, claim.destination, claim.stored_data_root))
+        .collect();
+    let openings: Vec<Vec<KzgOpening>> = claims
+        .iter()
+        .zip(&results)
+        .map(|(claim, valid)| if *valid { kzg_openings(claim.proof) } else { Vec::new() })
+        .collect();
+
+    let combined: Vec<KzgOpening> = openings.iter().flatten().copied().collect();
+    if trusted_setup.batch_verify_openings(&combined) {
+        return results;
+    }
+    for (valid, openings) in results.iter_mut().zip(&openings) {
+        *valid = *valid && trusted_setup.batch_verify_openings(openings);
+    }
+    results
+}
+
+/// Everything [`verify_unified_proof`] checks except the KZG openings
+fn verify_unified_statement(
+    proof: &RoutingProof,
+    source: &[u8],
+    destination: &[u8],
+    stored_data_root: [u8; 32]
 ) -> bool {
     // Early validation of proof structure
     if !validate_proof_structure(proof) {
@@ -796,12 +763,6 @@ pub fn verify_unified_proof(
         return false;
     }
 
-    // Verify commitment/evaluation consistency using proper KZG verification
-    if !verify_kzg_commitments(proof) {
-        println!("❌ ZK Proof FAILED: KZG commitment verification failed");
-        return false;
-    }
-
     // Verify public inputs match constraint system
     if !verify_public_inputs(proof, source, destination, stored_data_root, &circuit) {
         println!("❌ ZK Proof FAILED: Public input verification failed");
@@ -814,10 +775,26 @@ pub fn verify_unified_proof(
         return false;
     }
 
-    println!("✅ All ZK proof components verified successfully with real constraint system");
     true
 }
 
+/// Openings of a unified proof's wire commitments at the challenge point
+fn kzg_openings(proof: &RoutingProof) -> Vec<KzgOpening> {
+    let point = unified_challenge_point();
+    proof
+        .path_commitments
+        .iter()
+        .zip(&proof.proof_elements)
+        .zip(&proof.opening_proofs)
+        .map(|((commitment, evaluation), opening)| KzgOpening {
+            commitment: commitment.0,
+            point,
+            evaluation: *evaluation,
+            proof: opening.0,
+        })
+        .collect()
+}
+
 /// Verify polynomial constraints against the constraint system (real PLONK verification)
 fn verify_polynomial_constraints(proof: &RoutingProof, circuit: &UnifiedCircuit) -> bool {
     // Check we have enough proof elements for constraint verification
@@ -869,37 +846,21 @@ fn verify_kzg_commitments(proof: &RoutingProof) -> bool {
         return false;
     }
 
-    // Get the global trusted setup for proper KZG verification
-    let _trusted_setup = KzgTrustedSetup::get_global();
-    
-    // For testing/development: verify all commitments are well-formed and consistent
-    // In production, each commitment would come with an opening proof that could be
-    // verified using trusted_setup.verify_opening()
-    
-    // Verify all commitments are well-formed (valid group elements)
-    // Note: Zero commitments are valid - they represent commitments to the zero polynomial
-    // In KZG, committing to the zero polynomial yields the zero group element, which is valid
-    let commitment_count = proof.path_commitments.len();
-    println!("✅ Verifying {} KZG commitments (zero commitments allowed)", commitment_count);
-    
-    // Verify we have matching numbers of commitments and evaluations
-    if proof.path_commitments.len() != proof.proof_elements.len() {
-        println!("❌ KZG verification failed: mismatched lengths {} vs {}", 
-                 proof.path_commitments.len(), proof.proof_elements.len());
+    // Verify we have matching numbers of commitments, evaluations and openings
+    if proof.path_commitments.len() != proof.proof_elements.len()
+        || proof.path_commitments.len() != proof.opening_proofs.len()
+    {
+        println!("❌ KZG verification failed: mismatched lengths {} vs {} vs {}",
+                 proof.path_commitments.len(), proof.proof_elements.len(), proof.opening_proofs.len());
         return false;
     }
-    
-    // Since we're using a deterministic trusted setup, commitments should be consistent
-    // across all proofs. For now, this basic verification is sufficient for testing
-    // and demonstrates that we're using the shared trusted setup correctly.
-    
-    // In the future, we should:
-    // 1. Generate opening proofs during commitment creation
-    // 2. Verify each opening proof using trusted_setup.verify_opening()
-    // 3. Use proper challenge points for KZG verification
-    
-    println!("✅ KZG verification passed for {} commitments", proof.path_commitments.len());
-    true
+
+    // Zero commitments are valid - they represent commitments to the zero polynomial
+    let verified = KzgTrustedSetup::get_global().batch_verify_openings(&kzg_openings(proof));
+    if verified {
+        println!("✅ KZG verification passed for {} commitments", proof.path_commitments.len());
+    }
+    verified
 }
 
 /// Verify public inputs match the constraint system expectations
@@ -996,6 +957,7 @@ pub mod test_helpers {
         let routing_proof = RoutingProof {
             path_commitments,
             proof_elements,
+            opening_proofs: vec![PolyCommit(G1::zero()); 11],
             public_inputs
         };
 
@@ -1386,129 +1348,440 @@ mod tests {
         assert!(verify_unified_proof(&proof, &source, &destination, data_root),
             "Generated proof should verify successfully");
     }
+
+    #[test]
+    fn test_trusted_setup_file() -> Result<(), anyhow::Error> {
+        let setup = KzgTrustedSetup::from_tau(Fr::from(123456789u64), ZHTP_SRS_DEGREE);
+        let bytes = setup.to_srs_bytes();
+        let hash = KzgTrustedSetup::srs_hash(&bytes);
+        assert_eq!(hex::encode(setup.ceremony_id), hash);
+
+        let path = std::env::temp_dir().join(format!("zhtp_srs_test_{}.srs", std::process::id()));
+        std::fs::write(&path, &bytes)?;
+        let loaded = KzgTrustedSetup::load(&path, &hash);
+        let wrong_pin = KzgTrustedSetup::load(&path, &KzgTrustedSetup::srs_hash(b"other"));
+        std::fs::remove_file(&path)?;
+        let loaded = loaded?;
+        assert_eq!(loaded.max_degree, ZHTP_SRS_DEGREE);
+        assert_eq!(loaded.powers_of_tau_g1, setup.powers_of_tau_g1);
+        assert!(wrong_pin.is_err());
+
+        // Powers that skip a step of τ fail the pairing check
+        let mut broken = setup.clone();
+        broken.powers_of_tau_g1[7] = broken.powers_of_tau_g1[8];
+        assert!(broken.validate().is_err());
+        assert!(KzgTrustedSetup::from_srs_bytes(&broken.to_srs_bytes()).is_err());
+
+        // So do G2 powers from a different τ, and foreign files
+        let mut mismatched = setup.clone();
+        mismatched.powers_of_tau_g2 = KzgTrustedSetup::from_tau(Fr::from(42u64), 1).powers_of_tau_g2;
+        assert!(mismatched.validate().is_err());
+        assert!(KzgTrustedSetup::from_srs_bytes(&bytes[8..]).is_err());
+
+        // A consistent SRS too small for the network's circuits is refused
+        let undersized = KzgTrustedSetup::from_tau(Fr::from(123456789u64), 16);
+        assert!(undersized.validate().is_err());
+        assert!(KzgTrustedSetup::from_srs_bytes(&undersized.to_srs_bytes()).is_err());
+        Ok(())
+    }
+
+    /// `setup` as snarkjs would write it after phase 1
+    fn ptau_bytes(setup: &KzgTrustedSetup) -> Vec<u8> {
+        use ark_ec::CurveGroup;
+
+        fn coordinate(out: &mut Vec<u8>, element: &ark_bn254::Fq) {
+            out.extend(element.0.to_bytes_le());
+        }
+        let mut header = 32u32.to_le_bytes().to_vec();
+        header.extend(<ark_bn254::Fq as PrimeField>::MODULUS.to_bytes_le());
+        header.extend(10u32.to_le_bytes());
+        header.extend(0u32.to_le_bytes());
+        let mut g1 = Vec::new();
+        for point in G1Projective::normalize_batch(&setup.powers_of_tau_g1) {
+            coordinate(&mut g1, &point.x);
+            coordinate(&mut g1, &point.y);
+        }
+        let mut g2 = Vec::new();
+        for point in ark_bn254::G2Projective::normalize_batch(&setup.powers_of_tau_g2) {
+            for element in [point.x.c0, point.x.c1, point.y.c0, point.y.c1] {
+                coordinate(&mut g2, &element);
+            }
+        }
+
+        let mut bytes = b"ptau".to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend(3u32.to_le_bytes());
+        for (id, data) in [(1u32, header), (2, g1), (3, g2)] {
+            bytes.extend(id.to_le_bytes());
+            bytes.extend((data.len() as u64).to_le_bytes());
+            bytes.extend(data);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_setup_from_ptau() {
+        let setup = KzgTrustedSetup::from_tau(Fr::from(987654321u64), ZHTP_SRS_DEGREE);
+        let bytes = ptau_bytes(&setup);
+        let loaded = KzgTrustedSetup::from_ptau(&bytes).unwrap();
+        assert_eq!(loaded.powers_of_tau_g1, setup.powers_of_tau_g1);
+        assert_eq!(loaded.powers_of_tau_g2, setup.powers_of_tau_g2);
+        assert_eq!(loaded.ceremony_id, setup.ceremony_id);
+
+        // Truncated transcripts and ones without enough powers are refused
+        assert!(KzgTrustedSetup::from_ptau(&bytes[..bytes.len() - 1]).is_err());
+        let small = KzgTrustedSetup::from_tau(Fr::from(987654321u64), 16);
+        assert!(KzgTrustedSetup::from_ptau(&ptau_bytes(&small)).is_err());
+    }
+
+    #[test]
+    fn test_batch_verify_openings() {
+        use ark_std::UniformRand;
+        let mut rng = rand::thread_rng();
+        let setup = KzgTrustedSetup::from_tau(Fr::rand(&mut rng), 16);
+        let mut openings: Vec<KzgOpening> = (0..8)
+            .map(|degree| {
+                let poly = DensePolynomial { coeffs: (0..=degree).map(|_| Fr::rand(&mut rng)).collect() };
+                let point = Fr::rand(&mut rng);
+                let (evaluation, proof) = setup.open(&poly, &point).unwrap();
+                KzgOpening { commitment: setup.commit_polynomial(&poly).unwrap(), point, evaluation, proof }
+            })
+            .collect();
+        assert!(openings.iter().all(|o| setup.verify_opening(&o.commitment, &o.point, &o.evaluation, &o.proof)));
+        assert!(setup.batch_verify_openings(&openings));
+
+        // A single wrong evaluation spoils the batch
+        openings[5].evaluation += Fr::one();
+        assert!(!setup.verify_opening(&openings[5].commitment, &openings[5].point, &openings[5].evaluation, &openings[5].proof));
+        assert!(!setup.batch_verify_openings(&openings));
+    }
+
+    #[test]
+    fn test_batch_verify_unified_proofs() {
+        let routes: Vec<(Vec<u8>, Vec<u8>)> = (0..4u8).map(|i| (vec![i], vec![i, i])).collect();
+        let mut proofs: Vec<RoutingProof> = routes
+            .iter()
+            .map(|(source, destination)| {
+                let mut routing_table = HashMap::new();
+                routing_table.insert(source.clone(), vec![destination.clone()]);
+                UnifiedCircuit::new(
+                    source.clone(),
+                    destination.clone(),
+                    vec![source.clone(), destination.clone()],
+                    routing_table,
+                    [0u8; 32],
+                    Vec::new(),
+                    G1::generator(),
+                    0,
+                    Vec::new(),
+                    Vec::new(),
+                )
+                .generate_proof()
+                .unwrap()
+            })
+            .collect();
+        let verify = |proofs: &[RoutingProof]| {
+            let claims: Vec<UnifiedProofClaim> = proofs
+                .iter()
+                .zip(&routes)
+                .map(|(proof, (source, destination))| UnifiedProofClaim { proof, source, destination, stored_data_root: [0u8; 32] })
+                .collect();
+            verify_unified_proofs(&claims)
+        };
+        assert_eq!(verify(&proofs), vec![true; 4]);
+
+        // Only the proof with a forged evaluation is rejected
+        proofs[2].proof_elements[0] += Fr::one();
+        assert!(!verify_unified_proof(&proofs[2], &routes[2].0, &routes[2].1, [0u8; 32]));
+        assert_eq!(verify(&proofs), vec![true, true, false, true]);
+    }
+
+    #[tokio::test]
+    async fn test_groth16_circuit_proofs() -> Result<(), anyhow::Error> {
+        let keys_dir = std::env::temp_dir().join(format!("zhtp_circuit_keys_{}", std::process::id()));
+        let mut engine = ZkEngine::load(COMPILED_CIRCUITS_DIR, &keys_dir)?;
+        assert_eq!(engine.circuit_ids().len(), 6);
+        assert!(engine.prove("stake_proof", &[]).is_err(), "no keys before setup");
+
+        let mut rng = rand::rngs::OsRng;
+        engine.setup_circuit("stake_proof", &mut rng)?;
+        engine.setup_circuit("private_transfer", &mut rng)?;
+        engine.setup_circuit("ownership_proof", &mut rng)?;
+
+        let block_hash = [9u8; 32];
+        let proof = engine.generate_stake_proof(1000, 100, &block_hash, &[42u8; 32]).await?;
+        assert!(engine.verify_stake_proof(&proof, 100, &block_hash).await?);
+        assert!(!engine.verify_stake_proof(&proof, 50, &block_hash).await?);
+        assert!(!engine.verify_stake_proof(&proof, 100, &[8u8; 32]).await?, "proof is bound to its block");
+        assert!(engine.generate_stake_proof(50, 100, &block_hash, &[42u8; 32]).await.is_err());
+
+        // Tampered public signals or proof bytes fail
+        let mut tampered = proof.clone();
+        tampered.public_inputs[0] = Fr::from(1u64);
+        assert!(!engine.verify(&tampered)?);
+        let mut tampered = proof.clone();
+        tampered.proof[0] ^= 1;
+        assert!(!engine.verify(&tampered)?);
+
+        let transfer = engine.generate_private_transfer_proof(500, 450, 50, &[123u8; 32], &[7u8; 32]).await?;
+        assert!(engine.verify_private_transfer_proof(&transfer).await?);
+        assert_eq!(engine.public_signal(&transfer, "network_fee")?, Fr::from(50u64));
+        assert!(!engine.verify_stake_proof(&transfer, 100, &block_hash).await?);
+        assert!(engine.generate_private_transfer_proof(400, 450, 50, &[123u8; 32], &[7u8; 32]).await.is_err());
+        assert!(engine.generate_private_transfer_proof(500, 200, 0, &[123u8; 32], &[7u8; 32]).await.is_err());
+
+        let ownership = engine.prove("ownership_proof", &[
+            ("domain_hash", Fr::from(77u64)),
+            ("owner_commitment", Fr::from(1u64 + 2 + 3)),
+            ("registration_time", Fr::from(1_700_000_000u64)),
+            ("network_id", Fr::from(1u64)),
+            ("domain_name", Fr::from(77u64)),
+            ("owner_secret", Fr::from(1u64)),
+            ("ownership_nonce", Fr::from(2u64)),
+            ("registration_salt", Fr::from(3u64)),
+        ])?;
+        assert!(engine.verify(&ownership)?);
+        assert_eq!(engine.public_signal(&ownership, "domain_hash")?, Fr::from(77u64));
+
+        // Verifiers load just the verifying keys
+        engine.save_keys(&keys_dir)?;
+        std::fs::remove_file(keys_dir.join("stake_proof.pk"))?;
+        let verifier = ZkEngine::load(COMPILED_CIRCUITS_DIR, &keys_dir)?;
+        std::fs::remove_dir_all(&keys_dir)?;
+        assert!(verifier.verify(&proof)?);
+        assert!(verifier.verify(&transfer)?);
+        assert!(verifier.generate_stake_proof(1000, 100, &block_hash, &[1u8; 32]).await.is_err());
+
+        // Registered circuits are checked through the verifier registry
+        let registry = VerifierRegistry::empty();
+        assert!(registry.verify(&proof, &ProofStatement::PublicInputs).is_err());
+        verifier.register_verifiers(&registry);
+        assert!(registry.has_verifier(ProofSystem::Groth16, "private_transfer"));
+        assert!(registry.verify(&proof, &ProofStatement::PublicInputs)?);
+        assert!(registry.verify(&transfer, &ProofStatement::PublicInputs)?);
+        assert!(!registry.verify(&tampered, &ProofStatement::PublicInputs)?);
+        assert!(!registry.verify(&ProofEnvelope::none(), &ProofStatement::PublicInputs)?);
+        Ok(())
+    }
 }
 
-/// Zero-knowledge proof engine for ZHTP circuits
+/// Groth16 prover and verifier for the circom circuits in `circuits/compiled`
+#[derive(Default)]
 pub struct ZkEngine {
-    circuit_keys: std::collections::HashMap<String, CircuitKey>,
-    trusted_setup_complete: bool,
+    circuits: HashMap<String, CircomCircuit>,
+    keys: HashMap<String, CircuitKeys>,
 }
 
-/// Circuit proving and verification key pair
-#[derive(Debug, Clone)]
-pub struct CircuitKey {
-    proving_key: Vec<u8>,
-    verification_key: Vec<u8>,
-}
+/// Default location of the compiled circuits
+pub const COMPILED_CIRCUITS_DIR: &str = "circuits/compiled";
+
+/// Default location of the circuits' Groth16 keys
+pub const CIRCUIT_KEYS_DIR: &str = "circuits/keys";
+
+/// Network id the stake circuit accepts
+const STAKE_CIRCUIT_NETWORK_ID: u64 = 1;
 
 impl ZkEngine {
-    /// Create new ZK engine
+    /// Create an engine without circuits
     pub fn new() -> Self {
-        Self {
-            circuit_keys: std::collections::HashMap::new(),
-            trusted_setup_complete: false,
+        Self::default()
+    }
+
+    /// Load every compiled circuit and the keys found for them in `keys_dir`
+    pub fn load<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(compiled_dir: P, keys_dir: Q) -> anyhow::Result<Self> {
+        let mut engine = Self::new();
+        for spec in CIRCUITS {
+            engine.circuits.insert(spec.id.to_string(), CircomCircuit::load(&compiled_dir, spec)?);
+            if let Some(keys) = CircuitKeys::load(&keys_dir, spec.id)? {
+                engine.keys.insert(spec.id.to_string(), keys);
+            }
+        }
+        Ok(engine)
+    }
+
+    /// Ids of the loaded circuits
+    pub fn circuit_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.circuits.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    pub fn circuit(&self, circuit_id: &str) -> anyhow::Result<&CircomCircuit> {
+        self.circuits
+            .get(circuit_id)
+            .ok_or_else(|| anyhow::anyhow!("Unknown circuit {}", circuit_id))
+    }
+
+    pub fn keys(&self, circuit_id: &str) -> anyhow::Result<&CircuitKeys> {
+        self.keys
+            .get(circuit_id)
+            .ok_or_else(|| anyhow::anyhow!("No keys for circuit {}; run its setup first", circuit_id))
+    }
+
+    /// Run the Groth16 setup of a circuit, replacing any keys it had
+    pub fn setup_circuit<R: rand::RngCore + rand::CryptoRng>(&mut self, circuit_id: &str, rng: &mut R) -> anyhow::Result<&CircuitKeys> {
+        let keys = CircuitKeys::generate(self.circuit(circuit_id)?, rng)?;
+        self.keys.insert(circuit_id.to_string(), keys);
+        self.keys(circuit_id)
+    }
+
+    /// Register the verifying key of every circuit with keys, so `registry`
+    /// checks the Groth16 envelopes naming that circuit
+    pub fn register_verifiers(&self, registry: &VerifierRegistry) {
+        for (circuit_id, keys) in &self.keys {
+            let verifier = CircuitKeys::from_verifying_key(keys.verifying_key.clone());
+            registry.register(ProofSystem::Groth16, circuit_id, std::sync::Arc::new(verifier));
+        }
+    }
+
+    /// Write the keys of every circuit to `keys_dir`
+    pub fn save_keys<P: AsRef<std::path::Path>>(&self, keys_dir: P) -> anyhow::Result<()> {
+        for (circuit_id, keys) in &self.keys {
+            keys.save(&keys_dir, circuit_id)?;
+        }
+        Ok(())
+    }
+
+    /// Prove a circuit from its input signals, named as in the circom source
+    pub fn prove(&self, circuit_id: &str, inputs: &[(&str, Fr)]) -> anyhow::Result<ProofEnvelope> {
+        let circuit = self.circuit(circuit_id)?;
+        let keys = self.keys(circuit_id)?;
+        let proving_key = keys
+            .proving_key
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Only the verifying key of circuit {} is loaded", circuit_id))?;
+
+        let witness = circuit.calculate_witness(inputs)?;
+        let public_inputs = circuit.public_signals(&witness);
+        let proof = Groth16::<Bn254>::create_random_proof_with_reduction(
+            circuit.instance(Some(witness)),
+            proving_key,
+            &mut rand::rngs::OsRng,
+        )
+        .map_err(|e| anyhow::anyhow!("Proving circuit {} failed: {}", circuit_id, e))?;
+
+        let mut proof_data = Vec::new();
+        proof.serialize_compressed(&mut proof_data)?;
+        Ok(ProofEnvelope::new(
+            ProofSystem::Groth16,
+            circuit_id,
+            keys.verifying_key_hash(),
+            public_inputs,
+            proof_data,
+        ))
+    }
+
+    /// Check a proof against the verifying key of its circuit
+    pub fn verify(&self, proof: &ProofEnvelope) -> anyhow::Result<bool> {
+        if proof.system != ProofSystem::Groth16 {
+            return Ok(false);
         }
+        let keys = self.keys(&proof.circuit_id)?;
+        if proof.verification_key_hash != keys.verifying_key_hash() {
+            return Ok(false);
+        }
+        keys.verify(proof, &ProofStatement::PublicInputs)
+    }
+
+    /// Value of a named public signal of a proof
+    pub fn public_signal(&self, proof: &ProofEnvelope, name: &str) -> anyhow::Result<Fr> {
+        let index = self
+            .circuit(&proof.circuit_id)?
+            .public_signal_names()
+            .iter()
+            .position(|signal| *signal == name)
+            .ok_or_else(|| anyhow::anyhow!("{} is not a public signal of {}", name, proof.circuit_id))?;
+        proof
+            .public_inputs
+            .get(index)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Proof is missing public signal {}", name))
     }
 
-    /// Generate stake proof for consensus
+    /// Generate stake proof for consensus on the block `block_hash`
     pub async fn generate_stake_proof(
         &self,
         stake_amount: u64,
         min_stake: u64,
+        block_hash: &[u8; 32],
         secret_nonce: &[u8; 32],
-    ) -> anyhow::Result<ZkProof> {
+    ) -> anyhow::Result<ProofEnvelope> {
         if stake_amount < min_stake {
             return Err(anyhow::anyhow!("Insufficient stake: {} < {}", stake_amount, min_stake));
         }
 
-        // Simulate quantum-resistant stake proof generation
-        let mut hasher = Sha256::new();
-        hasher.update(&stake_amount.to_le_bytes());
-        hasher.update(&min_stake.to_le_bytes());
-        hasher.update(secret_nonce);
-        hasher.update(b"ZHTP_CONSENSUS_STAKE_PROOF");
-        
-        let proof_hash = hasher.finalize();
-        
-        Ok(ZkProof {
-            circuit_id: "consensus_stake_proof".to_string(),
-            proof_data: proof_hash.to_vec(),
-            public_inputs: vec![min_stake],
-            verification_key_hash: self.get_circuit_vk_hash("consensus_stake_proof"),
-        })
+        let validator_commitment = Fr::from_le_bytes_mod_order(&Sha256::digest([secret_nonce.as_slice(), b"ZHTP_VALIDATOR_COMMITMENT"].concat()));
+        let validator_nonce = u64::from_le_bytes(secret_nonce[..8].try_into()?);
+        self.prove("stake_proof", &[
+            ("minimum_stake", Fr::from(min_stake)),
+            ("validator_commitment", validator_commitment),
+            ("block_hash", Fr::from_le_bytes_mod_order(block_hash)),
+            ("network_id", Fr::from(STAKE_CIRCUIT_NETWORK_ID)),
+            ("actual_stake", Fr::from(stake_amount)),
+            ("validator_nonce", Fr::from(validator_nonce)),
+            ("stake_salt", Fr::from_le_bytes_mod_order(secret_nonce)),
+        ])
     }
 
-    /// Verify stake proof
-    pub async fn verify_stake_proof(&self, proof: &ZkProof, min_stake: u64) -> anyhow::Result<bool> {
-        if proof.circuit_id != "consensus_stake_proof" {
+    /// Verify stake proof for consensus on the block `block_hash`
+    pub async fn verify_stake_proof(&self, proof: &ProofEnvelope, min_stake: u64, block_hash: &[u8; 32]) -> anyhow::Result<bool> {
+        if proof.circuit_id != "stake_proof" {
             return Ok(false);
         }
 
-        if proof.public_inputs.is_empty() || proof.public_inputs[0] != min_stake {
+        if self.public_signal(proof, "minimum_stake")? != Fr::from(min_stake)
+            || self.public_signal(proof, "block_hash")? != Fr::from_le_bytes_mod_order(block_hash)
+        {
             return Ok(false);
         }
 
-        // Simulate verification (in real implementation, would use snarkjs/arkworks)
-        Ok(proof.proof_data.len() == 32 && !proof.proof_data.iter().all(|&b| b == 0))
+        self.verify(proof)
     }
 
-    /// Generate private transfer proof
+    /// Generate private transfer proof spending `sender_balance`. The
+    /// circuit has no change output, so the balance must be exactly the
+    /// transfer amount plus the public network fee.
     pub async fn generate_private_transfer_proof(
         &self,
         sender_balance: u64,
         transfer_amount: u64,
+        network_fee: u64,
         recipient_nullifier: &[u8; 32],
         secret_nonce: &[u8; 32],
-    ) -> anyhow::Result<ZkProof> {
-        if sender_balance < transfer_amount {
-            return Err(anyhow::anyhow!("Insufficient balance: {} < {}", sender_balance, transfer_amount));
+    ) -> anyhow::Result<ProofEnvelope> {
+        let required = transfer_amount
+            .checked_add(network_fee)
+            .ok_or_else(|| anyhow::anyhow!("Transfer amount plus fee overflows"))?;
+        if sender_balance < required {
+            return Err(anyhow::anyhow!("Insufficient balance: {} < {}", sender_balance, required));
+        }
+        if sender_balance != required {
+            return Err(anyhow::anyhow!(
+                "Balance of {} must be spent in full, but the transfer and fee only use {}",
+                sender_balance, required
+            ));
         }
 
-        let mut hasher = Sha256::new();
-        hasher.update(&sender_balance.to_le_bytes());
-        hasher.update(&transfer_amount.to_le_bytes());
-        hasher.update(recipient_nullifier);
-        hasher.update(secret_nonce);
-        hasher.update(b"ZHTP_PRIVATE_TRANSFER_PROOF");
-        
-        let proof_hash = hasher.finalize();
-        
-        Ok(ZkProof {
-            circuit_id: "private_transfer".to_string(),
-            proof_data: proof_hash.to_vec(),
-            public_inputs: vec![transfer_amount],
-            verification_key_hash: self.get_circuit_vk_hash("private_transfer"),
-        })
+        self.prove("private_transfer", &[
+            ("network_fee", Fr::from(network_fee)),
+            ("quantum_proof_id", Fr::zero()),
+            ("amount_in", Fr::from(sender_balance)),
+            ("amount_out", Fr::from(transfer_amount)),
+            ("recipient_key", Fr::from_le_bytes_mod_order(recipient_nullifier)),
+            ("blinding_factor", Fr::from_le_bytes_mod_order(secret_nonce)),
+        ])
     }
 
     /// Verify private transfer proof
-    pub async fn verify_private_transfer_proof(&self, proof: &ZkProof) -> anyhow::Result<bool> {
+    pub async fn verify_private_transfer_proof(&self, proof: &ProofEnvelope) -> anyhow::Result<bool> {
         if proof.circuit_id != "private_transfer" {
             return Ok(false);
         }
 
-        // Simulate verification
-        Ok(proof.proof_data.len() == 32 && !proof.proof_data.iter().all(|&b| b == 0))
-    }
-
-    /// Get verification key hash for circuit
-    fn get_circuit_vk_hash(&self, circuit_id: &str) -> Vec<u8> {
-        let mut hasher = Sha256::new();
-        hasher.update(circuit_id.as_bytes());
-        hasher.update(b"ZHTP_VERIFICATION_KEY");
-        hasher.finalize().to_vec()
+        self.verify(proof)
     }
 }
 
-/// Zero-knowledge proof structure
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ZkProof {
-    pub circuit_id: String,
-    pub proof_data: Vec<u8>,
-    pub public_inputs: Vec<u64>,
-    pub verification_key_hash: Vec<u8>,
-}
-
 /// Generate a unified ZK proof using the circuit
 pub fn generate_unified_proof(
     circuit: &mut UnifiedCircuit,
@@ -1525,6 +1798,26 @@ pub fn generate_unified_proof(
 
 use std::sync::OnceLock;
 
+/// Magic bytes opening a serialized structured reference string (SRS) file
+pub const SRS_FILE_MAGIC: &[u8; 8] = b"ZHTPSRS1";
+
+/// Highest polynomial degree the network's proofs commit to
+pub const ZHTP_SRS_DEGREE: usize = 1024;
+
+/// Magic bytes opening a snarkjs powers-of-tau (`.ptau`) file, the
+/// transcript phase 1 of the ceremony accumulates
+const PTAU_FILE_MAGIC: &[u8; 4] = b"ptau";
+
+/// Claim that the polynomial committed to by `commitment` evaluates to
+/// `evaluation` at `point`, with `proof` the commitment to the quotient
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KzgOpening {
+    pub commitment: G1Projective,
+    pub point: Fr,
+    pub evaluation: Fr,
+    pub proof: G1Projective,
+}
+
 /// KZG Trusted Setup for ZHTP Network
 /// This replaces the broken per-proof random secret generation
 #[derive(Debug, Clone)]
@@ -1535,27 +1828,34 @@ pub struct KzgTrustedSetup {
     pub powers_of_tau_g2: Vec<ark_bn254::G2Projective>,
     /// Maximum polynomial degree supported
     pub max_degree: usize,
-    /// Setup ceremony identifier for network consensus
+    /// Setup ceremony identifier for network consensus: the SHA-256 of the
+    /// setup's SRS encoding
     pub ceremony_id: [u8; 32],
 }
 
 /// Global trusted setup instance for ZHTP network
 static ZHTP_TRUSTED_SETUP: OnceLock<KzgTrustedSetup> = OnceLock::new();
 
+/// Whether [`KzgTrustedSetup::get_global`] may fall back to the insecure
+/// development setup; always true in unit tests
+static INSECURE_SETUP_ALLOWED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(cfg!(test));
+
 impl KzgTrustedSetup {
-    /// Initialize trusted setup for ZHTP network
-    /// In production, this would be loaded from a completed trusted setup ceremony
-    pub fn initialize_for_zhtp_network() -> Self {
-        // For ZHTP network, we need to support polynomials up to degree 1024
-        // This is sufficient for our routing, storage, and consensus proofs
-        let max_degree = 1024;
-        
-        // CRITICAL: In production, these would come from a multi-party trusted setup ceremony
-        // For now, we use a deterministic setup for consistency across all nodes
-        let tau = Self::get_deterministic_tau_for_network();
-        
+    /// Development setup whose τ is derived from a public constant.
+    ///
+    /// SECURITY: anyone can compute this τ and forge proofs against it. It is
+    /// only used when no ceremony output was loaded with
+    /// [`KzgTrustedSetup::install_global`] and the process opted in with
+    /// [`KzgTrustedSetup::allow_insecure_development_setup`].
+    pub fn insecure_development_setup() -> Self {
+        Self::from_tau(Self::get_deterministic_tau_for_network(), ZHTP_SRS_DEGREE)
+    }
+
+    /// Setup holding the powers of `tau` up to `max_degree`. Only a ceremony
+    /// (or a test) ever knows τ; nodes load the powers with
+    /// [`KzgTrustedSetup::load`].
+    pub fn from_tau(tau: Fr, max_degree: usize) -> Self {
         let mut powers_g1 = Vec::with_capacity(max_degree + 1);
-        let mut powers_g2 = Vec::with_capacity(2); // Only need [1, τ] in G2
         
         // Generate powers of τ in G1: [g, g^τ, g^τ², ..., g^τ^max_degree]
         let g1_gen = ark_bn254::G1Projective::generator();
@@ -1568,20 +1868,20 @@ impl KzgTrustedSetup {
         
         // Generate powers of τ in G2: [h, h^τ]
         let g2_gen = ark_bn254::G2Projective::generator();
-        powers_g2.push(g2_gen);
-        powers_g2.push(g2_gen * tau);
-        
-        // Create ceremony ID from tau (for network identification)
-        let mut ceremony_id = [0u8; 32];
-        let tau_bytes = tau.into_bigint().to_bytes_le();
-        ceremony_id[..tau_bytes.len().min(32)].copy_from_slice(&tau_bytes[..tau_bytes.len().min(32)]);
+        let powers_g2 = vec![g2_gen, g2_gen * tau];
         
-        Self {
-            powers_of_tau_g1: powers_g1,
-            powers_of_tau_g2: powers_g2,
-            max_degree,
-            ceremony_id,
-        }
+        Self::from_powers(powers_g1, powers_g2)
+    }
+
+    fn from_powers(powers_of_tau_g1: Vec<G1Projective>, powers_of_tau_g2: Vec<ark_bn254::G2Projective>) -> Self {
+        let mut setup = Self {
+            max_degree: powers_of_tau_g1.len().saturating_sub(1),
+            powers_of_tau_g1,
+            powers_of_tau_g2,
+            ceremony_id: [0u8; 32],
+        };
+        setup.ceremony_id = Sha256::digest(setup.to_srs_bytes()).into();
+        setup
     }
     
     /// Get deterministic tau for ZHTP network
@@ -1598,10 +1898,187 @@ impl KzgTrustedSetup {
         let hash = hasher.finalize();
         ark_bn254::Fr::from_le_bytes_mod_order(&hash)
     }
+
+    /// Encode as an SRS file: [`SRS_FILE_MAGIC`] followed by the compressed
+    /// G1 powers and G2 powers, each prefixed with their count
+    pub fn to_srs_bytes(&self) -> Vec<u8> {
+        use ark_ec::CurveGroup;
+
+        let g1 = G1Projective::normalize_batch(&self.powers_of_tau_g1);
+        let g2 = ark_bn254::G2Projective::normalize_batch(&self.powers_of_tau_g2);
+        let mut bytes = SRS_FILE_MAGIC.to_vec();
+        g1.serialize_compressed(&mut bytes).expect("writing to a Vec cannot fail");
+        g2.serialize_compressed(&mut bytes).expect("writing to a Vec cannot fail");
+        bytes
+    }
+
+    /// Decode and validate an SRS file's contents
+    pub fn from_srs_bytes(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        let body = bytes
+            .strip_prefix(SRS_FILE_MAGIC.as_slice())
+            .ok_or_else(|| anyhow::anyhow!("Not a ZHTP SRS file"))?;
+        let mut reader = Cursor::new(body);
+        let g1 = Vec::<ark_bn254::G1Affine>::deserialize_compressed(&mut reader)
+            .map_err(|e| anyhow::anyhow!("Invalid G1 powers in SRS file: {}", e))?;
+        let g2 = Vec::<ark_bn254::G2Affine>::deserialize_compressed(&mut reader)
+            .map_err(|e| anyhow::anyhow!("Invalid G2 powers in SRS file: {}", e))?;
+        if reader.position() as usize != body.len() {
+            return Err(anyhow::anyhow!("Trailing bytes after SRS powers"));
+        }
+
+        let setup = Self::from_powers(
+            g1.into_iter().map(Into::into).collect(),
+            g2.into_iter().map(Into::into).collect(),
+        );
+        setup.validate()?;
+        Ok(setup)
+    }
+
+    /// Read the accumulated powers from a snarkjs `.ptau` transcript: the
+    /// first [`ZHTP_SRS_DEGREE`] + 1 τ powers in G1 and the first two in G2.
+    /// Every contribution multiplied the powers by a secret nobody else saw,
+    /// so τ itself exists nowhere.
+    pub fn from_ptau(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        let sections = ptau_sections(bytes)?;
+        let section = |id: u32| {
+            sections
+                .get(&id)
+                .copied()
+                .ok_or_else(|| anyhow::anyhow!("ptau file has no section {}", id))
+        };
+
+        // Header: field element size, the base field modulus, and the power
+        let header = section(1)?;
+        let modulus = <ark_bn254::Fq as PrimeField>::MODULUS.to_bytes_le();
+        if header.len() < 4 + modulus.len() || header[..4] != (modulus.len() as u32).to_le_bytes() {
+            return Err(anyhow::anyhow!("ptau file is not for a 32-byte base field"));
+        }
+        if header[4..4 + modulus.len()] != modulus[..] {
+            return Err(anyhow::anyhow!("ptau file is not for BN254"));
+        }
+
+        let g1 = section(2)?;
+        let g2 = section(3)?;
+        if g1.len() < (ZHTP_SRS_DEGREE + 1) * 64 || g2.len() < 2 * 128 {
+            return Err(anyhow::anyhow!("ptau file holds too few powers for degree {}", ZHTP_SRS_DEGREE));
+        }
+        let powers_g1 = g1
+            .chunks_exact(64)
+            .take(ZHTP_SRS_DEGREE + 1)
+            .map(|point| ptau_g1(point).map(Into::into))
+            .collect::<Result<Vec<G1Projective>, _>>()?;
+        let powers_g2 = g2
+            .chunks_exact(128)
+            .take(2)
+            .map(|point| ptau_g2(point).map(Into::into))
+            .collect::<Result<Vec<ark_bn254::G2Projective>, _>>()?;
+
+        let setup = Self::from_powers(powers_g1, powers_g2);
+        setup.validate()?;
+        Ok(setup)
+    }
+
+    /// Hex SHA-256 of an SRS file's contents, the value operators pin
+    pub fn srs_hash(bytes: &[u8]) -> String {
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    /// Load ceremony output from an SRS file, refusing it unless its hash
+    /// is `pinned_hash` and its powers are consistent
+    pub fn load<P: AsRef<std::path::Path>>(path: P, pinned_hash: &str) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read SRS file {}: {}", path.display(), e))?;
+        let hash = Self::srs_hash(&bytes);
+        if !hash.eq_ignore_ascii_case(pinned_hash.trim()) {
+            return Err(anyhow::anyhow!(
+                "SRS file {} has hash {}, expected pinned hash {}",
+                path.display(), hash, pinned_hash
+            ));
+        }
+        Self::from_srs_bytes(&bytes)
+    }
+
+    /// Check that the powers cover [`ZHTP_SRS_DEGREE`] and come from a
+    /// single τ: both lists start at the generators, τ is neither 0 nor 1,
+    /// and every G1 power is τ times the previous one. The last check pairs
+    /// random combinations of consecutive powers,
+    /// `e(Σ rᵢ·g^τ^(i+1), h) = e(Σ rᵢ·g^τ^i, h^τ)`.
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        use ark_ec::{pairing::Pairing, CurveGroup, VariableBaseMSM};
+        use ark_std::UniformRand;
+
+        let g1 = &self.powers_of_tau_g1;
+        let g2 = &self.powers_of_tau_g2;
+        if g2.len() != 2 {
+            return Err(anyhow::anyhow!("SRS needs exactly two G2 powers"));
+        }
+        if g1.len() < ZHTP_SRS_DEGREE + 1 {
+            return Err(anyhow::anyhow!(
+                "SRS supports degree {}, the network needs {}",
+                g1.len().saturating_sub(1), ZHTP_SRS_DEGREE
+            ));
+        }
+        if g1[0] != G1Projective::generator() || g2[0] != ark_bn254::G2Projective::generator() {
+            return Err(anyhow::anyhow!("SRS powers do not start at the group generators"));
+        }
+        if g2[1].is_zero() || g2[1] == g2[0] {
+            return Err(anyhow::anyhow!("SRS τ is degenerate"));
+        }
+        if ark_bn254::Bn254::pairing(g1[1], g2[0]) != ark_bn254::Bn254::pairing(g1[0], g2[1]) {
+            return Err(anyhow::anyhow!("SRS G1 and G2 powers use different τ"));
+        }
+
+        let mut rng = rand::thread_rng();
+        let scalars: Vec<Fr> = (1..g1.len()).map(|_| Fr::rand(&mut rng)).collect();
+        let bases = G1Projective::normalize_batch(g1);
+        let shifted = G1Projective::msm(&bases[1..], &scalars)
+            .map_err(|_| anyhow::anyhow!("SRS power count mismatch"))?;
+        let unshifted = G1Projective::msm(&bases[..bases.len() - 1], &scalars)
+            .map_err(|_| anyhow::anyhow!("SRS power count mismatch"))?;
+        if ark_bn254::Bn254::pairing(shifted, g2[0]) != ark_bn254::Bn254::pairing(unshifted, g2[1]) {
+            return Err(anyhow::anyhow!("SRS G1 powers are not consecutive powers of τ"));
+        }
+        Ok(())
+    }
+
+    /// Make `setup` the network-wide trusted setup. Must happen before any
+    /// proof is created or checked, since the setup cannot change afterwards.
+    pub fn install_global(setup: Self) -> Result<&'static KzgTrustedSetup, anyhow::Error> {
+        ZHTP_TRUSTED_SETUP
+            .set(setup)
+            .map_err(|_| anyhow::anyhow!("Trusted setup is already initialized"))?;
+        Ok(Self::get_global())
+    }
     
-    /// Get the global trusted setup instance
+    /// Let [`KzgTrustedSetup::get_global`] fall back to the insecure
+    /// development setup when none was installed. For local development,
+    /// demos and benchmarks only.
+    pub fn allow_insecure_development_setup() {
+        INSECURE_SETUP_ALLOWED.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Whether [`KzgTrustedSetup::allow_insecure_development_setup`] was
+    /// called, or this is a unit test. Other development parameters, such as
+    /// the shielded spend keys, follow the same switch.
+    pub fn insecure_development_setup_allowed() -> bool {
+        INSECURE_SETUP_ALLOWED.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    /// Get the global trusted setup instance.
+    ///
+    /// # Panics
+    /// If no setup was installed and the insecure development setup was not
+    /// allowed. Nodes install theirs at startup, before any proof exists.
     pub fn get_global() -> &'static KzgTrustedSetup {
-        ZHTP_TRUSTED_SETUP.get_or_init(|| Self::initialize_for_zhtp_network())
+        ZHTP_TRUSTED_SETUP.get_or_init(|| {
+            assert!(
+                Self::insecure_development_setup_allowed(),
+                "No trusted setup installed; load the ceremony SRS before creating or checking proofs"
+            );
+            log::warn!("No trusted setup loaded, using the insecure development setup");
+            Self::insecure_development_setup()
+        })
     }
     
     /// Commit to a polynomial using the trusted setup
@@ -1614,18 +2091,31 @@ impl KzgTrustedSetup {
             ));
         }
         
-        let mut commitment = G1Projective::zero();
-        
-        // Compute commitment: C = Σ(a_i * g^(τ^i)) where a_i are polynomial coefficients
-        for (i, coeff) in poly.coeffs.iter().enumerate() {
-            if !coeff.is_zero() {
-                commitment += self.powers_of_tau_g1[i] * coeff;
+        use ark_ec::{CurveGroup, VariableBaseMSM};
+
+        // Compute commitment: C = Σ(a_i * g^(τ^i)) where a_i are polynomial
+        // coefficients, as one (parallel) multi-scalar multiplication
+        let bases = G1Projective::normalize_batch(&self.powers_of_tau_g1[..poly.coeffs.len()]);
+        Ok(G1Projective::msm_unchecked(&bases, &poly.coeffs))
+    }
+    
+    /// Open a polynomial at `point`: its evaluation there, and the
+    /// commitment to the quotient `(p(X) - p(point)) / (X - point)`
+    pub fn open(&self, poly: &DensePolynomial<Fr>, point: &Fr) -> Result<(Fr, G1Projective), String> {
+        // Synthetic division by (X - point), from the leading coefficient
+        // down; what is carried past the constant term is p(point)
+        let mut quotient = vec![Fr::zero(); poly.coeffs.len().saturating_sub(1)];
+        let mut carry = Fr::zero();
+        for (i, coeff) in poly.coeffs.iter().enumerate().rev() {
+            carry = *coeff + carry * point;
+            if i > 0 {
+                quotient[i - 1] = carry;
             }
         }
-        
-        Ok(commitment)
+        let proof = self.commit_polynomial(&DensePolynomial { coeffs: quotient })?;
+        Ok((carry, proof))
     }
-    
+
     /// Verify a KZG commitment opening
     pub fn verify_opening(
         &self,
@@ -1634,27 +2124,124 @@ impl KzgTrustedSetup {
         evaluation: &Fr,
         proof: &G1Projective,
     ) -> bool {
-        // Verify: e(commitment - evaluation * g, h) = e(proof, h^τ - point * h)
-        // This is the core KZG verification equation
-        
-        let g1_gen = ark_bn254::G1Projective::generator();
-        let h = &self.powers_of_tau_g2[0]; // h
-        let h_tau = &self.powers_of_tau_g2[1]; // h^τ
-        
-        // Left side: commitment - evaluation * g
-        let left_g1 = *commitment - (g1_gen * evaluation);
-        
-        // Right side: h^τ - point * h
-        let right_g2 = *h_tau - (*h * point);
-        
-        // In a full implementation, we would use pairing verification:
-        // e(left_g1, h) == e(proof, right_g2)
-        // For now, we do a simplified check that the proof is non-zero and well-formed
-        !proof.is_zero() && !left_g1.is_zero()
+        self.batch_verify_openings(&[KzgOpening {
+            commitment: *commitment,
+            point: *point,
+            evaluation: *evaluation,
+            proof: *proof,
+        }])
+    }
+
+    /// Verify many openings with one multi-pairing.
+    ///
+    /// Each opening satisfies `e(C - y·g, h) = e(π, h^τ - z·h)`, which
+    /// rearranges to `e(C - y·g + z·π, h) = e(π, h^τ)`. Summing the
+    /// equations with random weights `rᵢ` leaves two pairings,
+    /// `e(Σ rᵢ·(Cᵢ - yᵢ·g + zᵢ·πᵢ), h) = e(Σ rᵢ·πᵢ, h^τ)`, which holds when
+    /// any opening is invalid only with negligible probability.
+    pub fn batch_verify_openings(&self, openings: &[KzgOpening]) -> bool {
+        use ark_ec::{pairing::Pairing, CurveGroup, VariableBaseMSM};
+        use ark_std::UniformRand;
+
+        if openings.is_empty() {
+            return true;
+        }
+        let mut rng = rand::thread_rng();
+        let weights: Vec<Fr> = std::iter::once(Fr::one())
+            .chain((1..openings.len()).map(|_| Fr::rand(&mut rng)))
+            .collect();
+
+        // Σ rᵢ·Cᵢ + Σ (rᵢ·zᵢ)·πᵢ - (Σ rᵢ·yᵢ)·g as one MSM
+        let mut points = Vec::with_capacity(2 * openings.len() + 1);
+        let mut scalars = Vec::with_capacity(2 * openings.len() + 1);
+        let mut combined_evaluation = Fr::zero();
+        for (opening, weight) in openings.iter().zip(&weights) {
+            points.push(opening.commitment);
+            scalars.push(*weight);
+            points.push(opening.proof);
+            scalars.push(*weight * opening.point);
+            combined_evaluation += *weight * opening.evaluation;
+        }
+        points.push(G1Projective::generator());
+        scalars.push(-combined_evaluation);
+
+        let proofs: Vec<G1Projective> = openings.iter().map(|opening| opening.proof).collect();
+        let (Ok(left), Ok(right)) = (
+            G1Projective::msm(&G1Projective::normalize_batch(&points), &scalars),
+            G1Projective::msm(&G1Projective::normalize_batch(&proofs), &weights),
+        ) else {
+            return false;
+        };
+
+        let h = self.powers_of_tau_g2[0].into_affine();
+        let h_tau = self.powers_of_tau_g2[1].into_affine();
+        Bn254::multi_pairing([left.into_affine(), (-right).into_affine()], [h, h_tau]).is_zero()
     }
     
     /// Get ceremony info for network identification
     pub fn get_ceremony_info(&self) -> ([u8; 32], usize) {
         (self.ceremony_id, self.max_degree)
     }
-}
\ No newline at end of file
+}
+
+/// Sections of a snarkjs binary file by id: after the magic, a version and
+/// section count, each section is its id, its byte length and its data
+fn ptau_sections(bytes: &[u8]) -> Result<HashMap<u32, &[u8]>, anyhow::Error> {
+    let body = bytes
+        .strip_prefix(PTAU_FILE_MAGIC.as_slice())
+        .ok_or_else(|| anyhow::anyhow!("Not a ptau file"))?;
+    let truncated = || anyhow::anyhow!("Truncated ptau file");
+    let u32_at = |offset: usize| -> Result<u32, anyhow::Error> {
+        let word = body.get(offset..offset + 4).ok_or_else(truncated)?;
+        Ok(u32::from_le_bytes(word.try_into()?))
+    };
+
+    let count = u32_at(4)?;
+    let mut sections = HashMap::new();
+    let mut offset = 8;
+    for _ in 0..count {
+        let id = u32_at(offset)?;
+        let size = body.get(offset + 4..offset + 12).ok_or_else(truncated)?;
+        let size = usize::try_from(u64::from_le_bytes(size.try_into()?))?;
+        let start = offset + 12;
+        let data = start
+            .checked_add(size)
+            .and_then(|end| body.get(start..end))
+            .ok_or_else(truncated)?;
+        sections.insert(id, data);
+        offset = start + size;
+    }
+    Ok(sections)
+}
+
+/// A base field element as snarkjs stores it: Montgomery form, little-endian
+fn ptau_fq(bytes: &[u8]) -> Result<ark_bn254::Fq, anyhow::Error> {
+    let mut limbs = [0u64; 4];
+    for (limb, word) in limbs.iter_mut().zip(bytes.chunks_exact(8)) {
+        *limb = u64::from_le_bytes(word.try_into()?);
+    }
+    let montgomery = ark_ff::BigInt::new(limbs);
+    if montgomery >= <ark_bn254::Fq as PrimeField>::MODULUS {
+        return Err(anyhow::anyhow!("ptau coordinate is not a field element"));
+    }
+    Ok(ark_bn254::Fq::new_unchecked(montgomery))
+}
+
+fn ptau_g1(bytes: &[u8]) -> Result<ark_bn254::G1Affine, anyhow::Error> {
+    let point = ark_bn254::G1Affine::new_unchecked(ptau_fq(&bytes[..32])?, ptau_fq(&bytes[32..])?);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(anyhow::anyhow!("ptau G1 power is not in the group"));
+    }
+    Ok(point)
+}
+
+fn ptau_g2(bytes: &[u8]) -> Result<ark_bn254::G2Affine, anyhow::Error> {
+    let fq2 = |bytes: &[u8]| -> Result<ark_bn254::Fq2, anyhow::Error> {
+        Ok(ark_bn254::Fq2::new(ptau_fq(&bytes[..32])?, ptau_fq(&bytes[32..])?))
+    };
+    let point = ark_bn254::G2Affine::new_unchecked(fq2(&bytes[..64])?, fq2(&bytes[64..])?);
+    if !point.is_on_curve() || !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(anyhow::anyhow!("ptau G2 power is not in the group"));
+    }
+    Ok(point)
+}